@@ -0,0 +1,1855 @@
+use crate::{completions, man};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+/// Builds the command line interface, the one source of `--help` and the man pages
+pub fn build<'a, 'b>(bin: &str) -> App<'a, 'b> {
+    App::new(bin)
+        .about("Bridge to run .deb packages on your distribution using docker")
+        .long_about(MAIN_ABOUT)
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .global(true)
+                .help("Set a custom config file [env: DEBIAN_BRIDGE_CONFIG]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .value_name("DIR")
+                .global(true)
+                .help("Set a custom cache directory [env: DEBIAN_BRIDGE_CACHE]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .global(true)
+                .help(
+                    "Use a separate set of programs with its own config, cache, images and \
+                     desktop entries [env: DEBIAN_BRIDGE_PROFILE]",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .value_name("NAME")
+                .global(true)
+                .help(
+                    "Container engine, only docker is supported [env: \
+                     DEBIAN_BRIDGE_BACKEND]",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("docker-host")
+                .long("docker-host")
+                .value_name("URL")
+                .global(true)
+                .help(
+                    "Docker daemon to use instead of DOCKER_HOST or the local socket, e.g. \
+                     tcp://10.0.0.2:2375 [env: DEBIAN_BRIDGE_DOCKER_HOST]",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-desktop-entry")
+                .long("no-desktop-entry")
+                .global(true)
+                .help(
+                    "Don't write desktop entries, --desktop-icon is ignored [env: \
+                     DEBIAN_BRIDGE_NO_DESKTOP_ENTRY=1]",
+                ),
+        )
+        .arg(
+            Arg::with_name("no-cache-probe")
+                .long("no-cache-probe")
+                .global(true)
+                .help(
+                    "Probe the host and the docker daemon anew instead of reusing the result of \
+                     a run of the last minute, doctor and test always do [env: \
+                     DEBIAN_BRIDGE_NO_CACHE_PROBE=1]",
+                ),
+        )
+        .arg(Arg::with_name("notify").long("notify").global(true).help(
+            "Notify on the desktop when create, edit, repair or rebuild-base ends after \
+                     the notify-after setting, 30 seconds by default [env: \
+                     DEBIAN_BRIDGE_NOTIFY=1]",
+        ))
+        .arg(
+            Arg::with_name("no-notify")
+                .long("no-notify")
+                .global(true)
+                .conflicts_with("notify")
+                .help("Don't notify on the desktop, even if the notify setting is on"),
+        )
+        .arg(Arg::with_name("offline").long("offline").global(true).help(
+            "Refuse operations which need the network, e.g. pulls and builds \
+                     installing packages, builds install nothing [env: \
+                     DEBIAN_BRIDGE_OFFLINE=1]",
+        ))
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .global(true)
+                .help("Don't ask for confirmation"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .global(true)
+                .possible_values(&["always", "auto", "never"])
+                .default_value("auto")
+                .help(
+                    "Color the output, auto colors it only for a terminal and if \
+                     NO_COLOR isn't set",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help(
+                    "Print a single JSON document, or a JSON error object on failure, for \
+                     scripts",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Set the level of verbosity"),
+        )
+        .subcommands(subcommands())
+}
+
+/// Short names of subcommands, shown in `--help` and completed like the subcommands
+pub const ALIASES: &[(&str, &[&str])] = &[
+    ("run", &["start"]),
+    ("list", &["ls"]),
+    ("remove", &["rm"]),
+    ("create", &["add", "install"]),
+];
+
+/// Subcommand `name` stands for if it's an alias, `name` itself otherwise
+pub fn resolve(name: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&name))
+        .map_or(name, |(command, _)| command)
+}
+
+/// Aliases of the subcommand
+pub fn aliases(command: &str) -> &'static [&'static str] {
+    ALIASES
+        .iter()
+        .find(|(known, _)| *known == command)
+        .map_or(&[], |(_, aliases)| aliases)
+}
+
+/// Subcommands having a man page each
+pub fn subcommands<'a, 'b>() -> Vec<App<'a, 'b>> {
+    let subcommands = vec![
+        run(),
+        stop(),
+        service(),
+        remove(),
+        restore(),
+        rename(),
+        label(),
+        rollback(),
+        list(),
+        search(),
+        info(),
+        logs(),
+        permissions(),
+        export(),
+        import(),
+        test(),
+        du(),
+        repair(),
+        verify(),
+        doctor(),
+        config(),
+        presets(),
+        clean(),
+        purge(),
+        rebuild_base(),
+        completions(),
+        create(),
+        edit(),
+    ];
+
+    subcommands
+        .into_iter()
+        .map(|subcommand| {
+            let aliases = aliases(subcommand.get_name());
+
+            subcommand.visible_aliases(aliases)
+        })
+        .collect()
+}
+
+/// Whether `name` is a subcommand or an alias, hidden ones and `help` included
+pub fn is_subcommand(name: &str) -> bool {
+    let name = resolve(name);
+
+    [man::MAN_COMMAND, completions::COMPLETE_COMMAND, "help"].contains(&name)
+        || subcommands()
+            .iter()
+            .any(|subcommand| subcommand.get_name() == name)
+}
+
+/// Argument given in place of a subcommand which isn't one, e.g. a program name for an implicit
+/// `run`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownSubcommand {
+    pub name: String,
+    /// Position in the arguments, `run` goes there
+    pub position: usize,
+}
+
+/// Finds the argument in place of the subcommand if it isn't a subcommand, so
+/// `debian_bridge firefox --detach` can run firefox. Subcommands always win, a program can't
+/// shadow one. Returns the global options parsed too, e.g. the config to look the name up in
+pub fn unknown_subcommand<'a>(
+    app: &App<'a, '_>,
+    args: &[String],
+) -> Option<(UnknownSubcommand, ArgMatches<'a>)> {
+    let matches = app
+        .clone()
+        .setting(AppSettings::AllowExternalSubcommands)
+        .get_matches_from_safe(args)
+        .ok()?;
+    let unknown = match matches.subcommand() {
+        (name, Some(rest)) if !is_subcommand(name) => UnknownSubcommand {
+            name: name.to_string(),
+            // Everything after the name is collected as arguments of the unknown subcommand
+            position: args.len() - 1 - rest.values_of("").map_or(0, Iterator::count),
+        },
+        _ => return None,
+    };
+
+    Some((unknown, matches))
+}
+
+fn run<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("run")
+        .version("stable")
+        .about("Run installed program")
+        .long_about(RUN_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required_unless("group")
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("LABEL")
+                .takes_value(true)
+                .conflicts_with("name")
+                .help("Start every program with the label in the background"),
+        )
+        .arg(
+            Arg::with_name("keep-container")
+                .long("keep-container")
+                .help(
+                    "Keep the container after exit instead of removing it, e.g. to \
+                     copy logs out of it",
+                ),
+        )
+        .arg(Arg::with_name("detach").long("detach").help(
+            "Run in the background without a terminal and return at once, \
+             e.g. from an autostart entry",
+        ))
+        .arg(
+            Arg::with_name("display")
+                .long("display")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .help(
+                    "Show the program on a remote X server, e.g. 192.168.1.5:0 \
+                     (unencrypted)",
+                ),
+        )
+        .arg(
+            Arg::with_name("pulse-server")
+                .long("pulse-server")
+                .value_name("IP")
+                .takes_value(true)
+                .help(
+                    "Play sound on a remote PulseAudio server listening on TCP port \
+                     4713",
+                ),
+        )
+        .arg(
+            Arg::with_name("pulse-cookie")
+                .long("pulse-cookie")
+                .value_name("PATH")
+                .takes_value(true)
+                .requires("pulse-server")
+                .help("Cookie file the remote PulseAudio server authenticates with"),
+        )
+        .arg(
+            Arg::with_name("no-log")
+                .long("no-log")
+                .help("Don't write the output of this run to a log file"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .long("env")
+                .value_name("NAME[=VALUE]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Set a variable for this run, a bare name passes the one of the host"),
+        )
+        .arg(
+            Arg::with_name("share")
+                .long("share")
+                .value_name("PATH[:ro]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Share a host path at the same path for this run, :ro makes it read-only"),
+        )
+        .arg(
+            Arg::with_name("network")
+                .long("network")
+                .value_name("MODE")
+                .takes_value(true)
+                .help("Docker network instead of the host one for this run, e.g. none"),
+        )
+        .arg(
+            Arg::with_name("no-sound")
+                .long("no-sound")
+                .help("Run without sound this time"),
+        )
+        .arg(
+            Arg::with_name("command")
+                .long("command")
+                .value_name("COMMAND")
+                .takes_value(true)
+                .help("Shell command to run instead of the program command"),
+        )
+        .arg(
+            Arg::with_name("args")
+                .value_name("ARGS")
+                .multiple(true)
+                .last(true)
+                .help("Arguments passed on to the command, after --"),
+        )
+}
+
+fn stop<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("stop")
+        .version("stable")
+        .about("Stop running containers of a program")
+        .long_about(STOP_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+}
+
+fn service<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("service")
+        .version("stable")
+        .about("Run a program as a systemd user service")
+        .long_about(SERVICE_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("action")
+                .required(true)
+                .index(2)
+                .possible_values(&["enable", "disable", "status"])
+                .help(
+                    "Install, enable and start the service, stop and delete it or \
+                     show its state",
+                ),
+        )
+}
+
+fn remove<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("remove")
+        .version("stable")
+        .about("Remove programs")
+        .long_about(REMOVE_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .index(1)
+                .multiple(true)
+                .required_unless_one(&["all", "group"])
+                .help("Program names"),
+        )
+        .arg(
+            Arg::with_name("all")
+                .long("all")
+                .conflicts_with_all(&["name", "group"])
+                .help("Remove all programs"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("LABEL")
+                .takes_value(true)
+                .conflicts_with("name")
+                .help("Remove the programs with the label"),
+        )
+        .arg(
+            Arg::with_name("keep-image")
+                .long("keep-image")
+                .help("Keep the image a program was created from with --from-image"),
+        )
+}
+
+fn restore<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("restore")
+        .version("stable")
+        .about("Create a removed program again from its backup")
+        .long_about(RESTORE_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+}
+
+fn rename<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("rename")
+        .version("stable")
+        .about("Rename program")
+        .long_about(RENAME_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("new-name")
+                .required(true)
+                .index(2)
+                .help("New program name"),
+        )
+}
+
+fn label<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("label")
+        .version("stable")
+        .about("Add a label to a program or take it away")
+        .long_about(LABEL_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("action")
+                .required(true)
+                .index(2)
+                .possible_values(&["add", "remove"])
+                .help("Whether to add the label or take it away"),
+        )
+        .arg(
+            Arg::with_name("label")
+                .required(true)
+                .index(3)
+                .help("Label, without commas and whitespace"),
+        )
+}
+
+fn rollback<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("rollback")
+        .version("stable")
+        .about("Go back to the image a program had before its last update")
+        .long_about(ROLLBACK_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+}
+
+fn list<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("list")
+        .version("stable")
+        .about("Show installed programs")
+        .long_about(LIST_ABOUT)
+        .arg(
+            Arg::with_name("long")
+                .short("l")
+                .long("long")
+                .help("Show when each program was launched last time and how many times"),
+        )
+        .arg(
+            Arg::with_name("unused-since")
+                .long("unused-since")
+                .value_name("AGE")
+                .takes_value(true)
+                .help(
+                    "Show programs not launched during AGE only, e.g. 90d (units are \
+                     h, d, w, y)",
+                ),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("LABEL")
+                .takes_value(true)
+                .help("Show the programs with the label only"),
+        )
+        .arg(
+            Arg::with_name("all-profiles")
+                .long("all-profiles")
+                .conflicts_with_all(&["long", "unused-since", "group"])
+                .help("Show every profile and how many programs it has"),
+        )
+}
+
+fn search<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("search")
+        .version("stable")
+        .about("Find programs by name, command or description, exits with an error if none matches")
+        .long_about(SEARCH_ABOUT)
+        .arg(
+            Arg::with_name("term")
+                .required(true)
+                .index(1)
+                .help("Text to look for, case is ignored"),
+        )
+}
+
+fn info<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("info")
+        .version("stable")
+        .about("Show program settings")
+        .long_about(INFO_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+}
+
+fn logs<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("logs")
+        .version("stable")
+        .about("Print the output of the latest run of a program")
+        .long_about(LOGS_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .help("List the log files of the program instead, the oldest first"),
+        )
+}
+
+fn permissions<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("permissions")
+        .version("stable")
+        .about("Show what a program can reach on the host")
+        .long_about(PERMISSIONS_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+}
+
+fn export<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("export")
+        .version("stable")
+        .about("Export a program to run it without debian_bridge")
+        .long_about(EXPORT_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .possible_values(&["compose", "image"])
+                .default_value("compose")
+                .help(
+                    "Write a docker compose file with a service running the program, \
+                     or save its image to a tarball",
+                ),
+        )
+        .arg(
+            Arg::with_name("out-file")
+                .short("o")
+                .long("out-file")
+                .value_name("FILE")
+                .takes_value(true)
+                .required_if("format", "image")
+                .help("Write to a file instead of the standard output"),
+        )
+}
+
+fn import<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("import")
+        .version("stable")
+        .about("Load program images exported with `export --format image`")
+        .long_about(IMPORT_ABOUT)
+        .arg(Arg::with_name("path").required(true).index(1).help(
+            "Tarball with images, verified against the .sha256 file next to \
+             it",
+        ))
+}
+
+fn test<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("test")
+        .version("stable")
+        .about("Test compatibility and feature access")
+        .long_about(TEST_ABOUT)
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the report as a JSON document"),
+        )
+}
+
+fn du<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("du")
+        .version("stable")
+        .about("Show disk space taken by programs, base images and the cache")
+        .long_about(DU_ABOUT)
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the report as a JSON document"),
+        )
+}
+
+fn repair<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("repair")
+        .version("stable")
+        .about("Rebuild images which are missing in docker")
+        .long_about(REPAIR_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .index(1)
+                .required_unless_one(&["all", "group"])
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("all")
+                .long("all")
+                .conflicts_with_all(&["name", "group"])
+                .help("Repair all programs"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("LABEL")
+                .takes_value(true)
+                .conflicts_with("name")
+                .help("Repair the programs with the label"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("N")
+                .takes_value(true)
+                .help(
+                    "Build up to N programs at once, the jobs setting or one per core up to 4 \
+                     by default",
+                ),
+        )
+}
+
+fn verify<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("verify")
+        .version("stable")
+        .about("Check a program is healthy, e.g. after a system upgrade")
+        .long_about(VERIFY_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .index(1)
+                .required_unless("all")
+                .help("Program name"),
+        )
+        .arg(
+            Arg::with_name("all")
+                .long("all")
+                .conflicts_with("name")
+                .help("Verify all programs"),
+        )
+}
+
+fn doctor<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("doctor")
+        .version("stable")
+        .about("Diagnose environment problems and suggest fixes")
+        .long_about(DOCTOR_ABOUT)
+}
+
+fn config<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("config")
+        .version("stable")
+        .about("Manage the config file")
+        .long_about(CONFIG_ABOUT)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Rewrite the config file in another format")
+                .long_about(CONVERT_ABOUT)
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("FORMAT")
+                        .required(true)
+                        .possible_values(&["json", "toml"])
+                        .help("Target format"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Print the global settings or one of them")
+                .long_about(CONFIG_GET_ABOUT)
+                .arg(Arg::with_name("key").help("Setting to print, all are printed without it")),
+        )
+        .subcommand(
+            SubCommand::with_name("set")
+                .about("Change a global setting")
+                .long_about(CONFIG_SET_ABOUT)
+                .arg(
+                    Arg::with_name("key")
+                        .required(true)
+                        .help("Setting to change"),
+                )
+                .arg(
+                    Arg::with_name("value")
+                        .required(true)
+                        .help("New value, an empty one unsets the setting"),
+                ),
+        )
+}
+
+fn presets<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("presets")
+        .version("stable")
+        .about("Manage the presets programs are created from")
+        .long_about(PRESETS_ABOUT)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("list")
+                .about("Show the built-in and user presets with their values")
+                .long_about(PRESETS_LIST_ABOUT),
+        )
+}
+
+fn clean<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("clean")
+        .version("stable")
+        .about("Remove exited containers and base images which are not used by any program")
+        .long_about(CLEAN_ABOUT)
+        .arg(
+            Arg::with_name("trash")
+                .long("trash")
+                .help("Delete the backups of removed programs too, they can't be restored then"),
+        )
+        .arg(
+            Arg::with_name("logs")
+                .long("logs")
+                .help("Delete all but the latest log of every program too"),
+        )
+}
+
+fn purge<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("purge")
+        .version("stable")
+        .about("Remove all programs, images, containers, the cache and the config")
+        .long_about(PURGE_ABOUT)
+        .arg(Arg::with_name("i-understand").long("i-understand").help(
+            "Confirm that everything created by debian_bridge is going to be \
+             removed",
+        ))
+}
+
+fn rebuild_base<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("rebuild-base")
+        .version("stable")
+        .about("Rebuild the shared base image to pick up security updates")
+        .long_about(REBUILD_BASE_ABOUT)
+}
+
+fn completions<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("completions")
+        .version("stable")
+        .about("Print or install a completion script for the given shell")
+        .long_about(COMPLETIONS_ABOUT)
+        .arg(
+            Arg::with_name("shell")
+                .long("shell")
+                .value_name("SHELL")
+                .required(true)
+                .possible_values(&["bash", "zsh", "fish"])
+                .help("Target shell"),
+        )
+        .arg(Arg::with_name("install").long("install").help(
+            "Write the script to the completion directory of the current user \
+             instead of printing it",
+        ))
+}
+
+fn create<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("create")
+        .version("stable")
+        .about("Create new docker build for existed package")
+        .long_about(CREATE_ABOUT)
+        .arg(
+            Arg::with_name("package")
+                .index(1)
+                .required_unless("from-image")
+                .help("Path to .deb package"),
+        )
+        .arg(
+            Arg::with_name("from-image")
+                .long("from-image")
+                .value_name("IMAGE")
+                .takes_value(true)
+                .conflicts_with("package")
+                .requires("name")
+                .help(
+                    "Pull an existing docker image, e.g. jess/firefox:latest, instead \
+                     of building one from a package",
+                ),
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("Program name, required with --from-image"),
+        )
+        .arg(
+            Arg::with_name("preset")
+                .long("preset")
+                .value_name("PRESET")
+                .takes_value(true)
+                .help(
+                    "Start from the features and settings of a preset, e.g. browser, see \
+                     `presets list`. Flags override its values",
+                ),
+        )
+        .args(&program_args())
+        .arg(Arg::with_name("skip-check").long("skip-check").help(
+            "Don't check that the command exists in the built image, e.g. for \
+             one which starts only with a display",
+        ))
+        .arg(Arg::with_name("force").long("force").help(
+            "Overwrite an image of the same name which belongs to another profile or \
+             tool",
+        ))
+        .arg(
+            Arg::with_name("dockerfile-extra")
+                .long("dockerfile-extra")
+                .value_name("FILE")
+                .takes_value(true)
+                .help(
+                    "Dockerfile instructions, e.g. RUN, to append after the package \
+                     is installed",
+                ),
+        )
+        .arg(
+            Arg::with_name("local-repo")
+                .long("local-repo")
+                .value_name("DIR")
+                .takes_value(true)
+                .conflicts_with("from-image")
+                .help(
+                    "Install packages from a file based apt repository, e.g. a mirror \
+                     of the Debian archive, instead of the network. Updates reuse it",
+                ),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .conflicts_with("from-image")
+                .help("Print the Dockerfile of the program instead of building it"),
+        )
+        .arg(
+            Arg::with_name("non-interactive")
+                .long("non-interactive")
+                .help("Don't ask for the features and the command of a package given alone"),
+        )
+        .arg(
+            Arg::with_name("autostart")
+                .long("autostart")
+                .help("Start the program in the background at login"),
+        )
+        .arg(
+            Arg::with_name("label")
+                .long("label")
+                .value_name("LABEL")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Put the program into a group, e.g. work, see `list --group`"),
+        )
+        .arg(Arg::with_name("wrapper").long("wrapper").help(
+            "Write a script into ~/.local/bin which runs the program like a host \
+             command, with its arguments",
+        ))
+        .arg(
+            Arg::with_name("remote-display")
+                .long("remote-display")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .help(
+                    "Show the program on a remote X server by default, e.g. \
+                     192.168.1.5:0 (unencrypted)",
+                ),
+        )
+        .arg(
+            Arg::with_name("pulse-server")
+                .long("pulse-server")
+                .value_name("IP")
+                .takes_value(true)
+                .help(
+                    "Play sound on a remote PulseAudio server by default, listening \
+                     on TCP port 4713",
+                ),
+        )
+        .arg(
+            Arg::with_name("pulse-cookie")
+                .long("pulse-cookie")
+                .value_name("PATH")
+                .takes_value(true)
+                .requires("pulse-server")
+                .help("Cookie file the remote PulseAudio server authenticates with"),
+        )
+}
+
+fn edit<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("edit")
+        .version("stable")
+        .about("Change features and settings of existed program")
+        .long_about(EDIT_ABOUT)
+        .arg(
+            Arg::with_name("name")
+                .required(true)
+                .index(1)
+                .help("Program name"),
+        )
+        .args(&program_args())
+        .arg(
+            Arg::with_name("autostart")
+                .long("autostart")
+                .value_name("STATE")
+                .takes_value(true)
+                .possible_values(&["on", "off"])
+                .help("Start the program in the background at login or stop doing so"),
+        )
+        .arg(
+            Arg::with_name("remove-capability")
+                .long("remove-capability")
+                .value_name("CAPABILITY")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Take a capability given with --cap-add away from the program"),
+        )
+        .arg(
+            Arg::with_name("remove-device")
+                .long("remove-device")
+                .value_name("PATH")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Stop passing a device or pattern given with --device or \
+                     --device-glob through to the program",
+                ),
+        )
+        .arg(
+            Arg::with_name("remove-ulimit")
+                .long("remove-ulimit")
+                .value_name("NAME")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Drop a ulimit set with --ulimit, the default of docker applies \
+                     again",
+                ),
+        )
+        .arg(
+            Arg::with_name("remove-pre-run")
+                .long("remove-pre-run")
+                .conflicts_with("pre-run")
+                .help("Stop running the pre-run hook"),
+        )
+        .arg(
+            Arg::with_name("remove-post-run")
+                .long("remove-post-run")
+                .conflicts_with("post-run")
+                .help("Stop running the post-run hook"),
+        )
+        .arg(
+            Arg::with_name("remove-apparmor-profile")
+                .long("remove-apparmor-profile")
+                .conflicts_with("apparmor-profile")
+                .help("Stop confining the program with an AppArmor profile"),
+        )
+        .arg(
+            Arg::with_name("remove-feature")
+                .long("remove-feature")
+                .value_name("FEATURE")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .possible_values(&[
+                    "display",
+                    "sound",
+                    "home",
+                    "notifications",
+                    "timezone",
+                    "devices",
+                    "read-only",
+                    "input-method",
+                    "theme",
+                    "keyring",
+                    "gamepad",
+                    "bluetooth",
+                    "scanner",
+                    "mdns",
+                    "open-urls",
+                    "portals",
+                ])
+                .help("Stop sharing a feature with the program"),
+        )
+}
+
+/// Settings of a program `create` and `edit` take alike
+fn program_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("command")
+            .long("command")
+            .takes_value(true)
+            .help("Custom command to run"),
+        Arg::with_name("dependencies")
+            .long("dependencies")
+            .takes_value(true)
+            .help("Additional dependencies to install"),
+        Arg::with_name("apt-mirror")
+            .long("apt-mirror")
+            .value_name("URL")
+            .takes_value(true)
+            .help("Debian mirror to install packages from"),
+        Arg::with_name("build-proxy")
+            .long("build-proxy")
+            .value_name("URL")
+            .takes_value(true)
+            .help("HTTP proxy used while building an image"),
+        Arg::with_name("snapshot")
+            .long("snapshot")
+            .value_name("TIMESTAMP")
+            .takes_value(true)
+            .help(
+                "Pin packages to a snapshot.debian.org timestamp \
+                 (YYYYMMDDTHHMMSSZ) or 'latest'",
+            ),
+        Arg::with_name("display")
+            .short("d")
+            .long("display")
+            .help("Share host display"),
+        Arg::with_name("sound")
+            .short("s")
+            .long("sound")
+            .help("Share sound device"),
+        Arg::with_name("home")
+            .short("h")
+            .long("home")
+            .help("Mount home directory"),
+        Arg::with_name("notifications")
+            .short("n")
+            .long("notifications")
+            .help("Mount dbus"),
+        Arg::with_name("timezone")
+            .short("t")
+            .long("timezone")
+            .help("Share local timezone"),
+        Arg::with_name("devices")
+            .short("i")
+            .long("devices")
+            .help("Enable devices"),
+        Arg::with_name("read-only").long("read-only").help(
+            "Mount the root filesystem read-only, only temporary directories \
+             and the home are writable",
+        ),
+        Arg::with_name("input-method")
+            .long("input-method")
+            .help("Type with IBus or Fcitx of the host, e.g. for CJK input"),
+        Arg::with_name("theme")
+            .long("theme")
+            .help("Share fonts, GTK theme and icons of the host"),
+        Arg::with_name("keyring").long("keyring").help(
+            "Let the program store passwords in the keyring, it can read all \
+             your keyring entries",
+        ),
+        Arg::with_name("gamepad")
+            .long("gamepad")
+            .help("Share gamepads and joysticks connected when the program starts"),
+        Arg::with_name("bluetooth").long("bluetooth").help(
+            "Let the program talk to bluetooth devices through BlueZ on the \
+             system bus",
+        ),
+        Arg::with_name("scanner").long("scanner").help(
+            "Let the program use scanners of the host, USB ones directly, the \
+             others through saned",
+        ),
+        Arg::with_name("mdns").long("mdns").help(
+            "Let the program resolve .local names and discover services \
+             through Avahi of the host",
+        ),
+        Arg::with_name("portals").long("portals").help(
+            "Let the program pick files and reach other desktop portals of \
+             the host, without sharing the home",
+        ),
+        Arg::with_name("open-urls").long("open-urls").help(
+            "Open the http, https and mailto links the program opens with \
+             xdg-open of the host",
+        ),
+        Arg::with_name("desktop-icon")
+            .long("desktop-icon")
+            .takes_value(true)
+            .help(
+                "Set a path for a desktop icon of current application or use \
+                 'default'",
+            ),
+        Arg::with_name("cli")
+            .long("cli")
+            .conflicts_with("gui")
+            .help(
+                "Run the program in the terminal it's started from, without the \
+                 graphical features and a desktop entry",
+            ),
+        Arg::with_name("gui")
+            .long("gui")
+            .help("Run the program as a graphical one, with a desktop entry"),
+        Arg::with_name("container-mode")
+            .long("container-mode")
+            .value_name("MODE")
+            .takes_value(true)
+            .possible_values(&["ephemeral", "persistent"])
+            .help("Create a fresh container for every run or keep one between runs"),
+        Arg::with_name("restart")
+            .long("restart")
+            .value_name("POLICY")
+            .takes_value(true)
+            .possible_values(&["no", "on-failure", "always"])
+            .help("When systemd restarts the program run as a service"),
+        Arg::with_name("pre-run")
+            .long("pre-run")
+            .value_name("COMMAND")
+            .takes_value(true)
+            .help(
+                "Shell command run on the host before every run, a failure aborts \
+                 the run",
+            ),
+        Arg::with_name("post-run")
+            .long("post-run")
+            .value_name("COMMAND")
+            .takes_value(true)
+            .help(
+                "Shell command run on the host after every run, even if the \
+                 program crashed",
+            ),
+        Arg::with_name("security")
+            .long("security")
+            .value_name("MODE")
+            .takes_value(true)
+            .possible_values(&["builtin", "unconfined"])
+            .help(
+                "Filter syscalls with the built-in seccomp profile or disable \
+                 filtering for programs it breaks, unconfined programs aren't \
+                 labeled by SELinux either",
+            ),
+        Arg::with_name("seccomp-profile")
+            .long("seccomp-profile")
+            .value_name("PATH")
+            .takes_value(true)
+            .conflicts_with("security")
+            .help(
+                "Filter syscalls with a docker seccomp profile instead of the \
+                 built-in one",
+            ),
+        Arg::with_name("apparmor-profile")
+            .long("apparmor-profile")
+            .value_name("NAME")
+            .takes_value(true)
+            .help("Confine the program with an AppArmor profile loaded on the host"),
+        Arg::with_name("cap-add")
+            .long("cap-add")
+            .value_name("CAPABILITY")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help(
+                "Give the program a Linux capability back, all of them are \
+                 dropped by default",
+            ),
+        Arg::with_name("shm-size")
+            .long("shm-size")
+            .value_name("SIZE")
+            .takes_value(true)
+            .help(
+                "Give the program its own /dev/shm of the size, e.g. 2g, browsers \
+                 and Electron apps need a large one",
+            ),
+        Arg::with_name("device")
+            .long("device")
+            .value_name("PATH")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Pass a device node through to the program, e.g. /dev/ttyUSB0"),
+        Arg::with_name("device-glob")
+            .long("device-glob")
+            .value_name("PATTERN")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help(
+                "Pass the device nodes matching the pattern through to the \
+                 program, e.g. '/dev/ttyACM*', it's expanded on every run",
+            ),
+        Arg::with_name("ulimit")
+            .long("ulimit")
+            .value_name("NAME=VALUE")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help(
+                "Set a ulimit of the program, nofile, rtprio or memlock, rtprio \
+                 and memlock add the capabilities they need",
+            ),
+        Arg::with_name("nice")
+            .long("nice")
+            .value_name("NICENESS")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .help(
+                "Start the program with the niceness, from -20 to 19, a negative \
+                 one adds SYS_NICE",
+            ),
+        Arg::with_name("oom-score-adj")
+            .long("oom-score-adj")
+            .value_name("ADJUSTMENT")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+            .help(
+                "Adjust the OOM killer score of the program, from -1000 to 1000, \
+                 a high one gets the program killed before the rest of your \
+                 session",
+            ),
+    ]
+}
+
+const MAIN_ABOUT: &str = "\
+Runs .deb packages on any distribution: every program gets a docker image built from
+its package and starts from a desktop entry or the command line with only the host
+features it was given, e.g. the display or sound.
+
+Examples:
+    debian_bridge test
+    debian_bridge create ./skypeforlinux-64.deb --display --sound --desktop-icon default
+    debian_bridge run skypeforlinux
+    debian_bridge skypeforlinux --detach
+    debian_bridge list --long
+    debian_bridge --output json info skypeforlinux
+    debian_bridge --profile work create ./slack-desktop.deb --display
+
+Exit codes:
+    0    success
+    1    user error, e.g. an unknown program or a malformed argument
+    2    environment error, e.g. docker is unreachable or failed
+    3    program run by `run` exited with a nonzero code or was killed";
+
+const RUN_ABOUT: &str = "\
+Run installed program in a new container, or in the kept one of a persistent program. The
+pre-run and post-run hooks of the program run on the host around it. The output is shown
+and written to a log file as well, see `debian_bridge logs`, unless --no-log is given or
+the logs setting is false.
+
+--env, --share, --network, --no-sound and --command change the container for this run
+only, the stored program stays as it is, and so do arguments after --, which are appended
+to the command. A persistent container keeps the settings it was created with. `-vv` prints
+the run spec the container is started with. --group starts every program with a label in
+the background, as --detach does.
+
+Examples:
+    debian_bridge run skypeforlinux
+    debian_bridge run skypeforlinux --detach
+    debian_bridge run --group work
+    debian_bridge run skypeforlinux --display 192.168.1.5:0 --pulse-server 192.168.1.5
+    debian_bridge run skypeforlinux --share ~/Shared:ro --env LANG=C.UTF-8 --no-sound
+    debian_bridge run skypeforlinux --network none --command 'skypeforlinux --safe-mode'
+    debian_bridge run htop -- --tree";
+
+const STOP_ABOUT: &str = "\
+Stop running containers of a program, e.g. one started with --detach or at login.
+
+Examples:
+    debian_bridge stop skypeforlinux";
+
+const SERVICE_ABOUT: &str = "\
+Run a program as a systemd user service. `enable` writes the unit, enables and starts
+it, `disable` stops and deletes it and `status` shows the state systemd reports.
+
+Examples:
+    debian_bridge service syncthing enable
+    debian_bridge service syncthing status";
+
+const REMOVE_ABOUT: &str = "\
+Remove programs with their images, containers and desktop entries. The image of a
+program created with --from-image is removed as well unless --keep-image is given. A
+backup of every program is kept, see `restore`.
+
+Examples:
+    debian_bridge remove skypeforlinux
+    debian_bridge remove firefox --keep-image
+    debian_bridge remove --group games
+    debian_bridge remove --all --yes";
+
+const RESTORE_ABOUT: &str = "\
+Create a removed program again from its latest backup with all its settings. The image is
+rebuilt from the package the program was created from, which has to be in place still.
+The last 5 backups of every program are kept in ~/.local/state/debian_bridge/trash until
+`clean --trash` deletes them.
+
+Examples:
+    debian_bridge restore skypeforlinux";
+
+const RENAME_ABOUT: &str = "\
+Rename program with its image and desktop entry, user data isn't bound to the name.
+
+Examples:
+    debian_bridge rename skypeforlinux skype";
+
+const LABEL_ABOUT: &str = "\
+Add a label to a program or take it away. Labels group programs, `list`, `repair` and
+`remove` take one with --group. Taking away a label the program hasn't got changes nothing.
+
+Examples:
+    debian_bridge label skypeforlinux add work
+    debian_bridge label skypeforlinux remove work";
+
+const ROLLBACK_ABOUT: &str = "\
+Go back to the image a program had before its last update, e.g. if a new version of the
+package is broken. `edit` keeps the image it rebuilds tagged <prefix>_<name>:previous, the
+two images swap their tags, so a second rollback returns to the update. `clean` removes
+previous images older than the keep-previous setting, 30 days by default.
+
+Examples:
+    debian_bridge rollback skypeforlinux";
+
+const LIST_ABOUT: &str = "\
+Show installed programs with their labels, with --long also when each one was launched
+last time. --group shows the programs with a label only, --all-profiles shows the profiles
+instead, see --profile.
+
+Examples:
+    debian_bridge list
+    debian_bridge list --group work
+    debian_bridge list --long --unused-since 90d
+    debian_bridge list --all-profiles";
+
+const SEARCH_ABOUT: &str = "\
+Find programs by name, command or description, exits with an error if none matches.
+
+Examples:
+    debian_bridge search office";
+
+const INFO_ABOUT: &str = "\
+Show program settings: the package, the command, the features and the limits it runs with.
+
+Examples:
+    debian_bridge info skypeforlinux";
+
+const LOGS_ABOUT: &str = "\
+Print the output of the latest run of a program, the one in progress included. The logs
+are kept in ~/.local/state/debian_bridge/logs, the last 10 files and 16 MB of every
+program. `clean --logs` deletes all but the latest one.
+
+Examples:
+    debian_bridge logs skypeforlinux
+    debian_bridge logs skypeforlinux --list";
+
+const PERMISSIONS_ABOUT: &str = "\
+Show what a run of a program can reach on the host, grouped by filesystem, devices,
+network and IPC: the mounts, device nodes, variables and bus names `run` gives its
+container, each with the feature asking for it. They are computed the way `run` starts
+the container, from the host as it is now.
+
+Examples:
+    debian_bridge permissions skypeforlinux";
+
+const EXPORT_ABOUT: &str = "\
+Export a program to run it without debian_bridge. The compose format writes a docker
+compose file with a service running the program, the image format saves its images to a
+tarball with a .sha256 file next to it for `import`.
+
+Examples:
+    debian_bridge export skypeforlinux > docker-compose.yml
+    debian_bridge export skypeforlinux --format image --out-file skype.tar";
+
+const IMPORT_ABOUT: &str = "\
+Load program images exported with `export --format image`. The tarball is verified
+against the .sha256 file next to it before it's loaded.
+
+Examples:
+    debian_bridge import skype.tar";
+
+const TEST_ABOUT: &str = "\
+Test compatibility and feature access: shows what was found on the host and which
+features programs can be given.
+
+Examples:
+    debian_bridge test
+    debian_bridge test --json";
+
+const DU_ABOUT: &str = "\
+Show disk space taken by programs, base images and the cache.
+
+Examples:
+    debian_bridge du
+    debian_bridge du --json";
+
+const REPAIR_ABOUT: &str = "\
+Rebuild images which are missing in docker, e.g. after `docker system prune`. Images
+found under another tag are tagged back, images of programs created with --from-image
+are pulled again.
+
+Several programs are built at once, a failed one doesn't stop the others. The progress of
+each is prefixed with its name, the results end with the time taken.
+
+Examples:
+    debian_bridge repair skypeforlinux
+    debian_bridge repair --all
+    debian_bridge repair --group work
+    debian_bridge repair --all --jobs 2";
+
+const VERIFY_ABOUT: &str = "\
+Check a program is healthy, e.g. after a system upgrade: its image, the package it was
+built from, the desktop entry, the icon and the host features it needs. Exits with an
+error if a check fails.
+
+Examples:
+    debian_bridge verify skypeforlinux
+    debian_bridge verify --all";
+
+const DOCTOR_ABOUT: &str = "\
+Diagnose environment problems and suggest fixes: the docker daemon and its access, the
+display and sound sockets, the cache and the images and packages of the programs. Exits
+with an error if a check fails.
+
+Examples:
+    debian_bridge doctor";
+
+const CONFIG_ABOUT: &str = "\
+Manage the config file.
+
+Examples:
+    debian_bridge config convert --to toml
+    debian_bridge config set features display,sound";
+
+const PRESETS_ABOUT: &str = "\
+Manage the presets programs are created from with `create --preset`. A preset gives the
+features and settings a kind of program needs, e.g. browser or chat.
+
+Examples:
+    debian_bridge presets list";
+
+const PRESETS_LIST_ABOUT: &str = "\
+Show the built-in presets and the user ones with their values. A user preset is a TOML file
+~/.config/debian_bridge/presets/<name>.toml, one named like a built-in preset replaces it:
+
+    features = [\"display\", \"sound\", \"home\"]
+    shm-size = \"2g\"
+    devices = [\"/dev/video*\"]
+    shares = [\"~/Videos\", \"/srv/media:ro\"]
+    container-mode = \"persistent\"
+    kind = \"gui\"
+
+Examples:
+    debian_bridge presets list";
+
+const CONVERT_ABOUT: &str = "\
+Rewrite the config file in another format next to it, the old file is removed.
+
+Examples:
+    debian_bridge config convert --to json";
+
+const CONFIG_GET_ABOUT: &str = "\
+Print the global settings, which are defaults of every program, or one of them. The keys are
+features, desktop-entries, backend, base-image, apt-mirror, jobs, logs, notify,
+notify-after, keep-previous and offline-probe.
+
+Examples:
+    debian_bridge config get
+    debian_bridge config get base-image";
+
+const CONFIG_SET_ABOUT: &str = "\
+Change a global setting, the flags of a command take precedence over it:
+
+    features          features of programs created without feature flags, names of the
+                      flags separated by commas, e.g. display,sound
+    desktop-entries   whether created programs get a desktop entry, true or false
+    backend           container engine, only docker is supported
+    base-image        image the base of every program is built from, debian:9-slim by
+                      default
+    apt-mirror        Debian mirror used by every build
+    jobs              programs `repair` builds at once, one per core up to 4 by default
+    logs              whether the output of the runs is written to log files, true by
+                      default
+    notify            whether create, edit, repair and rebuild-base notify on the desktop
+                      when they end, false by default
+    notify-after      seconds a build takes before it's notified, 30 by default
+    keep-previous     age after which `clean` removes the images `rollback` goes back to,
+                      e.g. 2w, 30d by default
+    offline-probe     whether builds probe the Debian mirror and work offline, like
+                      --offline, if it's unreachable, true by default
+
+An empty value unsets the setting.
+
+Examples:
+    debian_bridge config set features display,sound
+    debian_bridge config set desktop-entries false
+    debian_bridge config set apt-mirror ''";
+
+const CLEAN_ABOUT: &str = "\
+Remove exited containers, base images which are not used by any program and previous
+images, see `rollback`, older than the keep-previous setting. --trash deletes the backups
+of removed programs as well, --logs all but the latest log of every program.
+
+Examples:
+    debian_bridge clean
+    debian_bridge clean --trash --logs";
+
+const PURGE_ABOUT: &str = "\
+Remove all programs, images, containers, the cache and the config. Nothing is removed
+unless --i-understand is given.
+
+Examples:
+    debian_bridge purge --i-understand";
+
+const REBUILD_BASE_ABOUT: &str = "\
+Rebuild the shared base image to pick up security updates, programs pick it up when
+they are rebuilt.
+
+Examples:
+    debian_bridge rebuild-base";
+
+const COMPLETIONS_ABOUT: &str = "\
+Print or install a completion script for the given shell. Installed scripts go to the
+directory the shell loads them from, rc files are never touched.
+
+Examples:
+    debian_bridge completions --shell bash > /etc/bash_completion.d/debian_bridge
+    debian_bridge completions --shell zsh --install";
+
+const CREATE_ABOUT: &str = "\
+Create new docker build for existed package, or for an existing docker image with
+--from-image. Features share parts of the host with the program, everything else is
+isolated. A package given alone on a terminal starts a wizard asking for the features
+and the command, --non-interactive skips it.
+
+Examples:
+    debian_bridge create ./skypeforlinux-64.deb --display --sound --desktop-icon default
+    debian_bridge create ./app.deb --dependencies \"libgtk-3-0 libnss3\" --dry-run
+    debian_bridge create ./app.deb --local-repo /srv/mirror/debian
+    debian_bridge create ./htop.deb --cli --wrapper
+    debian_bridge create ./slack.deb --display --label work
+    debian_bridge create --preset browser ./chrome.deb --shm-size 1g
+    debian_bridge create --from-image jess/firefox:latest --name firefox --display";
+
+const EDIT_ABOUT: &str = "\
+Change features and settings of existed program. The image is rebuilt if the change
+needs it, e.g. for new dependencies.
+
+Examples:
+    debian_bridge edit skypeforlinux --home --remove-feature devices
+    debian_bridge edit skypeforlinux --shm-size 2g --autostart on";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ErrorKind;
+
+    fn parse(args: &[&str]) -> clap::Result<clap::ArgMatches<'static>> {
+        build("debian_bridge")
+            .get_matches_from_safe(std::iter::once("debian_bridge").chain(args.iter().cloned()))
+    }
+
+    fn error(args: &[&str]) -> ErrorKind {
+        parse(args).err().map(|err| err.kind).unwrap()
+    }
+
+    #[test]
+    fn test_help_without_arguments() {
+        assert_eq!(error(&[]), ErrorKind::MissingArgumentOrSubcommand);
+        assert_eq!(error(&["config"]), ErrorKind::MissingArgumentOrSubcommand);
+        assert_eq!(error(&["--help"]), ErrorKind::HelpDisplayed);
+    }
+
+    #[test]
+    fn test_global_arguments() {
+        let matches = parse(&["-vv", "list", "--color", "never", "-y", "-c", "cfg"]).unwrap();
+        let list = matches.subcommand_matches("list").unwrap();
+
+        assert_eq!(matches.occurrences_of("verbose"), 2);
+        assert_eq!(list.value_of("color"), Some("never"));
+        assert_eq!(list.value_of("config"), Some("cfg"));
+        assert!(list.is_present("yes"));
+        assert_eq!(parse(&["list"]).unwrap().value_of("color"), Some("auto"));
+        assert_eq!(
+            parse(&["export", "foo", "--output", "json", "-o", "foo.yaml"])
+                .unwrap()
+                .value_of("output"),
+            Some("json")
+        );
+        assert_eq!(
+            error(&["--output", "yaml", "list"]),
+            ErrorKind::InvalidValue
+        );
+        assert_eq!(
+            error(&["--color", "sometimes", "list"]),
+            ErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn test_create_and_edit() {
+        let matches = parse(&[
+            "create",
+            "foo.deb",
+            "-dsh",
+            "--cap-add",
+            "NET_ADMIN",
+            "--cap-add",
+            "SYS_PTRACE",
+            "--nice",
+            "-5",
+            "--restart",
+            "no",
+            "--autostart",
+            "--label",
+            "work",
+        ])
+        .unwrap();
+        let create = matches.subcommand_matches("create").unwrap();
+
+        assert_eq!(create.value_of("package"), Some("foo.deb"));
+        assert!(create.is_present("display") && create.is_present("sound"));
+        assert!(create.is_present("home") && create.is_present("autostart"));
+        assert_eq!(create.values_of("cap-add").unwrap().count(), 2);
+        assert_eq!(create.value_of("nice"), Some("-5"));
+        assert_eq!(create.value_of("label"), Some("work"));
+
+        let matches = parse(&[
+            "edit",
+            "foo",
+            "--autostart",
+            "off",
+            "--remove-feature",
+            "sound",
+            "--oom-score-adj",
+            "-500",
+        ])
+        .unwrap();
+        let edit = matches.subcommand_matches("edit").unwrap();
+
+        assert_eq!(edit.value_of("autostart"), Some("off"));
+        assert_eq!(edit.value_of("remove-feature"), Some("sound"));
+        assert_eq!(edit.value_of("oom-score-adj"), Some("-500"));
+
+        assert_eq!(error(&["create"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            error(&["create", "--from-image", "jess/firefox"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            error(&[
+                "create",
+                "--from-image",
+                "jess/firefox",
+                "--name",
+                "ff",
+                "--dry-run"
+            ]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["edit", "foo", "--pre-run", "true", "--remove-pre-run"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["edit", "foo", "--remove-feature", "wifi"]),
+            ErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn test_requirements() {
+        assert!(parse(&["remove", "foo", "bar"]).is_ok());
+        assert!(parse(&["repair", "--all"]).is_ok());
+        assert!(parse(&["repair", "--group", "work"]).is_ok());
+        assert!(parse(&["run", "--group", "work"]).is_ok());
+        assert_eq!(error(&["remove"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(
+            error(&["remove", "foo", "--group", "work"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["list", "--group", "work", "--all-profiles"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["label", "foo", "rename", "work"]),
+            ErrorKind::InvalidValue
+        );
+        assert_eq!(
+            error(&["verify", "foo", "--all"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["run", "foo", "--pulse-cookie", "cookie"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse(&["run", "foo", "--", "--version", "-v"])
+                .unwrap()
+                .subcommand_matches("run")
+                .unwrap()
+                .values_of("args")
+                .unwrap()
+                .collect::<Vec<&str>>(),
+            vec!["--version", "-v"]
+        );
+        assert_eq!(
+            error(&["export", "foo", "--format", "image"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            error(&["service", "foo", "restart"]),
+            ErrorKind::InvalidValue
+        );
+        assert!(parse(&["config", "convert", "--to", "toml"]).is_ok());
+        assert!(parse(&["presets", "list"]).is_ok());
+        assert!(parse(&["create", "--preset", "browser", "foo.deb"]).is_ok());
+        assert_eq!(error(&["presets"]), ErrorKind::MissingArgumentOrSubcommand);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut help = vec![];
+
+        build("debian_bridge").write_help(&mut help).unwrap();
+
+        let help = String::from_utf8(help).unwrap();
+
+        for (command, aliases) in ALIASES {
+            for alias in *aliases {
+                let args: &[&str] = match *command {
+                    "list" => &[alias],
+                    _ => &[alias, "foo"],
+                };
+
+                assert_eq!(parse(args).unwrap().subcommand_name(), Some(*command));
+                assert_eq!(resolve(alias), *command);
+            }
+
+            assert!(help.contains(&format!("[aliases: {}]", aliases.join(", "))));
+        }
+
+        assert_eq!(
+            parse(&["add", "foo.deb", "--display"])
+                .unwrap()
+                .subcommand_matches("create")
+                .unwrap()
+                .value_of("package"),
+            Some("foo.deb")
+        );
+        assert_eq!(resolve("edit"), "edit");
+        assert_eq!(aliases("remove"), &["rm"]);
+        assert!(aliases("edit").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_subcommand() {
+        let unknown = |args: &[&str]| {
+            let args: Vec<String> = std::iter::once("debian_bridge")
+                .chain(args.iter().cloned())
+                .map(str::to_string)
+                .collect();
+
+            unknown_subcommand(&build("debian_bridge"), &args).map(|(unknown, _)| unknown)
+        };
+        let (firefox, matches) = unknown_subcommand(
+            &build("debian_bridge"),
+            &["debian_bridge", "-v", "-c", "cfg", "firefox", "--detach"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<String>>(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            firefox,
+            UnknownSubcommand {
+                name: "firefox".to_string(),
+                position: 4,
+            }
+        );
+        assert_eq!(matches.value_of("config"), Some("cfg"));
+        assert_eq!(unknown(&["firefox"]).unwrap().position, 1);
+        assert_eq!(unknown(&["list", "--long"]), None);
+        assert_eq!(unknown(&["run", "firefox"]), None);
+        assert_eq!(unknown(&["help"]), None);
+        assert_eq!(unknown(&["man"]), None);
+        assert_eq!(unknown(&[]), None);
+        assert!(is_subcommand("rebuild-base"));
+        assert!(is_subcommand("ls"));
+        assert!(!is_subcommand("firefox"));
+    }
+}
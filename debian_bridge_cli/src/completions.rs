@@ -0,0 +1,234 @@
+use crate::cli;
+use clap::{App, AppSettings, Arg, Shell, SubCommand};
+use std::{error::Error, io::Write, path::PathBuf};
+
+/// Subcommands which take a program name as their first argument
+pub const NAME_COMMANDS: &[&str] = &[
+    "run",
+    "remove",
+    "info",
+    "logs",
+    "permissions",
+    "repair",
+    "rename",
+    "label",
+    "rollback",
+    "edit",
+];
+
+/// `NAME_COMMANDS` with their aliases
+fn name_commands() -> Vec<&'static str> {
+    NAME_COMMANDS
+        .iter()
+        .flat_map(|command| std::iter::once(*command).chain(cli::aliases(command).iter().cloned()))
+        .collect()
+}
+
+/// Hidden subcommand completion scripts call to get program names
+pub const COMPLETE_COMMAND: &str = "__complete";
+
+/// Command of `__complete` printing profile names instead of program names
+pub const PROFILE_COMPLETION: &str = "profile";
+
+/// Hidden subcommand printing program names. It's added apart from the other subcommands as clap
+/// can't generate bash completion for a subcommand with `__` in its name
+pub fn complete_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(COMPLETE_COMMAND)
+        .about("Print names of the programs starting with a prefix, used by completion scripts")
+        .setting(AppSettings::Hidden)
+        .arg(
+            Arg::with_name("command")
+                .required(true)
+                .index(1)
+                .help("Subcommand being completed"),
+        )
+        .arg(
+            Arg::with_name("partial")
+                .index(2)
+                .help("Typed part of a program name"),
+        )
+}
+
+/// Writes a completion script for `shell`: static completion generated by clap, extended to
+/// complete program names by calling `<bin> __complete <subcommand> <partial>` and profile
+/// names by calling `<bin> __complete profile <partial>`
+pub fn generate<W: Write>(
+    cli: &mut App,
+    bin: &str,
+    shell: Shell,
+    out: &mut W,
+) -> std::io::Result<()> {
+    let mut script = vec![];
+
+    cli.gen_completions_to(bin, shell, &mut script);
+
+    let script = String::from_utf8_lossy(&script).to_string();
+    let script = match shell {
+        Shell::Bash => format!("{}\n{}", script, gen_bash(bin)),
+        Shell::Zsh => gen_zsh(&script, bin),
+        Shell::Fish => format!("{}\n{}", script, gen_fish(bin)),
+        _ => script,
+    };
+
+    out.write_all(script.as_bytes())
+}
+
+/// Writes a completion script where the shell loads it from automatically, rc files are never
+/// touched. Returns the script path and whether it was changed
+pub fn install(cli: &mut App, bin: &str, shell: Shell) -> Result<(PathBuf, bool), Box<dyn Error>> {
+    let path = get_install_path(bin, shell)?;
+    let mut script = vec![];
+
+    generate(cli, bin, shell, &mut script)?;
+
+    if std::fs::read(&path).ok().as_ref() == Some(&script) {
+        return Ok((path, false));
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(&path, script)?;
+
+    Ok((path, true))
+}
+
+fn get_install_path(bin: &str, shell: Shell) -> Result<PathBuf, Box<dyn Error>> {
+    let dirs = xdg::BaseDirectories::new()?;
+
+    match shell {
+        Shell::Bash => Ok(dirs
+            .get_data_home()
+            .join("bash-completion/completions")
+            .join(bin)),
+        Shell::Zsh => Ok(dirs
+            .get_data_home()
+            .join("zsh/site-functions")
+            .join(format!("_{}", bin))),
+        Shell::Fish => Ok(dirs
+            .get_config_home()
+            .join("fish/completions")
+            .join(format!("{}.fish", bin))),
+        _ => Err(format!("Completion can't be installed for {}", shell).into()),
+    }
+}
+
+/// Program names starting with `partial`, case is ignored
+pub fn complete_names(names: &[String], partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+
+    names
+        .iter()
+        .filter(|name| name.to_lowercase().starts_with(&partial))
+        .cloned()
+        .collect()
+}
+
+fn gen_bash(bin: &str) -> String {
+    format!(
+        r#"_{bin}_programs() {{
+    local i cmd profile=() cur="${{COMP_WORDS[COMP_CWORD]}}"
+
+    if [[ "${{COMP_WORDS[COMP_CWORD-1]}}" == --profile ]]; then
+        COMPREPLY=( $({bin} {complete} {profiles} "${{cur}}" 2>/dev/null) )
+        return 0
+    fi
+
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        case "${{COMP_WORDS[i]}}" in
+            --profile)
+                profile=( --profile "${{COMP_WORDS[i+1]}}" )
+                (( i++ ))
+                ;;
+            -c|--config|--cache-dir)
+                (( i++ ))
+                ;;
+            -*)
+                ;;
+            *)
+                cmd="${{COMP_WORDS[i]}}"
+                break
+                ;;
+        esac
+    done
+
+    case "${{cmd}}" in
+        {commands})
+            if [[ ${{cur}} != -* && ${{COMP_CWORD}} -eq $(( i + 1 )) ]]; then
+                COMPREPLY=( $({bin} "${{profile[@]}}" {complete} "${{cmd}}" "${{cur}}" 2>/dev/null) )
+                return 0
+            fi
+            ;;
+    esac
+
+    _{bin} "$@"
+}}
+
+complete -F _{bin}_programs -o bashdefault -o default {bin}
+"#,
+        bin = bin,
+        commands = name_commands().join("|"),
+        complete = COMPLETE_COMMAND,
+        profiles = PROFILE_COMPLETION,
+    )
+}
+
+fn gen_zsh(script: &str, bin: &str) -> String {
+    let function = format!(
+        r#"(( $+functions[_{bin}_programs] )) ||
+_{bin}_programs() {{
+    local cmd=${{${{curcontext##*-command-}}%%:*}}
+    compadd -- ${{(f)"$({bin} {complete} $cmd $PREFIX 2>/dev/null)"}}
+}}
+
+(( $+functions[_{bin}_profiles] )) ||
+_{bin}_profiles() {{
+    compadd -- ${{(f)"$({bin} {complete} {profiles} $PREFIX 2>/dev/null)"}}
+}}
+
+"#,
+        bin = bin,
+        complete = COMPLETE_COMMAND,
+        profiles = PROFILE_COMPLETION,
+    );
+    let script = script
+        .replace(
+            "':name -- Program name:_files'",
+            &format!("':name -- Program name:_{}_programs'", bin),
+        )
+        .replace(
+            "DEBIAN_BRIDGE_PROFILE\\]]'",
+            &format!("DEBIAN_BRIDGE_PROFILE\\]]:profile:_{}_profiles'", bin),
+        );
+    let entry = format!("_{} \"$@\"", bin);
+
+    match script.rfind(&entry) {
+        Some(idx) => format!("{}{}{}", &script[..idx], function, &script[idx..]),
+        None => format!("{}\n{}", script, function),
+    }
+}
+
+fn gen_fish(bin: &str) -> String {
+    let profiles = format!(
+        "complete -c {bin} -l profile -x -a \"({bin} {complete} {profiles} (commandline -ct) \
+         2>/dev/null)\"\n",
+        bin = bin,
+        complete = COMPLETE_COMMAND,
+        profiles = PROFILE_COMPLETION,
+    );
+
+    name_commands()
+        .iter()
+        .map(|command| {
+            format!(
+                "complete -c {bin} -n \"__fish_seen_subcommand_from {command}\" -f -a \"({bin} \
+                 {complete} {command} (commandline -ct) 2>/dev/null)\"\n",
+                bin = bin,
+                command = command,
+                complete = COMPLETE_COMMAND,
+            )
+        })
+        .chain(std::iter::once(profiles))
+        .collect()
+}
@@ -1,12 +1,21 @@
-#[macro_use]
 extern crate clap;
 #[macro_use]
 extern crate log;
 extern crate pretty_env_logger;
+#[macro_use]
+extern crate serde_json;
 extern crate xdg;
 
+mod cli;
+mod completions;
+mod man;
 mod matcher;
+mod notify;
+mod output;
+mod settings;
 mod starter;
+mod style;
+mod wizard;
 
 pub use matcher::*;
 pub use starter::start;
@@ -0,0 +1,145 @@
+use crate::cli;
+use clap::{App, AppSettings, Arg, SubCommand};
+
+/// Hidden subcommand writing the man pages, for distribution packaging
+pub const MAN_COMMAND: &str = "man";
+
+pub fn man_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(MAN_COMMAND)
+        .about("Write the man pages of the program and its subcommands")
+        .setting(AppSettings::Hidden)
+        .arg(
+            Arg::with_name("out-dir")
+                .long("out-dir")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Write every page to the directory instead of printing the main one"),
+        )
+}
+
+/// Man pages rendered from the help of `cli`: `<bin>.1` first, then `<bin>-<subcommand>.1` for
+/// every subcommand. Returns file names with their contents
+pub fn pages(cli: &App, bin: &str, version: &str) -> Vec<(String, String)> {
+    let source = format!("{} {}", bin, version);
+    let subcommands = cli::subcommands();
+    let mut pages = vec![(
+        format!("{}.1", bin),
+        page(
+            cli.clone().bin_name(bin),
+            bin,
+            &source,
+            &subcommands
+                .iter()
+                .map(|subcommand| format!("{}-{}(1)", bin, subcommand.get_name()))
+                .collect::<Vec<String>>(),
+        ),
+    )];
+
+    for subcommand in subcommands {
+        let name = format!("{}-{}", bin, subcommand.get_name());
+        let usage = format!("{} {}", bin, subcommand.get_name());
+
+        pages.push((
+            format!("{}.1", name),
+            page(
+                subcommand.bin_name(usage),
+                &name,
+                &source,
+                &[format!("{}(1)", bin)],
+            ),
+        ));
+    }
+
+    pages
+}
+
+fn page(app: App, name: &str, source: &str, see_also: &[String]) -> String {
+    format!(
+        ".TH \"{}\" \"1\" \"\" \"{}\" \"User Commands\"\n\
+         .SH NAME\n{} \\- {}\n\
+         .SH SYNOPSIS\n.nf\n{}\n.fi\n\
+         .SH DESCRIPTION\n.nf\n{}\n.fi\n\
+         .SH OPTIONS\n.nf\n{}\n.fi\n\
+         .SH \"SEE ALSO\"\n{}\n",
+        escape(&name.to_uppercase()),
+        escape(source),
+        escape(name),
+        escape(&render(&app, "{about}", false)),
+        escape(&render(&app, "{usage}", false)),
+        escape(&render(&app, "{long-about}", true)),
+        escape(&render(&app, "{all-args}", true)),
+        escape(&see_also.join(", "))
+    )
+}
+
+/// Part of the help `template` selects, trailing spaces clap pads the long help with are dropped
+fn render(app: &App, template: &str, long: bool) -> String {
+    let mut app = app.clone().template(template);
+    let mut out = vec![];
+    let _ = match long {
+        true => app.write_long_help(&mut out),
+        false => app.write_help(&mut out),
+    };
+
+    String::from_utf8_lossy(&out)
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Escapes text for roff, which takes a line starting with a dot or an apostrophe as a request
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(
+            |line| match line.starts_with('.') || line.starts_with('\'') {
+                true => format!("\\&{}", line),
+                false => line.to_string(),
+            },
+        )
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages() {
+        let pages = pages(&cli::build("debian_bridge"), "debian_bridge", "1.0.0");
+        let (name, main) = &pages[0];
+
+        assert_eq!(name, "debian_bridge.1");
+        assert!(main.starts_with(".TH \"DEBIAN_BRIDGE\" \"1\" \"\" \"debian_bridge 1.0.0\""));
+        assert!(main.contains("debian_bridge \\- Bridge to run .deb packages"));
+        assert!(main.contains("debian_bridge\\-run(1), debian_bridge\\-stop(1)"));
+        assert_eq!(pages.len(), cli::subcommands().len() + 1);
+
+        let (name, run) = pages
+            .iter()
+            .find(|(name, _)| name == "debian_bridge-run.1")
+            .unwrap();
+
+        assert_eq!(name, "debian_bridge-run.1");
+        assert!(run.contains(".SH NAME\ndebian_bridge\\-run \\- Run installed program\n"));
+        assert!(run.contains(
+            ".SH SYNOPSIS\n.nf\ndebian_bridge run [FLAGS] [OPTIONS] <name> [\\-\\- <ARGS>...]\n"
+        ));
+        assert!(run.contains("    debian_bridge run skypeforlinux \\-\\-detach\n"));
+        assert!(run.contains("\\-\\-pulse\\-cookie <PATH>"));
+        assert!(run.ends_with(".SH \"SEE ALSO\"\ndebian_bridge(1)\n"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(".dot\n'quote\nback\\slash --flag"),
+            "\\&.dot\n\\&'quote\nback\\eslash \\-\\-flag"
+        );
+    }
+}
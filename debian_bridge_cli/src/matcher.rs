@@ -1,3 +1,4 @@
+use crate::cli;
 use clap::ArgMatches;
 
 pub struct CommandMatcher<'a> {
@@ -9,17 +10,22 @@ impl<'a> CommandMatcher<'a> {
         CommandMatcher { matches }
     }
 
+    /// Subcommand given, an alias is resolved to the subcommand it stands for
+    pub fn command(&self) -> Option<&str> {
+        self.matches.subcommand_name().map(cli::resolve)
+    }
+
     pub fn is_option_present<T, S>(&self, command: T, option: S) -> bool
     where
         T: Into<String>,
         S: Into<String>,
     {
-        let command = command.into();
+        let command = cli::resolve(&command.into()).to_string();
         let option = option.into();
 
         self.matches
             .subcommand_matches(&command)
-            .expect(format!("No command '{}' presented", command).as_str())
+            .unwrap_or_else(|| panic!("No command '{}' presented", command))
             .is_present(option)
     }
 
@@ -28,13 +34,29 @@ impl<'a> CommandMatcher<'a> {
         T: Into<String>,
         S: Into<String>,
     {
-        let command = command.into();
+        let command = cli::resolve(&command.into()).to_string();
         let arg = arg.into();
 
         self.matches
             .subcommand_matches(&command)
-            .expect(format!("No command '{}' presented", command).as_str())
+            .unwrap_or_else(|| panic!("No command '{}' presented", command))
             .value_of(&arg)
             .map(|s| s.to_string())
     }
+
+    pub fn get_arguments<T, S>(&self, command: T, arg: S) -> Vec<String>
+    where
+        T: Into<String>,
+        S: Into<String>,
+    {
+        let command = cli::resolve(&command.into()).to_string();
+        let arg = arg.into();
+
+        self.matches
+            .subcommand_matches(&command)
+            .unwrap_or_else(|| panic!("No command '{}' presented", command))
+            .values_of(&arg)
+            .map(|values| values.map(|s| s.to_string()).collect())
+            .unwrap_or_default()
+    }
 }
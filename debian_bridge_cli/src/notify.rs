@@ -0,0 +1,144 @@
+use std::{
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/// Subcommands building images, their end is notified if they take long
+pub const NOTIFY_COMMANDS: &[&str] = &["create", "edit", "repair", "rebuild-base"];
+
+/// Summary and body of the notification about the end of `command` on `target`, e.g. a program
+pub fn message(
+    package_name: &str,
+    command: &str,
+    target: &str,
+    elapsed: Duration,
+    error: Option<&str>,
+) -> (String, String) {
+    let action = match command {
+        "create" => "Creating",
+        "edit" => "Updating",
+        "repair" => "Repairing",
+        _ => "Rebuilding",
+    };
+    let took = format!("Took {}", human_elapsed(elapsed));
+
+    match error {
+        Some(error) => (
+            format!("{}: {} {} failed", package_name, action, target),
+            format!("{}\n{}", error, took),
+        ),
+        None => (
+            format!("{}: {} {} is done", package_name, action, target),
+            took,
+        ),
+    }
+}
+
+/// Shows a notification on the desktop of the host with notify-send, or over the session bus
+/// with gdbus if it's missing
+pub fn send(summary: &str, body: &str, failed: bool) -> Result<(), String> {
+    let urgency = match failed {
+        true => "critical",
+        false => "normal",
+    };
+    let notify_send = Command::new("notify-send")
+        .args([
+            "--app-name",
+            "debian_bridge",
+            "--urgency",
+            urgency,
+            summary,
+            body,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let status = match notify_send {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Command::new("gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.Notifications",
+                "--object-path",
+                "/org/freedesktop/Notifications",
+                "--method",
+                "org.freedesktop.Notifications.Notify",
+                "'debian_bridge'",
+                "0",
+                "''",
+                &gvariant_string(summary),
+                &gvariant_string(body),
+                "[]",
+                "{}",
+                "-1",
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status(),
+        status => status,
+    };
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("the notifier exited with {}", status)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err("neither notify-send nor gdbus is installed".to_string())
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// String in the GVariant text format gdbus reads its arguments in
+fn gvariant_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// e.g. `4m 05s`
+fn human_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            message(
+                "debian_bridge",
+                "create",
+                "skypeforlinux",
+                Duration::from_secs(245),
+                None
+            ),
+            (
+                "debian_bridge: Creating skypeforlinux is done".to_string(),
+                "Took 4m 05s".to_string()
+            )
+        );
+        assert_eq!(
+            message(
+                "debian_bridge",
+                "repair",
+                "all programs",
+                Duration::from_secs(3700),
+                Some("Docker error")
+            ),
+            (
+                "debian_bridge: Repairing all programs failed".to_string(),
+                "Docker error\nTook 1h 01m".to_string()
+            )
+        );
+        assert_eq!(gvariant_string("it's \\ done"), "'it\\'s \\\\ done'");
+    }
+}
@@ -0,0 +1,308 @@
+use debian_bridge_core::{error::AppError, SystemError};
+use serde_json::{Map, Value};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Request was invalid, e.g. an unknown program or a malformed argument
+pub const EXIT_USER: i32 = 1;
+/// Docker or the host failed, e.g. the daemon is unreachable
+pub const EXIT_ENVIRONMENT: i32 = 2;
+/// Program run by `run` exited with a nonzero code or was killed
+pub const EXIT_PROGRAM: i32 = 3;
+
+/// How subcommands report, see `--output`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    /// Exactly one JSON document on the standard output, progress and logs go to the standard
+    /// error
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format '{}'", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Whether JSON output is asked for by raw arguments, for errors of parsing them
+    pub fn requested<T: AsRef<str>>(args: &[T]) -> Self {
+        let json = args
+            .windows(2)
+            .any(|pair| pair[0].as_ref() == "--output" && pair[1].as_ref() == "json")
+            || args.iter().any(|arg| arg.as_ref() == "--output=json");
+
+        match json {
+            true => OutputFormat::Json,
+            false => OutputFormat::Text,
+        }
+    }
+}
+
+/// Failures of the command line itself, beside the errors of the core
+#[derive(Debug)]
+pub enum CliError {
+    /// Arguments can't be parsed
+    Usage(String),
+    /// Subcommand finished without fully succeeding, e.g. a program wasn't removed. `details`
+    /// is the document it would have printed otherwise
+    Failed {
+        message: String,
+        details: Option<Value>,
+    },
+    /// Program exited with the code, `None` if it was killed by a signal
+    ProgramExit {
+        code: Option<i32>,
+        details: Option<Value>,
+    },
+}
+
+impl CliError {
+    pub fn failed<T: Into<String>>(message: T) -> Self {
+        CliError::Failed {
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn details(self, document: Option<Value>) -> Self {
+        match self {
+            CliError::Failed { message, .. } => CliError::Failed {
+                message,
+                details: document,
+            },
+            CliError::ProgramExit { code, .. } => CliError::ProgramExit {
+                code,
+                details: document,
+            },
+            err => err,
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Failed { message, .. } => write!(f, "{}", message),
+            CliError::ProgramExit {
+                code: Some(code), ..
+            } => {
+                write!(f, "Program exited with code {}", code)
+            }
+            CliError::ProgramExit { code: None, .. } => {
+                write!(f, "Program was terminated by a signal")
+            }
+        }
+    }
+}
+
+/// What the failed subcommand was about, taken from its arguments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorContext {
+    pub program: Option<String>,
+    /// Package of `create`
+    pub package: Option<PathBuf>,
+    /// Tarball of `import`
+    pub archive: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+}
+
+pub fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+    if let Some(err) = err.downcast_ref::<AppError>() {
+        return match err.is_environment() {
+            true => EXIT_ENVIRONMENT,
+            false => EXIT_USER,
+        };
+    }
+
+    match err.downcast_ref::<CliError>() {
+        Some(CliError::ProgramExit { .. }) => EXIT_PROGRAM,
+        _ if err.is::<SystemError>() => EXIT_ENVIRONMENT,
+        _ => EXIT_USER,
+    }
+}
+
+/// JSON document of an error: a stable `kind`, the message and the fields which apply of the
+/// program, the path, the docker status code and the exit code of the program
+pub fn error_document(err: &(dyn Error + 'static), context: &ErrorContext) -> Value {
+    let mut document = Map::new();
+    let app_error = err.downcast_ref::<AppError>();
+    let kind = match (app_error, err.downcast_ref::<CliError>()) {
+        (Some(err), _) => err.kind(),
+        (_, Some(CliError::Usage(_))) => "usage",
+        (_, Some(CliError::Failed { .. })) => "failed",
+        (_, Some(CliError::ProgramExit { .. })) => "program_exit",
+        _ => match err.downcast_ref::<SystemError>() {
+            Some(SystemError::DockerConnection) => "docker_connection",
+            Some(SystemError::UnsupportedDocker { .. }) => "unsupported_docker",
+            None if err.is::<std::io::Error>() => "file",
+            None => "other",
+        },
+    };
+    let path = match app_error {
+        Some(AppError::Deb(_, _)) => context.package.as_ref().or(context.archive.as_ref()),
+        Some(AppError::Config(_, _)) | Some(AppError::Locked) => context.config.as_ref(),
+        _ => None,
+    };
+
+    document.insert("kind".to_string(), kind.into());
+    document.insert("message".to_string(), err.to_string().into());
+
+    if let Some(program) = &context.program {
+        document.insert("program".to_string(), program.as_str().into());
+    }
+
+    if let Some(path) = path {
+        document.insert("path".to_string(), path.display().to_string().into());
+    }
+
+    match (app_error, err.downcast_ref::<CliError>()) {
+        (Some(AppError::DockerStatus { code, .. }), _) => {
+            document.insert("docker_status".to_string(), (*code).into());
+        }
+        (Some(AppError::Offline { steps, .. }), _) => {
+            document.insert("steps".to_string(), json!(steps));
+        }
+        (_, Some(CliError::ProgramExit { code, details })) => {
+            document.insert("exit_code".to_string(), json!(code));
+            document.insert("details".to_string(), json!(details));
+        }
+        (_, Some(CliError::Failed { details, .. })) => {
+            document.insert("details".to_string(), json!(details));
+        }
+        _ => (),
+    }
+
+    Value::Object(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use debian_bridge_core::NetworkStep;
+
+    fn context() -> ErrorContext {
+        ErrorContext {
+            program: Some("foo".to_string()),
+            package: Some(PathBuf::from("/tmp/foo.deb")),
+            archive: None,
+            config: Some(PathBuf::from("/tmp/config.json")),
+        }
+    }
+
+    #[test]
+    fn test_error_document() {
+        let err = AppError::DockerStatus {
+            code: 404,
+            message: "No such image".to_string(),
+            operation: "inspect an image".to_string(),
+        };
+
+        assert_eq!(
+            error_document(&err, &context()),
+            json!({
+                "kind": "docker_status",
+                "message": err.to_string(),
+                "program": "foo",
+                "docker_status": 404,
+            })
+        );
+        assert_eq!(
+            error_document(&AppError::Deb("no control".to_string(), None), &context())["path"],
+            json!("/tmp/foo.deb")
+        );
+        assert_eq!(
+            error_document(
+                &CliError::ProgramExit {
+                    code: Some(4),
+                    details: None
+                },
+                &ErrorContext::default()
+            ),
+            json!({
+                "kind": "program_exit",
+                "message": "Program exited with code 4",
+                "exit_code": 4,
+                "details": null,
+            })
+        );
+        assert_eq!(
+            error_document(
+                &CliError::failed("1 check(s) failed").details(Some(json!([]))),
+                &ErrorContext::default()
+            )["details"],
+            json!([])
+        );
+
+        let err: Box<dyn Error> = "Invalid pulse server".into();
+
+        assert_eq!(
+            error_document(err.as_ref(), &ErrorContext::default())["kind"],
+            json!("other")
+        );
+        assert_eq!(
+            error_document(
+                &AppError::Offline {
+                    operation: "build the image of foo".to_string(),
+                    steps: vec![NetworkStep::Pull {
+                        image: "debian:9-slim".to_string()
+                    }],
+                },
+                &context()
+            )["steps"],
+            json!([{ "step": "pull", "image": "debian:9-slim" }])
+        );
+        assert_eq!(
+            error_document(&AppError::Locked, &context())["path"],
+            json!("/tmp/config.json")
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(
+            exit_code(&AppError::Program("missing".to_string())),
+            EXIT_USER
+        );
+        assert_eq!(exit_code(&AppError::Argument("2x".to_string())), EXIT_USER);
+        assert_eq!(
+            exit_code(&AppError::DockerConnection(None)),
+            EXIT_ENVIRONMENT
+        );
+        assert_eq!(exit_code(&SystemError::DockerConnection), EXIT_ENVIRONMENT);
+        assert_eq!(
+            exit_code(&CliError::ProgramExit {
+                code: None,
+                details: None
+            }),
+            EXIT_PROGRAM
+        );
+        assert_eq!(exit_code(&CliError::failed("no match")), EXIT_USER);
+    }
+
+    #[test]
+    fn test_requested() {
+        assert_eq!(
+            OutputFormat::requested(&["list", "--output", "json"]),
+            OutputFormat::Json
+        );
+        assert_eq!(
+            OutputFormat::requested(&["--output=json", "lst"]),
+            OutputFormat::Json
+        );
+        assert_eq!(OutputFormat::requested(&["list"]), OutputFormat::Text);
+    }
+}
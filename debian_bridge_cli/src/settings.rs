@@ -0,0 +1,461 @@
+use clap::ArgMatches;
+use debian_bridge_core::{error::AppError, validate_profile, GlobalSettings, NetworkMode};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const BACKEND_ENV: &str = "DEBIAN_BRIDGE_BACKEND";
+pub const DOCKER_HOST_ENV: &str = "DEBIAN_BRIDGE_DOCKER_HOST";
+pub const CONFIG_ENV: &str = "DEBIAN_BRIDGE_CONFIG";
+pub const CACHE_ENV: &str = "DEBIAN_BRIDGE_CACHE";
+pub const NO_DESKTOP_ENTRY_ENV: &str = "DEBIAN_BRIDGE_NO_DESKTOP_ENTRY";
+pub const PROFILE_ENV: &str = "DEBIAN_BRIDGE_PROFILE";
+pub const NO_CACHE_PROBE_ENV: &str = "DEBIAN_BRIDGE_NO_CACHE_PROBE";
+pub const NOTIFY_ENV: &str = "DEBIAN_BRIDGE_NOTIFY";
+pub const OFFLINE_ENV: &str = "DEBIAN_BRIDGE_OFFLINE";
+/// Profile name standing for the programs of no profile
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Container engine the programs are built and run with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Docker,
+}
+
+impl FromStr for Backend {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "docker" => Ok(Backend::Docker),
+            _ => Err(AppError::Argument(format!(
+                "Unsupported backend '{}', only docker is supported",
+                s
+            ))),
+        }
+    }
+}
+
+/// Global options, each taken from its flag, then from its `DEBIAN_BRIDGE_*` environment
+/// variable, then the default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// `None` if neither the flag nor the variable is set, see `Settings::backend`
+    pub backend: Option<Backend>,
+    /// Daemon address, e.g. `unix:///run/user/1000/docker.sock`. `DOCKER_HOST` or the socket
+    /// found on the host is used without it
+    pub docker_host: Option<String>,
+    /// Config file, the one in the XDG config directory is used without it
+    pub config: Option<PathBuf>,
+    /// Cache directory, the XDG cache directory is used without it
+    pub cache_dir: Option<PathBuf>,
+    /// Programs are created and edited without desktop entries, e.g. on a headless machine
+    pub no_desktop_entry: bool,
+    /// Separate set of programs with its own config, cache, images and desktop entries, `None`
+    /// for the default one
+    pub profile: Option<String>,
+    /// The host and the daemon are probed anew instead of reusing the probe of a recent run
+    pub no_cache_probe: bool,
+    /// Whether long builds are notified on the desktop, `None` if neither `--notify`,
+    /// `--no-notify` nor the variable is set, see `Settings::notify`
+    pub notify: Option<bool>,
+    /// Operations needing the network are refused, see `Settings::network`
+    pub offline: bool,
+}
+
+impl Settings {
+    /// Resolves the settings of the process environment
+    pub fn detect(matches: &ArgMatches) -> Result<Self, AppError> {
+        Self::resolve(matches, |name| std::env::var(name).ok())
+    }
+
+    /// Resolves the settings with `env` looking up environment variables. Empty variables are
+    /// taken as unset
+    pub fn resolve<F>(matches: &ArgMatches, env: F) -> Result<Self, AppError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let value = |arg: &str, name: &str| {
+            matches
+                .value_of(arg)
+                .map(str::to_string)
+                .or_else(|| env(name).filter(|value| !value.is_empty()))
+        };
+        let flag = |name: &str| match env(name).as_deref() {
+            None | Some("") => Ok(None),
+            Some("0") | Some("false") | Some("no") => Ok(Some(false)),
+            Some("1") | Some("true") | Some("yes") => Ok(Some(true)),
+            Some(value) => Err(AppError::Argument(format!(
+                "{} is '{}', expected 1 or 0",
+                name, value
+            ))),
+        };
+        let switch = |arg: &str, name: &str| match matches.is_present(arg) {
+            true => Ok(true),
+            false => flag(name).map(|value| value.unwrap_or(false)),
+        };
+
+        let profile = match value("profile", PROFILE_ENV) {
+            Some(profile) if profile == DEFAULT_PROFILE => None,
+            Some(profile) => {
+                validate_profile(&profile)?;
+                Some(profile)
+            }
+            None => None,
+        };
+
+        Ok(Settings {
+            backend: value("backend", BACKEND_ENV)
+                .map(|backend| backend.parse())
+                .transpose()?,
+            docker_host: value("docker-host", DOCKER_HOST_ENV),
+            config: value("config", CONFIG_ENV).map(PathBuf::from),
+            cache_dir: value("cache-dir", CACHE_ENV).map(PathBuf::from),
+            no_desktop_entry: switch("no-desktop-entry", NO_DESKTOP_ENTRY_ENV)?,
+            profile,
+            no_cache_probe: switch("no-cache-probe", NO_CACHE_PROBE_ENV)?,
+            notify: match (
+                matches.is_present("notify"),
+                matches.is_present("no-notify"),
+            ) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => flag(NOTIFY_ENV)?,
+            },
+            offline: switch("offline", OFFLINE_ENV)?,
+        })
+    }
+
+    /// Backend of the flag or the variable, then of the global settings of the config, docker
+    /// by default
+    pub fn backend(&self, config: &GlobalSettings) -> Result<Backend, AppError> {
+        match (self.backend, &config.backend) {
+            (Some(backend), _) => Ok(backend),
+            (None, Some(backend)) => backend.parse(),
+            (None, None) => Ok(Backend::Docker),
+        }
+    }
+
+    /// Whether long builds are notified, by the flags or the variable, then by the notify
+    /// setting of the config
+    pub fn notify(&self, config: &GlobalSettings) -> bool {
+        self.notify.unwrap_or(config.notify)
+    }
+
+    /// Offline with the flag or the variable, else the network is probed unless the
+    /// offline-probe setting of the config is off
+    pub fn network(&self, config: &GlobalSettings) -> NetworkMode {
+        match (self.offline, config.offline_probe) {
+            (true, _) => NetworkMode::Offline,
+            (false, true) => NetworkMode::Detect,
+            (false, false) => NetworkMode::Online,
+        }
+    }
+}
+
+/// Name of the config file of the profile, e.g. `config.work.json`
+pub fn config_name(profile: Option<&str>, extension: &str) -> String {
+    match profile {
+        Some(profile) => format!("config.{}.{}", profile, extension),
+        None => format!("config.{}", extension),
+    }
+}
+
+/// Profiles which have a config file in `dir` with the file, sorted by name, the default one
+/// is `None`. A TOML file is taken over a JSON one like the config lookup does
+pub fn profiles(dir: &Path) -> Vec<(Option<String>, PathBuf)> {
+    let mut profiles: Vec<(Option<String>, PathBuf)> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter_map(|path| {
+                    let name = path.file_name()?.to_str()?.strip_prefix("config.")?;
+                    let profile = match name {
+                        "json" | "toml" => None,
+                        _ => match name.rsplit_once('.') {
+                            Some((profile, "json")) | Some((profile, "toml"))
+                                if validate_profile(profile).is_ok() =>
+                            {
+                                Some(profile.to_string())
+                            }
+                            _ => return None,
+                        },
+                    };
+
+                    Some((profile, path))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // TOML sorts after JSON, the last file of a profile is kept
+    profiles.sort();
+    profiles.reverse();
+    profiles.dedup_by(|a, b| a.0 == b.0);
+    profiles.reverse();
+    profiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use std::collections::HashMap;
+
+    fn resolve(args: &[&str], env: &[(&str, &str)]) -> Result<Settings, AppError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let matches = cli::build("debian_bridge")
+            .get_matches_from_safe(
+                std::iter::once("debian_bridge")
+                    .chain(args.iter().cloned())
+                    .chain(std::iter::once("list")),
+            )
+            .unwrap();
+
+        Settings::resolve(&matches, |name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(
+            resolve(&[], &[]).unwrap(),
+            Settings {
+                backend: None,
+                docker_host: None,
+                config: None,
+                cache_dir: None,
+                no_desktop_entry: false,
+                profile: None,
+                no_cache_probe: false,
+                notify: None,
+                offline: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_env() {
+        let env = [
+            (BACKEND_ENV, "docker"),
+            (DOCKER_HOST_ENV, "tcp://10.0.0.2:2375"),
+            (CONFIG_ENV, "/etc/debian_bridge.json"),
+            (CACHE_ENV, "/var/cache/debian_bridge"),
+            (NO_DESKTOP_ENTRY_ENV, "1"),
+            (NO_CACHE_PROBE_ENV, "yes"),
+            (NOTIFY_ENV, "0"),
+            (OFFLINE_ENV, "1"),
+        ];
+
+        assert_eq!(
+            resolve(&[], &env).unwrap(),
+            Settings {
+                backend: Some(Backend::Docker),
+                docker_host: Some("tcp://10.0.0.2:2375".to_string()),
+                config: Some(PathBuf::from("/etc/debian_bridge.json")),
+                cache_dir: Some(PathBuf::from("/var/cache/debian_bridge")),
+                no_desktop_entry: true,
+                profile: None,
+                no_cache_probe: true,
+                notify: Some(false),
+                offline: true,
+            }
+        );
+        assert_eq!(
+            resolve(&[], &[(CONFIG_ENV, ""), (NO_DESKTOP_ENTRY_ENV, "false")]).unwrap(),
+            resolve(&[], &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_flags_win() {
+        let env = [
+            (DOCKER_HOST_ENV, "tcp://10.0.0.2:2375"),
+            (CONFIG_ENV, "/etc/debian_bridge.json"),
+            (CACHE_ENV, "/var/cache/debian_bridge"),
+            (NO_DESKTOP_ENTRY_ENV, "0"),
+        ];
+        let settings = resolve(
+            &[
+                "--docker-host",
+                "unix:///run/docker.sock",
+                "-c",
+                "cfg.toml",
+                "--cache-dir",
+                "cache",
+                "--no-desktop-entry",
+                "--no-cache-probe",
+            ],
+            &env,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings.docker_host.as_deref(),
+            Some("unix:///run/docker.sock")
+        );
+        assert_eq!(settings.config, Some(PathBuf::from("cfg.toml")));
+        assert_eq!(settings.cache_dir, Some(PathBuf::from("cache")));
+        assert!(settings.no_desktop_entry);
+        assert!(settings.no_cache_probe);
+        assert_eq!(
+            resolve(&["--notify"], &[(NOTIFY_ENV, "0")]).unwrap().notify,
+            Some(true)
+        );
+        assert_eq!(
+            resolve(&["--no-notify"], &[(NOTIFY_ENV, "1")])
+                .unwrap()
+                .notify,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_notify() {
+        let config = GlobalSettings {
+            notify: true,
+            ..Default::default()
+        };
+
+        assert!(resolve(&[], &[]).unwrap().notify(&config));
+        assert!(!resolve(&[], &[])
+            .unwrap()
+            .notify(&GlobalSettings::default()));
+        assert!(!resolve(&["--no-notify"], &[]).unwrap().notify(&config));
+        assert!(resolve(&[], &[(NOTIFY_ENV, "yes")])
+            .unwrap()
+            .notify(&GlobalSettings::default()));
+    }
+
+    #[test]
+    fn test_network() {
+        let no_probe = GlobalSettings {
+            offline_probe: false,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resolve(&[], &[])
+                .unwrap()
+                .network(&GlobalSettings::default()),
+            NetworkMode::Detect
+        );
+        assert_eq!(
+            resolve(&[], &[]).unwrap().network(&no_probe),
+            NetworkMode::Online
+        );
+        assert_eq!(
+            resolve(&["--offline"], &[]).unwrap().network(&no_probe),
+            NetworkMode::Offline
+        );
+    }
+
+    #[test]
+    fn test_backend() {
+        let config = GlobalSettings {
+            backend: Some("docker".to_string()),
+            ..Default::default()
+        };
+        let unsupported = GlobalSettings {
+            backend: Some("podman".to_string()),
+            ..Default::default()
+        };
+        let settings = resolve(&[], &[]).unwrap();
+
+        assert_eq!(
+            settings.backend(&GlobalSettings::default()).unwrap(),
+            Backend::Docker
+        );
+        assert_eq!(settings.backend(&config).unwrap(), Backend::Docker);
+        assert!(settings.backend(&unsupported).is_err());
+        assert_eq!(
+            resolve(&["--backend", "docker"], &[])
+                .unwrap()
+                .backend(&unsupported)
+                .unwrap(),
+            Backend::Docker
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            resolve(&[], &[(BACKEND_ENV, "podman")]),
+            Err(AppError::Argument(_))
+        ));
+        assert!(matches!(
+            resolve(&[], &[(NO_DESKTOP_ENTRY_ENV, "maybe")]),
+            Err(AppError::Argument(_))
+        ));
+        assert!(matches!(
+            resolve(&[], &[(NO_CACHE_PROBE_ENV, "2")]),
+            Err(AppError::Argument(_))
+        ));
+        assert!(matches!(
+            resolve(&["--profile", "My_Work"], &[]),
+            Err(AppError::Argument(_))
+        ));
+    }
+
+    #[test]
+    fn test_profile() {
+        assert_eq!(
+            resolve(&[], &[(PROFILE_ENV, "work")]).unwrap().profile,
+            Some("work".to_string())
+        );
+        assert_eq!(
+            resolve(&["--profile", "home"], &[(PROFILE_ENV, "work")])
+                .unwrap()
+                .profile,
+            Some("home".to_string())
+        );
+        assert_eq!(
+            resolve(&["--profile", DEFAULT_PROFILE], &[])
+                .unwrap()
+                .profile,
+            None
+        );
+        assert_eq!(config_name(None, "json"), "config.json");
+        assert_eq!(config_name(Some("work"), "toml"), "config.work.toml");
+    }
+
+    #[test]
+    fn test_profiles() {
+        let dir = std::env::temp_dir().join(format!(
+            "debian_bridge_test_profiles_{}",
+            std::process::id()
+        ));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for name in [
+            "config.json",
+            "config.json.bak",
+            "config.work.json",
+            "config.work.toml",
+            "config.home.json",
+            "config.Bad_Name.json",
+            "history.json",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let profiles = profiles(&dir);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            profiles,
+            vec![
+                (None, dir.join("config.json")),
+                (Some("home".to_string()), dir.join("config.home.json")),
+                (Some("work".to_string()), dir.join("config.work.toml")),
+            ]
+        );
+        assert!(super::profiles(&dir).is_empty());
+    }
+}
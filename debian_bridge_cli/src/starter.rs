@@ -1,25 +1,34 @@
-use crate::CommandMatcher;
-use clap::{App, AppSettings, ArgMatches};
-use debian_bridge_core::{App as Wrapper, Config, Docker, Feature, Icon, Program, System};
+use crate::{
+    cli, completions, man, notify,
+    output::{self, CliError, ErrorContext, OutputFormat},
+    settings::{self, Backend, Settings},
+    style::{ColorChoice, Style},
+    wizard, CommandMatcher,
+};
+use clap::{App, AppSettings, ArgMatches, ErrorKind, Shell};
+use debian_bridge_core::{
+    check_docker, error::AppError, gen_profile_prefix, human_size, parse_age, parse_capability,
+    parse_device, parse_env, parse_label, parse_nice, parse_oom_score_adj, parse_share, parse_size,
+    parse_ulimit, user_bin_dir, App as Wrapper, AppEvent, BuildSettings, Config, ContainerMode,
+    CreateRequest, Docker, Feature, Icon, Preset, Program, ProgramChanges, ProgramKind,
+    PulseServer, RemoteDisplay, Repair, Resources, RunOptions, Seccomp, Security, System,
+    SystemError, PRESETS_DIR, SETTING_KEYS,
+};
+use serde_json::Value;
 use std::{
     error::Error,
-    net::IpAddr,
-    path::{Path, PathBuf},
+    io::{IsTerminal, Write},
+    path::{Component, Path, PathBuf},
     str::FromStr,
+    time::{Duration, Instant},
 };
 
-pub fn start<T, S, U>(package_name: T, authors: S, version: U)
-where
-    T: Into<String>,
-    S: Into<String>,
-    U: Into<String>,
-{
-    if let Err(err) = _start(package_name, authors, version) {
-        error!("{}", err.to_string());
-    }
-}
+/// Probe of the host and the daemon in the cache, see `System::cached`
+const PROBE_FILE: &str = "system.json";
 
-fn _start<T, S, U>(package_name: T, authors: S, version: U) -> Result<(), Box<dyn Error>>
+/// Runs the subcommand and exits with the code of `output::exit_code` if it fails. With
+/// `--output json` the failure is printed as a JSON document, parsing errors of clap included
+pub fn start<T, S, U>(package_name: T, authors: S, version: U)
 where
     T: Into<String>,
     S: Into<String>,
@@ -28,15 +37,244 @@ where
     let package_name = package_name.into();
     let authors = authors.into();
     let version = version.into();
+    let mut args: Vec<String> = std::env::args().collect();
+    let requested = OutputFormat::requested(&args);
 
-    let yaml = load_yaml!("../config/cli.yaml");
-    let matches = App::from_yaml(yaml)
-        .setting(AppSettings::ArgRequiredElseHelp)
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .name(&package_name)
+    let mut cli = cli::build(&package_name)
         .author(authors.as_str())
-        .version(version.as_str())
-        .get_matches();
+        .version(version.as_str());
+    // A program name in place of the subcommand runs it
+    let (unknown, config) = match cli::unknown_subcommand(&cli, &args) {
+        Some((unknown, matches)) => (Some(unknown), read_config(&package_name, &matches)),
+        None => (None, None),
+    };
+
+    if let (Some(unknown), Some(config)) = (&unknown, &config) {
+        if config.find(&unknown.name).is_some() {
+            args.insert(unknown.position, "run".to_string());
+        }
+    }
+
+    let parsed = cli
+        .clone()
+        .setting(match requested {
+            OutputFormat::Json => AppSettings::ColorNever,
+            OutputFormat::Text => AppSettings::ColorAuto,
+        })
+        .subcommand(completions::complete_subcommand())
+        .subcommand(man::man_subcommand())
+        .get_matches_from_safe(&args);
+    let matches = match parsed {
+        Ok(matches) => matches,
+        // clap suggests subcommands, the programs close to the name are added
+        Err(mut err)
+            if unknown.is_some()
+                && [
+                    ErrorKind::UnrecognizedSubcommand,
+                    ErrorKind::UnknownArgument,
+                ]
+                .contains(&err.kind) =>
+        {
+            let suggestions = config
+                .zip(unknown)
+                .map(|(config, unknown)| config.suggest(&unknown.name))
+                .unwrap_or_default();
+
+            if let (false, Some((first, rest))) =
+                (suggestions.is_empty(), err.message.clone().split_once('\n'))
+            {
+                err.message = format!(
+                    "{}\n\tDid you mean the program {}?\n{}",
+                    first,
+                    suggestions
+                        .iter()
+                        .map(|suggestion| format!("'{}'", suggestion))
+                        .collect::<Vec<String>>()
+                        .join(" or "),
+                    rest
+                );
+            }
+
+            fail_parsing(err, requested)
+        }
+        Err(err) => fail_parsing(err, requested),
+    };
+    let output = matches
+        .value_of("output")
+        .and_then(|format| format.parse().ok())
+        .unwrap_or(OutputFormat::Text);
+
+    let started = Instant::now();
+    let result = _start(&package_name, &version, &mut cli, &matches, output);
+
+    notify_end(
+        &package_name,
+        &matches,
+        started.elapsed(),
+        result.as_ref().err().map(|err| err.to_string()).as_deref(),
+    );
+
+    if let Err(err) = result {
+        match output {
+            OutputFormat::Json => print_json(&output::error_document(
+                err.as_ref(),
+                &get_error_context(&package_name, &matches),
+            )),
+            OutputFormat::Text => error!("{}", err.to_string()),
+        }
+
+        std::process::exit(output::exit_code(err.as_ref()));
+    }
+}
+
+/// Exits on an error of clap, printed as a JSON document if `--output json` is given
+fn fail_parsing(err: clap::Error, output: OutputFormat) -> ! {
+    if !err.use_stderr() || output == OutputFormat::Text {
+        err.exit();
+    }
+
+    // Only the error and its hints, without the usage which follows an empty line
+    let message = err
+        .message
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .map(|line| line.trim().trim_start_matches("error: "))
+        .collect::<Vec<&str>>()
+        .join(". ");
+    let err = CliError::Usage(message);
+
+    print_json(&output::error_document(&err, &ErrorContext::default()));
+    std::process::exit(output::exit_code(&err));
+}
+
+/// Notifies the end of a build which took longer than the notify-after setting if notifying is
+/// on. A notification which can't be sent doesn't fail the subcommand
+fn notify_end(package_name: &str, matches: &ArgMatches, elapsed: Duration, error: Option<&str>) {
+    let (command, sub_matches) = match matches.subcommand() {
+        (command, Some(sub_matches))
+            if notify::NOTIFY_COMMANDS.contains(&cli::resolve(command)) =>
+        {
+            (cli::resolve(command), sub_matches)
+        }
+        _ => return,
+    };
+    let config = read_config(package_name, matches).unwrap_or_default();
+    let enabled = Settings::detect(matches)
+        .map(|settings| settings.notify(&config.settings))
+        .unwrap_or(false);
+
+    if !enabled || elapsed < config.settings.notify_after() {
+        return;
+    }
+
+    let target = match command {
+        "rebuild-base" => "the base image".to_string(),
+        _ if sub_matches.is_present("all") => "all programs".to_string(),
+        _ => sub_matches
+            .value_of("name")
+            .or_else(|| sub_matches.value_of("from-image"))
+            .map(str::to_string)
+            .or_else(|| {
+                sub_matches
+                    .value_of("package")
+                    .and_then(|path| Path::new(path).file_stem())
+                    .map(|stem| stem.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| "the program".to_string()),
+    };
+    let (summary, body) = notify::message(package_name, command, &target, elapsed, error);
+
+    match notify::send(&summary, &body, error.is_some()) {
+        Ok(()) => debug!("Notified: {}", summary),
+        Err(err) => warn!("Can't notify on the desktop, {}", err),
+    }
+}
+
+/// Config of the settings, `None` if there is none yet or it can't be read
+fn read_config(package_name: &str, matches: &ArgMatches) -> Option<Config> {
+    let path = get_settings_config(package_name, &Settings::detect(matches).ok()?).ok()?;
+
+    match path.exists() {
+        true => Config::deserialize(&path).ok(),
+        false => None,
+    }
+}
+
+fn _start(
+    package_name: &str,
+    version: &str,
+    cli: &mut App,
+    matches: &ArgMatches,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let json = output == OutputFormat::Json;
+
+    if let Some(matches) = matches.subcommand_matches(man::MAN_COMMAND) {
+        let pages = man::pages(cli, package_name, version);
+
+        match (matches.value_of("out-dir"), json) {
+            (Some(dir), _) => {
+                let dir = resolve_path(dir)?;
+                let mut written = vec![];
+
+                std::fs::create_dir_all(&dir)?;
+
+                for (name, page) in &pages {
+                    std::fs::write(dir.join(name), page)?;
+                    written.push(dir.join(name));
+                }
+
+                match json {
+                    true => print_json(&json!({ "pages": written })),
+                    false => written
+                        .iter()
+                        .for_each(|path| println!("{}", path.display())),
+                }
+            }
+            (None, true) => print_json(&json!({ "page": pages[0].1 })),
+            (None, false) => print!("{}", pages[0].1),
+        }
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("completions") {
+        let shell = matches.value_of("shell").unwrap().parse::<Shell>()?;
+
+        if !matches.is_present("install") {
+            let mut script = vec![];
+
+            completions::generate(cli, package_name, shell, &mut script)?;
+
+            match json {
+                true => print_json(&json!({ "script": String::from_utf8_lossy(&script) })),
+                false => std::io::stdout().write_all(&script)?,
+            }
+
+            return Ok(());
+        }
+
+        let (path, changed) = completions::install(cli, package_name, shell)?;
+
+        if json {
+            print_json(&json!({ "path": path, "changed": changed }));
+            return Ok(());
+        }
+
+        match changed {
+            true => println!("Completion script installed to {}", path.display()),
+            false => println!("Completion script {} is up to date", path.display()),
+        }
+
+        if let Shell::Zsh = shell {
+            println!(
+                "Make sure {} is in your fpath",
+                path.parent().unwrap().display()
+            );
+        }
+
+        return Ok(());
+    }
 
     let debug_level = match matches.occurrences_of("verbose") {
         0 => "info",
@@ -49,74 +287,1185 @@ where
 
     debug!("Logger configured: debug level: {}", debug_level);
 
-    let config_path = matches
-        .value_of("config")
-        .map(|c| std::fs::canonicalize(c).unwrap())
-        .unwrap_or(
-            xdg::BaseDirectories::with_prefix(&package_name)?.place_config_file("config.json")?,
-        );
+    let settings = Settings::detect(matches)?;
+
+    debug!("Settings: {:?}", settings);
+
+    let mut config_path = get_settings_config(package_name, &settings)?;
+
+    if let (Some(_), Some(parent)) = (&settings.config, config_path.parent()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    debug!("Configuration path: {}", config_path.display());
+
+    if let Some(matches) = matches.subcommand_matches(completions::COMPLETE_COMMAND) {
+        if matches.value_of("command") == Some(completions::PROFILE_COMPLETION) {
+            let profiles: Vec<String> = get_profiles(package_name)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(profile, _)| profile)
+                .collect();
+
+            for profile in
+                completions::complete_names(&profiles, matches.value_of("partial").unwrap_or(""))
+            {
+                println!("{}", profile);
+            }
+        } else if completions::NAME_COMMANDS
+            .contains(&cli::resolve(matches.value_of("command").unwrap()))
+        {
+            // Completion must not create a config, nor print anything but names
+            let names = match config_path.exists() {
+                true => Config::deserialize(config_path.as_path())
+                    .map(|config| {
+                        config
+                            .programs
+                            .iter()
+                            .map(Program::get_name_short)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                false => vec![],
+            };
+
+            for name in
+                completions::complete_names(&names, matches.value_of("partial").unwrap_or(""))
+            {
+                println!("{}", name);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // Profiles are listed from their configs alone, docker isn't needed
+    if matches
+        .subcommand_matches("list")
+        .is_some_and(|matches| matches.is_present("all-profiles"))
+    {
+        let profiles = get_profiles(package_name)?;
+
+        match json {
+            true => print_json(&json!(profiles
+                .iter()
+                .map(|(profile, programs)| json!({
+                    "profile": profile,
+                    "programs": programs,
+                }))
+                .collect::<Vec<_>>())),
+            false => print!(
+                "Profiles: \n\n{}",
+                profiles
+                    .iter()
+                    .map(|(profile, programs)| format!(
+                        "\t{:<15} ===> {} programs\n",
+                        profile,
+                        programs.len()
+                    ))
+                    .collect::<String>()
+            ),
+        }
+
+        return Ok(());
+    }
+
+    // Presets are read from files alone, docker isn't needed
+    if matches
+        .subcommand_matches("presets")
+        .and_then(ArgMatches::subcommand_name)
+        == Some("list")
+    {
+        let presets = Preset::all(&get_presets_dir(package_name)?);
+
+        match json {
+            true => print_json(&json!(presets)),
+            false => {
+                for preset in &presets {
+                    println!(
+                        "{} ({}): \n\n{}",
+                        preset.name,
+                        match &preset.file {
+                            Some(file) => file.display().to_string(),
+                            None => "built-in".to_string(),
+                        },
+                        preset
+                    );
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    let cache_path = match &settings.cache_dir {
+        Some(path) => {
+            let path = resolve_path(path)?;
 
-    debug!("Configuration path: {}", config_path.to_str().unwrap());
+            std::fs::create_dir_all(&path)?;
+            path
+        }
+        None => xdg::BaseDirectories::with_prefix(package_name)?.place_cache_file("")?,
+    };
+    // The host is the same for every profile
+    let probe_path = cache_path.join(PROBE_FILE);
+    let cache_path = match &settings.profile {
+        Some(profile) => {
+            let path = cache_path.join(profile);
 
-    let cache_path = xdg::BaseDirectories::with_prefix(&package_name)?.place_cache_file("")?;
+            std::fs::create_dir_all(&path)?;
+            path
+        }
+        None => cache_path,
+    };
 
-    debug!("Cache path: {}", cache_path.to_str().unwrap());
+    debug!("Cache path: {}", cache_path.display());
 
-    let matcher = CommandMatcher::new(&matches);
-    let docker = Docker::new();
+    let matcher = CommandMatcher::new(matches);
+    let style = Style::detect(matches.value_of("color").unwrap().parse::<ColorChoice>()?);
+    // The config is written atomically, it's read without a lock. The app locks it to change it
     let config = Config::deserialize(config_path.as_path())?;
-    let system = System::try_new(&docker)?;
+    let docker = connect_docker(settings.backend(&config.settings)?, &settings);
+    // Diagnostics must see the host as it is now
+    let refresh =
+        settings.no_cache_probe || matches!(matcher.command(), Some("doctor") | Some("test"));
+    let system = match System::cached(&docker, &probe_path, refresh) {
+        Err(SystemError::DockerConnection) if matcher.command() == Some("doctor") => {
+            if !json {
+                println!("Diagnostics: \n\n{}", style.check(&check_docker(&docker)));
+            }
+
+            return Err(AppError::DockerConnection(None).into());
+        }
+        Err(SystemError::DockerConnection) if matcher.command() == Some("purge") => {
+            warn!("Docker daemon is unreachable, only files will be removed");
+            System::offline()
+        }
+        system => system?,
+    };
     let mut app = Wrapper::new(
-        &package_name,
-        &package_name,
+        package_name,
+        gen_profile_prefix(package_name, settings.profile.as_deref()),
         &cache_path,
         &config,
-        &system,
-        &docker,
-    );
+        system.to_owned(),
+        docker,
+    )?
+    .network(settings.network(&config.settings))
+    .config_file(&config_path);
+
+    if let Some(profile) = &settings.profile {
+        app = app.profile(profile);
+    }
 
     debug!("Subcommand processing...");
 
-    match matches.subcommand_name() {
+    let mut failure = None;
+    // Data of the subcommand printed at the end with `--output json`
+    let mut document = None;
+    // Destructive subcommands ask first, unless told not to or run unattended
+    let prompt = std::io::stdin().is_terminal()
+        && !matches
+            .subcommand()
+            .1
+            .is_some_and(|matches| matches.is_present("yes"));
+
+    match matcher.command() {
+        Some("test") if json || matcher.is_option_present("test", "json") => {
+            let mirror = app.check_mirror().map(|(url, reachable)| {
+                json!({
+                    "url": url,
+                    "reachable": reachable,
+                })
+            });
+
+            document = Some(json!({
+                "system": system,
+                "features": app.features,
+                "apt_mirror": mirror,
+            }));
+        }
         Some("test") => {
-            println!("System settings: {}", system);
-            println!("Available features: {}", app.features);
+            println!("System settings: \n\n{}", style.system(&system.rows()));
+            println!(
+                "Available features: \n\n{}",
+                style.features(app.features.iter())
+            );
+
+            if let Some((mirror, reachable)) = app.check_mirror() {
+                println!(
+                    "Apt mirror: {} ({})",
+                    mirror,
+                    if reachable {
+                        "reachable"
+                    } else {
+                        "unreachable"
+                    }
+                );
+            }
         }
         Some("create") => {
-            app.create(
-                get_create_package(&matcher)?.as_path(),
-                &get_create_features(&matcher),
-                &get_create_icon(&matcher),
-                &get_create_command(&matcher),
-                &get_create_deps(&matcher),
-            )?;
+            let (tx, rx) = std::sync::mpsc::channel();
+            let progress = std::thread::spawn(move || {
+                rx.iter().for_each(|event| print_event(event, "", output))
+            });
+            let preset = match matcher.get_argument("create", "preset") {
+                Some(name) => Some(Preset::find(&get_presets_dir(package_name)?, &name)?),
+                None => None,
+            };
+            let mut request = get_create_request(&matcher, preset.as_ref())?;
+
+            if let Some(preset) = &preset {
+                notice(
+                    output,
+                    format!(
+                        "Preset '{}' applied: \n\n{}",
+                        preset.name,
+                        preset.applied(&request)
+                    ),
+                );
+            }
+
+            if settings.no_desktop_entry && request.icon.take().is_some() {
+                info!("Desktop entries are disabled, the icon is ignored");
+            }
+
+            // Only a package given alone, the wizard would override the flags otherwise
+            if prompt
+                && !json
+                && std::io::stdout().is_terminal()
+                && matcher.get_argument("create", "package").is_some()
+                && !matcher.is_option_present("create", "non-interactive")
+                && !matcher.is_option_present("create", "dry-run")
+                && serde_json::to_value(&request)?
+                    == serde_json::to_value(CreateRequest::from_deb(&request.path))?
+            {
+                let answered = wizard::run(
+                    &app.inspect_package(&request.path)?,
+                    &app.features.iter().collect::<Vec<(Feature, bool)>>(),
+                    request,
+                    style,
+                    &mut std::io::stdin().lock(),
+                    &mut std::io::stdout(),
+                )?;
+
+                request = match answered {
+                    Some(request) => request,
+                    None => return Err(CliError::failed("Aborted").into()),
+                };
+            }
+
+            if matcher.is_option_present("create", "dry-run") {
+                let dockerfile = app.plan_create(&request)?;
+
+                match json {
+                    true => print_json(&json!({ "dockerfile": dockerfile })),
+                    false => print!("{}", dockerfile),
+                }
+
+                return Ok(());
+            }
+
+            let program = match matcher.get_argument("create", "from-image") {
+                Some(image) => {
+                    drop(tx);
+                    app.create_from_image(
+                        image,
+                        matcher.get_argument("create", "name").unwrap(),
+                        &request,
+                    )
+                }
+                None => app.create_with_events(&request, tx),
+            };
+
+            progress.join().unwrap_or(());
+
+            let mut program = program?;
+
             info!("Program successfuly created");
+            debug!("Program info:\n{}", program);
+
+            if matcher.is_option_present("create", "wrapper") {
+                match user_bin_dir()
+                    .and_then(|dir| app.install_wrapper(program.get_name_short(), &dir))
+                {
+                    Ok(path) => {
+                        info!("{} runs the program with its arguments", path.display());
+                        program.wrapper = Some(path);
+                    }
+                    Err(err) => warn!("Program is created without a wrapper: {}", err),
+                }
+            }
+
+            if program.kind == ProgramKind::Cli && program.wrapper.is_none() && !json {
+                let name = program.get_name_short();
+
+                info!(
+                    "{} runs in the terminal and has no desktop entry, you may add an alias: \
+                     alias {}='{} run {}'",
+                    name, name, package_name, name
+                );
+            }
+
+            if json {
+                document = Some(serde_json::to_value(&program)?);
+            }
+        }
+        Some("run") if matcher.get_argument("run", "group").is_some() => {
+            let label = matcher.get_argument("run", "group").unwrap();
+            let mut started = vec![];
+
+            for name in group_names(&app, &label)? {
+                let pid = detach(Some(&name))?;
+
+                info!("{} is started in the background (process {})", name, pid);
+                started.push(json!({ "program": name, "pid": pid }));
+            }
+
+            if json {
+                document = Some(json!(started));
+            }
+        }
+        Some("run") if matcher.is_option_present("run", "detach") => {
+            let name = matcher.get_argument("run", "name").unwrap();
+
+            // Fails early on a typo instead of in the background
+            app.info(&name)?;
+
+            let pid = detach(None)?;
+
+            info!("{} is started in the background (process {})", name, pid);
+
+            if json {
+                document = Some(json!({ "program": name, "pid": pid }));
+            }
         }
         Some("run") => {
-            app.run(
-                matches
-                    .subcommand_matches("run")
-                    .unwrap()
-                    .value_of(&"name")
-                    .unwrap(),
-            )?;
+            let name = matches
+                .subcommand_matches("run")
+                .unwrap()
+                .value_of("name")
+                .unwrap();
+            let mut options = RunOptions::new()
+                .keep_container(matcher.is_option_present("run", "keep-container"))
+                .no_log(matcher.is_option_present("run", "no-log"));
+
+            if let Some(display) = matcher.get_argument("run", "display") {
+                options = options.display(display.parse::<RemoteDisplay>()?);
+            }
+
+            if let Some(server) = get_pulse_server(&matcher, "run")? {
+                options = options.pulse_server(&server);
+            }
+
+            for env in matcher.get_arguments("run", "env") {
+                options = options.env(parse_env(&env)?);
+            }
+
+            for share in matcher.get_arguments("run", "share") {
+                let (path, read_only) = parse_share(&share)?;
+
+                let path = std::fs::canonicalize(&path).map_err(|err| {
+                    AppError::Argument(format!("Can't share {}: {}", path.display(), err))
+                })?;
+
+                options = options.share(&path, read_only);
+            }
+
+            if let Some(network) = matcher.get_argument("run", "network") {
+                options = options.network(network);
+            }
+
+            if let Some(command) = matcher.get_argument("run", "command") {
+                options = options.command(command);
+            }
+
+            for arg in matcher.get_arguments("run", "args") {
+                options = options.arg(arg);
+            }
+
+            options = options.no_sound(matcher.is_option_present("run", "no-sound"));
+
+            let outcome = app.run_with(name, &options)?;
+
+            // A read-only config must not fail the run
+            if let Err(err) = app.save(&config_path) {
+                warn!("Launch statistics aren't saved: {}", err);
+            }
+
+            debug!(
+                "Container {} ran for {:?}",
+                outcome.container_id.as_deref().unwrap_or("unknown"),
+                outcome.duration
+            );
+
+            if json {
+                document = Some(json!({
+                    "program": name,
+                    "exit_code": outcome.exit_code,
+                    "container_id": outcome.container_id,
+                    "duration_secs": outcome.duration.as_secs_f64(),
+                    "log": outcome.log,
+                }));
+            }
+
+            if outcome.exit_code != Some(0) && outcome.log.is_some() && !json {
+                println!("The output is kept, see `{} logs {}`", package_name, name);
+            }
+
+            if outcome.exit_code != Some(0) {
+                failure = Some(CliError::ProgramExit {
+                    code: outcome.exit_code,
+                    details: None,
+                });
+            }
+        }
+        Some("stop") => {
+            let name = matcher.get_argument("stop", "name").unwrap();
+
+            let stopped = app.stop(&name)?;
+
+            match stopped.len() {
+                0 => info!("Program '{}' isn't running", name),
+                count => info!("{} container(s) of '{}' stopped", count, name),
+            }
+
+            if json {
+                document = Some(json!({ "program": name, "containers": stopped }));
+            }
+        }
+        Some("service") => {
+            let name = matcher.get_argument("service", "name").unwrap();
+
+            match matcher.get_argument("service", "action").unwrap().as_str() {
+                "enable" => {
+                    let path = app.enable_service(&name)?;
+
+                    info!("Service {} is enabled and started", path.display());
+                    document = Some(json!({ "program": name, "service": path }));
+                }
+                "disable" => match app.disable_service(&name)? {
+                    Some(path) => {
+                        info!("Service {} is disabled and deleted", path.display());
+                        document = Some(json!({ "program": name, "service": path }));
+                    }
+                    None => {
+                        failure = Some(CliError::failed(format!(
+                            "Program '{}' has no service",
+                            name
+                        )))
+                    }
+                },
+                _ if json => {
+                    document = Some(json!({
+                        "program": name,
+                        "status": app.service_status(&name)?,
+                    }))
+                }
+                _ => print!("{}", app.service_status(&name)?),
+            }
         }
         Some("remove") => {
-            app.remove(
-                matches
-                    .subcommand_matches("remove")
+            let names = match matcher.get_argument("remove", "group") {
+                Some(label) => group_names(&app, &label)?,
+                None if matcher.is_option_present("remove", "all") => app.list(),
+                None => matcher.get_arguments("remove", "name"),
+            };
+            let keep_image = matcher.is_option_present("remove", "keep-image");
+
+            if prompt {
+                notice(output, "The following will be deleted: \n");
+
+                for name in &names {
+                    match app.plan_removal(name.as_str()) {
+                        Ok(mut plan) => {
+                            if keep_image {
+                                plan.source_image = None;
+                            }
+
+                            notice(output, plan)
+                        }
+                        Err(err) => warn!("{}", err),
+                    }
+                }
+
+                if !confirm(&format!("Remove {} program(s)?", names.len()), output)? {
+                    return Err(CliError::failed("Aborted").into());
+                }
+            }
+
+            let results = app.remove_many_with(&names, keep_image);
+            let mut summary = (0, 0, 0);
+
+            for (program, result) in &results {
+                match result {
+                    Ok(report) => {
+                        info!("Program '{}' successfuly removed", program);
+                        summary.0 += report.image.iter().count();
+                        summary.1 += report.containers.len();
+                        summary.2 += report.desktop_entry.iter().count();
+                    }
+                    Err(err) => error!("Program '{}' isn't removed: {}", program, err),
+                }
+            }
+
+            match json {
+                true => {
+                    document = Some(json!({
+                        "removed": results
+                            .iter()
+                            .filter_map(|(_, result)| result.as_ref().ok())
+                            .collect::<Vec<_>>(),
+                        "failed": results
+                            .iter()
+                            .filter_map(|(program, result)| {
+                                result.as_ref().err().map(|err| {
+                                    json!({ "program": program, "error": err.to_string() })
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    }))
+                }
+                false => println!(
+                    "Removed {} of {} program(s): {} image(s), {} container(s), {} desktop \
+                     entries",
+                    results.iter().filter(|(_, result)| result.is_ok()).count(),
+                    results.len(),
+                    summary.0,
+                    summary.1,
+                    summary.2
+                ),
+            }
+
+            let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+            if failed > 0 {
+                failure = Some(CliError::failed(format!(
+                    "{} program(s) couldn't be removed",
+                    failed
+                )));
+            }
+        }
+        Some("repair") => {
+            let jobs = match matcher.get_argument("repair", "jobs") {
+                Some(jobs) => match jobs.parse::<usize>() {
+                    Ok(jobs) if jobs > 0 => jobs,
+                    _ => {
+                        return Err(AppError::Argument(format!(
+                            "Invalid number of jobs '{}', expected a positive number",
+                            jobs
+                        ))
+                        .into())
+                    }
+                },
+                None => config.settings.jobs(),
+            };
+            let group = matcher
+                .get_argument("repair", "group")
+                .map(|label| group_names(&app, &label))
+                .transpose()?;
+            let started = Instant::now();
+            let (tx, rx) = std::sync::mpsc::channel::<(String, AppEvent)>();
+            let progress = std::thread::spawn(move || {
+                rx.iter().for_each(|(program, event)| {
+                    print_event(event, &format!("{}: ", program), output)
+                })
+            });
+            let results = match group {
+                Some(names) => app.repair_many_with_events(&names, jobs, tx),
+                None => app.repair_with_events(
+                    matcher.get_argument("repair", "name").as_deref(),
+                    jobs,
+                    tx,
+                ),
+            };
+
+            progress.join().unwrap_or(());
+
+            let results = results?;
+            let failed = results
+                .iter()
+                .filter(|(_, repair)| matches!(repair, Repair::Skipped(_) | Repair::Failed(_)))
+                .count();
+
+            match json {
+                true => {
+                    document = Some(json!(results
+                        .iter()
+                        .map(|(program, repair)| json!({ "program": program, "repair": repair }))
+                        .collect::<Vec<_>>()))
+                }
+                false => {
+                    println!("Repair results: \n");
+
+                    for (program, repair) in &results {
+                        println!("\t{:<15} ===> {}", program, style.repair(repair));
+                    }
+
+                    println!("\nFinished in {}", human_duration(started.elapsed()));
+                }
+            }
+
+            if failed > 0 {
+                failure = Some(CliError::failed(format!(
+                    "{} program(s) couldn't be repaired",
+                    failed
+                )));
+            }
+        }
+        Some("doctor") => {
+            let checks = app.doctor();
+            let failed = checks.iter().filter(|check| !check.passed).count();
+
+            match json {
+                true => document = Some(json!({ "checks": checks })),
+                false => {
+                    println!("Diagnostics: \n");
+
+                    for check in &checks {
+                        println!("{}", style.check(check));
+                    }
+                }
+            }
+
+            if failed > 0 {
+                failure = Some(CliError::failed(format!("{} check(s) failed", failed)));
+            }
+        }
+        Some("verify") => {
+            let names = match matcher.is_option_present("verify", "all") {
+                true => app.list(),
+                false => matcher.get_arguments("verify", "name"),
+            };
+            let mut failed = vec![];
+            let mut verified = vec![];
+
+            for name in &names {
+                let checks = app.verify(name.as_str());
+                let healthy = checks.iter().all(|check| check.passed);
+
+                if !json {
+                    println!("{}: \n", name);
+
+                    for check in &checks {
+                        println!("{}", style.check(check));
+                    }
+
+                    println!();
+                }
+
+                if !healthy {
+                    failed.push(name.as_str());
+                }
+
+                verified.push(json!({ "program": name, "healthy": healthy, "checks": checks }));
+            }
+
+            if json {
+                document = Some(json!(verified));
+            }
+
+            if !failed.is_empty() {
+                failure = Some(CliError::failed(format!(
+                    "Unhealthy program(s): {}",
+                    failed.join(", ")
+                )));
+            }
+        }
+        Some("config") => match matches.subcommand_matches("config").unwrap().subcommand() {
+            ("get", Some(matches)) => {
+                let keys = match matches.value_of("key") {
+                    Some(key) => vec![key],
+                    None => SETTING_KEYS.to_vec(),
+                };
+                let values = keys
+                    .iter()
+                    .map(|key| Ok((*key, app.setting(key)?)))
+                    .collect::<Result<Vec<(&str, Option<String>)>, AppError>>()?;
+
+                match (json, matches.value_of("key")) {
+                    (true, _) => {
+                        document = Some(Value::Object(
+                            values
+                                .into_iter()
+                                .map(|(key, value)| (key.to_string(), json!(value)))
+                                .collect(),
+                        ))
+                    }
+                    (false, Some(_)) => {
+                        if let Some(value) = &values[0].1 {
+                            println!("{}", value);
+                        }
+                    }
+                    (false, None) => println!("Global settings: \n\n{}", style.system(&values)),
+                }
+            }
+            ("set", Some(matches)) => {
+                let key = matches.value_of("key").unwrap();
+
+                app.set_setting(key, matches.value_of("value").unwrap())?;
+                info!("Setting {} changed", key);
+
+                if json {
+                    document = Some(json!({ key: app.setting(key)? }));
+                }
+            }
+            (_, matches) => {
+                let format = matches
+                    .and_then(|matches| matches.value_of("to"))
                     .unwrap()
-                    .value_of(&"name")
-                    .unwrap(),
+                    .parse()?;
+
+                config_path = app.convert_config(&config_path, format)?;
+                info!("Config converted to {}", config_path.display());
+
+                if json {
+                    document = Some(json!({ "config": config_path }));
+                }
+            }
+        },
+        Some("clean") => {
+            let stale = app.stale_containers()?;
+            let unused = app.unused_bases()?;
+            let previous = app.stale_previous();
+            let trash = match matcher.is_option_present("clean", "trash") {
+                true => app.trash()?,
+                false => vec![],
+            };
+            let logs = matcher.is_option_present("clean", "logs");
+            let mut targets = vec![];
+
+            if !stale.is_empty() {
+                targets.push(format!("{} exited containers", stale.len()));
+            }
+
+            if !unused.is_empty() {
+                targets.push(format!("images {}", unused.join(", ")));
+            }
+
+            if !previous.is_empty() {
+                targets.push(format!("previous images of {}", previous.join(", ")));
+            }
+
+            if !trash.is_empty() {
+                targets.push(format!("{} backups of removed programs", trash.len()));
+            }
+
+            if logs {
+                targets.push("old logs".to_string());
+            }
+
+            if prompt
+                && !targets.is_empty()
+                && !confirm(&format!("Remove {}?", targets.join(" and ")), output)?
+            {
+                return Err(CliError::failed("Aborted").into());
+            }
+
+            let mut removed = app.clean()?;
+
+            if !trash.is_empty() {
+                removed.trash = app.empty_trash()?;
+            }
+
+            if logs {
+                removed.logs = app.truncate_logs()?;
+            }
+
+            if json {
+                document = Some(serde_json::to_value(&removed)?);
+            } else if removed.images.is_empty()
+                && removed.previous.is_empty()
+                && removed.containers.is_empty()
+                && removed.trash.is_empty()
+                && removed.logs.is_empty()
+            {
+                println!("Nothing to clean");
+            }
+
+            if !removed.containers.is_empty() {
+                println!("Removed containers: {}", removed.containers.join(", "));
+            }
+
+            if !removed.images.is_empty() {
+                println!("Removed images: {}", removed.images.join(", "));
+            }
+
+            if !removed.previous.is_empty() {
+                println!("Removed previous images: {}", removed.previous.join(", "));
+            }
+
+            if !removed.trash.is_empty() {
+                println!("Removed backups: {}", removed.trash.len());
+            }
+
+            if !removed.logs.is_empty() {
+                println!("Removed logs: {}", removed.logs.len());
+            }
+        }
+        Some("purge") => {
+            if !matcher.is_option_present("purge", "i-understand") {
+                return Err(AppError::Argument(
+                    "Purge removes every program, image, container and the config, pass \
+                     --i-understand to proceed"
+                        .to_string(),
+                )
+                .into());
+            }
+
+            let report = app.purge(&config_path);
+            let document = match json {
+                true => Some(serde_json::to_value(&report)?),
+                false => {
+                    println!("Purged: \n\n{}", style.purge(&report));
+                    None
+                }
+            };
+
+            // The config is removed, it must not be saved again
+            return match (report.failures.is_empty(), document) {
+                (true, Some(document)) => {
+                    print_json(&document);
+                    Ok(())
+                }
+                (true, None) => Ok(()),
+                (false, document) => Err(CliError::failed("Some resources couldn't be removed")
+                    .details(document)
+                    .into()),
+            };
+        }
+        Some("rebuild-base") => {
+            app.rebuild_base()?;
+            info!("Base image successfuly rebuilt");
+
+            if json {
+                document = Some(json!({ "rebuilt": true }));
+            }
+        }
+        Some("info") => {
+            let name = matches
+                .subcommand_matches("info")
+                .unwrap()
+                .value_of("name")
+                .unwrap();
+            let program = app.info(name)?;
+
+            if json {
+                document = Some(json!({
+                    "program": program,
+                    "docker": {
+                        "version": app.system().docker_server,
+                        "api": app.system().docker_version,
+                    },
+                    "has_container": app.has_container(name)?,
+                    "user_dirs": app.user_dirs(name)?,
+                }));
+
+                // The config is only read, there is nothing to save
+                print_json(&document.unwrap());
+                return Ok(());
+            }
+
+            println!("Program settings: \n\n{}", program);
+            println!(
+                "\t{:<15} ===> {} (API {})",
+                "Docker",
+                app.system().docker_server.as_deref().unwrap_or("unknown"),
+                app.system().docker_version
+            );
+
+            if program.container_mode == ContainerMode::Persistent {
+                println!(
+                    "\t{:<15} ===> {}",
+                    "Container",
+                    match app.has_container(name)? {
+                        true => "created",
+                        false => "not created yet",
+                    }
+                );
+            }
+
+            let user_dirs = app.user_dirs(name)?;
+
+            if !user_dirs.is_empty() {
+                println!("\nUser dirs: \n");
+
+                for dir in &user_dirs {
+                    match dir.shared {
+                        true => println!("\t{:<15} ===> {}", dir.label(), dir.container.display()),
+                        false => println!(
+                            "\t{:<15} ===> {} ({} isn't shared)",
+                            dir.label(),
+                            dir.container.display(),
+                            dir.host.display()
+                        ),
+                    }
+                }
+            }
+        }
+        Some("edit") => {
+            let mut changes = get_edit_changes(&matcher)?;
+
+            if settings.no_desktop_entry && changes.icon.take().is_some() {
+                info!("Desktop entries are disabled, the icon is ignored");
+            }
+
+            let (program, rebuilt) =
+                app.edit(matcher.get_argument("edit", "name").unwrap(), &changes)?;
+
+            match rebuilt {
+                true => info!("Program successfuly updated, the image is rebuilt"),
+                false => info!("Program successfuly updated"),
+            }
+
+            match json {
+                true => document = Some(json!({ "program": program, "rebuilt": rebuilt })),
+                false => println!("Program settings: \n\n{}", program),
+            }
+        }
+        Some("logs") => {
+            let name = matcher.get_argument("logs", "name").unwrap();
+            let files = app.logs(&name)?;
+
+            if json {
+                document = Some(serde_json::to_value(&files)?);
+            } else if matcher.is_option_present("logs", "list") {
+                for file in &files {
+                    println!("{}\t{}", human_size(file.size), file.path.display());
+                }
+            } else {
+                match files.last() {
+                    Some(file) => {
+                        let mut log = std::fs::File::open(&file.path)?;
+
+                        std::io::copy(&mut log, &mut std::io::stdout())?;
+                    }
+                    None => {
+                        return Err(CliError::failed(format!("{} has no logs yet", name)).into())
+                    }
+                }
+            }
+        }
+        Some("permissions") => {
+            let permissions =
+                app.permissions(matcher.get_argument("permissions", "name").unwrap())?;
+
+            match json {
+                true => document = Some(serde_json::to_value(&permissions)?),
+                false => {
+                    for (i, permission) in permissions.iter().enumerate() {
+                        if i == 0 || permissions[i - 1].area != permission.area {
+                            println!("{}{}: \n", if i == 0 { "" } else { "\n" }, permission.area);
+                        }
+
+                        println!("\t{:<15} ===> {}", permission.reason, permission.value);
+                    }
+                }
+            }
+        }
+        Some("restore") => {
+            let program = app.restore(matcher.get_argument("restore", "name").unwrap())?;
+
+            info!("Program successfuly restored");
+
+            match json {
+                true => document = Some(serde_json::to_value(&program)?),
+                false => println!("Program settings: \n\n{}", program),
+            }
+        }
+        Some("rename") => {
+            let program = app.rename(
+                matcher.get_argument("rename", "name").unwrap(),
+                matcher.get_argument("rename", "new-name").unwrap(),
             )?;
-            info!("Program successfuly removed");
+
+            info!("Program successfuly renamed");
+
+            match json {
+                true => document = Some(serde_json::to_value(&program)?),
+                false => println!("Program settings: \n\n{}", program),
+            }
+        }
+        Some("label") => {
+            let name = matcher.get_argument("label", "name").unwrap();
+            let label = matcher.get_argument("label", "label").unwrap();
+            let add = matcher.get_argument("label", "action").as_deref() == Some("add");
+            let changed = match add {
+                true => app.add_label(name.as_str(), &label)?,
+                false => app.remove_label(name.as_str(), &label)?,
+            };
+
+            match (add, changed) {
+                (true, true) => info!("Label '{}' added to '{}'", label, name),
+                (false, true) => info!("Label '{}' removed from '{}'", label, name),
+                (true, false) => notice(
+                    output,
+                    format!("'{}' has the label '{}' already", name, label),
+                ),
+                (false, false) => notice(
+                    output,
+                    format!("'{}' has no label '{}', nothing to remove", name, label),
+                ),
+            }
+
+            if json {
+                document = Some(json!({
+                    "program": name,
+                    "labels": app.info(name.as_str())?.labels,
+                    "changed": changed,
+                }));
+            }
+        }
+        Some("rollback") => {
+            let program = app.rollback(matcher.get_argument("rollback", "name").unwrap())?;
+
+            info!("Program successfuly rolled back");
+
+            match json {
+                true => document = Some(serde_json::to_value(&program)?),
+                false => println!("Program settings: \n\n{}", program),
+            }
+        }
+        Some("du") if json || matcher.is_option_present("du", "json") => {
+            document = Some(serde_json::to_value(&app.disk_usage()?)?);
+        }
+        Some("du") => {
+            println!("Disk usage: \n\n{}", app.disk_usage()?);
+        }
+        Some("export") if matcher.get_argument("export", "format").as_deref() == Some("image") => {
+            let name = matcher.get_argument("export", "name").unwrap();
+            let path = PathBuf::from(matcher.get_argument("export", "out-file").unwrap());
+            let (tx, rx) = std::sync::mpsc::channel();
+            let progress = std::thread::spawn(move || {
+                rx.iter().for_each(|event| print_event(event, "", output))
+            });
+            let checksum = app.export_image_with_events(&name, &path, tx);
+
+            progress.join().unwrap_or(());
+
+            let checksum = checksum?;
+
+            info!(
+                "Image of '{}' is saved to {} (SHA-256 {})",
+                name,
+                path.display(),
+                checksum
+            );
+
+            if json {
+                document = Some(json!({ "program": name, "path": path, "sha256": checksum }));
+            }
+        }
+        Some("import") => {
+            let path = resolve_path(matcher.get_argument("import", "path").unwrap())?;
+
+            let imported = app.import_image(&path)?;
+
+            for program in &imported {
+                info!("Image of '{}' is imported", program.get_name_short());
+            }
+
+            if json {
+                document = Some(json!({
+                    "programs": imported
+                        .iter()
+                        .map(Program::get_name_short)
+                        .collect::<Vec<String>>(),
+                }));
+            }
+        }
+        Some("export") => {
+            let name = matcher.get_argument("export", "name").unwrap();
+            let exported = app.export_compose(&name)?;
+
+            match matcher.get_argument("export", "out-file") {
+                Some(path) => {
+                    std::fs::write(&path, exported)?;
+                    info!("Program '{}' is exported to {}", name, path);
+                    document = Some(json!({ "program": name, "path": path }));
+                }
+                None if json => document = Some(json!({ "program": name, "compose": exported })),
+                None => print!("{}", exported),
+            }
+        }
+        Some("search") => {
+            let term = matcher.get_argument("search", "term").unwrap();
+            let found = app.search(&term);
+
+            if found.is_empty() {
+                failure = Some(CliError::failed(format!("No program matches '{}'", term)));
+            }
+
+            if json {
+                document = Some(json!(found));
+            } else if !found.is_empty() {
+                println!("Found programs: \n");
+
+                for found in &found {
+                    println!("{}", style.search_match(found));
+                }
+            }
         }
         Some("list") => {
-            let list = app.list().join(", ");
+            let mut programs = match matcher.get_argument("list", "unused-since") {
+                Some(age) => app.unused_since(parse_age(&age)?),
+                None => app.programs().to_vec(),
+            };
+            let group = matcher
+                .get_argument("list", "group")
+                .map(|label| parse_label(&label))
+                .transpose()?;
+
+            if let Some(label) = &group {
+                programs.retain(|program| program.labels.contains(label));
+            }
+
+            match (
+                programs.is_empty(),
+                matcher.is_option_present("list", "long"),
+            ) {
+                _ if json => {
+                    document = Some(json!(programs
+                        .iter()
+                        .map(|program| json!({
+                            "name": program.get_name_short(),
+                            "last_run": program.last_run,
+                            "run_count": program.run_count,
+                            "labels": program.labels,
+                        }))
+                        .collect::<Vec<_>>()))
+                }
+                (true, _) if app.list().is_empty() => println!("No program added yet"),
+                (true, _) => match &group {
+                    Some(label) => println!("No program has the label '{}'", label),
+                    None => println!("No unused programs"),
+                },
+                (false, true) => {
+                    print!("Available programs: \n\n{}", style.programs(&programs));
+                }
+                (false, false) => println!(
+                    "Available programs list: {}",
+                    programs
+                        .iter()
+                        .map(|program| match program.labels.is_empty() {
+                            true => program.get_name_short(),
+                            false => format!(
+                                "{} ({})",
+                                program.get_name_short(),
+                                program.labels.join(", ")
+                            ),
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            }
 
-            match list.as_str() {
-                "" => println!("No program added yet"),
-                list => println!("Available programs list: {}", list),
+            for image in app.orphaned_images()? {
+                warn!(
+                    "Image {} of unknown program '{}' is left, remove it with `docker rmi`",
+                    image.tags.first().unwrap_or(&image.id),
+                    image.program.unwrap_or_default()
+                );
             }
         }
         _ => unreachable!(),
@@ -126,6 +1475,12 @@ where
 
     app.save(&config_path)?;
 
+    match (failure, document) {
+        (Some(failure), document) => return Err(failure.details(document).into()),
+        (None, Some(document)) => print_json(&document),
+        (None, None) => (),
+    }
+
     debug!("Exit");
 
     std::env::remove_var("RUST_APP_LOG");
@@ -133,57 +1488,595 @@ where
     Ok(())
 }
 
-fn get_create_features(matcher: &CommandMatcher) -> Vec<Feature> {
-    let mut features = vec![];
+/// Names of the programs with the label, it's an error if none has it
+fn group_names(app: &Wrapper, label: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let label = parse_label(label)?;
+    let names: Vec<String> = app
+        .group(&label)
+        .iter()
+        .map(Program::get_name_short)
+        .collect();
 
-    if matcher.is_option_present("create", "display") {
-        features.push(Feature::Display);
+    match names.is_empty() {
+        true => Err(AppError::Argument(format!("No program has the label '{}'", label)).into()),
+        false => Ok(names),
     }
+}
 
-    if matcher.is_option_present("create", "sound") {
-        features.push(Feature::Sound);
+/// Starts the same command line without `--detach` in a new process group, detached from the
+/// terminal, returns its process ID. The `--group` of `run` is replaced by the `program` name
+fn detach(program: Option<&str>) -> std::io::Result<u32> {
+    use std::os::unix::process::CommandExt;
+
+    let mut args = vec![];
+    let mut all = std::env::args_os().skip(1);
+
+    // Arguments after `--` belong to the program
+    while let Some(arg) = all.next() {
+        match (arg.to_str(), program) {
+            (Some("--"), _) => {
+                args.push(arg);
+                args.extend(all.by_ref());
+            }
+            (Some("--detach"), _) => (),
+            (Some("--group"), Some(program)) => {
+                all.next();
+                args.push(program.into());
+            }
+            (Some(group), Some(program)) if group.starts_with("--group=") => {
+                args.push(program.into())
+            }
+            _ => args.push(arg),
+        }
     }
 
-    if matcher.is_option_present("create", "home") {
-        features.push(Feature::HomePersistent);
+    let child = std::process::Command::new(std::env::current_exe()?)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .process_group(0)
+        .spawn()?;
+
+    Ok(child.id())
+}
+
+/// Asks a yes/no question on the terminal, anything but "y" or "yes" is a no
+fn confirm(question: &str, output: OutputFormat) -> std::io::Result<bool> {
+    match output {
+        OutputFormat::Json => {
+            eprint!("{} [y/N] ", question);
+            std::io::stderr().flush()?;
+        }
+        OutputFormat::Text => {
+            print!("{} [y/N] ", question);
+            std::io::stdout().flush()?;
+        }
     }
 
-    if matcher.is_option_present("create", "notifications") {
-        features.push(Feature::Notification);
+    let mut answer = String::new();
+
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Prints a message for the user, kept off the standard output with `--output json`
+fn notice<T: std::fmt::Display>(output: OutputFormat, message: T) {
+    match output {
+        OutputFormat::Json => eprintln!("{}", message),
+        OutputFormat::Text => println!("{}", message),
     }
+}
 
-    if matcher.is_option_present("create", "timezone") {
-        features.push(Feature::Time);
+/// Wall time like `12.3s` or `4m 05s`
+fn human_duration(duration: Duration) -> String {
+    match duration.as_secs() {
+        seconds if seconds < 60 => format!("{:.1}s", duration.as_secs_f64()),
+        seconds => format!("{}m {:02}s", seconds / 60, seconds % 60),
     }
+}
+
+fn print_json(document: &serde_json::Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(document).unwrap_or_default()
+    );
+}
 
-    if matcher.is_option_present("create", "devices") {
-        features.push(Feature::Devices);
+/// Program and paths the subcommand was given, for the JSON error document
+fn get_error_context(package_name: &str, matches: &ArgMatches) -> ErrorContext {
+    let (command, matches) = match matches.subcommand() {
+        (command, Some(matches)) => (command, matches),
+        _ => return ErrorContext::default(),
+    };
+    let names: Vec<&str> = matches
+        .values_of("name")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+
+    ErrorContext {
+        program: match names.as_slice() {
+            [name] => Some(name.to_string()),
+            _ => None,
+        },
+        package: matches
+            .value_of("package")
+            .filter(|_| command == "create")
+            .and_then(|path| resolve_path(path).ok()),
+        archive: matches
+            .value_of("path")
+            .filter(|_| command == "import")
+            .and_then(|path| resolve_path(path).ok()),
+        config: Settings::detect(matches)
+            .ok()
+            .and_then(|settings| get_settings_config(package_name, &settings).ok()),
     }
+}
 
-    features
+/// Resolves a path given by user against the current directory
+fn resolve_path<P: AsRef<Path>>(path: P) -> std::io::Result<PathBuf> {
+    Ok(std::env::current_dir()?
+        .join(path)
+        .components()
+        .filter(|component| component != &Component::CurDir)
+        .collect())
 }
 
-fn get_create_package(matcher: &CommandMatcher) -> std::io::Result<PathBuf> {
-    std::fs::canonicalize(Path::new(
-        matcher.get_argument("create", "package").unwrap().as_str(),
-    ))
+/// Connects to the daemon of the settings, `DOCKER_HOST`, the system daemon or a rootless daemon
+/// of the current user, in that order. The daemon is exported as `DOCKER_HOST`, so docker CLI
+/// calls reach the same one
+fn connect_docker(backend: Backend, settings: &Settings) -> Docker {
+    match (backend, &settings.docker_host) {
+        (Backend::Docker, Some(host)) => std::env::set_var("DOCKER_HOST", host),
+        (Backend::Docker, None) => (),
+    }
+
+    if std::env::var_os("DOCKER_HOST").is_none() && !Path::new("/var/run/docker.sock").exists() {
+        if let Some(socket) = System::rootless_socket() {
+            debug!("Using rootless docker daemon at {}", socket.display());
+            std::env::set_var("DOCKER_HOST", format!("unix://{}", socket.display()));
+        }
+    }
+
+    Docker::new()
 }
 
-fn get_create_command(matcher: &CommandMatcher) -> Option<String> {
-    matcher.get_argument("create", "command")
+/// Config file given by the settings, the default one otherwise
+fn get_settings_config(package_name: &str, settings: &Settings) -> Result<PathBuf, Box<dyn Error>> {
+    match &settings.config {
+        Some(path) => Ok(resolve_path(path)?),
+        None => get_config_path(package_name, settings.profile.as_deref()),
+    }
 }
 
-fn get_create_deps(matcher: &CommandMatcher) -> Option<String> {
-    matcher.get_argument("create", "dependencies")
+/// Config of the profile, prefers an existing TOML config, JSON is used otherwise
+fn get_config_path(package_name: &str, profile: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+    let dirs = xdg::BaseDirectories::with_prefix(package_name)?;
+
+    match dirs.find_config_file(settings::config_name(profile, "toml")) {
+        Some(path) => Ok(path),
+        None => Ok(dirs.place_config_file(settings::config_name(profile, "json"))?),
+    }
 }
 
-fn get_create_icon(matcher: &CommandMatcher) -> Option<Icon> {
-    let icon_owned = matcher.get_argument("create", "desktop-icon");
-    let icon = icon_owned.as_ref().map(String::as_str);
+/// Directory user presets are read from, see `Preset::all`
+fn get_presets_dir(package_name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(xdg::BaseDirectories::with_prefix(package_name)?
+        .get_config_home()
+        .join(PRESETS_DIR))
+}
+
+/// Name of a profile and the programs of its config
+type ProfilePrograms = (String, Vec<String>);
+
+/// Profiles with a config in the config directory and their programs, a config which can't
+/// be read counts as empty
+fn get_profiles(package_name: &str) -> Result<Vec<ProfilePrograms>, Box<dyn Error>> {
+    let dir = xdg::BaseDirectories::with_prefix(package_name)?.get_config_home();
+
+    Ok(settings::profiles(&dir)
+        .into_iter()
+        .map(|(profile, path)| {
+            let programs = Config::deserialize(&path)
+                .map(|config| {
+                    config
+                        .programs
+                        .iter()
+                        .map(Program::get_name_short)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            (
+                profile.unwrap_or_else(|| settings::DEFAULT_PROFILE.to_string()),
+                programs,
+            )
+        })
+        .collect())
+}
+
+/// Features enabled with flags of the given subcommand
+fn get_features(matcher: &CommandMatcher, command: &str) -> Vec<Feature> {
+    [
+        "display",
+        "sound",
+        "home",
+        "notifications",
+        "timezone",
+        "devices",
+        "read-only",
+        "input-method",
+        "theme",
+        "keyring",
+        "gamepad",
+        "bluetooth",
+        "scanner",
+        "mdns",
+        "open-urls",
+        "portals",
+    ]
+    .iter()
+    .filter(|flag| matcher.is_option_present(command, **flag))
+    .filter_map(|flag| flag.parse().ok())
+    .collect()
+}
+
+/// Prints the progress, `prefix` tells apart the programs of a bulk operation, e.g. `foo: `
+fn print_event(event: AppEvent, prefix: &str, output: OutputFormat) {
+    match event {
+        AppEvent::DebParsed { package, version } => info!(
+            "{}Package {} {} is read",
+            prefix,
+            package,
+            version.unwrap_or_default()
+        ),
+        AppEvent::BuildStep {
+            step,
+            total,
+            detail,
+        } => notice(output, format!("{}[{}/{}] {}", prefix, step, total, detail)),
+        AppEvent::ImageBuilt { id } => {
+            info!("{}Image {} is built", prefix, id.unwrap_or_default())
+        }
+        AppEvent::EntryCreated { path } => {
+            info!("{}Desktop entry {} is created", prefix, path.display())
+        }
+        AppEvent::ImageTransfer { bytes, total } => match total {
+            Some(total) => info!(
+                "{}{} of about {} written",
+                prefix,
+                human_size(bytes),
+                human_size(total)
+            ),
+            None => info!("{}{} written", prefix, human_size(bytes)),
+        },
+        _ => (),
+    }
+}
+
+/// The preset gives the values which aren't given with flags
+fn get_create_request(
+    matcher: &CommandMatcher,
+    preset: Option<&Preset>,
+) -> Result<CreateRequest, Box<dyn Error>> {
+    let path = match matcher.get_argument("create", "package") {
+        Some(package) => std::fs::canonicalize(Path::new(package.as_str()))?,
+        None => PathBuf::new(),
+    };
+    let mut build = BuildSettings::new(
+        &matcher.get_argument("create", "apt-mirror"),
+        &matcher.get_argument("create", "build-proxy"),
+        &matcher.get_argument("create", "snapshot"),
+    );
+
+    build.local_repo = matcher
+        .get_argument("create", "local-repo")
+        .map(|dir| resolve_path(&dir))
+        .transpose()?;
+
+    let mut request = CreateRequest::from_deb(&path);
+
+    if let Some(preset) = preset {
+        request = preset.apply(request);
+    }
+
+    request = request
+        .features(&get_features(matcher, "create"))
+        .skip_check(matcher.is_option_present("create", "skip-check"))
+        .force(matcher.is_option_present("create", "force"))
+        .autostart(matcher.is_option_present("create", "autostart"))
+        .build_settings(&build);
+
+    if let Some(command) = matcher.get_argument("create", "command") {
+        request = request.command(command);
+    }
+
+    if let Some(deps) = matcher.get_argument("create", "dependencies") {
+        request = request.deps(deps);
+    }
+
+    if let Some(path) = matcher.get_argument("create", "dockerfile-extra") {
+        request = request.dockerfile_extra(std::fs::read_to_string(resolve_path(&path)?)?);
+    }
+
+    if let Some(icon) = get_icon(matcher, "create") {
+        request = request.icon(icon);
+    }
+
+    if let Some(mode) = matcher.get_argument("create", "container-mode") {
+        request = request.container_mode(mode.parse()?);
+    }
+
+    if let Some(kind) = get_kind(matcher, "create") {
+        request = request.kind(kind);
+    }
+
+    if let Some(restart) = matcher.get_argument("create", "restart") {
+        request = request.restart(restart.parse()?);
+    }
+
+    if let Some(command) = matcher.get_argument("create", "pre-run") {
+        request = request.pre_run(command);
+    }
+
+    if let Some(command) = matcher.get_argument("create", "post-run") {
+        request = request.post_run(command);
+    }
+
+    for label in matcher.get_arguments("create", "label") {
+        request = request.label(label);
+    }
+
+    if let Some(display) = matcher.get_argument("create", "remote-display") {
+        let mut features = request.features.to_owned();
+
+        if !features.contains(&Feature::Display) {
+            features.push(Feature::Display);
+        }
+
+        request = request
+            .features(&features)
+            .remote_display(display.parse::<RemoteDisplay>()?);
+    }
+
+    if let Some(server) = get_pulse_server(matcher, "create")? {
+        let mut features = request.features.to_owned();
+
+        if !features.contains(&Feature::Sound) {
+            features.push(Feature::Sound);
+        }
+
+        request = request.features(&features).pulse_server(&server);
+    }
+
+    if let Some(size) = matcher.get_argument("create", "shm-size") {
+        request = request.shm_size(parse_size(&size)?);
+    }
+
+    let devices = get_devices(matcher, "create")?;
+
+    // Devices of a preset are kept unless others are given
+    if !devices.is_empty() {
+        request = request.devices(&devices);
+    }
 
-    if let Some("default") = icon {
-        return Some(Icon::default());
+    Ok(request
+        .security(&Security {
+            seccomp: get_seccomp(matcher, "create")?.unwrap_or_default(),
+            apparmor: matcher.get_argument("create", "apparmor-profile"),
+        })
+        .capabilities(
+            &matcher
+                .get_arguments("create", "cap-add")
+                .iter()
+                .map(|capability| parse_capability(capability))
+                .collect::<Result<Vec<String>, _>>()?,
+        )
+        .resources(&Resources {
+            ulimits: get_ulimits(matcher, "create")?.into_iter().collect(),
+            nice: matcher
+                .get_argument("create", "nice")
+                .map(|nice| parse_nice(&nice))
+                .transpose()?,
+            oom_score_adj: matcher
+                .get_argument("create", "oom-score-adj")
+                .map(|adj| parse_oom_score_adj(&adj))
+                .transpose()?,
+        }))
+}
+
+fn get_ulimits(
+    matcher: &CommandMatcher,
+    command: &str,
+) -> Result<Vec<(String, u64)>, Box<dyn Error>> {
+    Ok(matcher
+        .get_arguments(command, "ulimit")
+        .iter()
+        .map(|ulimit| parse_ulimit(ulimit))
+        .collect::<Result<Vec<(String, u64)>, _>>()?)
+}
+
+fn get_devices(matcher: &CommandMatcher, command: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(matcher
+        .get_arguments(command, "device")
+        .iter()
+        .chain(matcher.get_arguments(command, "device-glob").iter())
+        .map(|device| parse_device(device))
+        .collect::<Result<Vec<String>, _>>()?)
+}
+
+fn get_seccomp(matcher: &CommandMatcher, command: &str) -> Result<Option<Seccomp>, Box<dyn Error>> {
+    if let Some(profile) = matcher.get_argument(command, "seccomp-profile") {
+        return Ok(Some(Seccomp::Profile(std::fs::canonicalize(Path::new(
+            &profile,
+        ))?)));
     }
 
-    None
+    Ok(match matcher.get_argument(command, "security") {
+        Some(security) => Some(security.parse()?),
+        None => None,
+    })
+}
+
+fn get_pulse_server(
+    matcher: &CommandMatcher,
+    command: &str,
+) -> Result<Option<PulseServer>, Box<dyn Error>> {
+    let host = match matcher.get_argument(command, "pulse-server") {
+        Some(host) => host.parse().map_err(|_| {
+            AppError::Argument(format!(
+                "Invalid pulse server '{}', expected an IP address, e.g. 192.168.1.5",
+                host
+            ))
+        })?,
+        None => return Ok(None),
+    };
+    let server = PulseServer::new(host);
+
+    Ok(Some(match matcher.get_argument(command, "pulse-cookie") {
+        Some(cookie) => server.cookie(&std::fs::canonicalize(Path::new(&cookie))?),
+        None => server,
+    }))
+}
+
+fn get_edit_changes(matcher: &CommandMatcher) -> Result<ProgramChanges, Box<dyn Error>> {
+    let mut changes = ProgramChanges::new().build_settings(&BuildSettings::new(
+        &matcher.get_argument("edit", "apt-mirror"),
+        &matcher.get_argument("edit", "build-proxy"),
+        &matcher.get_argument("edit", "snapshot"),
+    ));
+
+    for feature in get_features(matcher, "edit") {
+        changes = changes.add_feature(feature);
+    }
+
+    for feature in matcher
+        .get_arguments("edit", "remove-feature")
+        .iter()
+        .filter_map(|flag| flag.parse().ok())
+    {
+        changes = changes.remove_feature(feature);
+    }
+
+    if let Some(command) = matcher.get_argument("edit", "command") {
+        changes = changes.command(command);
+    }
+
+    if let Some(deps) = matcher.get_argument("edit", "dependencies") {
+        changes = changes.deps(deps);
+    }
+
+    if let Some(icon) = get_icon(matcher, "edit") {
+        changes = changes.icon(icon);
+    }
+
+    if let Some(mode) = matcher.get_argument("edit", "container-mode") {
+        changes = changes.container_mode(mode.parse()?);
+    }
+
+    if let Some(kind) = get_kind(matcher, "edit") {
+        changes = changes.kind(kind);
+    }
+
+    if let Some(state) = matcher.get_argument("edit", "autostart") {
+        changes = changes.autostart(state == "on");
+    }
+
+    if let Some(restart) = matcher.get_argument("edit", "restart") {
+        changes = changes.restart(restart.parse()?);
+    }
+
+    if let Some(command) = matcher.get_argument("edit", "pre-run") {
+        changes = changes.pre_run(Some(command));
+    }
+
+    if matcher.is_option_present("edit", "remove-pre-run") {
+        changes = changes.pre_run(None);
+    }
+
+    if let Some(command) = matcher.get_argument("edit", "post-run") {
+        changes = changes.post_run(Some(command));
+    }
+
+    if matcher.is_option_present("edit", "remove-post-run") {
+        changes = changes.post_run(None);
+    }
+
+    if let Some(seccomp) = get_seccomp(matcher, "edit")? {
+        changes = changes.seccomp(seccomp);
+    }
+
+    if let Some(profile) = matcher.get_argument("edit", "apparmor-profile") {
+        changes = changes.apparmor(Some(profile));
+    }
+
+    if matcher.is_option_present("edit", "remove-apparmor-profile") {
+        changes = changes.apparmor(None);
+    }
+
+    if let Some(size) = matcher.get_argument("edit", "shm-size") {
+        changes = changes.shm_size(parse_size(&size)?);
+    }
+
+    for capability in matcher.get_arguments("edit", "cap-add") {
+        changes = changes.add_capability(parse_capability(&capability)?);
+    }
+
+    for capability in matcher.get_arguments("edit", "remove-capability") {
+        changes = changes.remove_capability(parse_capability(&capability)?);
+    }
+
+    for device in get_devices(matcher, "edit")? {
+        changes = changes.add_device(device);
+    }
+
+    for device in matcher.get_arguments("edit", "remove-device") {
+        changes = changes.remove_device(device);
+    }
+
+    for (name, value) in get_ulimits(matcher, "edit")? {
+        changes = changes.ulimit(name, value);
+    }
+
+    for name in matcher.get_arguments("edit", "remove-ulimit") {
+        changes = changes.remove_ulimit(name.to_lowercase());
+    }
+
+    if let Some(nice) = matcher.get_argument("edit", "nice") {
+        changes = changes.nice(parse_nice(&nice)?);
+    }
+
+    if let Some(adj) = matcher.get_argument("edit", "oom-score-adj") {
+        changes = changes.oom_score_adj(parse_oom_score_adj(&adj)?);
+    }
+
+    Ok(changes)
+}
+
+fn get_kind(matcher: &CommandMatcher, command: &str) -> Option<ProgramKind> {
+    match (
+        matcher.is_option_present(command, "cli"),
+        matcher.is_option_present(command, "gui"),
+    ) {
+        (true, _) => Some(ProgramKind::Cli),
+        (_, true) => Some(ProgramKind::Gui),
+        _ => None,
+    }
+}
+
+fn get_icon(matcher: &CommandMatcher, command: &str) -> Option<Icon> {
+    let icon_owned = matcher.get_argument(command, "desktop-icon");
+    let icon = icon_owned.as_ref().map(String::as_str);
+
+    match icon {
+        Some("default") => Icon::try_default()
+            .map_err(|err| warn!("Desktop entry won't be created: {}", err))
+            .ok(),
+        Some(path) => resolve_path(path)
+            .map(|path| Icon::new(&path))
+            .map_err(|err| warn!("Desktop entry won't be created: {}", err))
+            .ok(),
+        None => None,
+    }
 }
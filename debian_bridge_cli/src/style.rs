@@ -0,0 +1,311 @@
+use debian_bridge_core::{Check, Feature, Program, PurgeReport, Repair, SearchMatch};
+use std::{ffi::OsString, io::IsTerminal, str::FromStr};
+
+/// When to color the output, see `--color`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Always,
+    Auto,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorChoice::Always),
+            "auto" => Ok(ColorChoice::Auto),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Good,
+    Bad,
+    Warn,
+}
+
+impl Tone {
+    fn code(self) -> &'static str {
+        match self {
+            Tone::Good => "32",
+            Tone::Bad => "31",
+            Tone::Warn => "33",
+        }
+    }
+}
+
+/// Renders the output of the commands, colored only if asked to or if the output is a terminal
+/// and `NO_COLOR` isn't set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    colored: bool,
+}
+
+impl Style {
+    /// An empty `NO_COLOR` doesn't count, see https://no-color.org
+    pub fn new(choice: ColorChoice, no_color: Option<OsString>, terminal: bool) -> Self {
+        let colored = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => terminal && no_color.is_none_or(|value| value.is_empty()),
+        };
+
+        Style { colored }
+    }
+
+    pub fn detect(choice: ColorChoice) -> Self {
+        Self::new(
+            choice,
+            std::env::var_os("NO_COLOR"),
+            std::io::stdout().is_terminal(),
+        )
+    }
+
+    pub fn paint(&self, text: &str, tone: Tone) -> String {
+        match self.colored {
+            true => format!("\x1B[{}m{}\x1B[0m", tone.code(), text),
+            false => text.to_string(),
+        }
+    }
+
+    /// Rows of `System::rows`
+    pub fn system(&self, rows: &[(&str, Option<String>)]) -> String {
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        rows.iter()
+            .map(|(name, value)| {
+                format!(
+                    "\t{:<width$} ===> {}\n",
+                    name,
+                    match value {
+                        Some(value) => self.paint(value, Tone::Good),
+                        None => self.paint("None", Tone::Bad),
+                    },
+                    width = width
+                )
+            })
+            .collect()
+    }
+
+    pub fn features<T: IntoIterator<Item = (Feature, bool)>>(&self, features: T) -> String {
+        let features: Vec<(String, bool)> = features
+            .into_iter()
+            .map(|(feature, available)| (feature.to_string(), available))
+            .collect();
+        let width = features
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        features
+            .iter()
+            .map(|(name, available)| {
+                format!(
+                    "\t{:<width$} ===> {}\n",
+                    name,
+                    match available {
+                        true => self.paint("available", Tone::Good),
+                        false => self.paint("unavailable", Tone::Bad),
+                    },
+                    width = width
+                )
+            })
+            .collect()
+    }
+
+    pub fn repair(&self, repair: &Repair) -> String {
+        match repair {
+            Repair::Intact => self.paint("intact", Tone::Good),
+            Repair::Rebuilt => self.paint("repaired", Tone::Good),
+            Repair::Retagged => self.paint("retagged", Tone::Good),
+            Repair::Pulled => self.paint("pulled", Tone::Good),
+            Repair::Skipped(reason) => format!("{}: {}", self.paint("skipped", Tone::Warn), reason),
+            // A failed build ends with the line explaining it
+            Repair::Failed(err) => format!(
+                "{}: {}",
+                self.paint("failed", Tone::Bad),
+                err.to_string()
+                    .lines()
+                    .rev()
+                    .find(|line| !line.trim().is_empty())
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    pub fn check(&self, check: &Check) -> String {
+        let mut rendered = format!(
+            "\t[{}] {}: {}",
+            match check.passed {
+                true => self.paint("pass", Tone::Good),
+                false => self.paint("fail", Tone::Bad),
+            },
+            check.name,
+            check.details
+        );
+
+        if let Some(fix) = &check.fix {
+            rendered.push_str(&format!("\n\t       {}", fix));
+        }
+
+        rendered
+    }
+
+    pub fn search_match(&self, found: &SearchMatch) -> String {
+        format!(
+            "\t{:<15} ===> {}: {}{}{}",
+            found.name,
+            found.field,
+            &found.snippet[..found.range.start],
+            self.paint(&found.snippet[found.range.clone()], Tone::Warn),
+            &found.snippet[found.range.end..]
+        )
+    }
+
+    pub fn purge(&self, report: &PurgeReport) -> String {
+        let mut rendered = format!(
+            "\t{:<15} ===> {}\n\t{:<15} ===> {}\n\t{:<15} ===> {}\n\t{:<15} ===> {}\n",
+            "Programs",
+            report.programs.len(),
+            "Containers",
+            report.containers.len(),
+            "Images",
+            report.images.len(),
+            "Files",
+            report.files.len()
+        );
+
+        for failure in &report.failures {
+            // Padded before painting, escapes would count into the width otherwise
+            rendered.push_str(&format!(
+                "\t{} ===> {}\n",
+                self.paint(&format!("{:<15}", "Failed"), Tone::Bad),
+                failure
+            ));
+        }
+
+        rendered
+    }
+
+    /// Programs of `list --long`, never run ones are highlighted
+    pub fn programs(&self, programs: &[Program]) -> String {
+        programs
+            .iter()
+            .map(|program| {
+                format!(
+                    "\t{:<15} ===> last run {}, {} runs\n",
+                    program.get_name_short(),
+                    match program.format_last_run() {
+                        Some(last_run) => last_run,
+                        None => self.paint("never", Tone::Warn),
+                    },
+                    program.run_count
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn rows() -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("Docker API", Some("1.40".to_string())),
+            ("Display server", None),
+        ]
+    }
+
+    fn features() -> Vec<(Feature, bool)> {
+        vec![(Feature::Display, true), (Feature::HomePersistent, false)]
+    }
+
+    fn programs() -> Vec<Program> {
+        ["foo", "libreoffice"]
+            .iter()
+            .map(|name| {
+                Program::new(
+                    *name,
+                    Path::new("/tmp/foo.deb"),
+                    &vec![],
+                    &None,
+                    &None,
+                    &None,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let terminal = |no_color: Option<&str>| {
+            Style::new(ColorChoice::Auto, no_color.map(OsString::from), true).colored
+        };
+
+        assert!(terminal(None));
+        assert!(terminal(Some("")));
+        assert!(!terminal(Some("1")));
+        assert!(!Style::new(ColorChoice::Auto, None, false).colored);
+        assert!(Style::new(ColorChoice::Always, Some("1".into()), false).colored);
+        assert!(!Style::new(ColorChoice::Never, None, true).colored);
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn test_test_plain() {
+        let style = Style::new(ColorChoice::Never, None, true);
+
+        assert_eq!(
+            style.system(&rows()),
+            "\tDocker API     ===> 1.40\n\tDisplay server ===> None\n"
+        );
+        assert_eq!(
+            style.features(features()),
+            "\tDisplay         ===> available\n\tHome persistent ===> unavailable\n"
+        );
+    }
+
+    #[test]
+    fn test_test_colored() {
+        let style = Style::new(ColorChoice::Always, None, false);
+
+        assert_eq!(
+            style.system(&rows()),
+            "\tDocker API     ===> \x1B[32m1.40\x1B[0m\n\tDisplay server ===> \x1B[31mNone\x1B[0m\n"
+        );
+        assert_eq!(
+            style.features(features()),
+            "\tDisplay         ===> \x1B[32mavailable\x1B[0m\n\
+             \tHome persistent ===> \x1B[31munavailable\x1B[0m\n"
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let plain = Style::new(ColorChoice::Auto, Some("1".into()), true);
+        let colored = Style::new(ColorChoice::Auto, None, true);
+
+        assert_eq!(
+            plain.programs(&programs()),
+            "\tfoo             ===> last run never, 0 runs\n\
+             \tlibreoffice     ===> last run never, 0 runs\n"
+        );
+        assert_eq!(
+            colored.programs(&programs()),
+            "\tfoo             ===> last run \x1B[33mnever\x1B[0m, 0 runs\n\
+             \tlibreoffice     ===> last run \x1B[33mnever\x1B[0m, 0 runs\n"
+        );
+    }
+}
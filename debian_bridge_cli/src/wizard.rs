@@ -0,0 +1,279 @@
+use crate::style::{Style, Tone};
+use debian_bridge_core::{CreateRequest, Feature, PackageSummary};
+use std::io::{self, BufRead, Write};
+
+/// Interactive `create` of a package given without flags: shows the package, lets the user pick
+/// the features and the command and confirms the build. Answers are set on `request` like the
+/// flags would be, `None` is returned if the user declined or the input ended
+pub fn run<R: BufRead, W: Write>(
+    summary: &PackageSummary,
+    features: &[(Feature, bool)],
+    request: CreateRequest,
+    style: Style,
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<Option<CreateRequest>> {
+    writeln!(output, "Package: \n")?;
+    writeln!(output, "\t{:<15} ===> {}", "Name", summary.package)?;
+
+    if let Some(version) = &summary.version {
+        writeln!(output, "\t{:<15} ===> {}", "Version", version)?;
+    }
+
+    if let Some(synopsis) = &summary.synopsis {
+        writeln!(output, "\t{:<15} ===> {}", "Description", synopsis)?;
+    }
+
+    writeln!(output, "\t{:<15} ===> {}", "Kind", summary.kind)?;
+
+    let mut chosen: Vec<Feature> = summary
+        .features
+        .iter()
+        .filter(|feature| {
+            features
+                .iter()
+                .any(|(known, available)| known == *feature && *available)
+        })
+        .cloned()
+        .collect();
+
+    loop {
+        writeln!(output, "\nFeatures: \n")?;
+
+        for (index, (feature, available)) in features.iter().enumerate() {
+            writeln!(
+                output,
+                "\t{:>2}. [{}] {:<15} ===> {}",
+                index + 1,
+                match chosen.contains(feature) {
+                    true => "x",
+                    false => " ",
+                },
+                feature.to_string(),
+                match available {
+                    true => style.paint("available", Tone::Good),
+                    false => style.paint("unavailable", Tone::Bad),
+                }
+            )?;
+        }
+
+        let answer = match ask(
+            input,
+            output,
+            "\nToggle features by number, e.g. 1 3, or press Enter to keep them: ",
+        )? {
+            Some(answer) => answer,
+            None => return Ok(None),
+        };
+
+        if answer.is_empty() {
+            break;
+        }
+
+        if let Err(message) = toggle(&mut chosen, features, &answer) {
+            writeln!(output, "{}", style.paint(&message, Tone::Bad))?;
+        }
+    }
+
+    writeln!(output, "\nCommands: \n")?;
+
+    for (index, command) in summary.commands.iter().enumerate() {
+        writeln!(output, "\t{:>2}. {}", index + 1, command)?;
+    }
+
+    let command = loop {
+        let question = match &summary.command {
+            Some(command) => format!("\nCommand, a number or your own [{}]: ", command),
+            None => "\nCommand, a number or your own: ".to_string(),
+        };
+        let answer = match ask(input, output, &question)? {
+            Some(answer) => answer,
+            None => return Ok(None),
+        };
+
+        match (answer.parse::<usize>(), &summary.command) {
+            (Ok(index), _) if (1..=summary.commands.len()).contains(&index) => {
+                break summary.commands[index - 1].to_owned()
+            }
+            (Ok(_), _) => writeln!(output, "{}", style.paint("No such command", Tone::Bad))?,
+            (_, Some(command)) if answer.is_empty() => break command.to_owned(),
+            _ if answer.is_empty() => {
+                writeln!(output, "{}", style.paint("Pick a command", Tone::Bad))?
+            }
+            _ => break answer,
+        }
+    };
+    let chosen: Vec<Feature> = features
+        .iter()
+        .map(|(feature, _)| feature.to_owned())
+        .filter(|feature| chosen.contains(feature))
+        .collect();
+    let question = format!(
+        "\nCreate {} running `{}` with {}? [Y/n] ",
+        summary.package,
+        command,
+        match chosen.is_empty() {
+            true => "no features".to_string(),
+            false => chosen
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        }
+    );
+
+    let answer = ask(input, output, &question)?.map(|answer| answer.to_lowercase());
+
+    Ok(match answer.as_deref() {
+        Some("") | Some("y") | Some("yes") => Some(request.features(&chosen).command(command)),
+        _ => None,
+    })
+}
+
+/// Toggles features by their numbers in `features`, nothing changes if any number is wrong
+fn toggle(
+    chosen: &mut Vec<Feature>,
+    features: &[(Feature, bool)],
+    answer: &str,
+) -> Result<(), String> {
+    let picked = answer
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|number| !number.is_empty())
+        .map(|number| match number.parse::<usize>() {
+            Ok(index) if (1..=features.len()).contains(&index) => {
+                Ok(features[index - 1].to_owned())
+            }
+            _ => Err(format!("No feature numbered '{}'", number)),
+        })
+        .collect::<Result<Vec<(Feature, bool)>, String>>()?;
+
+    if let Some((feature, _)) = picked
+        .iter()
+        .find(|(feature, available)| !available && !chosen.contains(feature))
+    {
+        return Err(format!(
+            "{} is unavailable, see `debian_bridge test` for the reason",
+            feature
+        ));
+    }
+
+    for (feature, _) in picked {
+        match chosen.iter().position(|chosen| *chosen == feature) {
+            Some(position) => {
+                chosen.remove(position);
+            }
+            None => chosen.push(feature),
+        }
+    }
+
+    Ok(())
+}
+
+/// Trimmed answer to `question`, `None` once the input ends
+fn ask<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+) -> io::Result<Option<String>> {
+    write!(output, "{}", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+
+    Ok(match input.read_line(&mut answer)? {
+        0 => None,
+        _ => Some(answer.trim().to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::ColorChoice;
+    use debian_bridge_core::ProgramKind;
+    use std::{io::Cursor, path::Path};
+
+    fn summary() -> PackageSummary {
+        PackageSummary {
+            package: "foo".to_string(),
+            version: Some("1.0".to_string()),
+            synopsis: Some("Foo viewer".to_string()),
+            commands: vec!["foo".to_string(), "foo-helper".to_string()],
+            command: Some("foo".to_string()),
+            features: vec![Feature::Display, Feature::Sound],
+            kind: ProgramKind::Gui,
+        }
+    }
+
+    fn features() -> Vec<(Feature, bool)> {
+        vec![
+            (Feature::Display, true),
+            (Feature::Sound, false),
+            (Feature::HomePersistent, true),
+        ]
+    }
+
+    fn answer(summary: &PackageSummary, input: &str) -> (Option<CreateRequest>, String) {
+        let mut output = vec![];
+        let request = run(
+            summary,
+            &features(),
+            CreateRequest::from_deb(Path::new("/tmp/foo.deb")),
+            Style::new(ColorChoice::Never, None, true),
+            &mut Cursor::new(input.as_bytes()),
+            &mut output,
+        )
+        .unwrap();
+
+        (request, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_defaults() {
+        let (request, output) = answer(&summary(), "\n\n\n");
+        let request = request.unwrap();
+
+        assert_eq!(request.path, Path::new("/tmp/foo.deb"));
+        assert_eq!(request.features, vec![Feature::Display]);
+        assert_eq!(request.command.as_deref(), Some("foo"));
+        assert!(output.contains("\tDescription     ===> Foo viewer\n"));
+        assert!(output.contains("\tKind            ===> gui\n"));
+        assert!(output.contains("\t 1. [x] Display         ===> available\n"));
+        assert!(output.contains("\t 2. [ ] Sound           ===> unavailable\n"));
+        assert!(output.contains("Create foo running `foo` with Display? [Y/n] "));
+    }
+
+    #[test]
+    fn test_choices() {
+        let (request, output) = answer(&summary(), "2\n9\n1, 3\n\n4\n2\ny\n");
+        let request = request.unwrap();
+
+        assert_eq!(request.features, vec![Feature::HomePersistent]);
+        assert_eq!(request.command.as_deref(), Some("foo-helper"));
+        assert!(output.contains("Sound is unavailable"));
+        assert!(output.contains("No feature numbered '9'"));
+        assert!(output.contains("No such command"));
+
+        let (request, _) = answer(&summary(), "\n/opt/foo/foo --safe\n\n");
+
+        assert_eq!(
+            request.unwrap().command.as_deref(),
+            Some("/opt/foo/foo --safe")
+        );
+    }
+
+    #[test]
+    fn test_declined() {
+        assert!(answer(&summary(), "\n\nn\n").0.is_none());
+        assert!(answer(&summary(), "1\n").0.is_none());
+
+        let summary = PackageSummary {
+            command: None,
+            ..summary()
+        };
+        let (request, output) = answer(&summary, "\n\n2\n\n");
+
+        assert!(output.contains("Pick a command"));
+        assert_eq!(request.unwrap().command.as_deref(), Some("foo-helper"));
+    }
+}
@@ -0,0 +1,185 @@
+use super::{
+    context::BuildContext,
+    docker::{BuildSpec, ManagedImage, RunOutcome},
+    error::AppError,
+    events::Reporter,
+    Program, RunOptions, System,
+};
+use std::path::Path;
+
+type AppResult<T> = Result<T, AppError>;
+
+/// The first API version shipped with BuildKit (docker 18.09)
+const BUILDKIT_API_VERSION: (u32, u32) = (1, 39);
+
+/// Container engine the app manages images and containers with. `App::new` talks to the docker
+/// daemon, `App::with_backend` accepts another one, e.g. `testing::MockBackend`.
+/// Missing images and containers are reported as `AppError::DockerStatus` with code 404
+pub trait Backend: Send {
+    fn system(&self) -> &System;
+
+    /// Prefix of the image and container names
+    fn prefix(&self) -> &str;
+
+    /// Copy of the backend for another thread, it must work with the same engine
+    fn boxed_clone(&self) -> Box<dyn Backend>;
+
+    /// Checks whether the engine responds
+    fn ping(&self) -> AppResult<()>;
+
+    /// IDs of the containers created from the image
+    fn get_containers(&self, image: &str) -> AppResult<Vec<String>>;
+
+    /// Names of the exited containers started by runs of any program
+    fn exited_containers(&self) -> AppResult<Vec<String>>;
+
+    /// IDs of all containers started by runs of any program
+    fn labeled_containers(&self) -> AppResult<Vec<String>>;
+
+    /// IDs of the running containers started for the program
+    fn running_containers(&self, program: &str) -> AppResult<Vec<String>>;
+
+    /// Total size of the writable layers of the containers started for the program
+    fn containers_size(&self, program: &str) -> AppResult<u64>;
+
+    /// ID of the container with the given name, `None` if there is no such container
+    fn container_id(&self, name: &str) -> AppResult<Option<String>>;
+
+    fn rename_container(&self, from: &str, to: &str) -> AppResult<()>;
+
+    fn delete_container(&self, id: &str) -> AppResult<()>;
+
+    /// Stops the container, killing it if it doesn't exit within a timeout
+    fn stop_container(&self, id: &str) -> AppResult<()>;
+
+    /// Builds an image tagged with `tag` from the dockerfile found in `context`
+    fn build(
+        &self,
+        context: &BuildContext,
+        tag: &str,
+        spec: &BuildSpec,
+        reporter: &Reporter,
+    ) -> AppResult<()>;
+
+    /// Pulls a fresh version of a public image, e.g. `debian:9-slim`
+    fn pull(&self, image: &str) -> AppResult<()>;
+
+    fn image_exists(&self, tag: &str) -> AppResult<bool>;
+
+    /// Size of the image including the parent layers, `None` if there is no such image
+    fn image_size(&self, tag: &str) -> AppResult<Option<u64>>;
+
+    fn image_id(&self, tag: &str) -> AppResult<String>;
+
+    /// Prefix of the app which built the image, see `PREFIX_LABEL`. `None` if there is no such
+    /// image or it isn't labeled with one
+    fn image_prefix(&self, tag: &str) -> AppResult<Option<String>>;
+
+    /// Images built by debian_bridge
+    fn managed_images(&self) -> AppResult<Vec<ManagedImage>>;
+
+    /// Adds `to` tag to the image
+    fn tag(&self, from: &str, to: &str) -> AppResult<()>;
+
+    fn delete_image(&self, tag: &str) -> AppResult<()>;
+
+    /// Streams the image to a tarball at `path` like `docker save`, returns the SHA-256 of the
+    /// tarball. The written size is reported as `AppEvent::ImageTransfer`
+    fn save_image(&self, tag: &str, path: &Path, reporter: &Reporter) -> AppResult<String>;
+
+    /// Loads the images of a tarball written by `save_image`, returns their tags
+    fn load_image(&self, path: &Path) -> AppResult<Vec<String>>;
+
+    /// Runs a shell script in a throwaway container of the image, returns its exit code and
+    /// standard output
+    fn probe(&self, image: &str, script: &str) -> AppResult<(i32, String)>;
+
+    /// Runs the program attached to the terminal until it exits. Its output is copied to `log`
+    /// as well if it's given
+    fn run(
+        &self,
+        program: &Program,
+        options: &RunOptions,
+        log: Option<&Path>,
+    ) -> AppResult<RunOutcome>;
+
+    /// Deletes the persistent container of the program, returns whether there was one
+    fn delete_persistent(&self, program: &Program) -> AppResult<bool> {
+        match self.container_id(&program.get_name(self.prefix()))? {
+            Some(id) => self.delete_container(&id).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Stops all running containers of the program, returns their IDs
+    fn stop(&self, program: &Program) -> AppResult<Vec<String>> {
+        let ids = self.running_containers(&program.get_name_short())?;
+
+        ids.iter().try_for_each(|id| self.stop_container(id))?;
+
+        Ok(ids)
+    }
+
+    /// Deletes the image of the program with all its containers, returns IDs of the containers
+    fn delete(&self, program: &Program) -> AppResult<Vec<String>> {
+        let name = program.get_name(self.prefix());
+        let containers_ids = self.get_containers(&name)?;
+
+        containers_ids
+            .iter()
+            .try_for_each(|id| self.delete_container(id))?;
+
+        self.delete_image(&name)?;
+
+        Ok(containers_ids)
+    }
+
+    /// Builds the image of the program named `name`. Names of two prefixes may collide, e.g.
+    /// program `foo_bar` of `debian_bridge` and program `bar` of `debian_bridge_foo`, so an
+    /// image labeled with another prefix is overwritten only if `force` is set
+    fn create(
+        &self,
+        name: &str,
+        context: &BuildContext,
+        spec: &BuildSpec,
+        reporter: &Reporter,
+        force: bool,
+    ) -> AppResult<()> {
+        let tag = format!("{}_{}", self.prefix(), name);
+
+        match self.image_prefix(&tag)? {
+            Some(owner) if owner != self.prefix() && !force => {
+                return Err(AppError::Program(format!(
+                    "Image '{}' belongs to programs of '{}', pass --force to overwrite it",
+                    tag, owner
+                )))
+            }
+            _ => (),
+        }
+
+        self.build(context, &tag, spec, reporter)
+    }
+
+    /// Moves the image from `from` tag to `to` tag
+    fn retag(&self, from: &str, to: &str) -> AppResult<()> {
+        self.tag(from, to)?;
+        self.delete_image(from)
+    }
+
+    /// Whether the engine is recent enough to build images with BuildKit, warns if it isn't
+    fn use_buildkit(&self) -> bool {
+        let version = &self.system().docker_version;
+
+        if version.api().is_some_and(|v| v >= BUILDKIT_API_VERSION) {
+            return true;
+        }
+
+        warn!(
+            "Docker API {} doesn't support BuildKit (requires {}.{}), apt cache won't be shared \
+             between builds",
+            version, BUILDKIT_API_VERSION.0, BUILDKIT_API_VERSION.1
+        );
+
+        false
+    }
+}
@@ -0,0 +1,656 @@
+use super::{
+    docker::{
+        host_theme, AVAHI_SOCKET, DEVICE_GROUPS, DEVICE_RULES, DEVICE_RULES_API_VERSION,
+        MANAGED_LABEL, PREFIX_LABEL, PROGRAM_LABEL, PROXY_BUS, PULSE_COOKIE, SANE_NET_CONF,
+        SYSTEM_BUS,
+    },
+    error::AppError,
+    util::{expand_devices, get_group_ids, home_dir, host_devices, path_str},
+    ContainerMode, Feature, Program, Seccomp, System,
+};
+use std::{
+    fmt::{Display, Formatter},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Bind of a host path into the container
+struct Volume {
+    source: String,
+    target: String,
+    read_only: bool,
+}
+
+/// docker compose service starting a program the way `App::run` does. Devices, groups and paths
+/// are resolved on this host when the service is generated. What compose can't do, e.g. starting
+/// xdg-dbus-proxy, is written as comments describing the manual step
+pub struct ComposeService {
+    name: String,
+    image: String,
+    container_name: Option<String>,
+    labels: Vec<String>,
+    user: Option<String>,
+    read_only: bool,
+    shm_size: Option<u64>,
+    oom_score_adj: Option<i32>,
+    cap_drop: Vec<String>,
+    cap_add: Vec<String>,
+    security_opt: Vec<String>,
+    group_add: Vec<String>,
+    devices: Vec<String>,
+    device_cgroup_rules: Vec<String>,
+    tmpfs: Vec<String>,
+    volumes: Vec<Volume>,
+    environment: Vec<String>,
+    ulimits: Vec<(String, u64)>,
+    entrypoint: Vec<String>,
+    command: Vec<String>,
+    notes: Vec<String>,
+}
+
+impl ComposeService {
+    pub fn try_new(program: &Program, system: &System, prefix: &str) -> AppResult<Self> {
+        let home = path_str(&home_dir()?)?.to_string();
+        let image = program.get_name(prefix);
+        let short_name = program.get_name_short();
+        let groups = std::fs::read_to_string("/etc/group").unwrap_or_default();
+        let mut service = ComposeService {
+            name: short_name.to_owned(),
+            image: image.to_owned(),
+            container_name: None,
+            labels: vec![
+                format!("{}=true", MANAGED_LABEL),
+                format!("{}={}", PROGRAM_LABEL, short_name),
+                format!("{}={}", PREFIX_LABEL, prefix),
+            ],
+            user: None,
+            read_only: false,
+            shm_size: program.shm_size,
+            oom_score_adj: program.resources.oom_score_adj,
+            cap_drop: vec![],
+            cap_add: vec![],
+            security_opt: vec![],
+            group_add: vec![],
+            devices: vec![],
+            device_cgroup_rules: vec![],
+            tmpfs: vec![],
+            volumes: vec![],
+            environment: vec![],
+            ulimits: program
+                .resources
+                .ulimits
+                .iter()
+                .map(|(name, value)| (name.to_owned(), *value))
+                .collect(),
+            entrypoint: vec![],
+            command: vec![],
+            notes: vec![],
+        };
+
+        service
+            .volume("/etc/machine-id", "/etc/machine-id", false)
+            .volume("/var/lib/dbus", "/var/lib/dbus", false);
+
+        if program.shm_size.is_none() {
+            service.volume("/dev/shm", "/dev/shm", false);
+        }
+
+        let devices = program.uses(&Feature::Devices);
+        let device_rules = system
+            .docker_version
+            .api()
+            .is_some_and(|v| v >= DEVICE_RULES_API_VERSION);
+
+        if devices && !device_rules {
+            return Err(AppError::Feature(format!(
+                "Docker API {} can't open devices to a container (requires {}.{}), remove the \
+                 devices feature or upgrade Docker",
+                system.docker_version, DEVICE_RULES_API_VERSION.0, DEVICE_RULES_API_VERSION.1
+            )));
+        }
+
+        service.cap_drop.push("ALL".to_string());
+        service.cap_add.extend(program.capabilities.iter().cloned());
+
+        for (capability, _) in program.resources.capabilities() {
+            if !service.cap_add.iter().any(|c| c == capability) {
+                service.cap_add.push(capability.to_string());
+            }
+        }
+
+        service.security(program, system);
+
+        if system.rootless {
+            service.user = Some("0:0".to_string());
+        }
+
+        if program.uses(&Feature::Display) {
+            match program.remote_display {
+                Some(display) => service.environment.push(format!("DISPLAY={}", display)),
+                None => {
+                    service
+                        .volume("/tmp/.X11-unix", "/tmp/.X11-unix", false)
+                        .environment
+                        .push("DISPLAY".to_string());
+                    service.notes.push(
+                        "The X server must accept the container, allow local clients with \
+                         `xhost +local:` before starting the service"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        if program.uses(&Feature::Sound) {
+            match &program.pulse_server {
+                Some(server) => {
+                    service
+                        .environment
+                        .push(format!("PULSE_SERVER={}", server.address()));
+
+                    if let Some(cookie) = &server.cookie {
+                        service
+                            .volume(path_str(cookie)?, PULSE_COOKIE, true)
+                            .environment
+                            .push(format!("PULSE_COOKIE={}", PULSE_COOKIE));
+                    }
+                }
+                None => service.devices.push("/dev/snd".to_string()),
+            }
+        }
+
+        if program.uses(&Feature::HomePersistent) {
+            service.volume(&home, &home, false);
+        }
+
+        let runtime_dir = system.runtime_dir.to_owned();
+        let session_bus = runtime_dir
+            .as_ref()
+            .map(|dir| dir.join("bus"))
+            .filter(|bus| bus.exists());
+        let socket_dir = runtime_dir.unwrap_or_else(std::env::temp_dir);
+        let session_names: Vec<&str> = [
+            (Feature::Keyring, "org.freedesktop.secrets"),
+            (Feature::Portals, "org.freedesktop.portal.*"),
+        ]
+        .iter()
+        .filter(|(feature, _)| program.settings.contains(feature))
+        .map(|(_, name)| *name)
+        .collect();
+
+        // The proxy isn't looked up, compose can't start it anyway
+        if program.uses(&Feature::InputMethod) {
+            if let Some(bus) = &session_bus {
+                let bus = path_str(bus)?;
+
+                service
+                    .volume(bus, bus, false)
+                    .environment
+                    .push(format!("DBUS_SESSION_BUS_ADDRESS=unix:path={}", bus));
+            }
+        } else if !session_names.is_empty() {
+            let socket = socket_dir.join(format!("{}.secrets", image));
+
+            service.proxy(session_bus.as_deref(), &socket, PROXY_BUS, &session_names)?;
+            service
+                .environment
+                .push(format!("DBUS_SESSION_BUS_ADDRESS=unix:path={}", PROXY_BUS));
+        }
+
+        if program.uses(&Feature::Portals) {
+            service.environment.push("GTK_USE_PORTAL=1".to_string());
+
+            if let Some(doc) = system.runtime_dir.as_ref().map(|dir| dir.join("doc")) {
+                if system.rootless && doc.is_dir() {
+                    let doc = path_str(&doc)?;
+
+                    service.volume(doc, doc, false);
+                }
+            }
+        }
+
+        let system_names: Vec<&str> = [
+            (Feature::Bluetooth, "org.bluez"),
+            (Feature::Mdns, "org.freedesktop.Avahi"),
+        ]
+        .iter()
+        .filter(|(feature, _)| program.settings.contains(feature))
+        .map(|(_, name)| *name)
+        .collect();
+
+        if !system_names.is_empty() {
+            let socket = socket_dir.join(format!("{}.system", image));
+
+            service.proxy(
+                Some(Path::new(SYSTEM_BUS)),
+                &socket,
+                SYSTEM_BUS,
+                &system_names,
+            )?;
+        }
+
+        if program.uses(&Feature::Mdns) {
+            service.volume(AVAHI_SOCKET, AVAHI_SOCKET, false);
+        }
+
+        if program.uses(&Feature::OpenUrls) {
+            service.notes.push(
+                "Links aren't opened on the host, only `debian_bridge run` listens for them"
+                    .to_string(),
+            );
+        }
+
+        if program.uses(&Feature::InputMethod) {
+            service.environment.extend(
+                ["XMODIFIERS", "GTK_IM_MODULE", "QT_IM_MODULE"]
+                    .iter()
+                    .map(|name| name.to_string()),
+            );
+
+            for (dir, read_only) in [(".config/ibus", true), (".cache/ibus", false)] {
+                let dir = format!("{}/{}", home, dir);
+
+                if Path::new(&dir).exists() {
+                    service.volume(&dir, &dir, read_only);
+                }
+            }
+        }
+
+        if program.uses(&Feature::HostTheme) {
+            let (binds, envs) = host_theme(&home, std::env::var("GTK_THEME").ok().as_deref());
+
+            for (source, target) in &binds {
+                service.volume(source, target, true);
+            }
+
+            service.environment.extend(envs);
+        }
+
+        if program.uses(&Feature::Gamepad) {
+            service.devices.extend(
+                System::connected_gamepads()
+                    .iter()
+                    .chain(Some(PathBuf::from("/dev/uinput")).iter())
+                    .filter(|device| device.exists())
+                    .map(|device| device.display().to_string()),
+            );
+            service.group_add.extend(get_group_ids(&groups, &["input"]));
+            service.notes.push(
+                "Gamepads connected when the service was exported are passed through, export it \
+                 again after plugging in another one"
+                    .to_string(),
+            );
+        }
+
+        if program.uses(&Feature::Scanner) {
+            let scanners: Vec<PathBuf> = System::connected_scanners()
+                .iter()
+                .filter_map(|device| System::scanner_node(device))
+                .filter(|node| node.exists())
+                .collect();
+
+            match scanners.is_empty() {
+                false => {
+                    for node in &scanners {
+                        service.devices.push(node.display().to_string());
+
+                        if let Ok(metadata) = std::fs::metadata(node) {
+                            service.group_add.push(metadata.gid().to_string());
+                        }
+                    }
+                }
+                true => {
+                    let conf = format!("./{}.sane-net.conf", short_name);
+
+                    service
+                        .volume(&conf, SANE_NET_CONF, true)
+                        .notes
+                        .push(format!(
+                            "Scanners are reached over saned of the host, write `localhost` to {}",
+                            conf
+                        ));
+                }
+            }
+        }
+
+        for share in &program.shares {
+            let path = share.path.display().to_string();
+
+            service.volume(&path, &path, share.read_only);
+        }
+
+        let (shared_devices, _) = expand_devices(&program.devices);
+
+        for device in &shared_devices {
+            service.devices.push(device.display().to_string());
+
+            if let Ok(metadata) = std::fs::metadata(device) {
+                service.group_add.push(metadata.gid().to_string());
+            }
+        }
+
+        if program.uses(&Feature::ReadOnlyRoot) {
+            service.read_only = true;
+            service
+                .tmpfs
+                .extend(["/tmp".to_string(), "/run".to_string()]);
+
+            if !program.uses(&Feature::HomePersistent) {
+                service.tmpfs.push(format!("{}:mode=1777", home));
+            }
+        }
+
+        if program.uses(&Feature::Time) {
+            service.volume("/etc/localtime", "/etc/localtime", false);
+        }
+
+        if program.uses(&Feature::Notification) {
+            service.volume("/var/lib/dbus", "/var/lib/dbus", false);
+        }
+
+        // Like `build_run_spec`, only the device classes are shared, never the whole /dev
+        if devices {
+            let (dirs, nodes) = host_devices();
+
+            for dir in dirs.iter().map(|dir| dir.display().to_string()) {
+                if !service.devices.contains(&dir) {
+                    service.volume(&dir, &dir, false);
+                }
+            }
+
+            service
+                .devices
+                .extend(nodes.iter().map(|node| node.display().to_string()));
+            service
+                .device_cgroup_rules
+                .extend(DEVICE_RULES.iter().map(|rule| rule.to_string()));
+            service
+                .group_add
+                .extend(get_group_ids(&groups, DEVICE_GROUPS));
+        }
+
+        service.group_add.sort();
+        service.group_add.dedup();
+
+        match program.container_mode {
+            ContainerMode::Persistent => service.container_name = Some(image.to_owned()),
+            ContainerMode::Ephemeral => service.notes.push(format!(
+                "The program runs in a fresh container every time, start it with \
+                 `docker compose run --rm {}`",
+                short_name
+            )),
+        }
+
+        // docker can't set the niceness, the command of the image is started through nice
+        if let Some(nice) = program.resources.nice {
+            service.entrypoint = vec!["nice".to_string()];
+            service.command = vec![
+                "-n".to_string(),
+                nice.to_string(),
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                program.command.to_owned(),
+            ];
+        } else if program.source_image.is_some() && !program.command.is_empty() {
+            service.command = vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                program.command.to_owned(),
+            ];
+        }
+
+        for (kind, hook) in [
+            ("pre-run", &program.pre_run),
+            ("post-run", &program.post_run),
+        ] {
+            if let Some(hook) = hook {
+                service.notes.push(format!(
+                    "The {} hook isn't run by compose, run `{}` on the host yourself",
+                    kind, hook
+                ));
+            }
+        }
+
+        Ok(service)
+    }
+
+    fn volume(&mut self, source: &str, target: &str, read_only: bool) -> &mut Self {
+        if !self.volumes.iter().any(|volume| volume.target == target) {
+            self.volumes.push(Volume {
+                source: source.to_string(),
+                target: target.to_string(),
+                read_only,
+            });
+        }
+
+        self
+    }
+
+    /// Mounts the socket of xdg-dbus-proxy filtering `bus`, which has to be started by hand
+    fn proxy(
+        &mut self,
+        bus: Option<&Path>,
+        socket: &Path,
+        target: &str,
+        names: &[&str],
+    ) -> AppResult<()> {
+        let socket = path_str(socket)?.to_string();
+        let bus = match bus {
+            Some(bus) => path_str(bus)?.to_string(),
+            None => "$XDG_RUNTIME_DIR/bus".to_string(),
+        };
+
+        self.volume(&socket, target, false).notes.push(format!(
+            "Start the D-Bus filter first: xdg-dbus-proxy unix:path={} {} --filter {}",
+            bus,
+            socket,
+            names
+                .iter()
+                .map(|name| format!("--talk={}", name))
+                .collect::<Vec<String>>()
+                .join(" ")
+        ));
+
+        Ok(())
+    }
+
+    fn security(&mut self, program: &Program, system: &System) {
+        match &program.security.seccomp {
+            Seccomp::Builtin => {
+                let path = format!("./{}.seccomp.json", self.name);
+
+                self.security_opt.push(format!("seccomp={}", path));
+                self.notes.push(format!(
+                    "Save the built-in seccomp profile of debian_bridge (resources/seccomp.json) \
+                     as {}",
+                    path
+                ));
+            }
+            Seccomp::Unconfined => self.security_opt.push("seccomp=unconfined".to_string()),
+            Seccomp::Profile(path) => self
+                .security_opt
+                .push(format!("seccomp={}", path.display())),
+        }
+
+        if let Some(profile) = &program.security.apparmor {
+            match system.apparmor {
+                true => self.security_opt.push(format!("apparmor={}", profile)),
+                false => self.notes.push(format!(
+                    "AppArmor profile {} is left out, AppArmor isn't enabled on this host",
+                    profile
+                )),
+            }
+        }
+    }
+}
+
+impl Display for ComposeService {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        writeln!(f, "services:")?;
+
+        for note in &self.notes {
+            writeln!(f, "  # {}", note)?;
+        }
+
+        writeln!(f, "  {}:", self.name)?;
+        writeln!(f, "    image: {}", quote(&self.image))?;
+
+        if let Some(name) = &self.container_name {
+            writeln!(f, "    container_name: {}", quote(name))?;
+        }
+
+        writeln!(f, "    stdin_open: true")?;
+        writeln!(f, "    tty: true")?;
+        writeln!(f, "    network_mode: host")?;
+
+        if let Some(user) = &self.user {
+            writeln!(f, "    user: {}", quote(user))?;
+        }
+
+        if self.read_only {
+            writeln!(f, "    read_only: true")?;
+        }
+
+        if let Some(size) = self.shm_size {
+            writeln!(f, "    shm_size: {}", size)?;
+        }
+
+        if let Some(adj) = self.oom_score_adj {
+            writeln!(f, "    oom_score_adj: {}", adj)?;
+        }
+
+        write_list(f, "labels", &self.labels)?;
+        write_list(f, "cap_drop", &self.cap_drop)?;
+        write_list(f, "cap_add", &self.cap_add)?;
+        write_list(f, "security_opt", &self.security_opt)?;
+        write_list(f, "group_add", &self.group_add)?;
+        write_list(f, "devices", &self.devices)?;
+        write_list(f, "device_cgroup_rules", &self.device_cgroup_rules)?;
+        write_list(f, "tmpfs", &self.tmpfs)?;
+
+        if !self.volumes.is_empty() {
+            writeln!(f, "    volumes:")?;
+
+            for volume in &self.volumes {
+                writeln!(f, "      - type: bind")?;
+                writeln!(f, "        source: {}", quote(&volume.source))?;
+                writeln!(f, "        target: {}", quote(&volume.target))?;
+
+                if volume.read_only {
+                    writeln!(f, "        read_only: true")?;
+                }
+            }
+        }
+
+        write_list(f, "environment", &self.environment)?;
+
+        if !self.ulimits.is_empty() {
+            writeln!(f, "    ulimits:")?;
+
+            for (name, value) in &self.ulimits {
+                writeln!(f, "      {}:", name)?;
+                writeln!(f, "        soft: {}", value)?;
+                writeln!(f, "        hard: {}", value)?;
+            }
+        }
+
+        write_list(f, "entrypoint", &self.entrypoint)?;
+        write_list(f, "command", &self.command)
+    }
+}
+
+fn write_list(f: &mut Formatter, key: &str, values: &[String]) -> std::fmt::Result {
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(f, "    {}:", key)?;
+
+    for value in values {
+        writeln!(f, "      - {}", quote(value))?;
+    }
+
+    Ok(())
+}
+
+/// Double quoted YAML scalar, `$` is doubled as compose interpolates variables
+fn quote(value: &str) -> String {
+    let escaped: String = value
+        .chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '$' => "$$".to_string(),
+            c => c.to_string(),
+        })
+        .collect();
+
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sys::driver::DockerVersion, PulseServer};
+
+    #[test]
+    fn test_compose_service() {
+        let mut program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![Feature::Display, Feature::Sound, Feature::Devices],
+            &None,
+            &None,
+            &None,
+        );
+        let mut system = System::offline();
+
+        system.docker_version = DockerVersion("1.40".to_string());
+        program.pulse_server = Some(PulseServer::new("192.168.1.5".parse().unwrap()));
+        program.resources.ulimits.insert("nofile".to_string(), 4096);
+        program.resources.nice = Some(5);
+        program.pre_run = Some("vpn-guard on".to_string());
+
+        let compose = ComposeService::try_new(&program, &system, "debian_bridge")
+            .unwrap()
+            .to_string();
+
+        assert!(compose.starts_with("services:\n  # Save the built-in seccomp profile"));
+        assert!(compose.contains("\n  # The X server must accept the container"));
+        assert!(compose.contains("  foo:\n    image: \"debian_bridge_foo\"\n"));
+        assert!(compose.contains("      - \"PULSE_SERVER=tcp:192.168.1.5:4713\"\n"));
+        assert!(compose.contains("        source: \"/tmp/.X11-unix\"\n"));
+        assert!(compose.contains("    device_cgroup_rules:\n      - \"c 81:* rmw\"\n"));
+        assert!(compose.contains("      nofile:\n        soft: 4096\n        hard: 4096\n"));
+        assert!(compose.contains("    entrypoint:\n      - \"nice\"\n"));
+        assert!(compose.contains("run `vpn-guard on` on the host yourself"));
+        assert!(!compose.contains("privileged"));
+        assert_eq!(quote("a\"$HOME"), "\"a\\\"$$HOME\"");
+    }
+
+    #[test]
+    fn test_compose_portals() {
+        let program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![Feature::Keyring, Feature::Portals],
+            &None,
+            &None,
+            &None,
+        );
+        let compose = ComposeService::try_new(&program, &System::offline(), "debian_bridge")
+            .unwrap()
+            .to_string();
+
+        assert!(compose
+            .contains("--filter --talk=org.freedesktop.secrets --talk=org.freedesktop.portal.*"));
+        assert!(compose.contains("      - \"GTK_USE_PORTAL=1\"\n"));
+        assert!(compose.contains(&format!(
+            "      - \"DBUS_SESSION_BUS_ADDRESS=unix:path={}\"\n",
+            PROXY_BUS
+        )));
+    }
+}
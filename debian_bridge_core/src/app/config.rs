@@ -1,16 +1,52 @@
-use super::error::AppError;
+use super::{
+    error::AppError,
+    repo::LocalRepo,
+    usage::human_size,
+    util::{edit_distance, parse_age, sanitize_name, user_icons_dir, with_suffix, BASE_RELEASE},
+};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
     error::Error,
     fmt::Display,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, Read, Write},
+    net::IpAddr,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 pub type AppResult<T> = Result<T, AppError>;
 
-const ICON_NAME_DEFAULT: &str = "debian_bridge_default.ico";
+/// Default icon in the hicolor theme of the user
+const ICON_DEFAULT: &str = "hicolor/256x256/apps/debian_bridge.png";
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const SNAPSHOT_LATEST: &str = "latest";
+/// Keys of `Config::setting` and `Config::set_setting`
+pub const SETTING_KEYS: &[&str] = &[
+    "features",
+    "desktop-entries",
+    "backend",
+    "base-image",
+    "apt-mirror",
+    "jobs",
+    "logs",
+    "notify",
+    "notify-after",
+    "keep-previous",
+    "offline-probe",
+];
+/// Most builds `GlobalSettings::jobs` runs at once by default, more rarely pays off as they
+/// compete for the disk and the network
+const DEFAULT_JOBS_MAX: usize = 4;
+/// Operations shorter than this aren't notified unless `GlobalSettings::notify_after` is set
+const DEFAULT_NOTIFY_AFTER: std::time::Duration = std::time::Duration::from_secs(30);
+/// Age of the previous images `clean` removes unless `GlobalSettings::keep_previous` is set
+const DEFAULT_KEEP_PREVIOUS: &str = "30d";
+const BACKENDS: &[&str] = &["docker"];
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Icon {
@@ -25,18 +61,20 @@ impl Icon {
     }
 }
 
-impl Default for Icon {
-    fn default() -> Self {
-        let mut path = dirs::home_dir().unwrap();
-
-        path.push(".icons");
-        path.push(ICON_NAME_DEFAULT);
+impl Icon {
+    /// Icon shipped with debian_bridge, the file is written out when a program is created with it
+    pub fn try_default() -> AppResult<Self> {
+        Ok(Icon {
+            path: user_icons_dir()?.join(ICON_DEFAULT),
+        })
+    }
 
-        Icon { path }
+    pub fn is_default(&self) -> bool {
+        Icon::try_default().is_ok_and(|icon| icon.path == self.path)
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum Feature {
     Display,
     Sound,
@@ -44,6 +82,85 @@ pub enum Feature {
     Devices,
     HomePersistent,
     Time,
+    /// The root filesystem is mounted read-only, temporary directories and the home stay
+    /// writable
+    ReadOnlyRoot,
+    /// IBus or Fcitx running on the host, their client modules are installed into the image
+    InputMethod,
+    /// Fonts, GTK theme and icons of the host, theme engines are installed into the image
+    HostTheme,
+    /// Secret service of the session bus, the program can read and write the keyring entries
+    Keyring,
+    /// Gamepads and joysticks connected when the program starts, without the other devices
+    Gamepad,
+    /// BlueZ on the system bus, the program talks only to `org.bluez` there
+    Bluetooth,
+    /// Scanners of the host, a USB one is passed through, the others are reached over saned
+    Scanner,
+    /// Avahi of the host, `.local` names are resolved and services are discovered through it
+    Mdns,
+    /// Links the program opens go to `xdg-open` of the host, see `urls::check_url`
+    OpenUrls,
+    /// xdg-desktop-portal of the host, e.g. its file chooser, on the filtered session bus
+    Portals,
+}
+
+/// Names of the features as the flags of `create` spell them
+const FEATURE_NAMES: &[(&str, Feature)] = &[
+    ("display", Feature::Display),
+    ("sound", Feature::Sound),
+    ("home", Feature::HomePersistent),
+    ("notifications", Feature::Notification),
+    ("timezone", Feature::Time),
+    ("devices", Feature::Devices),
+    ("read-only", Feature::ReadOnlyRoot),
+    ("input-method", Feature::InputMethod),
+    ("theme", Feature::HostTheme),
+    ("keyring", Feature::Keyring),
+    ("gamepad", Feature::Gamepad),
+    ("bluetooth", Feature::Bluetooth),
+    ("scanner", Feature::Scanner),
+    ("mdns", Feature::Mdns),
+    ("open-urls", Feature::OpenUrls),
+    ("portals", Feature::Portals),
+];
+
+impl Feature {
+    /// Name of the feature as its flag spells it, e.g. `home`
+    pub fn name(&self) -> &'static str {
+        FEATURE_NAMES
+            .iter()
+            .find(|(_, feature)| feature == self)
+            .map(|(name, _)| *name)
+            .unwrap_or_default()
+    }
+
+    /// Whether the feature installs packages, the image has to be rebuilt when it's added
+    pub fn installs_packages(&self) -> bool {
+        matches!(
+            self,
+            Feature::InputMethod
+                | Feature::HostTheme
+                | Feature::Keyring
+                | Feature::Bluetooth
+                | Feature::Scanner
+                | Feature::Mdns
+                | Feature::OpenUrls
+        )
+    }
+
+    /// Whether the feature serves a graphical program, CLI programs go without it, see
+    /// `Program::uses`
+    pub fn is_gui(&self) -> bool {
+        matches!(
+            self,
+            Feature::Display
+                | Feature::Sound
+                | Feature::InputMethod
+                | Feature::HostTheme
+                | Feature::Portals
+        )
+    }
 }
 
 impl Display for Feature {
@@ -58,11 +175,384 @@ impl Display for Feature {
                 Feature::Devices => "Devices",
                 Feature::HomePersistent => "Home persistent",
                 Feature::Time => "Timezone",
+                Feature::ReadOnlyRoot => "Read-only root",
+                Feature::InputMethod => "Input method",
+                Feature::HostTheme => "Host theme",
+                Feature::Keyring => "Keyring",
+                Feature::Gamepad => "Gamepad",
+                Feature::Bluetooth => "Bluetooth",
+                Feature::Scanner => "Scanner",
+                Feature::Mdns => "mDNS",
+                Feature::OpenUrls => "Open URLs",
+                Feature::Portals => "Portals",
             }
         )
     }
 }
 
+impl FromStr for Feature {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FEATURE_NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, feature)| feature.to_owned())
+            .ok_or_else(|| {
+                AppError::Argument(format!(
+                    "Unknown feature '{}', expected one of {}",
+                    s,
+                    FEATURE_NAMES
+                        .iter()
+                        .map(|(name, _)| *name)
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ))
+            })
+    }
+}
+
+/// How containers of a program are managed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ContainerMode {
+    /// A fresh container is created for every run and removed afterwards
+    #[default]
+    Ephemeral,
+    /// A container is created once and started again on the next runs, keeping its state
+    Persistent,
+}
+
+impl Display for ContainerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ContainerMode::Ephemeral => write!(f, "ephemeral"),
+            ContainerMode::Persistent => write!(f, "persistent"),
+        }
+    }
+}
+
+impl FromStr for ContainerMode {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ephemeral" => Ok(ContainerMode::Ephemeral),
+            "persistent" => Ok(ContainerMode::Persistent),
+            _ => Err(AppError::Argument(format!(
+                "Unsupported container mode '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Whether a program is graphical or runs in the terminal it's started from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ProgramKind {
+    #[default]
+    Gui,
+    /// Attached to the terminal of `run`, without the graphical features and a desktop entry
+    Cli,
+}
+
+impl Display for ProgramKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProgramKind::Gui => write!(f, "gui"),
+            ProgramKind::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+impl FromStr for ProgramKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gui" => Ok(ProgramKind::Gui),
+            "cli" => Ok(ProgramKind::Cli),
+            _ => Err(AppError::Argument(format!(
+                "Unsupported program kind '{}', expected gui or cli",
+                s
+            ))),
+        }
+    }
+}
+
+/// When systemd restarts a program installed as a user service, see `App::install_service`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RestartPolicy {
+    No,
+    /// The program is restarted if it exits with an error or is killed
+    #[default]
+    OnFailure,
+    Always,
+}
+
+impl Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RestartPolicy::No => write!(f, "no"),
+            RestartPolicy::OnFailure => write!(f, "on-failure"),
+            RestartPolicy::Always => write!(f, "always"),
+        }
+    }
+}
+
+impl FromStr for RestartPolicy {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(RestartPolicy::No),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            _ => Err(AppError::Argument(format!(
+                "Unsupported restart policy '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Seccomp profile a container of a program is started with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Seccomp {
+    /// Profile shipped with debian_bridge, blocks the syscalls desktop programs don't need
+    #[default]
+    Builtin,
+    /// No syscall filtering, for programs the built-in profile breaks
+    Unconfined,
+    /// Docker seccomp profile in a JSON file
+    Profile(PathBuf),
+}
+
+impl Display for Seccomp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Seccomp::Builtin => write!(f, "builtin"),
+            Seccomp::Unconfined => write!(f, "unconfined"),
+            Seccomp::Profile(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl FromStr for Seccomp {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "builtin" => Ok(Seccomp::Builtin),
+            "unconfined" => Ok(Seccomp::Unconfined),
+            _ => Err(AppError::Argument(format!(
+                "Unsupported security mode '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Confinement of the containers of a program
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Security {
+    #[serde(default)]
+    pub seccomp: Seccomp,
+    /// AppArmor profile loaded on the host, ignored if the daemon doesn't use AppArmor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apparmor: Option<String>,
+}
+
+impl Display for Security {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "seccomp {}", self.seccomp)?;
+
+        match &self.apparmor {
+            Some(profile) => write!(f, ", AppArmor {}", profile),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Host path mounted at the same path in the containers of a program
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Share {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl Display for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.read_only {
+            true => write!(f, "{}:ro", self.path.display()),
+            false => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+/// Ulimits a program may set, soft and hard limits get the same value
+pub const ULIMITS: &[&str] = &["nofile", "rtprio", "memlock"];
+
+/// Process limits of the containers of a program
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+    /// Values of the `ULIMITS` by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ulimits: BTreeMap<String, u64>,
+    /// Niceness of the command, from -20 to 19
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    /// From -1000 to 1000, the OOM killer picks programs with a higher score first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oom_score_adj: Option<i32>,
+}
+
+impl Resources {
+    pub fn is_empty(&self) -> bool {
+        self == &Resources::default()
+    }
+
+    /// Capabilities the limits take effect with and what needs them, they are added on top of
+    /// the ones of the program
+    pub fn capabilities(&self) -> Vec<(&'static str, &'static str)> {
+        let mut capabilities = vec![];
+
+        if self.ulimits.get("rtprio").is_some_and(|value| *value > 0) {
+            capabilities.push(("SYS_NICE", "rtprio"));
+        }
+
+        if self.nice.is_some_and(|nice| nice < 0) {
+            capabilities.push(("SYS_NICE", "negative nice"));
+        }
+
+        if self.ulimits.contains_key("memlock") {
+            capabilities.push(("IPC_LOCK", "memlock"));
+        }
+
+        capabilities
+    }
+}
+
+impl Display for Resources {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut parts: Vec<String> = self
+            .ulimits
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        if let Some(nice) = self.nice {
+            parts.push(format!("nice {}", nice));
+        }
+
+        if let Some(adj) = self.oom_score_adj {
+            parts.push(format!("OOM score {:+}", adj));
+        }
+
+        match parts.is_empty() {
+            true => write!(f, "default"),
+            false => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// X server reached over TCP instead of the local socket, e.g. `192.168.1.5:0`. IPv6 hosts are
+/// written in brackets: `[fd00::5]:0`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct RemoteDisplay {
+    pub host: IpAddr,
+    pub display: u16,
+}
+
+impl Display for RemoteDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.host {
+            IpAddr::V4(host) => write!(f, "{}:{}", host, self.display),
+            IpAddr::V6(host) => write!(f, "[{}]:{}", host, self.display),
+        }
+    }
+}
+
+impl FromStr for RemoteDisplay {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            AppError::Argument(format!(
+                "Invalid display '{}', expected <ip>:<display>, e.g. 192.168.1.5:0",
+                s
+            ))
+        };
+        let (host, display) = s.rsplit_once(':').ok_or_else(invalid)?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        Ok(RemoteDisplay {
+            host: host.parse().map_err(|_| invalid())?,
+            // A screen number, e.g. `:0.1`, is meaningless for a single display
+            display: display
+                .split('.')
+                .next()
+                .and_then(|display| display.parse().ok())
+                .ok_or_else(invalid)?,
+        })
+    }
+}
+
+impl From<RemoteDisplay> for String {
+    fn from(display: RemoteDisplay) -> Self {
+        display.to_string()
+    }
+}
+
+impl TryFrom<String> for RemoteDisplay {
+    type Error = AppError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Port a PulseAudio server listens on with `module-native-protocol-tcp`
+const PULSE_PORT: u16 = 4713;
+
+/// PulseAudio server reached over TCP instead of the local sound devices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PulseServer {
+    pub host: IpAddr,
+    /// Cookie the server authenticates clients with, a copy of its `~/.config/pulse/cookie`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<PathBuf>,
+}
+
+impl PulseServer {
+    pub fn new(host: IpAddr) -> Self {
+        PulseServer { host, cookie: None }
+    }
+
+    pub fn cookie(mut self, path: &Path) -> Self {
+        self.cookie = Some(path.to_owned());
+        self
+    }
+
+    /// Value of `PULSE_SERVER`, e.g. `tcp:192.168.1.5:4713`
+    pub fn address(&self) -> String {
+        match self.host {
+            IpAddr::V4(host) => format!("tcp:{}:{}", host, PULSE_PORT),
+            IpAddr::V6(host) => format!("tcp6:[{}]:{}", host, PULSE_PORT),
+        }
+    }
+}
+
+impl Display for PulseServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.cookie {
+            Some(cookie) => write!(f, "{} (cookie {})", self.address(), cookie.display()),
+            None => write!(f, "{}", self.address()),
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Program {
     name: String,
@@ -71,17 +561,117 @@ pub struct Program {
     pub icon: Option<Icon>,
     pub command: String,
     pub deps: Option<String>,
+    /// Dockerfile instructions appended after the package is installed, kept to rebuild the
+    /// image the same way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dockerfile_extra: Option<String>,
+    /// Description of the package, the synopsis on the first line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub base: Option<String>,
+    #[serde(default)]
+    pub snapshot: Option<String>,
+    /// Apt repository directory the image is built from, see `BuildSettings::local_repo`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_repo: Option<PathBuf>,
+    /// ID of the last built image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<String>,
+    /// When the last image was built or pulled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_built: Option<DateTime<Utc>>,
+    /// ID of the image the last update replaced, it's tagged `previous` to roll back to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_image_id: Option<String>,
+    /// When the previous image was built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_image_built: Option<DateTime<Utc>>,
+    /// Name in the image and container names if the name isn't valid there, see `sanitize_name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_name: Option<String>,
+    #[serde(default)]
+    pub container_mode: ContainerMode,
+    /// Detected from the desktop entries of the package when it's created
+    #[serde(default)]
+    pub kind: ProgramKind,
+    /// When the program was launched last time, `None` if it never was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub run_count: u64,
+    /// X server the program is shown on instead of the local one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_display: Option<RemoteDisplay>,
+    /// PulseAudio server the program plays sound on instead of the local devices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pulse_server: Option<PulseServer>,
+    #[serde(default)]
+    pub security: Security,
+    /// Capabilities added back after all are dropped, e.g. `NET_ADMIN`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+    /// Size of a private `/dev/shm` in bytes, the one of the host is shared if it's not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shm_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Resources::is_empty")]
+    pub resources: Resources,
+    /// Device nodes passed through, e.g. `/dev/ttyUSB0`, patterns like `/dev/ttyACM*` are
+    /// expanded at each run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<String>,
+    /// Host paths mounted into every container, see `--share` of `run` for a single run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shares: Vec<Share>,
+    /// Whether the program is started in the background at login through an autostart entry
+    #[serde(default)]
+    pub autostart: bool,
+    /// Shell script running the program like a host command, see `App::install_wrapper`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<PathBuf>,
+    /// Groups the program belongs to, e.g. `work`, see `parse_label`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Restart policy of the systemd user service of the program
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    /// Shell command run on the host before the container starts, a failure aborts the run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_run: Option<String>,
+    /// Shell command run on the host after the container exits, also if it crashed or was
+    /// interrupted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
+    /// Image the program was created from instead of a package, e.g. `jess/firefox:latest`.
+    /// It's pulled rather than built, `path` is empty then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_image: Option<String>,
+    /// Fields unknown to this version, kept so they survive a save
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Program {
     pub fn get_name<T: Into<String>>(&self, prefix: T) -> String {
-        format!("{}_{}", prefix.into(), self.name)
+        format!("{}_{}", prefix.into(), self.get_image_name())
+    }
+
+    /// Name of the program valid in docker image and container names
+    pub fn get_image_name(&self) -> String {
+        self.image_name.to_owned().unwrap_or(self.name.to_owned())
     }
 
     pub fn get_name_short(&self) -> String {
         self.name.to_owned()
     }
 
+    /// Changes the name only, the command run in the container is kept
+    pub fn set_name<T: Into<String>>(&mut self, name: T) -> &mut Self {
+        self.name = name.into();
+        self.image_name = Some(sanitize_name(&self.name)).filter(|image| image != &self.name);
+        self
+    }
+
     pub fn new<T>(
         name: T,
         path: &Path,
@@ -94,6 +684,7 @@ impl Program {
         T: Into<String>,
     {
         let name = name.into();
+        let image_name = Some(sanitize_name(&name)).filter(|image| image != &name);
 
         Program {
             name: name.to_owned(),
@@ -102,91 +693,1543 @@ impl Program {
             icon: icon.to_owned(),
             command: cmd.to_owned().unwrap_or(name),
             deps: deps.to_owned(),
+            dockerfile_extra: None,
+            description: None,
+            base: None,
+            snapshot: None,
+            local_repo: None,
+            image_id: None,
+            image_built: None,
+            previous_image_id: None,
+            previous_image_built: None,
+            image_name,
+            container_mode: ContainerMode::default(),
+            kind: ProgramKind::default(),
+            last_run: None,
+            run_count: 0,
+            remote_display: None,
+            pulse_server: None,
+            security: Security::default(),
+            capabilities: vec![],
+            devices: vec![],
+            shares: vec![],
+            autostart: false,
+            wrapper: None,
+            labels: vec![],
+            restart: RestartPolicy::default(),
+            pre_run: None,
+            post_run: None,
+            source_image: None,
+            resources: Resources::default(),
+            shm_size: None,
+            extra: Map::new(),
         }
     }
-}
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub programs: Vec<Program>,
-}
+    pub fn record_run(&mut self, at: DateTime<Utc>) -> &mut Self {
+        self.last_run = Some(at);
+        self.run_count += 1;
+        self
+    }
 
-impl Config {
-    pub fn deserialize(path: &Path) -> AppResult<Self> {
-        if !path.exists() {
-            return File::create(path)
-                .map(|_| {
-                    let config = Config { programs: vec![] };
+    /// Local time of the last launch, e.g. `2020-01-31 18:05`
+    pub fn format_last_run(&self) -> Option<String> {
+        self.last_run.map(format_local)
+    }
 
-                    config.serialize(path);
+    /// Whether a previous image is kept for the program to roll back to
+    pub fn has_previous(&self) -> bool {
+        self.previous_image_id.is_some()
+    }
 
-                    config
-                })
-                .map_err(|err| AppError::File(err.to_string()));
-        }
+    /// Swaps the current image with the previous one
+    pub fn swap_images(&mut self) -> &mut Self {
+        std::mem::swap(&mut self.image_id, &mut self.previous_image_id);
+        std::mem::swap(&mut self.image_built, &mut self.previous_image_built);
+        self
+    }
 
-        let mut config_str = String::new();
-        let config_file = File::open(path).map_err(|err| AppError::File(err.to_string()))?;
+    /// Forgets the previous image, e.g. once it's removed
+    pub fn drop_previous(&mut self) -> &mut Self {
+        self.previous_image_id = None;
+        self.previous_image_built = None;
+        self
+    }
 
-        let mut br = BufReader::new(config_file);
+    /// Whether the program uses the feature. CLI programs keep their graphical features, but
+    /// they aren't set up for them
+    pub fn uses(&self, feature: &Feature) -> bool {
+        self.settings.contains(feature) && !(self.kind == ProgramKind::Cli && feature.is_gui())
+    }
 
-        br.read_to_string(&mut config_str)
-            .map_err(|err| AppError::File(err.to_string()))?;
+    /// Features the program uses, see `Program::uses`
+    pub fn features(&self) -> Vec<Feature> {
+        self.settings
+            .iter()
+            .filter(|feature| self.uses(feature))
+            .cloned()
+            .collect()
+    }
 
-        if config_str.is_empty() {
-            return Ok(Config { programs: vec![] });
+    /// Whether the program wasn't launched during `age` before `now`, never launched programs
+    /// are unused
+    pub fn is_unused_since(&self, age: Duration, now: DateTime<Utc>) -> bool {
+        self.last_run.is_none_or(|last_run| last_run < now - age)
+    }
+}
+
+/// Settings applied to every image build
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct BuildSettings {
+    /// Debian mirror used instead of deb.debian.org, e.g. `http://mirror.example.com/debian`
+    pub apt_mirror: Option<String>,
+    /// HTTP proxy passed to the build as `http_proxy`/`https_proxy` build arguments
+    pub proxy: Option<String>,
+    /// snapshot.debian.org timestamp (`YYYYMMDDTHHMMSSZ` or `latest`) to pin packages to.
+    /// Takes precedence over `apt_mirror`
+    #[serde(default)]
+    pub snapshot: Option<String>,
+    /// Image the shared base image is built from instead of `debian:9-slim`. The mirror and
+    /// snapshot sources name stretch, so it has to be a stretch based image when they are set
+    #[serde(default)]
+    pub base_image: Option<String>,
+    /// Directory of a file based apt repository, e.g. a mirror of the Debian archive. Builds
+    /// install from it instead of the mirror and the snapshot, without the network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_repo: Option<PathBuf>,
+}
+
+impl BuildSettings {
+    pub fn new(
+        apt_mirror: &Option<String>,
+        proxy: &Option<String>,
+        snapshot: &Option<String>,
+    ) -> Self {
+        BuildSettings {
+            apt_mirror: apt_mirror.to_owned(),
+            proxy: proxy.to_owned(),
+            snapshot: snapshot.to_owned(),
+            base_image: None,
+            local_repo: None,
         }
+    }
 
-        serde_json::from_str(config_str.as_str()).map_err(|err| AppError::File(err.to_string()))
+    /// Returns settings where values set in `overrides` take precedence
+    pub fn merge(&self, overrides: &BuildSettings) -> Self {
+        BuildSettings {
+            apt_mirror: overrides
+                .apt_mirror
+                .to_owned()
+                .or(self.apt_mirror.to_owned()),
+            proxy: overrides.proxy.to_owned().or(self.proxy.to_owned()),
+            snapshot: overrides.snapshot.to_owned().or(self.snapshot.to_owned()),
+            base_image: overrides
+                .base_image
+                .to_owned()
+                .or(self.base_image.to_owned()),
+            local_repo: overrides
+                .local_repo
+                .to_owned()
+                .or(self.local_repo.to_owned()),
+        }
     }
 
-    pub fn serialize(&self, path: &Path) -> AppResult<&Self> {
-        let data = serde_json::to_string(&self).map_err(|err| AppError::File(err.to_string()))?;
+    /// Replaces a `latest` snapshot with the current timestamp, so it can be reproduced later,
+    /// and a relative repository directory with its absolute path
+    pub fn resolve(&self) -> Self {
+        let mut settings = self.to_owned();
+
+        if let Some(SNAPSHOT_LATEST) = settings.snapshot.as_deref() {
+            settings.snapshot = Some(Utc::now().format(SNAPSHOT_FORMAT).to_string());
+        }
 
-        std::fs::write(path, data.as_bytes())
-            .map(|_| self)
-            .map_err(|err| AppError::File(err.to_string()))
+        if let Some(dir) = &settings.local_repo {
+            settings.local_repo = Some(std::fs::canonicalize(dir).unwrap_or(dir.to_owned()));
+        }
+
+        settings
     }
 
-    pub fn push(&mut self, program: &Program) -> AppResult<&Self> {
-        match self.programs.iter().find(|&x| x.name == program.name) {
-            Some(elem) => {
-                return Err(AppError::Program(
+    pub fn validate(&self) -> AppResult<&Self> {
+        for url in self.apt_mirror.iter().chain(self.proxy.iter()) {
+            if !url.starts_with("http://") && !url.starts_with("https://")
+                || url.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"')
+            {
+                return Err(AppError::Config(
                     format!(
-                        "Program with such name already exists '{}'. Remove it first or use a \
-                         custom tag with -t (--tag) option",
-                        program.name
-                    )
-                    .to_string(),
-                ))
+                        "Invalid url '{}', only http:// and https:// are supported",
+                        url
+                    ),
+                    None,
+                ));
             }
-            None => (),
-        };
+        }
+
+        if let Some(image) = &self.base_image {
+            if image.is_empty() || image.contains(char::is_whitespace) {
+                return Err(AppError::Config(
+                    format!("Invalid base image '{}'", image),
+                    None,
+                ));
+            }
+        }
+
+        if let Some(snapshot) = &self.snapshot {
+            if snapshot != SNAPSHOT_LATEST
+                && NaiveDateTime::parse_from_str(snapshot, SNAPSHOT_FORMAT).is_err()
+            {
+                return Err(AppError::Config(
+                    format!(
+                        "Invalid snapshot '{}', expected format is YYYYMMDDTHHMMSSZ or '{}'",
+                        snapshot, SNAPSHOT_LATEST
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        if let Some(dir) = &self.local_repo {
+            LocalRepo::open(dir, BASE_RELEASE)?;
+        }
 
-        self.programs.push(program.to_owned());
         Ok(self)
     }
 
-    pub fn find<T: Into<String>>(&self, name: T) -> Option<(Program, usize)> {
-        let name = name.into();
-        let idx = self.programs.iter().position(|x| x.name == name)?;
-        self.programs.get(idx).map(|p| (p.to_owned(), idx))
+    /// Returns build arguments which have to be set in the environment of a build.
+    /// These are predefined docker arguments, so they never get into image history
+    pub fn build_args(&self) -> Vec<(String, String)> {
+        match &self.proxy {
+            Some(proxy) => ["http_proxy", "https_proxy", "HTTP_PROXY", "HTTPS_PROXY"]
+                .iter()
+                .map(|arg| (arg.to_string(), proxy.to_owned()))
+                .collect(),
+            None => vec![],
+        }
     }
+}
 
-    pub fn remove(&mut self, program: &Program) -> AppResult<&Self> {
-        let program_idx = self
-            .find(&program.name)
-            .ok_or(AppError::Program(
-                format!("Can't find a program '{}'", program.name).to_string(),
-            ))?
-            .1;
+/// Defaults of the whole tool, the flags of a command take precedence over them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlobalSettings {
+    /// Features of a program created without feature flags
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    /// Whether created programs get a desktop entry
+    #[serde(default = "default_desktop_entries")]
+    pub desktop_entries: bool,
+    /// Container engine, only `docker` is supported
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Programs `repair` builds at once, see `GlobalSettings::jobs`
+    #[serde(default)]
+    pub jobs: Option<usize>,
+    /// Whether the output of the runs is written to log files
+    #[serde(default = "default_logs")]
+    pub logs: bool,
+    /// Whether a desktop notification tells when a long build finishes
+    #[serde(default)]
+    pub notify: bool,
+    /// Seconds a build takes before it's notified, see `GlobalSettings::notify_after`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notify_after: Option<u64>,
+    /// Age like `30d` after which `clean` removes the previous images, see
+    /// `GlobalSettings::keep_previous`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_previous: Option<String>,
+    /// Whether a build probes the apt source and works offline if it's unreachable, see
+    /// `NetworkMode::Detect`
+    #[serde(default = "default_offline_probe")]
+    pub offline_probe: bool,
+}
 
-        self.programs.remove(program_idx);
-        Ok(self)
+impl GlobalSettings {
+    /// Builds run at once, the configured number or one per core up to four
+    pub fn jobs(&self) -> usize {
+        self.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(usize::from)
+                .unwrap_or(1)
+                .min(DEFAULT_JOBS_MAX)
+        })
     }
 
-    pub fn clear(&mut self) -> &Self {
-        self.programs = vec![];
-        self
+    /// How long a build takes before its end is notified, the configured time or 30 seconds
+    pub fn notify_after(&self) -> std::time::Duration {
+        self.notify_after
+            .map_or(DEFAULT_NOTIFY_AFTER, std::time::Duration::from_secs)
+    }
+
+    /// How long previous images are kept after they were built, the configured age or 30 days
+    pub fn keep_previous(&self) -> Duration {
+        self.keep_previous
+            .as_deref()
+            .and_then(|age| parse_age(age).ok())
+            .unwrap_or_else(|| parse_age(DEFAULT_KEEP_PREVIOUS).unwrap())
+    }
+}
+
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        GlobalSettings {
+            features: vec![],
+            desktop_entries: default_desktop_entries(),
+            backend: None,
+            jobs: None,
+            logs: default_logs(),
+            notify: false,
+            notify_after: None,
+            keep_previous: None,
+            offline_probe: default_offline_probe(),
+        }
+    }
+}
+
+fn default_desktop_entries() -> bool {
+    true
+}
+
+fn default_logs() -> bool {
+    true
+}
+
+fn default_offline_probe() -> bool {
+    true
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let features = self
+            .settings
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        writeln!(f, "\t{:<15} ===> {}", "Name", self.name)?;
+        match &self.source_image {
+            Some(image) => writeln!(f, "\t{:<15} ===> {}", "Image", image)?,
+            None => writeln!(f, "\t{:<15} ===> {}", "Package", self.path.display())?,
+        }
+
+        writeln!(f, "\t{:<15} ===> {}", "Command", self.command)?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Description",
+            or_none(
+                self.description
+                    .as_ref()
+                    .and_then(|d| d.lines().next())
+                    .map(str::to_string)
+            )
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Features", or_none(Some(features)))?;
+
+        if !self.labels.is_empty() {
+            writeln!(f, "\t{:<15} ===> {}", "Labels", self.labels.join(", "))?;
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Dependencies",
+            or_none(self.deps.to_owned())
+        )?;
+
+        if self.source_image.is_none() {
+            writeln!(
+                f,
+                "\t{:<15} ===> {}",
+                "Dockerfile",
+                match &self.dockerfile_extra {
+                    Some(extra) => format!("generated, {} extra line(s)", extra.lines().count()),
+                    None => "generated".to_string(),
+                }
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Icon",
+            or_none(self.icon.as_ref().map(|i| i.path.display().to_string()))
+        )?;
+
+        if let Some(wrapper) = &self.wrapper {
+            writeln!(f, "\t{:<15} ===> {}", "Wrapper", wrapper.display())?;
+        }
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Base image",
+            or_none(self.base.to_owned())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Snapshot",
+            or_none(self.snapshot.to_owned())
+        )?;
+
+        if let Some(dir) = &self.local_repo {
+            writeln!(f, "\t{:<15} ===> {}", "Local repo", dir.display())?;
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Image ID",
+            or_none(self.image_id.to_owned())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Image built",
+            or_none(self.image_built.map(format_local))
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Previous image",
+            or_none(self.previous_image_id.as_ref().map(|id| {
+                match self.previous_image_built {
+                    Some(built) => format!("{}, built {}", id, format_local(built)),
+                    None => id.to_owned(),
+                }
+            }))
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Kind", self.kind)?;
+        writeln!(f, "\t{:<15} ===> {}", "Container mode", self.container_mode)?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Autostart",
+            match self.autostart {
+                true => "on",
+                false => "off",
+            }
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Service restart", self.restart)?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Pre-run hook",
+            self.pre_run.as_deref().unwrap_or("none")
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Post-run hook",
+            self.post_run.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Security", self.security)?;
+
+        if self.settings.contains(&Feature::Keyring) {
+            writeln!(
+                f,
+                "\t{:<15} ===> shared, the program can read all your keyring entries",
+                "Keyring"
+            )?;
+        }
+
+        if self.settings.contains(&Feature::Bluetooth) {
+            writeln!(
+                f,
+                "\t{:<15} ===> shared, the program can pair, connect and control all bluetooth \
+                 devices over the system bus",
+                "Bluetooth"
+            )?;
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Shared memory",
+            match self.shm_size {
+                Some(size) => human_size(size),
+                None => "shared with the host".to_string(),
+            }
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Resources", self.resources)?;
+
+        let implied = self
+            .resources
+            .capabilities()
+            .into_iter()
+            .filter(|(capability, _)| !self.capabilities.iter().any(|c| c == capability))
+            .map(|(capability, reason)| format!("{} ({})", capability, reason));
+        let capabilities: Vec<String> = self.capabilities.iter().cloned().chain(implied).collect();
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Capabilities",
+            match capabilities.is_empty() {
+                true => "none".to_string(),
+                false => capabilities.join(", "),
+            }
+        )?;
+
+        if !self.devices.is_empty() {
+            writeln!(
+                f,
+                "\t{:<15} ===> {}",
+                "Shared devices",
+                self.devices.join(", ")
+            )?;
+        }
+
+        if !self.shares.is_empty() {
+            writeln!(
+                f,
+                "\t{:<15} ===> {}",
+                "Shared paths",
+                self.shares
+                    .iter()
+                    .map(Share::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?;
+        }
+
+        if let Some(display) = &self.remote_display {
+            writeln!(f, "\t{:<15} ===> {}", "Remote display", display)?;
+        }
+
+        if let Some(server) = &self.pulse_server {
+            writeln!(f, "\t{:<15} ===> {}", "Pulse server", server)?;
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Last run",
+            or_none(self.format_last_run())
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Run count", self.run_count)
+    }
+}
+
+fn get_backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Writes to a temporary file in the same directory, so the rename can't leave a partially
+/// written file behind. The directory is synced too, or a crash may still lose the rename
+fn write_atomic(path: &Path, data: &[u8]) -> AppResult<()> {
+    let tmp = with_suffix(path, ".tmp");
+    let error = |err| AppError::config(format!("Can not write {}", path.display()), err);
+
+    File::create(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|err| {
+            std::fs::remove_file(&tmp).unwrap_or(());
+            error(err)
+        })?;
+
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(error)
+}
+
+fn or_none(value: Option<String>) -> String {
+    value
+        .filter(|value| !value.is_empty())
+        .unwrap_or("None".to_string())
+}
+
+/// Local time, e.g. `2020-01-31 18:05`
+fn format_local(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Current layout of the config file, bump it together with a new step in `MIGRATIONS`.
+///
+/// A new field doesn't bump it: every field added since version 2, e.g. `security`, `labels`
+/// or `wrapper`, has a serde default which is its value for programs made before it. Unknown
+/// fields are kept in `extra`, so an older version saving the config doesn't drop them. Only
+/// a change of the meaning or shape of an existing field needs a migration step
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Config layout upgrades, the step at index `n` turns version `n + 1` into `n + 2`
+const MIGRATIONS: &[fn(&mut Value)] = &[migrate_v1];
+
+/// Version 1 files have no `version` field and no build settings
+fn migrate_v1(config: &mut Value) {
+    if let Some(programs) = config["programs"].as_array_mut() {
+        for program in programs.iter_mut().filter_map(|p| p.as_object_mut()) {
+            program.entry("base").or_insert(Value::Null);
+            program.entry("snapshot").or_insert(Value::Null);
+        }
+    }
+
+    if let Some(config) = config.as_object_mut() {
+        config
+            .entry("build")
+            .or_insert(json!({ "apt_mirror": null, "proxy": null, "snapshot": null }));
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Files written before versioning was introduced are version 1
+    #[serde(default = "default_version")]
+    pub version: u32,
+    pub programs: Vec<Program>,
+    #[serde(default)]
+    pub build: BuildSettings,
+    #[serde(default)]
+    pub settings: GlobalSettings,
+    /// Fields unknown to this version, kept so they survive a save
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn unknown_setting(key: &str) -> AppError {
+    AppError::Argument(format!(
+        "Unknown setting '{}', valid keys are {}",
+        key,
+        SETTING_KEYS.join(", ")
+    ))
+}
+
+/// Config file format, detected by the file extension
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+        }
+    }
+
+    fn parse(&self, data: &str) -> AppResult<Value> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::from_str(data)?,
+            ConfigFormat::Toml => toml::from_str(data)?,
+        })
+    }
+
+    fn format(&self, config: &Config) -> AppResult<String> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::to_string(config)?,
+            // Going through `toml::Value` puts plain values before tables as TOML requires
+            ConfigFormat::Toml => toml::to_string_pretty(&toml::Value::try_from(config)?)?,
+        })
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            _ => Err(AppError::Config(
+                format!("Unsupported config format '{}'", s),
+                None,
+            )),
+        }
+    }
+}
+
+fn default_version() -> u32 {
+    1
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            programs: vec![],
+            build: BuildSettings::default(),
+            settings: GlobalSettings::default(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads config, falling back to the backup made by the last successful `serialize`
+    /// when the file is corrupted
+    pub fn deserialize(path: &Path) -> AppResult<Self> {
+        if !path.exists() {
+            return File::create(path)
+                .map(|_| {
+                    let config = Config::default();
+
+                    config.serialize(path);
+
+                    config
+                })
+                .map_err(|err| {
+                    AppError::config(format!("Can not create {}", path.display()), err)
+                });
+        }
+
+        match Config::read(path) {
+            Ok(Some(config)) => Ok(config),
+            Ok(None) | Err(_) if get_backup_path(path).exists() => {
+                let backup = get_backup_path(path);
+                let config = Config::read(&backup)?.unwrap_or_default();
+
+                warn!(
+                    "Config {} is corrupted, recovered from {}",
+                    path.display(),
+                    backup.display()
+                );
+
+                Ok(config)
+            }
+            result => result.map(|config| config.unwrap_or_default()),
+        }
+    }
+
+    /// Saves config atomically and keeps a copy of it as a backup.
+    /// The format is chosen by the file extension, JSON is used by default
+    pub fn serialize(&self, path: &Path) -> AppResult<&Self> {
+        let data = ConfigFormat::from_path(path).format(self)?;
+
+        write_atomic(path, data.as_bytes())?;
+        write_atomic(&get_backup_path(path), data.as_bytes())?;
+
+        Ok(self)
+    }
+
+    /// Returns `None` for an empty file
+    fn read(path: &Path) -> AppResult<Option<Self>> {
+        let mut config_str = String::new();
+        let read_error = |err| AppError::config(format!("Can not read {}", path.display()), err);
+        let config_file = File::open(path).map_err(read_error)?;
+
+        let mut br = BufReader::new(config_file);
+
+        br.read_to_string(&mut config_str).map_err(read_error)?;
+
+        if config_str.is_empty() {
+            return Ok(None);
+        }
+
+        ConfigFormat::from_path(path)
+            .parse(config_str.as_str())
+            .map_err(|err| match err {
+                AppError::Config(message, source) => {
+                    AppError::Config(format!("{} in {}", message, path.display()), source)
+                }
+                err => err,
+            })
+            .and_then(Config::migrate)
+            .map(Some)
+    }
+
+    /// Upgrades config layout step by step up to `CONFIG_VERSION`.
+    /// The upgraded layout gets written on the next `serialize`
+    fn migrate(mut value: Value) -> AppResult<Self> {
+        let version = value["version"]
+            .as_u64()
+            .map(|version| version as u32)
+            .unwrap_or(default_version());
+
+        if version > CONFIG_VERSION {
+            return Err(AppError::Config(
+                format!(
+                    "Config version {} is newer than supported version {}, update debian_bridge \
+                     to use it",
+                    version, CONFIG_VERSION
+                ),
+                None,
+            ));
+        }
+
+        for (idx, step) in MIGRATIONS
+            .iter()
+            .enumerate()
+            .skip((version as usize).saturating_sub(1))
+        {
+            step(&mut value);
+            debug!("Config migrated to version {}", idx + 2);
+        }
+
+        let mut config: Config = serde_json::from_value(value)?;
+
+        config.version = CONFIG_VERSION;
+
+        Ok(config)
+    }
+
+    /// Rewrites config at `path` in another format next to it, removing the old file.
+    /// Returns the new path
+    pub fn convert(&self, path: &Path, format: ConfigFormat) -> AppResult<PathBuf> {
+        let target = path.with_extension(format.extension());
+
+        if target == path {
+            return Ok(target);
+        }
+
+        self.serialize(&target)?;
+
+        for old in [path.to_owned(), get_backup_path(path)].iter() {
+            if old.exists() {
+                std::fs::remove_file(old)?;
+            }
+        }
+
+        Ok(target)
+    }
+
+    /// Value of a setting of `SETTING_KEYS`, `None` if it isn't set. Features are listed by
+    /// their flag names separated by commas
+    pub fn setting(&self, key: &str) -> AppResult<Option<String>> {
+        Ok(match key {
+            "features" => match self.settings.features.is_empty() {
+                true => None,
+                false => Some(
+                    self.settings
+                        .features
+                        .iter()
+                        .map(Feature::name)
+                        .collect::<Vec<&str>>()
+                        .join(","),
+                ),
+            },
+            "desktop-entries" => Some(self.settings.desktop_entries.to_string()),
+            "backend" => self.settings.backend.to_owned(),
+            "base-image" => self.build.base_image.to_owned(),
+            "apt-mirror" => self.build.apt_mirror.to_owned(),
+            "jobs" => self.settings.jobs.map(|jobs| jobs.to_string()),
+            "logs" => Some(self.settings.logs.to_string()),
+            "notify" => Some(self.settings.notify.to_string()),
+            "notify-after" => self.settings.notify_after.map(|secs| secs.to_string()),
+            "keep-previous" => self.settings.keep_previous.to_owned(),
+            "offline-probe" => Some(self.settings.offline_probe.to_string()),
+            _ => return Err(unknown_setting(key)),
+        })
+    }
+
+    /// Sets a setting of `SETTING_KEYS`, an empty value unsets it
+    pub fn set_setting(&mut self, key: &str, value: &str) -> AppResult<&Self> {
+        let optional = || match value.is_empty() {
+            true => None,
+            false => Some(value.to_string()),
+        };
+
+        match key {
+            "features" => {
+                let mut features = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::parse)
+                    .collect::<AppResult<Vec<Feature>>>()?;
+
+                features.sort();
+                features.dedup();
+                self.settings.features = features;
+            }
+            "desktop-entries" | "logs" | "notify" | "offline-probe" => {
+                let enabled = match value {
+                    "true" | "yes" | "1" => true,
+                    "false" | "no" | "0" => false,
+                    _ => {
+                        return Err(AppError::Argument(format!(
+                            "Invalid value '{}' of {}, expected true or false",
+                            value, key
+                        )))
+                    }
+                };
+
+                match key {
+                    "logs" => self.settings.logs = enabled,
+                    "notify" => self.settings.notify = enabled,
+                    "offline-probe" => self.settings.offline_probe = enabled,
+                    _ => self.settings.desktop_entries = enabled,
+                }
+            }
+            "backend" => {
+                if !value.is_empty() && !BACKENDS.contains(&value) {
+                    return Err(AppError::Argument(format!(
+                        "Unsupported backend '{}', only docker is supported",
+                        value
+                    )));
+                }
+
+                self.settings.backend = optional();
+            }
+            "base-image" | "apt-mirror" => {
+                let mut build = self.build.to_owned();
+
+                match key {
+                    "base-image" => build.base_image = optional(),
+                    _ => build.apt_mirror = optional(),
+                }
+
+                build.validate()?;
+                self.build = build;
+            }
+            "jobs" => {
+                self.settings.jobs = match value {
+                    "" => None,
+                    _ => match value.parse::<usize>() {
+                        Ok(jobs) if jobs > 0 => Some(jobs),
+                        _ => {
+                            return Err(AppError::Argument(format!(
+                                "Invalid value '{}' of jobs, expected a positive number",
+                                value
+                            )))
+                        }
+                    },
+                }
+            }
+            "notify-after" => {
+                self.settings.notify_after = match value {
+                    "" => None,
+                    _ => Some(value.parse::<u64>().map_err(|_| {
+                        AppError::Argument(format!(
+                            "Invalid value '{}' of notify-after, expected seconds",
+                            value
+                        ))
+                    })?),
+                }
+            }
+            "keep-previous" => {
+                if !value.is_empty() {
+                    parse_age(value)?;
+                }
+
+                self.settings.keep_previous = optional();
+            }
+            _ => return Err(unknown_setting(key)),
+        }
+
+        Ok(self)
+    }
+
+    pub fn push(&mut self, program: &Program) -> AppResult<&Self> {
+        match self.programs.iter().find(|&x| x.name == program.name) {
+            Some(elem) => {
+                return Err(AppError::Program(
+                    format!(
+                        "Program with such name already exists '{}'. Remove it first or use a \
+                         custom tag with -t (--tag) option",
+                        program.name
+                    )
+                    .to_string(),
+                ))
+            }
+            None => (),
+        };
+
+        self.programs.push(program.to_owned());
+        Ok(self)
+    }
+
+    /// Finds a program by its name, ignoring case unless several programs differ only by case.
+    /// Exact matches always win
+    pub fn find<T: Into<String>>(&self, name: T) -> Option<(Program, usize)> {
+        let name = name.into();
+        let idx = self
+            .programs
+            .iter()
+            .position(|x| x.name == name || x.image_name.as_ref() == Some(&name))
+            .or_else(|| {
+                let matches: Vec<usize> = self
+                    .programs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| x.name.to_lowercase() == name.to_lowercase())
+                    .map(|(idx, _)| idx)
+                    .collect();
+
+                match matches.as_slice() {
+                    [idx] => Some(*idx),
+                    _ => None,
+                }
+            })?;
+
+        self.programs.get(idx).map(|p| (p.to_owned(), idx))
+    }
+
+    /// Same as `find`, but returns an error suggesting the closest names if nothing is found
+    pub fn lookup<T: Into<String>>(&self, name: T) -> AppResult<Program> {
+        let name = name.into();
+
+        if let Some((program, _)) = self.find(name.as_str()) {
+            return Ok(program);
+        }
+
+        let suggestions = self.suggest(&name);
+
+        Err(AppError::Program(match suggestions.is_empty() {
+            true => format!("Program '{}' not found", name),
+            false => format!(
+                "Program '{}' not found. Did you mean {}?",
+                name,
+                suggestions
+                    .iter()
+                    .map(|suggestion| format!("'{}'", suggestion))
+                    .collect::<Vec<String>>()
+                    .join(" or ")
+            ),
+        }))
+    }
+
+    /// Names of the programs closest to `name`, too distant ones aren't suggested
+    pub fn suggest(&self, name: &str) -> Vec<String> {
+        let name = name.to_lowercase();
+        let max_distance = (name.chars().count() / 3).max(2);
+        let distances: Vec<(usize, &String)> = self
+            .programs
+            .iter()
+            .map(|x| (edit_distance(&name, &x.name.to_lowercase()), &x.name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        let closest = distances.iter().map(|(distance, _)| *distance).min();
+
+        distances
+            .iter()
+            .filter(|(distance, _)| Some(*distance) == closest)
+            .map(|(_, name)| name.to_string())
+            .collect()
+    }
+
+    /// Counts a launch of the program
+    pub fn record_run(&mut self, name: &str, at: DateTime<Utc>) -> AppResult<&Self> {
+        let program_idx = self
+            .find(name)
+            .ok_or(AppError::Program(format!(
+                "Can't find a program '{}'",
+                name
+            )))?
+            .1;
+
+        self.programs[program_idx].record_run(at);
+        Ok(self)
+    }
+
+    pub fn update(&mut self, program: &Program) -> AppResult<&Self> {
+        let program_idx = self
+            .find(&program.name)
+            .ok_or(AppError::Program(format!(
+                "Can't find a program '{}'",
+                program.name
+            )))?
+            .1;
+
+        self.programs[program_idx] = program.to_owned();
+        Ok(self)
+    }
+
+    pub fn remove(&mut self, program: &Program) -> AppResult<&Self> {
+        let program_idx = self
+            .find(&program.name)
+            .ok_or(AppError::Program(
+                format!("Can't find a program '{}'", program.name).to_string(),
+            ))?
+            .1;
+
+        self.programs.remove(program_idx);
+        Ok(self)
+    }
+
+    pub fn clear(&mut self) -> &Self {
+        self.programs = vec![];
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_config() -> Config {
+        let mut config = Config::default();
+
+        config
+            .push(&Program::new(
+                "foo",
+                Path::new("/tmp/foo.deb"),
+                &vec![Feature::Display],
+                &None,
+                &None,
+                &None,
+            ))
+            .unwrap();
+
+        config
+    }
+
+    /// Directory of a test, removed when the test ends
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).unwrap_or(());
+        }
+    }
+
+    fn get_path(name: &str) -> (TempDir, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+
+        std::fs::remove_dir_all(&dir).unwrap_or(());
+        std::fs::create_dir_all(&dir).unwrap();
+
+        (TempDir(dir.to_owned()), dir.join("config.json"))
+    }
+
+    #[test]
+    fn test_find() {
+        let mut config = get_config();
+
+        assert_eq!(config.find("FOO").unwrap().0.name, "foo");
+
+        config
+            .push(&Program::new(
+                "Foo",
+                Path::new("/tmp/foo.deb"),
+                &vec![],
+                &None,
+                &None,
+                &None,
+            ))
+            .unwrap();
+
+        assert_eq!(config.find("Foo").unwrap().0.name, "Foo");
+        assert_eq!(config.find("foo").unwrap().0.name, "foo");
+        assert!(config.find("FOO").is_none());
+    }
+
+    #[test]
+    fn test_image_name() {
+        let mut config = Config::default();
+        let mut program = Program::new(
+            "g++",
+            Path::new("/tmp/g++.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        );
+
+        assert_eq!(program.get_name("debian_bridge"), "debian_bridge_gplusplus");
+        assert_eq!(program.command, "g++");
+
+        config.push(&program).unwrap();
+
+        assert_eq!(config.lookup("g++").unwrap().name, "g++");
+        assert_eq!(config.lookup("gplusplus").unwrap().name, "g++");
+
+        program.set_name("gcc");
+
+        assert_eq!(program.image_name, None);
+        assert_eq!(program.get_name("debian_bridge"), "debian_bridge_gcc");
+    }
+
+    #[test]
+    fn test_lookup_suggests() {
+        let config = get_config();
+
+        assert_eq!(config.lookup("Foo").unwrap().name, "foo");
+        assert_eq!(
+            config.lookup("fo").err().unwrap().to_string(),
+            "Program errors occured: Program 'fo' not found. Did you mean 'foo'?"
+        );
+        assert_eq!(
+            config.lookup("firefox").err().unwrap().to_string(),
+            "Program errors occured: Program 'firefox' not found"
+        );
+    }
+
+    #[test]
+    fn test_serialize_keeps_backup() {
+        let (_dir, path) = get_path("debian_bridge_test_backup");
+
+        get_config().serialize(&path).unwrap();
+
+        assert!(get_backup_path(&path).exists());
+        assert!(!path.with_file_name("config.json.tmp").exists());
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            std::fs::read(get_backup_path(&path)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_deserialize_truncated() {
+        let (_dir, path) = get_path("debian_bridge_test_truncated");
+
+        get_config().serialize(&path).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &data[..data.len() / 2]).unwrap();
+
+        let config = Config::deserialize(&path).unwrap();
+
+        assert_eq!(config.programs.len(), 1);
+        assert_eq!(config.programs[0].name, "foo");
+
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(Config::deserialize(&path).unwrap().programs.len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_current() {
+        let (_dir, path) = get_path("debian_bridge_test_migrate_current");
+
+        get_config().serialize(&path).unwrap();
+
+        let config = Config::deserialize(&path).unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.programs.len(), 1);
+        assert_eq!(config.programs[0].settings.len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        let (_dir, path) = get_path("debian_bridge_test_migrate_v1");
+
+        std::fs::write(
+            &path,
+            r#"{"programs":[{"name":"foo","path":"/tmp/foo.deb","settings":["Sound"],"icon":null,"command":"foo","deps":"libfoo"}]}"#,
+        )
+        .unwrap();
+
+        let config = Config::deserialize(&path).unwrap();
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.programs[0].deps, Some("libfoo".to_string()));
+        assert!(config.programs[0].base.is_none());
+        assert!(config.build.apt_mirror.is_none());
+
+        config.serialize(&path).unwrap();
+
+        let value: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(value["version"], json!(CONFIG_VERSION));
+        assert_eq!(Config::deserialize(&path).unwrap().programs.len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_v2_defaults() {
+        let (_dir, path) = get_path("debian_bridge_test_migrate_v2_defaults");
+
+        std::fs::write(
+            &path,
+            r#"{"version":2,"programs":[{"name":"foo","path":"/tmp/foo.deb","settings":["Sound"],"icon":null,"command":"foo","deps":null,"base":null,"snapshot":null,"future":1}],"build":{}}"#,
+        )
+        .unwrap();
+
+        let config = Config::deserialize(&path).unwrap();
+        let program = &config.programs[0];
+
+        assert_eq!(program.kind, ProgramKind::Gui);
+        assert_eq!(program.container_mode, ContainerMode::Ephemeral);
+        assert_eq!(program.security, Security::default());
+        assert!(program.labels.is_empty() && program.shares.is_empty());
+        assert!(program.wrapper.is_none() && program.capabilities.is_empty());
+
+        config.serialize(&path).unwrap();
+
+        let value: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(value["programs"][0]["future"], json!(1));
+    }
+
+    #[test]
+    fn test_migrate_newer() {
+        let (_dir, path) = get_path("debian_bridge_test_migrate_newer");
+
+        std::fs::write(
+            &path,
+            format!(r#"{{"version":{},"programs":[]}}"#, CONFIG_VERSION + 1),
+        )
+        .unwrap();
+
+        assert!(Config::deserialize(&path).is_err());
+    }
+
+    #[test]
+    fn test_toml() {
+        let (_dir, path) = get_path("debian_bridge_test_toml");
+        let path = path.with_extension("toml");
+        let mut config = get_config();
+
+        config.build.proxy = Some("http://proxy:3128".to_string());
+        config.serialize(&path).unwrap();
+
+        let data = std::fs::read_to_string(&path).unwrap();
+
+        assert!(data.contains("[[programs]]"));
+
+        let config = Config::deserialize(&path).unwrap();
+
+        assert_eq!(config.programs[0].name, "foo");
+        assert_eq!(config.build.proxy, Some("http://proxy:3128".to_string()));
+    }
+
+    #[test]
+    fn test_convert_keeps_unknown_fields() {
+        let (_dir, path) = get_path("debian_bridge_test_convert");
+
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"version":{},"future":"bar","programs":[{{"name":"foo","path":"/tmp/foo.deb","settings":[],"icon":null,"command":"foo","deps":null,"limits":{{"memory":512}}}}]}}"#,
+                CONFIG_VERSION
+            ),
+        )
+        .unwrap();
+
+        let target = Config::deserialize(&path)
+            .unwrap()
+            .convert(&path, ConfigFormat::Toml)
+            .unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(target, path.with_extension("toml"));
+
+        let config = Config::deserialize(&target).unwrap();
+
+        assert_eq!(config.extra["future"], json!("bar"));
+        assert_eq!(config.programs[0].extra["limits"], json!({ "memory": 512 }));
+    }
+
+    #[test]
+    fn test_record_run() {
+        let mut config = get_config();
+        let now = Utc::now();
+
+        assert!(config.programs[0].is_unused_since(Duration::days(90), now));
+
+        config.record_run("foo", now - Duration::days(30)).unwrap();
+
+        assert_eq!(config.programs[0].run_count, 1);
+        assert!(!config.programs[0].is_unused_since(Duration::days(90), now));
+        assert!(config.programs[0].is_unused_since(Duration::days(7), now));
+        assert!(config.record_run("bar", now).is_err());
+    }
+
+    #[test]
+    fn test_container_mode() {
+        assert_eq!(
+            "persistent".parse::<ContainerMode>().unwrap(),
+            ContainerMode::Persistent
+        );
+        assert!("forever".parse::<ContainerMode>().is_err());
+        assert_eq!(
+            get_config().programs[0].container_mode,
+            ContainerMode::Ephemeral
+        );
+    }
+
+    #[test]
+    fn test_program_kind() {
+        let mut program = get_config().programs[0].to_owned();
+
+        assert_eq!("cli".parse::<ProgramKind>().unwrap(), ProgramKind::Cli);
+        assert!("tui".parse::<ProgramKind>().is_err());
+        assert_eq!(program.kind, ProgramKind::Gui);
+
+        program.settings = vec![Feature::Display, Feature::HomePersistent, Feature::Sound];
+        program.kind = ProgramKind::Cli;
+
+        assert!(!program.uses(&Feature::Display));
+        assert!(program.uses(&Feature::HomePersistent));
+        assert_eq!(program.features(), vec![Feature::HomePersistent]);
+    }
+
+    #[test]
+    fn test_remote_display() {
+        let display = "192.168.1.5:10.0".parse::<RemoteDisplay>().unwrap();
+
+        assert_eq!(display.to_string(), "192.168.1.5:10");
+        assert_eq!(
+            "[fd00::5]:0".parse::<RemoteDisplay>().unwrap().to_string(),
+            "[fd00::5]:0"
+        );
+        assert!("localhost:0".parse::<RemoteDisplay>().is_err());
+        assert!("192.168.1.5".parse::<RemoteDisplay>().is_err());
+        assert_eq!(
+            serde_json::to_value(display).unwrap(),
+            serde_json::json!("192.168.1.5:10")
+        );
+    }
+
+    #[test]
+    fn test_pulse_server() {
+        let server = PulseServer::new("192.168.1.5".parse().unwrap());
+
+        assert_eq!(server.address(), "tcp:192.168.1.5:4713");
+        assert_eq!(
+            PulseServer::new("fd00::5".parse().unwrap()).address(),
+            "tcp6:[fd00::5]:4713"
+        );
+        assert_eq!(
+            serde_json::to_value(&server).unwrap(),
+            serde_json::json!({"host": "192.168.1.5"})
+        );
+        assert_eq!(
+            server.cookie(Path::new("/tmp/cookie")).to_string(),
+            "tcp:192.168.1.5:4713 (cookie /tmp/cookie)"
+        );
+    }
+
+    #[test]
+    fn test_resources() {
+        let mut resources = Resources::default();
+
+        assert_eq!(resources.to_string(), "default");
+        assert!(resources.capabilities().is_empty());
+
+        resources.ulimits.insert("memlock".to_string(), 1 << 20);
+        resources.ulimits.insert("rtprio".to_string(), 95);
+        resources.oom_score_adj = Some(-200);
+
+        assert_eq!(
+            resources.to_string(),
+            "memlock=1048576, rtprio=95, OOM score -200"
+        );
+        assert_eq!(
+            resources.capabilities(),
+            vec![("SYS_NICE", "rtprio"), ("IPC_LOCK", "memlock")]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_truncated_without_backup() {
+        let (_dir, path) = get_path("debian_bridge_test_no_backup");
+
+        std::fs::write(&path, b"{\"programs\": [").unwrap();
+
+        assert!(Config::deserialize(&path).is_err());
+    }
+
+    #[test]
+    fn test_settings() {
+        let mut config = get_config();
+
+        assert_eq!(config.setting("features").unwrap(), None);
+        assert_eq!(
+            config.setting("desktop-entries").unwrap(),
+            Some("true".to_string())
+        );
+
+        config
+            .set_setting("features", "sound, display,sound")
+            .unwrap();
+        config.set_setting("desktop-entries", "no").unwrap();
+        config.set_setting("base-image", "debian:9").unwrap();
+        config.set_setting("backend", "docker").unwrap();
+        config.set_setting("jobs", "2").unwrap();
+        config.set_setting("logs", "false").unwrap();
+        config.set_setting("notify", "yes").unwrap();
+        config.set_setting("notify-after", "120").unwrap();
+        config.set_setting("keep-previous", "2w").unwrap();
+        config.set_setting("offline-probe", "no").unwrap();
+
+        assert_eq!(
+            config.settings.features,
+            vec![Feature::Display, Feature::Sound]
+        );
+        assert_eq!(
+            config.setting("features").unwrap(),
+            Some("display,sound".to_string())
+        );
+        assert!(!config.settings.desktop_entries);
+        assert_eq!(config.build.base_image, Some("debian:9".to_string()));
+        assert_eq!(config.settings.jobs(), 2);
+        assert_eq!(config.setting("jobs").unwrap(), Some("2".to_string()));
+        assert!(!config.settings.logs);
+        assert!(config.settings.notify);
+        assert_eq!(config.settings.notify_after().as_secs(), 120);
+        assert_eq!(config.settings.keep_previous(), Duration::weeks(2));
+        assert_eq!(
+            config.setting("offline-probe").unwrap(),
+            Some("false".to_string())
+        );
+        assert_eq!(
+            GlobalSettings::default().keep_previous(),
+            Duration::days(30)
+        );
+
+        config.set_setting("base-image", "").unwrap();
+
+        assert_eq!(config.setting("base-image").unwrap(), None);
+
+        for (key, value) in [
+            ("features", "display,wifi"),
+            ("desktop-entries", "maybe"),
+            ("backend", "podman"),
+            ("apt-mirror", "ftp://mirror"),
+            ("jobs", "0"),
+            ("jobs", "many"),
+            ("logs", "off"),
+            ("notify-after", "1m"),
+            ("keep-previous", "forever"),
+            ("offline-probe", "auto"),
+        ] {
+            assert!(config.set_setting(key, value).is_err());
+        }
+
+        match config.set_setting("color", "red") {
+            Err(AppError::Argument(message)) => assert_eq!(
+                message,
+                "Unknown setting 'color', valid keys are features, desktop-entries, backend, \
+                 base-image, apt-mirror, jobs, logs, notify, notify-after, keep-previous, \
+                 offline-probe"
+            ),
+            _ => panic!("An unknown key is accepted"),
+        }
+        assert!(config.setting("color").is_err());
+        assert_eq!(config.settings.features.len(), 2);
+    }
+
+    #[test]
+    fn test_settings_default() {
+        let (_dir, path) = get_path("debian_bridge_test_settings");
+
+        std::fs::write(
+            &path,
+            format!(r#"{{"version":{},"programs":[]}}"#, CONFIG_VERSION),
+        )
+        .unwrap();
+
+        let mut config = Config::deserialize(&path).unwrap();
+
+        assert_eq!(config.settings, GlobalSettings::default());
+        assert!(config.settings.desktop_entries);
+
+        config.set_setting("features", "home").unwrap();
+        config.serialize(&path).unwrap();
+
+        assert_eq!(
+            Config::deserialize(&path).unwrap().settings.features,
+            vec![Feature::HomePersistent]
+        );
     }
 }
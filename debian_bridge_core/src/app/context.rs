@@ -0,0 +1,234 @@
+use super::{error::AppError, AppResult};
+use std::{
+    fs::File,
+    io::Write,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix of build context directories inside the cache
+pub const CONTEXT_PREFIX: &str = "build-";
+
+/// `FICLONE` ioctl request, clones file extents on btrfs/xfs without copying data
+const FICLONE: libc::c_ulong = 0x4004_9409;
+/// How often the context upload progress is reported
+const PROGRESS_STEP: u64 = 64 * 1024 * 1024;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Docker build context in its own directory of the cache, removed with everything inside
+/// on drop. Parallel builds never share files this way
+pub struct BuildContext {
+    path: PathBuf,
+    /// Files which couldn't be linked into the context, streamed from their original location
+    external: Vec<(String, PathBuf)>,
+}
+
+impl BuildContext {
+    pub fn new(cache_path: &Path) -> AppResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+        let path = cache_path.join(format!(
+            "{}{}-{:x}-{}",
+            CONTEXT_PREFIX,
+            std::process::id(),
+            nanos,
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        std::fs::create_dir_all(cache_path).and_then(|_| std::fs::create_dir(&path))?;
+        debug!("Build context: {}", path.display());
+
+        Ok(BuildContext {
+            path,
+            external: vec![],
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Adds a file to the context without copying it: a hard link is tried first, then a
+    /// reflink. When both fail, e.g. across filesystems, the file is read from `source`
+    /// while the context gets sent to docker
+    pub fn add_file(&mut self, name: &str, source: &Path) -> AppResult<&Self> {
+        let target = self.path.join(name);
+
+        if std::fs::hard_link(source, &target).is_ok() {
+            debug!("{} hard linked into the build context", source.display());
+        } else if reflink(source, &target).is_ok() {
+            debug!("{} reflinked into the build context", source.display());
+        } else {
+            debug!(
+                "{} will be streamed into the build context",
+                source.display()
+            );
+            std::fs::metadata(source)?;
+            self.external.push((name.to_string(), source.to_owned()));
+        }
+
+        Ok(self)
+    }
+
+    /// Writes the context as a tar archive, reporting progress as it goes
+    pub fn write_tar<W: Write>(&self, writer: W) -> AppResult<()> {
+        let mut files = std::fs::read_dir(&self.path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| (entry.file_name(), entry.path())))
+                    .collect::<std::io::Result<Vec<_>>>()
+            })?
+            .into_iter()
+            .map(|(name, path)| (name.to_string_lossy().to_string(), path))
+            .collect::<Vec<(String, PathBuf)>>();
+
+        files.extend(self.external.iter().cloned());
+
+        let total = files
+            .iter()
+            .filter_map(|(_, path)| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        let mut archive = tar::Builder::new(Progress::new(writer, total));
+
+        for (name, path) in &files {
+            archive.append_path_with_name(path, name)?;
+        }
+
+        archive
+            .into_inner()
+            .and_then(|mut progress| progress.flush())
+            .map_err(AppError::from)
+    }
+}
+
+impl Drop for BuildContext {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.path) {
+            warn!(
+                "Failed to remove build context {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+fn reflink(source: &Path, target: &Path) -> std::io::Result<()> {
+    let source = File::open(source)?;
+    let target_file = File::create(target)?;
+
+    if unsafe { libc::ioctl(target_file.as_raw_fd(), FICLONE, source.as_raw_fd()) } != 0 {
+        let err = std::io::Error::last_os_error();
+
+        std::fs::remove_file(target).unwrap_or(());
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Logs how much of the build context is uploaded, large contexts make the daemon look hung
+struct Progress<W: Write> {
+    inner: W,
+    total: u64,
+    written: u64,
+    reported: u64,
+}
+
+impl<W: Write> Progress<W> {
+    fn new(inner: W, total: u64) -> Self {
+        Progress {
+            inner,
+            total,
+            written: 0,
+            reported: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for Progress<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+
+        self.written += written as u64;
+
+        if self.written - self.reported >= PROGRESS_STEP {
+            self.reported = self.written;
+            debug!(
+                "Build context upload: {} / {} MiB",
+                self.written / 1024 / 1024,
+                self.total / 1024 / 1024
+            );
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_context() {
+        let cache =
+            std::env::temp_dir().join(format!("debian_bridge_test_context_{}", std::process::id()));
+        let first = BuildContext::new(&cache).unwrap();
+        let second = BuildContext::new(&cache).unwrap();
+        let path = first.path().to_owned();
+
+        assert_ne!(first.path(), second.path());
+
+        std::fs::write(path.join("tmp.deb"), b"").unwrap();
+        drop(first);
+
+        assert!(!path.exists());
+        assert!(second.path().exists());
+
+        drop(second);
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn test_write_tar() {
+        let cache = std::env::temp_dir().join(format!(
+            "debian_bridge_test_context_tar_{}",
+            std::process::id()
+        ));
+        let source = cache.join("source.deb");
+        let mut context = BuildContext::new(&cache).unwrap();
+
+        std::fs::write(&source, b"deb").unwrap();
+        std::fs::write(context.path().join("Dockerfile"), b"FROM scratch").unwrap();
+        context.add_file("tmp.deb", &source).unwrap();
+        context
+            .external
+            .push(("external.deb".to_string(), source.to_owned()));
+
+        let mut data = vec![];
+
+        context.write_tar(&mut data).unwrap();
+
+        let mut names = tar::Archive::new(data.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect::<Vec<String>>();
+
+        names.sort();
+        drop(context);
+        std::fs::remove_dir_all(&cache).unwrap();
+
+        assert_eq!(names, vec!["Dockerfile", "external.deb", "tmp.deb"]);
+    }
+}
@@ -1,10 +1,98 @@
-use super::error::AppError;
-use colorful::core::StrMarker;
+use super::{
+    config::{Feature, ProgramKind},
+    error::AppError,
+};
 #[cfg(test)]
 use mocktopus::macros::*;
-use pipers::Pipe;
 use regex::Regex;
-use std::{convert::TryInto, ffi::OsStr, path::Path};
+use std::{
+    collections::BTreeSet,
+    convert::TryInto,
+    ffi::OsStr,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+/// Where packages install their desktop entries
+const APPLICATIONS_DIR: &str = "/usr/share/applications/";
+
+/// Where packages install their executables
+const BIN_DIRS: &[&str] = &["/usr/bin/", "/usr/games/"];
+
+/// Dependencies which tell a feature is likely needed, e.g. a GTK program needs a display
+const FEATURE_HINTS: &[(Feature, &[&str])] = &[
+    (
+        Feature::Display,
+        &[
+            "libx11-6",
+            "libgtk2.0-0",
+            "libgtk-3-0",
+            "libgtk-4-1",
+            "libqt5gui5",
+            "libqt6gui6",
+        ],
+    ),
+    (
+        Feature::Sound,
+        &["libasound2", "libpulse0", "libpipewire-0.3-0"],
+    ),
+    (Feature::Notification, &["libnotify4"]),
+    (Feature::Keyring, &["libsecret-1-0"]),
+];
+
+/// What is known about a package before a program is created from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSummary {
+    pub package: String,
+    pub version: Option<String>,
+    pub synopsis: Option<String>,
+    /// Executables of the desktop entries and names of the ones in the binary directories,
+    /// sorted
+    pub commands: Vec<String>,
+    /// Command `create` picks without one, `None` if the candidates disagree
+    pub command: Option<String>,
+    /// Features the package likely needs, judged by its desktop entries and dependencies
+    pub features: Vec<Feature>,
+    /// Kind of the program, graphical if the package ships a desktop entry
+    pub kind: ProgramKind,
+}
+
+impl PackageSummary {
+    pub fn new(deb: &Deb, files: &[String], entries: &[String]) -> Self {
+        let commands: BTreeSet<String> = entries
+            .iter()
+            .filter_map(|entry| parse_exec(entry))
+            .chain(
+                files
+                    .iter()
+                    .filter(|file| BIN_DIRS.iter().any(|dir| file.starts_with(dir)))
+                    .filter_map(|file| Path::new(file).file_name())
+                    .map(|name| name.to_string_lossy().to_string()),
+            )
+            .collect();
+        let features = FEATURE_HINTS
+            .iter()
+            .filter(|(feature, hints)| {
+                (*feature == Feature::Display && !entries.is_empty())
+                    || deb
+                        .dependencies
+                        .as_ref()
+                        .is_some_and(|deps| hints.iter().any(|hint| deps.contains(hint)))
+            })
+            .map(|(feature, _)| feature.to_owned())
+            .collect();
+
+        PackageSummary {
+            package: deb.package.to_owned(),
+            version: deb.version.to_owned(),
+            synopsis: deb.synopsis().map(str::to_string),
+            commands: commands.into_iter().collect(),
+            command: detect_command(&deb.package, files, entries).ok(),
+            features,
+            kind: detect_kind(files),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Dependencies {
@@ -42,6 +130,10 @@ impl Dependencies {
     pub fn extract(&self) -> String {
         self.list.join(" ")
     }
+
+    pub fn contains(&self, package: &str) -> bool {
+        self.list.iter().any(|dep| dep == package)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -63,26 +155,19 @@ pub struct Deb {
 impl Deb {
     pub fn try_new(path: &Path) -> Result<Self, AppError> {
         if !path.exists() || !path.extension().and_then(OsStr::to_str).eq(&Some("deb")) {
-            return Err(AppError::File(
-                "Input application doesn't exist or is in incorrect format".to_str(),
+            return Err(AppError::Deb(
+                "Input application doesn't exist or is in incorrect format".to_string(),
+                None,
             ));
         }
 
-        let info = Pipe::new(format!("ar p {} control.tar.gz", path.to_str().unwrap()).as_str())
-            .then("tar xzOf - ./control")
-            .finally()
-            .map_err(|err| {
-                AppError::Program(format!("Can not parse a package: {}", err.to_string()))
-            })?
-            .wait_with_output();
-
-        let output = info
-            .map(|o| (*String::from_utf8_lossy(&o.stdout)).to_owned())
-            .map_err(|e| AppError::File(e.to_string()))?;
+        let output = Deb::read_member(path, "control.tar", &["-xO", "./control"])?;
+        let output = String::from_utf8_lossy(&output.stdout).to_string();
 
         Ok(Deb {
-            package: Deb::parse_output(&output, "Package").ok_or(AppError::Program(
+            package: Deb::parse_output(&output, "Package").ok_or(AppError::Deb(
                 "Can not parse an input package".to_string(),
+                None,
             ))?,
             version: Deb::parse_output(&output, "Version"),
             license: Deb::parse_output(&output, "License"),
@@ -94,24 +179,402 @@ impl Deb {
             section: Deb::parse_output(&output, "Section"),
             priority: Deb::parse_output(&output, "Priority"),
             homepage: Deb::parse_output(&output, "Homepage"),
-            description: Deb::parse_output(&output, "Description"),
+            description: Deb::parse_description(&output),
         })
     }
 
+    /// First line of the description, e.g. `universal document viewer`
+    pub fn synopsis(&self) -> Option<&str> {
+        self.description.as_deref().and_then(|d| d.lines().next())
+    }
+
+    /// Chromium and Electron based programs depend on NSS, they need a larger `/dev/shm`
+    pub fn is_chromium_based(&self) -> bool {
+        self.dependencies
+            .as_ref()
+            .is_some_and(|deps| deps.contains("libnss3"))
+    }
+
+    /// Paths the package installs, e.g. `/usr/bin/foo`, without directories
+    pub fn list_files(path: &Path) -> Result<Vec<String>, AppError> {
+        let output = Deb::read_member(path, "data.tar", &["-t"])?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.ends_with('/'))
+            .map(|line| line.trim_start_matches('.').to_string())
+            .collect())
+    }
+
+    /// Contents of a file the package installs, e.g. a desktop entry
+    pub fn read_file(path: &Path, file: &str) -> Result<String, AppError> {
+        let output = Deb::read_member(path, "data.tar", &["-xO", &format!(".{}", file)])?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Pipes the `archive` member of the package, e.g. `data.tar.zst`, from `ar` to `tar` run
+    /// with the operation and the arguments in `args`
+    fn read_member(path: &Path, archive: &str, args: &[&str]) -> Result<Output, AppError> {
+        let member = Deb::find_member(path, archive)?;
+        let (compression, option) = compression(&member, archive)?;
+        let mut ar = Command::new("ar")
+            .arg("p")
+            .arg(path)
+            .arg(&member)
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| AppError::deb("Can not read a package", err))?;
+        let output = Command::new("tar")
+            .arg(args[0])
+            .args(option)
+            .args(["-f", "-"])
+            .args(&args[1..])
+            .stdin(ar.stdout.take().map(Stdio::from).unwrap_or(Stdio::null()))
+            .output();
+
+        ar.wait()
+            .map_err(|err| AppError::deb("Can not read a package", err))?;
+
+        let output = output.map_err(|err| AppError::deb("Can not read a package", err))?;
+
+        match output.status.success() {
+            true => Ok(output),
+            false => Err(AppError::Deb(
+                format!(
+                    "Can not unpack '{}' ({}): {}",
+                    member,
+                    compression,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                None,
+            )),
+        }
+    }
+
+    /// Name of the `archive` member, other members like signatures are skipped. Only the 2.x
+    /// format is read, the format version is the first member
+    fn find_member(path: &Path, archive: &str) -> Result<String, AppError> {
+        let output = Command::new("ar")
+            .arg("t")
+            .arg(path)
+            .output()
+            .map_err(|err| AppError::deb("Can not list a package", err))?;
+
+        if !output.status.success() {
+            return Err(AppError::Deb(
+                format!(
+                    "{} isn't an ar archive: {}",
+                    path.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                None,
+            ));
+        }
+
+        let members = String::from_utf8_lossy(&output.stdout).to_string();
+        let mut members = members.lines();
+
+        if members.next() != Some("debian-binary") {
+            return Err(AppError::Deb(
+                format!("{} has no debian-binary member", path.display()),
+                None,
+            ));
+        }
+
+        let version = Command::new("ar")
+            .arg("p")
+            .arg(path)
+            .arg("debian-binary")
+            .output()
+            .map_err(|err| AppError::deb("Can not read a package", err))?;
+        let version = String::from_utf8_lossy(&version.stdout).trim().to_string();
+
+        if !version.starts_with("2.") {
+            return Err(AppError::Deb(
+                format!("Package format {} isn't supported, only 2.x is", version),
+                None,
+            ));
+        }
+
+        members
+            .find(|member| member.starts_with(archive))
+            .map(|member| member.to_string())
+            .ok_or_else(|| AppError::Deb(format!("Package has no {} member", archive), None))
+    }
+
     fn parse_output<T: Into<String>, S: Into<String>>(output: T, param: S) -> Option<String> {
         let pattern = Regex::new(format!(r"{}: (.*)\n", param.into()).as_str()).unwrap();
 
         for caps in pattern.captures_iter(&output.into()) {
-            return Some(caps.get(1).unwrap().as_str().to_str());
+            return Some(caps.get(1).unwrap().as_str().to_string());
         }
 
         None
     }
+
+    /// Synopsis on the first line followed by the extended description, if there is one
+    fn parse_description(output: &str) -> Option<String> {
+        let mut lines = output
+            .lines()
+            .skip_while(|line| !line.starts_with("Description:"));
+        let mut description = vec![lines.next()?["Description:".len()..].trim().to_string()];
+
+        for line in lines.take_while(|line| line.starts_with(' ') || line.starts_with('\t')) {
+            // A lone dot stands for an empty line
+            match line.trim() {
+                "." => description.push(String::new()),
+                line => description.push(line.to_string()),
+            }
+        }
+
+        Some(description.join("\n"))
+    }
+}
+
+/// Compression of an archive member by its extension, e.g. `zstd` for `data.tar.zst`, along
+/// with the option `tar` unpacks it with
+fn compression(
+    member: &str,
+    archive: &str,
+) -> Result<(&'static str, Option<&'static str>), AppError> {
+    match member.trim_start_matches(archive) {
+        "" => Ok(("uncompressed", None)),
+        ".gz" => Ok(("gzip", Some("--gzip"))),
+        ".xz" => Ok(("xz", Some("--xz"))),
+        ".zst" => Ok(("zstd", Some("--zstd"))),
+        ".bz2" => Ok(("bzip2", Some("--bzip2"))),
+        ".lzma" => Ok(("lzma", Some("--lzma"))),
+        extension => Err(AppError::Deb(
+            format!(
+                "Compression '{}' of '{}' isn't supported, expected gzip, xz, zstd, bzip2, lzma \
+                 or none",
+                extension.trim_start_matches('.'),
+                member
+            ),
+            None,
+        )),
+    }
+}
+
+/// Desktop entries among the files installed by a package
+pub fn desktop_entries(files: &[String]) -> Vec<&String> {
+    files
+        .iter()
+        .filter(|file| file.starts_with(APPLICATIONS_DIR) && file.ends_with(".desktop"))
+        .collect()
+}
+
+/// Kind of the program of a package: graphical if it ships a desktop entry, a command line
+/// one otherwise
+pub fn detect_kind(files: &[String]) -> ProgramKind {
+    match desktop_entries(files).is_empty() {
+        true => ProgramKind::Cli,
+        false => ProgramKind::Gui,
+    }
+}
+
+/// Picks the command of a package: the `Exec` of its desktop entries, then its only executable
+/// in the binary directories, then the package name. Fails with the candidates if they disagree,
+/// e.g. entries of the package start different executables
+pub fn detect_command(
+    package: &str,
+    files: &[String],
+    entries: &[String],
+) -> Result<String, AppError> {
+    let execs: BTreeSet<String> = entries
+        .iter()
+        .filter_map(|entry| parse_exec(entry))
+        .collect();
+
+    if execs.len() == 1 {
+        debug!("Command of {} is the Exec of its desktop entry", package);
+        return Ok(execs.into_iter().next().unwrap());
+    }
+
+    let binaries: BTreeSet<String> = files
+        .iter()
+        .filter(|file| BIN_DIRS.iter().any(|dir| file.starts_with(dir)))
+        .filter_map(|file| Path::new(file).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+
+    if execs.is_empty() && binaries.len() == 1 {
+        debug!("Command of {} is its only executable", package);
+        return Ok(binaries.into_iter().next().unwrap());
+    }
+
+    let candidates: Vec<String> = match execs.is_empty() {
+        true => binaries.into_iter().collect(),
+        false => execs.into_iter().collect(),
+    };
+
+    if candidates.is_empty() || candidates.iter().any(|candidate| candidate == package) {
+        debug!("Command of {} is the package name", package);
+        return Ok(package.to_string());
+    }
+
+    Err(AppError::Program(format!(
+        "Can't tell the command of {}, pass one with --command: {}",
+        package,
+        candidates.join(", ")
+    )))
+}
+
+/// Executable a desktop entry starts, e.g. `/usr/share/code/code` for
+/// `Exec=env FOO=1 /usr/share/code/code --unity-launch %F`
+fn parse_exec(entry: &str) -> Option<String> {
+    entry
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Exec="))
+        .and_then(|exec| {
+            exec.split_whitespace()
+                .find(|word| *word != "env" && !word.contains('='))
+        })
+        .map(|word| word.trim_matches('"').to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/tests")
+            .join(format!("fixture-{}.deb", name))
+    }
+
+    #[test]
+    fn test_compressions() {
+        for name in ["gzip", "xz", "zstd", "none", "signed"] {
+            let path = fixture(name);
+            let deb = Deb::try_new(&path).unwrap();
+
+            assert_eq!(deb.package, "fixture", "{}", name);
+            assert_eq!(deb.synopsis(), Some("Fixture package"), "{}", name);
+            assert!(
+                Deb::list_files(&path)
+                    .unwrap()
+                    .contains(&"/usr/bin/fixture".to_string()),
+                "{}",
+                name
+            );
+            assert!(
+                Deb::read_file(&path, "/usr/share/applications/fixture.desktop")
+                    .unwrap()
+                    .contains("Exec=/usr/bin/fixture %U"),
+                "{}",
+                name
+            );
+        }
+
+        let lz4 = fixture("lz4");
+
+        assert!(Deb::try_new(&lz4).is_ok());
+
+        match Deb::list_files(&lz4) {
+            Err(AppError::Deb(message, _)) => {
+                assert!(message.contains("'lz4' of 'data.tar.lz4'"), "{}", message)
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_description() {
+        let output =
+            "Package: okular\nDescription: universal document viewer\n Okular can view PDF.\n \
+                      .\n Features:\nHomepage: https://okular.kde.org\n";
+
+        assert_eq!(
+            Deb::parse_description(output),
+            Some("universal document viewer\nOkular can view PDF.\n\nFeatures:".to_string())
+        );
+        assert_eq!(Deb::parse_description("Package: foo\n"), None);
+    }
+
+    #[test]
+    fn test_parse_exec() {
+        assert_eq!(
+            parse_exec("[Desktop Entry]\nName=Code\nExec=/usr/share/code/code --unity-launch %F\n"),
+            Some("/usr/share/code/code".to_string())
+        );
+        assert_eq!(
+            parse_exec("Exec=env BAMF_DESKTOP_FILE_HINT=foo.desktop foo %U"),
+            Some("foo".to_string())
+        );
+        assert_eq!(parse_exec("[Desktop Entry]\nName=Foo\n"), None);
+    }
+
+    #[test]
+    fn test_detect_command() {
+        let files = |files: &[&str]| files.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+        let chrome = "Exec=/usr/bin/google-chrome-stable %U\n".to_string();
+
+        assert_eq!(
+            detect_command(
+                "google-chrome-stable",
+                &files(&["/usr/bin/google-chrome-stable", "/usr/bin/google-chrome"]),
+                &[chrome.to_owned(), chrome],
+            )
+            .unwrap(),
+            "/usr/bin/google-chrome-stable"
+        );
+        assert_eq!(
+            detect_command("slack-desktop", &files(&["/usr/bin/slack"]), &[]).unwrap(),
+            "slack"
+        );
+        assert_eq!(
+            detect_command("foo", &files(&["/usr/bin/foo", "/usr/bin/foo-helper"]), &[]).unwrap(),
+            "foo"
+        );
+        assert_eq!(detect_command("foo", &[], &[]).unwrap(), "foo");
+
+        match detect_command("foo-suite", &files(&["/usr/bin/bar", "/usr/bin/baz"]), &[]) {
+            Err(AppError::Program(message)) => assert!(message.ends_with("bar, baz")),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_package_summary() {
+        let mut deb = Deb::try_new(&fixture("gzip")).unwrap();
+        let files = vec![
+            "/usr/bin/fixture".to_string(),
+            "/usr/bin/fixture-helper".to_string(),
+            "/usr/share/doc/fixture/copyright".to_string(),
+        ];
+        let entry = "[Desktop Entry]\nExec=/opt/fixture/fixture %U\n".to_string();
+
+        deb.dependencies = Some(Dependencies::new("libc6, libasound2 (>= 1.0.16)"));
+
+        let summary = PackageSummary::new(&deb, &files, &[entry]);
+
+        assert_eq!(summary.package, "fixture");
+        assert_eq!(summary.synopsis.as_deref(), Some("Fixture package"));
+        assert_eq!(
+            summary.commands,
+            vec!["/opt/fixture/fixture", "fixture", "fixture-helper"]
+        );
+        assert_eq!(summary.command.as_deref(), Some("/opt/fixture/fixture"));
+        assert_eq!(summary.features, vec![Feature::Display, Feature::Sound]);
+        assert_eq!(summary.kind, ProgramKind::Cli);
+        assert_eq!(
+            detect_kind(&["/usr/share/applications/fixture.desktop".to_string()]),
+            ProgramKind::Gui
+        );
+
+        deb.dependencies = Some(Dependencies::new("libnotify4, libgtk-3-0"));
+
+        let summary = PackageSummary::new(&deb, &files, &[]);
+
+        assert_eq!(summary.command.as_deref(), Some("fixture"));
+        assert_eq!(
+            summary.features,
+            vec![Feature::Display, Feature::Notification]
+        );
+    }
 
     #[test]
     fn dep_parses_success() {
@@ -127,6 +590,8 @@ mod tests {
              xdg-utils libx11-xcb1 libxss1 libasound2 libxkbfile1 libcurl3 policykit-1",
             deps.extract()
         );
+        assert!(deps.contains("libnss3"));
+        assert!(!deps.contains("libnss"));
 
         let deps = Dependencies::new("one_dep");
 
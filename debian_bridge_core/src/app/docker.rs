@@ -1,11 +1,30 @@
-use super::{error::AppError, Feature, Program, System};
-use colorful::core::StrMarker;
+use super::{
+    backend::Backend,
+    cancel::Cancel,
+    context::BuildContext,
+    error::AppError,
+    events::{parse_build_step, AppEvent, Reporter},
+    logs::{self, LogWriter, LOG_MAX_BYTES},
+    spec::{build_run_spec, HostState, SpecProxy},
+    urls::UrlOpener,
+    Program, RunOptions, RunOverrides, System,
+};
+use chrono::{DateTime, Utc};
+use openssl::sha::Sha256;
 use serde_json::Value;
-use shiplift::{BuildOptions, ContainerListOptions, Docker};
+use shiplift::{
+    builder::ContainerFilter,
+    rep::{Container, Image},
+    ContainerListOptions, Docker, ImageListOptions, PullOptions,
+};
 use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, Command, Stdio},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use tokio::{
     prelude::{Future, Stream},
@@ -14,29 +33,225 @@ use tokio::{
 
 type AppResult<T> = Result<T, AppError>;
 
-pub struct DockerFacade<'a> {
-    docker: &'a Docker,
-    system: &'a System,
+/// Marks images and containers created by debian_bridge
+pub const MANAGED_LABEL: &str = "org.debian-bridge.managed";
+/// Program an image was built for or a container was started for, base images don't have it
+pub const PROGRAM_LABEL: &str = "org.debian-bridge.program";
+/// Name prefix of the profile an image or a container belongs to, those made before profiles
+/// don't have it and count for every profile
+pub const PREFIX_LABEL: &str = "org.debian-bridge.prefix";
+/// Version of debian_bridge which built the image
+pub const VERSION_LABEL: &str = "org.debian-bridge.version";
+/// SHA-256 of the package an image was built from
+pub const CREATED_FROM_LABEL: &str = "org.debian-bridge.created-from";
+/// How long a stopped container gets to exit before it's killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the progress of an image export is reported, in bytes
+const TRANSFER_STEP: u64 = 64 * 1024 * 1024;
+/// How many last lines of the build output are kept for an error
+const OUTPUT_TAIL: usize = 10;
+/// Where a PulseAudio cookie is mounted in a container
+pub const PULSE_COOKIE: &str = "/tmp/pulse-cookie";
+/// Where the session bus filtered by xdg-dbus-proxy is mounted in a container
+pub const PROXY_BUS: &str = "/run/dbus-proxy/bus";
+/// Socket of the system bus, on the host and in a container
+pub const SYSTEM_BUS: &str = "/run/dbus/system_bus_socket";
+/// Socket of avahi-daemon, on the host and in a container
+pub const AVAHI_SOCKET: &str = "/run/avahi-daemon/socket";
+/// Hosts the SANE network backend of a container asks for scanners
+pub const SANE_NET_CONF: &str = "/etc/sane.d/net.conf";
+/// Where data directories of the host, e.g. themes, are mounted in a container
+const HOST_SHARE: &str = "/opt/host/share";
+/// The first API version accepting `--device-cgroup-rule` (docker 17.04)
+pub const DEVICE_RULES_API_VERSION: (u32, u32) = (1, 28);
+/// The first API version whose CLI builds with BuildKit and takes `--build-context` (docker 23.0)
+pub const BUILD_CONTEXT_API_VERSION: (u32, u32) = (1, 42);
+/// Device classes the devices feature opens, by major number: video capture, input, ALSA, USB
+/// serial adapters and modems, raw USB and DRM
+pub const DEVICE_RULES: &[&str] = &[
+    "c 81:* rmw",
+    "c 13:* rmw",
+    "c 116:* rmw",
+    "c 188:* rmw",
+    "c 166:* rmw",
+    "c 189:* rmw",
+    "c 226:* rmw",
+];
+/// Device classes the devices feature shares, see `host_devices`. Their directories are bound
+/// to see the devices plugged in later, the nodes right in `/dev` are passed as they are
+pub const DEVICE_CLASSES: &[&str] = &[
+    "/dev/snd",
+    "/dev/dri",
+    "/dev/input",
+    "/dev/bus/usb",
+    "/dev/video*",
+    "/dev/ttyUSB*",
+    "/dev/ttyACM*",
+];
+/// Host groups owning the devices, the program user joins them to open the devices
+pub const DEVICE_GROUPS: &[&str] = &["video", "render", "input", "audio", "plugdev", "dialout"];
+
+/// Describes how an image gets built
+#[derive(Default)]
+pub struct BuildSpec {
+    pub nocache: bool,
+    pub buildkit: bool,
+    /// Build arguments, see `BuildSettings::build_args`
+    pub args: Vec<(String, String)>,
+    pub labels: Vec<(String, String)>,
+    /// Named build contexts by name, directories of the host read by BuildKit itself
+    pub contexts: Vec<(String, PathBuf)>,
+    /// Raised when nobody waits for the build anymore, the build is stopped then
+    pub cancel: Cancel,
+}
+
+impl BuildSpec {
+    /// Labels every image built by debian_bridge with the name prefix gets
+    pub fn managed_labels(prefix: &str) -> Vec<(String, String)> {
+        vec![
+            (MANAGED_LABEL.to_string(), "true".to_string()),
+            (PREFIX_LABEL.to_string(), prefix.to_string()),
+            (
+                VERSION_LABEL.to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+        ]
+    }
+}
+
+/// Image built by debian_bridge
+#[derive(Debug, Clone)]
+pub struct ManagedImage {
+    pub id: String,
+    pub tags: Vec<String>,
+    /// Program the image was built for, `None` for base images
+    pub program: Option<String>,
+    /// Size including the parent layers
+    pub size: u64,
+    /// Whether the image is labeled, otherwise it was recognized by its tags
+    pub labeled: bool,
+    /// SHA-256 of the package the image was built from, see `CREATED_FROM_LABEL`
+    pub created_from: Option<String>,
+    /// Prefix of the app which built the image, see `PREFIX_LABEL`. `None` for images of older
+    /// versions
+    pub prefix: Option<String>,
+}
+
+/// How a program run went
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    /// `None` if the container was killed by a signal
+    pub exit_code: Option<i32>,
+    /// `None` if docker failed before creating the container
+    pub container_id: Option<String>,
+    pub started: DateTime<Utc>,
+    pub duration: Duration,
+    /// File the output was written to, `None` if it wasn't logged
+    pub log: Option<PathBuf>,
+}
+
+pub struct DockerFacade {
+    docker: Docker,
+    system: System,
     prefix: String,
-    cache_path: PathBuf,
 }
 
-impl<'a> DockerFacade<'a> {
-    pub fn new<T: Into<String>>(
-        docker: &'a Docker,
-        system: &'a System,
-        prefix: T,
-        cache_path: &Path,
-    ) -> Self {
+impl DockerFacade {
+    pub fn new<T: Into<String>>(docker: Docker, system: System, prefix: T) -> Self {
         DockerFacade {
             docker,
             system,
             prefix: prefix.into(),
-            cache_path: cache_path.into(),
         }
     }
 
-    fn get_containers(&self, image: &String) -> AppResult<Vec<String>> {
+    /// Runs docker CLI attached to the terminal until it exits, the output goes to `log` as well
+    /// if it's given
+    fn spawn(
+        &self,
+        args: &[&str],
+        container_id: Option<String>,
+        log: Option<&Path>,
+    ) -> AppResult<RunOutcome> {
+        let started_at = Utc::now();
+        let started = Instant::now();
+        let log = log
+            .map(|path| LogWriter::create(path, LOG_MAX_BYTES))
+            .transpose()?
+            .map(|log| Arc::new(Mutex::new(log)));
+        let output = match log {
+            Some(_) => Stdio::piped,
+            None => Stdio::inherit,
+        };
+        let mut cmd = Command::new("docker")
+            .args(args)
+            .stdout(output())
+            .stderr(output())
+            .spawn()
+            .map_err(|err| AppError::docker_run("Can't start docker", err))?;
+        let tees = match &log {
+            Some(log) => vec![
+                cmd.stdout
+                    .take()
+                    .map(|out| logs::tee(out, std::io::stdout(), log.clone())),
+                cmd.stderr
+                    .take()
+                    .map(|err| logs::tee(err, std::io::stderr(), log.clone())),
+            ],
+            None => vec![],
+        };
+
+        let status = cmd
+            .wait()
+            .map_err(|err| AppError::docker_run("Can't wait for docker", err))?;
+
+        for tee in tees.into_iter().flatten() {
+            tee.join().unwrap_or(());
+        }
+
+        info!("Exited with status {:?}", status);
+
+        Ok(RunOutcome {
+            exit_code: status.code(),
+            container_id,
+            started: started_at,
+            duration: started.elapsed(),
+            log: None,
+        })
+    }
+}
+
+impl Backend for DockerFacade {
+    fn system(&self) -> &System {
+        &self.system
+    }
+
+    fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Backend> {
+        Box::new(DockerFacade::new(
+            self.docker.clone(),
+            self.system.to_owned(),
+            self.prefix.to_owned(),
+        ))
+    }
+
+    fn ping(&self) -> AppResult<()> {
+        let mut rt = Runtime::new().unwrap();
+        let result = rt
+            .block_on(self.docker.ping())
+            .map(|_| ())
+            .map_err(|err| AppError::DockerConnection(Some(err.into())));
+
+        rt.shutdown_now().wait().unwrap_or(());
+
+        result
+    }
+
+    fn get_containers(&self, image: &str) -> AppResult<Vec<String>> {
         let program_name = Arc::new(image.to_owned());
 
         let fut = self
@@ -54,182 +269,1007 @@ impl<'a> DockerFacade<'a> {
                         }
                     })
                     .collect()
-            })
-            .map_err(|_| ());
+            });
 
         let mut rt = Runtime::new().unwrap();
 
         let container_ids = match rt.block_on(fut) {
             Ok(res) => Ok(res),
-            Err(err) => Err(AppError::Docker),
+            Err(err) => Err(AppError::docker("list containers", err)),
         };
 
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
         container_ids
     }
 
-    fn delete_container(&self, id: &String) -> AppResult<&Self> {
+    /// Names of the exited containers started by runs of any program of the prefix
+    fn exited_containers(&self) -> AppResult<Vec<String>> {
+        let prefix = self.prefix.to_owned();
         let fut = self
             .docker
             .containers()
-            .get(&id)
-            .delete()
-            .map_err(|_| AppError::Docker);
+            .list(
+                &ContainerListOptions::builder()
+                    .all()
+                    .filter(vec![
+                        ContainerFilter::Status("exited".to_string()),
+                        ContainerFilter::LabelName(PROGRAM_LABEL.to_string()),
+                    ])
+                    .build(),
+            )
+            .map(move |containers| {
+                containers
+                    .iter()
+                    .filter(|c| is_of_prefix(c, &prefix))
+                    .filter_map(|c| c.names.first())
+                    .map(|name| name.trim_start_matches('/').to_string())
+                    .collect()
+            });
+
         let mut rt = Runtime::new().unwrap();
 
-        rt.block_on(fut).map_err(|err| {
-            warn!("{}", err.to_string());
-            AppError::Docker
-        })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+        let names = match rt.block_on(fut) {
+            Ok(res) => Ok(res),
+            Err(err) => Err(AppError::docker("list containers", err)),
+        };
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
-        Ok(self)
+        names
     }
 
-    pub fn delete(&mut self, program: &Program) -> AppResult<&Self> {
-        let name = program.get_name(&self.prefix);
-        let containers_ids = self.get_containers(&name)?;
+    /// IDs of all containers started by runs of any program of the prefix
+    fn labeled_containers(&self) -> AppResult<Vec<String>> {
+        let prefix = self.prefix.to_owned();
+        let fut = self
+            .docker
+            .containers()
+            .list(
+                &ContainerListOptions::builder()
+                    .all()
+                    .filter(vec![ContainerFilter::LabelName(PROGRAM_LABEL.to_string())])
+                    .build(),
+            )
+            .map(move |containers| {
+                containers
+                    .into_iter()
+                    .filter(|c| is_of_prefix(c, &prefix))
+                    .map(|c| c.id)
+                    .collect()
+            });
 
-        containers_ids
-            .iter()
-            .try_for_each(|id| self.delete_container(&id).map(|_| ()))?;
+        let mut rt = Runtime::new().unwrap();
+
+        let ids = rt
+            .block_on(fut)
+            .map_err(|err| AppError::docker("list containers", err));
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        ids
+    }
+
+    /// IDs of the running containers started for the program
+    fn running_containers(&self, program: &str) -> AppResult<Vec<String>> {
+        let prefix = self.prefix.to_owned();
+        let fut = self
+            .docker
+            .containers()
+            .list(
+                &ContainerListOptions::builder()
+                    .filter(vec![ContainerFilter::Label(
+                        PROGRAM_LABEL.to_string(),
+                        program.to_string(),
+                    )])
+                    .build(),
+            )
+            .map(move |containers| {
+                containers
+                    .into_iter()
+                    .filter(|c| is_of_prefix(c, &prefix))
+                    .map(|c| c.id)
+                    .collect()
+            });
 
-        let fut = self.docker.images().get(&name).delete();
+        let mut rt = Runtime::new().unwrap();
+
+        let ids = rt
+            .block_on(fut)
+            .map_err(|err| AppError::docker("list containers", err));
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        ids
+    }
+
+    /// Total size of the writable layers of the containers started for the program
+    fn containers_size(&self, program: &str) -> AppResult<u64> {
+        let prefix = self.prefix.to_owned();
+        let fut = self
+            .docker
+            .containers()
+            .list(
+                &ContainerListOptions::builder()
+                    .all()
+                    .sized()
+                    .filter(vec![ContainerFilter::Label(
+                        PROGRAM_LABEL.to_string(),
+                        program.to_string(),
+                    )])
+                    .build(),
+            )
+            .map(move |containers| {
+                containers
+                    .iter()
+                    .filter(|c| is_of_prefix(c, &prefix))
+                    .filter_map(|c| c.size_rw)
+                    .sum::<u64>()
+            });
+
+        let mut rt = Runtime::new().unwrap();
+
+        let size = rt
+            .block_on(fut)
+            .map_err(|err| AppError::docker("list containers", err));
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        size
+    }
+
+    /// ID of the container with the given name, `None` if there is no such container
+    fn container_id(&self, name: &str) -> AppResult<Option<String>> {
+        let fut = self.docker.containers().get(name).inspect();
+        let mut rt = Runtime::new().unwrap();
+
+        let id = match rt.block_on(fut) {
+            Ok(details) => Ok(Some(details.id)),
+            Err(shiplift::Error::Fault { code, .. }) if code.as_u16() == 404 => Ok(None),
+            Err(err) => Err(AppError::docker(
+                format!("inspect container '{}'", name),
+                err,
+            )),
+        };
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        id
+    }
+
+    /// Renames a container, used to keep a persistent container when its program is renamed
+    fn rename_container(&self, from: &str, to: &str) -> AppResult<()> {
+        let output = Command::new("docker")
+            .args(["rename", from, to])
+            .output()
+            .map_err(|err| AppError::docker_run("Can't start docker rename", err))?;
+
+        if !output.status.success() {
+            return Err(AppError::DockerRun(
+                format!(
+                    "Can't rename container '{}' to '{}': {}",
+                    from,
+                    to,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn delete_container(&self, id: &str) -> AppResult<()> {
+        let fut = self.docker.containers().get(&id).delete();
         let mut rt = Runtime::new().unwrap();
 
         rt.block_on(fut).map_err(|err| {
             warn!("{}", err.to_string());
-            AppError::DockerStatus(404)
+            AppError::docker(format!("delete container '{}'", id), err)
         })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
-        Ok(self)
+        Ok(())
     }
 
-    pub fn create<T: Into<String>>(&mut self, name: T) -> AppResult<&Self> {
-        let tag = format!("{}_{}", self.prefix, name.into());
+    fn stop_container(&self, id: &str) -> AppResult<()> {
+        let fut = self.docker.containers().get(id).stop(Some(STOP_TIMEOUT));
+        let mut rt = Runtime::new().unwrap();
+
+        rt.block_on(fut)
+            .map_err(|err| AppError::docker(format!("stop container '{}'", id), err))?;
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
+        Ok(())
+    }
+
+    /// Builds an image tagged with `tag` from the dockerfile found in `context`.
+    /// docker CLI is used as shiplift can neither open a BuildKit session, pass build arguments
+    /// nor stream a context: it packs the whole context into memory first. The context is
+    /// written to stdin of `docker build -` instead, directly from where the files are.
+    /// The output goes to the terminal, unless a frontend listens to the build steps.
+    /// docker is killed once `spec.cancel` is raised, which stops the build in the daemon too
+    fn build(
+        &self,
+        context: &BuildContext,
+        tag: &str,
+        spec: &BuildSpec,
+        reporter: &Reporter,
+    ) -> AppResult<()> {
         info!("Image name: {}", tag);
 
+        let mut cmd = Command::new("docker");
+
+        cmd.env("DOCKER_BUILDKIT", if spec.buildkit { "1" } else { "0" })
+            .args(["build", "-t", tag]);
+
+        if spec.buildkit {
+            cmd.arg("--progress=plain");
+        }
+
+        if spec.nocache {
+            cmd.arg("--no-cache");
+        }
+
+        for (name, value) in &spec.labels {
+            cmd.arg("--label").arg(format!("{}={}", name, value));
+        }
+
+        for (name, dir) in &spec.contexts {
+            cmd.arg("--build-context")
+                .arg(format!("{}={}", name, dir.display()));
+        }
+
+        for (name, value) in &spec.args {
+            // Values are passed through the environment to keep them out of the process list
+            cmd.env(name, value).args(["--build-arg", name]);
+        }
+
+        let output = match reporter.is_listened() {
+            true => Stdio::piped,
+            false => Stdio::inherit,
+        };
+        let mut child = cmd
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(output())
+            .stderr(output())
+            .spawn()
+            .map_err(|err| AppError::docker_build("Can't start docker build", err))?;
+        // Both streams are read while the context is uploaded, a full pipe would block docker
+        let readers = vec![
+            child.stdout.take().map(|out| report_steps(out, reporter)),
+            child.stderr.take().map(|err| report_steps(err, reporter)),
+        ];
+        let stdin = child.stdin.take().unwrap();
+        let (upload, status) = std::thread::scope(|scope| {
+            let upload = scope.spawn(|| context.write_tar(stdin));
+            let status = wait_build(&mut child, &spec.cancel);
+
+            (upload.join().unwrap_or(Ok(())), status)
+        });
+        let status = status?;
+
+        let tail: Vec<String> = readers
+            .into_iter()
+            .flatten()
+            .flat_map(|reader| reader.join().unwrap_or_default())
+            .collect();
+
+        if !status.success() {
+            return Err(AppError::DockerBuild(
+                match tail.is_empty() {
+                    true => status.to_string(),
+                    false => format!("{}\n{}", status, tail.join("\n")),
+                },
+                None,
+            ));
+        }
+
+        upload?;
+
+        Ok(())
+    }
+
+    /// Pulls a fresh version of a public image, e.g. `debian:9-slim` or
+    /// `registry.example.com:5000/foo:1.0`
+    fn pull(&self, image: &str) -> AppResult<()> {
+        let mut options = PullOptions::builder();
+
+        // The port of a registry is separated by a colon as well
+        match image.rsplit_once(':').filter(|(_, tag)| !tag.contains('/')) {
+            Some((name, tag)) => options.image(name).tag(tag),
+            None => options.image(image),
+        };
+
         let fut = self
             .docker
             .images()
-            .build(
-                &BuildOptions::builder(self.cache_path.as_os_str().to_str().unwrap())
-                    .tag(&tag)
-                    .build(),
-            )
+            .pull(&options.build())
             .for_each(|output| {
-                let output = output.as_object().unwrap();
+                debug!("Docker output: {}", output);
+                Ok(())
+            });
+        let mut rt = Runtime::new().unwrap();
 
-                if output.contains_key("error") {
-                    error!("Docker output: {}", output.get("error").unwrap());
-                    return Err(shiplift::Error::InvalidResponse(
-                        "Failed to build an image".to_string(),
-                    ));
-                }
+        rt.block_on(fut).map_err(|err| {
+            error!("{}", err.to_string());
+            AppError::docker(format!("pull image '{}'", image), err)
+        })?;
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
-                if output.contains_key("stream") {
-                    info!("Docker output: {}", output.get("stream").unwrap());
-                }
+        Ok(())
+    }
 
-                Ok(())
+    fn image_exists(&self, tag: &str) -> AppResult<bool> {
+        let fut = self.docker.images().get(tag).inspect();
+        let mut rt = Runtime::new().unwrap();
+
+        let exists = match rt.block_on(fut) {
+            Ok(_) => Ok(true),
+            Err(shiplift::Error::Fault { code, .. }) if code.as_u16() == 404 => Ok(false),
+            Err(err) => {
+                warn!("{}", err.to_string());
+                Err(AppError::docker(format!("inspect image '{}'", tag), err))
+            }
+        };
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        exists
+    }
+
+    /// Size of the image including the parent layers, `None` if there is no such image
+    fn image_size(&self, tag: &str) -> AppResult<Option<u64>> {
+        let fut = self.docker.images().get(tag).inspect();
+        let mut rt = Runtime::new().unwrap();
+
+        let size = match rt.block_on(fut) {
+            Ok(details) => Ok(Some(details.size)),
+            Err(shiplift::Error::Fault { code, .. }) if code.as_u16() == 404 => Ok(None),
+            Err(err) => Err(AppError::docker(format!("inspect image '{}'", tag), err)),
+        };
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        size
+    }
+
+    fn image_id(&self, tag: &str) -> AppResult<String> {
+        let fut = self.docker.images().get(tag).inspect();
+        let mut rt = Runtime::new().unwrap();
+
+        let id = rt
+            .block_on(fut)
+            .map(|details| details.id)
+            .map_err(|err| AppError::docker(format!("inspect image '{}'", tag), err));
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        id
+    }
+
+    fn image_prefix(&self, tag: &str) -> AppResult<Option<String>> {
+        let fut = self.docker.images().get(tag).inspect();
+        let mut rt = Runtime::new().unwrap();
+
+        let prefix = match rt.block_on(fut) {
+            Ok(details) => Ok(details
+                .config
+                .labels
+                .and_then(|labels| labels.get(PREFIX_LABEL).cloned())),
+            Err(shiplift::Error::Fault { code, .. }) if code.as_u16() == 404 => Ok(None),
+            Err(err) => Err(AppError::docker(format!("inspect image '{}'", tag), err)),
+        };
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        prefix
+    }
+
+    /// Images built by debian_bridge. Images built before labels were introduced are recognized
+    /// by their tags: the base repository or the `<prefix>_<program>` name
+    fn managed_images(&self) -> AppResult<Vec<ManagedImage>> {
+        let prefix = self.prefix.to_owned();
+        let fut = self
+            .docker
+            .images()
+            .list(&ImageListOptions::builder().build())
+            .map(move |images| {
+                images
+                    .into_iter()
+                    .filter_map(|image| classify_image(image, &prefix))
+                    .collect()
             });
         let mut rt = Runtime::new().unwrap();
 
+        let images = rt.block_on(fut).map_err(|err| {
+            warn!("{}", err.to_string());
+            AppError::docker("list images", err)
+        });
+
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        images
+    }
+
+    /// Adds `to` tag to the image. docker CLI is used as shiplift can't tag images
+    fn tag(&self, from: &str, to: &str) -> AppResult<()> {
+        let output = Command::new("docker")
+            .args(["tag", from, to])
+            .output()
+            .map_err(|err| AppError::docker_build("Can't start docker tag", err))?;
+
+        if !output.status.success() {
+            return Err(AppError::DockerBuild(
+                format!(
+                    "Can't tag '{}' as '{}': {}",
+                    from,
+                    to,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                None,
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn delete_image(&self, tag: &str) -> AppResult<()> {
+        let fut = self.docker.images().get(tag).delete();
+        let mut rt = Runtime::new().unwrap();
+
         rt.block_on(fut).map_err(|err| {
-            error!("{}", err.to_string());
-            AppError::Docker
+            warn!("{}", err.to_string());
+            AppError::docker(format!("delete image '{}'", tag), err)
         })?;
-        rt.shutdown_now().wait().map_err(|err| AppError::Docker)?;
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
 
-        Ok(self)
+        Ok(())
     }
 
-    //TODO: add more options and rewrite with docker API if possible
-    pub fn run(&self, program: &Program) -> AppResult<&Self> {
-        let home = std::env::var_os("HOME")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        let cmd_name = program.get_name(&self.prefix);
-        let home_volume = format!("{}:{}", home, home);
-        let mut args = vec![
-            "run",
-            "-ti",
-            "--net=host",
-            "--rm",
-            "-v",
-            "/dev/shm:/dev/shm",
-            "-v",
-            "/etc/machine-id:/etc/machine-id",
-            "-v",
-            "/var/lib/dbus:/var/lib/dbus",
-            "--privileged",
-        ];
+    fn save_image(&self, tag: &str, path: &Path, reporter: &Reporter) -> AppResult<String> {
+        let total = self.image_size(tag)?;
+        let file = File::create(path)?;
+        let events = reporter.to_owned();
+        let fut = self.docker.images().get(tag).export().fold(
+            (file, Sha256::new(), 0, 0),
+            move |(mut file, mut hasher, written, mut reported), chunk| {
+                file.write_all(&chunk).map_err(shiplift::Error::IO)?;
+                hasher.update(&chunk);
+
+                let written = written + chunk.len() as u64;
+
+                if written - reported >= TRANSFER_STEP {
+                    events.emit(AppEvent::ImageTransfer {
+                        bytes: written,
+                        total,
+                    });
+                    reported = written;
+                }
+
+                Ok::<_, shiplift::Error>((file, hasher, written, reported))
+            },
+        );
+        let mut rt = Runtime::new().unwrap();
+        let saved = rt.block_on(fut);
 
-        if program.settings.contains(&Feature::Display) {
-            args.push_volume("/tmp/.X11-unix:/tmp/.X11-unix")
-                .push_env("DISPLAY");
+        rt.shutdown_now()
+            .wait()
+            .map_err(|_| AppError::DockerConnection(None))?;
+
+        let (file, hasher, written, _) = match saved {
+            Ok(saved) => saved,
+            Err(err) => {
+                std::fs::remove_file(path).unwrap_or(());
+
+                return Err(match err {
+                    shiplift::Error::IO(err) => err.into(),
+                    err => AppError::docker(format!("save image '{}'", tag), err),
+                });
+            }
+        };
+
+        file.sync_all()?;
+        reporter.emit(AppEvent::ImageTransfer {
+            bytes: written,
+            total,
+        });
+
+        Ok(hasher
+            .finish()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// shiplift can't load images, docker CLI streams the tarball from the disk instead
+    fn load_image(&self, path: &Path) -> AppResult<Vec<String>> {
+        let output = Command::new("docker")
+            .arg("load")
+            .arg("-i")
+            .arg(path)
+            .output()
+            .map_err(|err| AppError::docker_run("Can't start docker load", err))?;
+
+        if !output.status.success() {
+            return Err(AppError::DockerStatus {
+                code: output.status.code().unwrap_or_default() as u16,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                operation: format!("load images from {}", path.display()),
+            });
         }
 
-        if program.settings.contains(&Feature::Sound) {
-            args.push_volume("/dev/snd:/dev/snd");
+        Ok(parse_loaded(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn probe(&self, image: &str, script: &str) -> AppResult<(i32, String)> {
+        let output = Command::new("docker")
+            .args([
+                "run",
+                "--rm",
+                "--entrypoint",
+                "/bin/sh",
+                image,
+                "-c",
+                script,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| AppError::docker_run("Can't start docker run", err))?;
+
+        Ok((
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+        ))
+    }
+
+    //TODO: add more options and rewrite with docker API if possible
+    fn run(
+        &self,
+        program: &Program,
+        options: &RunOptions,
+        log: Option<&Path>,
+    ) -> AppResult<RunOutcome> {
+        let overrides = RunOverrides {
+            host: HostState::read(program, &self.system, &options.overrides)?,
+            ..options.overrides.to_owned()
+        };
+        let spec = build_run_spec(program, &self.system, &self.prefix, &overrides)?;
+
+        trace!("Run spec of {}: {:#?}", program.get_name_short(), spec);
+
+        let _proxies = [&spec.session_proxy, &spec.system_proxy]
+            .iter()
+            .filter_map(|proxy| proxy.as_ref())
+            .map(BusProxy::spawn)
+            .collect::<AppResult<Vec<_>>>()?;
+        let _url_opener = match &spec.url_socket {
+            Some(socket) => Some(UrlOpener::spawn(socket, "xdg-open")?),
+            None => None,
+        };
+
+        if let Some(name) = &spec.name {
+            if let Some(id) = self.container_id(name)? {
+                if options.overrides.changes_container() {
+                    warn!(
+                        "The persistent container of {} keeps the settings it was created with, \
+                         the options of this run are ignored",
+                        program.get_name_short()
+                    );
+                }
+
+                debug!("Starting persistent container {}", id);
+                return self.spawn(&["start", "-ai", name], Some(id), log);
+            }
         }
 
-        if program.settings.contains(&Feature::HomePersistent) {
-            args.push_volume(&home_volume);
+        for dir in &spec.dirs {
+            std::fs::create_dir_all(dir)?;
         }
 
-        if program.settings.contains(&Feature::Time) {
-            args.push_volume("/etc/localtime:/etc/localtime");
+        for file in &spec.files {
+            std::fs::write(&file.path, &file.content)?;
         }
 
-        if program.settings.contains(&Feature::Notification) {
-            args.push_volume("/var/lib/dbus:/var/lib/dbus");
+        std::fs::remove_file(&spec.cid_file).unwrap_or(());
+
+        let args = spec.args();
+        let outcome = self.spawn(
+            &args.iter().map(String::as_str).collect::<Vec<_>>(),
+            None,
+            log,
+        );
+
+        for file in spec.files.iter().filter(|file| file.temporary) {
+            std::fs::remove_file(&file.path).unwrap_or(());
         }
 
-        if program.settings.contains(&Feature::Devices) {
-            args.push_volume("/dev:/dev");
+        let mut outcome = outcome?;
+
+        outcome.container_id = std::fs::read_to_string(&spec.cid_file)
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty());
+        std::fs::remove_file(&spec.cid_file).unwrap_or(());
+
+        if spec.read_only && outcome.exit_code != Some(0) {
+            warn!(
+                "The root filesystem of {} is read-only, if it failed to write somewhere, share \
+                 the location with the home feature or turn read-only mode off with \
+                 `debian_bridge edit --remove-feature read-only {}`",
+                program.get_name_short(),
+                program.get_name_short()
+            );
         }
 
-        args.push(&cmd_name);
+        Ok(outcome)
+    }
+}
+
+/// xdg-dbus-proxy letting a container talk only to some services of a bus, stopped on drop
+struct BusProxy {
+    child: Child,
+    socket: PathBuf,
+}
 
-        let mut cmd = Command::new("docker")
-            .args(args)
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
+impl BusProxy {
+    /// How long the proxy may take to listen
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn spawn(spec: &SpecProxy) -> AppResult<Self> {
+        let socket = &spec.socket;
+
+        std::fs::remove_file(socket).unwrap_or(());
+
+        let child = Command::new("xdg-dbus-proxy")
+            .arg(format!("unix:path={}", spec.bus.display()))
+            .arg(socket)
+            .arg("--filter")
+            .args(spec.names.iter().map(|name| format!("--talk={}", name)))
+            .stderr(Stdio::null())
             .spawn()
-            .map_err(|err| AppError::Docker)?;
+            .map_err(|err| AppError::Environment(format!("Can't start xdg-dbus-proxy: {}", err)))?;
+        // Dropped on timeout, which stops the proxy
+        let proxy = BusProxy {
+            child,
+            socket: socket.to_owned(),
+        };
+        let started = Instant::now();
 
-        let status = cmd.wait().map_err(|err| {
-            error!("{}", err.to_string());
-            AppError::Docker
-        })?;
+        while !socket.exists() {
+            if started.elapsed() > Self::TIMEOUT {
+                return Err(AppError::Environment(
+                    "xdg-dbus-proxy didn't create its socket".to_string(),
+                ));
+            }
 
-        info!("Exited with status {:?}", status);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(proxy)
+    }
+}
+
+impl Drop for BusProxy {
+    fn drop(&mut self) {
+        self.child.kill().unwrap_or(());
+        self.child.wait().map(|_| ()).unwrap_or(());
+        std::fs::remove_file(&self.socket).unwrap_or(());
+    }
+}
+
+/// Read-only binds sharing fonts and the GTK theme of the host as source and target, and the
+/// variables pointing the programs at them. Paths missing on the host are skipped. Themes and
+/// icons of the host are added to the data directories, the ones of the image stay in front
+pub fn host_theme(home: &str, gtk_theme: Option<&str>) -> (Vec<(String, String)>, Vec<String>) {
+    let exists = |path: &String| Path::new(path).exists();
+    let mut mounts: Vec<(String, String)> = [
+        "/usr/share/fonts".to_string(),
+        "/var/cache/fontconfig".to_string(),
+        format!("{}/.local/share/fonts", home),
+        format!("{}/.fonts", home),
+        format!("{}/.cache/fontconfig", home),
+        format!("{}/.config/gtk-3.0/settings.ini", home),
+        format!("{}/.themes", home),
+        format!("{}/.icons", home),
+    ]
+    .iter()
+    .filter(|path| exists(path))
+    .map(|path| (path.to_owned(), path.to_owned()))
+    .collect();
+    let mut envs = vec![];
+
+    for dir in ["themes", "icons"] {
+        let path = format!("/usr/share/{}", dir);
+
+        if exists(&path) {
+            mounts.push((path, format!("{}/{}", HOST_SHARE, dir)));
+        }
+    }
+
+    if !mounts.is_empty() {
+        envs.push(format!(
+            "XDG_DATA_DIRS=/usr/local/share:/usr/share:{}",
+            HOST_SHARE
+        ));
+    }
+
+    // A theme missing in the container would leave the program unstyled, GTK picks its
+    // default one without the variable
+    if let Some(theme) = gtk_theme {
+        let name = theme.split(':').next().unwrap_or(theme);
+
+        match [
+            format!("/usr/share/themes/{}", name),
+            format!("{}/.themes/{}", home, name),
+        ]
+        .iter()
+        .any(exists)
+        {
+            true => envs.push(format!("GTK_THEME={}", theme)),
+            false => warn!("GTK theme {} isn't found, the default one is used", theme),
+        }
+    }
+
+    (mounts, envs)
+}
+
+/// Tags `docker load` reports as `Loaded image: <tag>`, images without a tag are skipped
+fn parse_loaded(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Loaded image: "))
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+/// Waits for `docker build` to exit, kills it once the build is cancelled
+fn wait_build(child: &mut Child, cancel: &Cancel) -> AppResult<std::process::ExitStatus> {
+    loop {
+        if let Some(status) = child
+            .try_wait()
+            .map_err(|err| AppError::docker_build("Can't wait for docker build", err))?
+        {
+            return Ok(status);
+        }
+
+        if cancel.is_cancelled() {
+            child.kill().unwrap_or(());
+            child.wait().ok();
+
+            return Err(AppError::DockerBuild(
+                "The build was cancelled".to_string(),
+                None,
+            ));
+        }
 
-        Ok(self)
+        std::thread::sleep(Duration::from_millis(100));
     }
 }
 
-trait PushArgument<T: Into<String>> {
-    fn push_volume(&mut self, v: T) -> &mut Self;
-    fn push_env(&mut self, v: T) -> &mut Self;
+/// Reports build steps found in the output, the rest is logged. Returns the last lines, they
+/// explain a failed build
+fn report_steps<R: Read + Send + 'static>(
+    output: R,
+    reporter: &Reporter,
+) -> std::thread::JoinHandle<Vec<String>> {
+    let reporter = reporter.to_owned();
+
+    std::thread::spawn(move || {
+        let mut tail = VecDeque::with_capacity(OUTPUT_TAIL);
+
+        for line in BufReader::new(output).lines().map_while(Result::ok) {
+            match parse_build_step(&line) {
+                Some(event) => reporter.emit(event),
+                None => debug!("Docker output: {}", line),
+            }
+
+            if tail.len() == OUTPUT_TAIL {
+                tail.pop_front();
+            }
+
+            tail.push_back(line);
+        }
+
+        tail.into()
+    })
+}
+
+/// Whether the container was started for a program of the prefix, see `PREFIX_LABEL`
+fn is_of_prefix(container: &Container, prefix: &str) -> bool {
+    container
+        .labels
+        .get(PREFIX_LABEL)
+        .is_none_or(|label| label == prefix)
+}
+
+fn classify_image(image: Image, prefix: &str) -> Option<ManagedImage> {
+    let labels = image.labels.unwrap_or_default();
+    let tags: Vec<String> = image
+        .repo_tags
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|tag| tag != "<none>:<none>")
+        .collect();
+
+    if labels
+        .get(PREFIX_LABEL)
+        .is_some_and(|label| label != prefix)
+    {
+        return None;
+    }
+
+    if labels.contains_key(MANAGED_LABEL) {
+        return Some(ManagedImage {
+            id: image.id,
+            tags,
+            program: labels.get(PROGRAM_LABEL).cloned(),
+            size: image.virtual_size,
+            labeled: true,
+            created_from: labels.get(CREATED_FROM_LABEL).cloned(),
+            prefix: labels.get(PREFIX_LABEL).cloned(),
+        });
+    }
+
+    let base = format!("{}:", super::util::gen_base_repository(prefix));
+    let program = format!("{}_", prefix);
+
+    if tags.iter().any(|tag| tag.starts_with(&base)) {
+        return Some(ManagedImage {
+            id: image.id,
+            tags,
+            program: None,
+            size: image.virtual_size,
+            labeled: false,
+            created_from: None,
+            prefix: None,
+        });
+    }
+
+    let name = tags
+        .iter()
+        .find_map(|tag| tag.strip_prefix(&program))
+        .map(|name| name.trim_end_matches(":latest").to_string())?;
+
+    Some(ManagedImage {
+        id: image.id,
+        tags,
+        program: Some(name),
+        size: image.virtual_size,
+        labeled: false,
+        created_from: None,
+        prefix: None,
+    })
 }
 
-impl<'a> PushArgument<&'a str> for Vec<&'a str> {
-    fn push_volume(&mut self, v: &'a str) -> &mut Self {
-        self.push("-v");
-        self.push(v);
-        self
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_image(tags: &[&str], labels: serde_json::Value) -> Image {
+        serde_json::from_value(serde_json::json!({
+            "Created": 0,
+            "Id": "sha256:foo",
+            "ParentId": "",
+            "Labels": labels,
+            "RepoTags": tags,
+            "RepoDigests": null,
+            "VirtualSize": 0
+        }))
+        .unwrap()
     }
 
-    fn push_env(&mut self, v: &'a str) -> &mut Self {
-        self.push("--env");
-        self.push(v);
-        self
+    #[test]
+    fn test_classify_image() {
+        let labeled = classify_image(
+            get_image(
+                &["renamed:latest"],
+                serde_json::json!({ MANAGED_LABEL: "true", PROGRAM_LABEL: "foo" }),
+            ),
+            "debian_bridge",
+        )
+        .unwrap();
+
+        assert_eq!(labeled.program, Some("foo".to_string()));
+        assert!(labeled.labeled);
+
+        let profile =
+            serde_json::json!({ MANAGED_LABEL: "true", PREFIX_LABEL: "debian_bridge.work" });
+
+        assert!(classify_image(
+            get_image(&["debian_bridge.work_foo:latest"], profile.to_owned()),
+            "debian_bridge"
+        )
+        .is_none());
+        assert!(classify_image(
+            get_image(&["debian_bridge.work_foo:latest"], profile),
+            "debian_bridge.work"
+        )
+        .is_some());
+
+        let base = classify_image(
+            get_image(&["debian_bridge_base:0123"], serde_json::Value::Null),
+            "debian_bridge",
+        )
+        .unwrap();
+
+        assert!(base.program.is_none());
+
+        let legacy = classify_image(
+            get_image(&["debian_bridge_bar:latest"], serde_json::Value::Null),
+            "debian_bridge",
+        )
+        .unwrap();
+
+        assert_eq!(legacy.program, Some("bar".to_string()));
+        assert!(!legacy.labeled);
+        assert!(classify_image(
+            get_image(&["debian:9-slim"], serde_json::Value::Null),
+            "debian_bridge"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_parse_loaded() {
+        let output = "Loaded image: debian_bridge_foo:latest\nLoaded image ID: sha256:abc\n";
+
+        assert_eq!(parse_loaded(output), vec!["debian_bridge_foo:latest"]);
+    }
+
+    #[test]
+    fn test_host_theme() {
+        let home =
+            std::env::temp_dir().join(format!("debian_bridge_test_theme_{}", std::process::id()));
+        let home_str = home.to_string_lossy().to_string();
+
+        std::fs::create_dir_all(home.join(".fonts")).unwrap();
+        std::fs::create_dir_all(home.join(".themes/Foo")).unwrap();
+
+        let (mounts, envs) = host_theme(&home_str, Some("Foo:dark"));
+
+        let fonts = format!("{}/.fonts", home_str);
+
+        assert!(mounts.contains(&(fonts.to_owned(), fonts)));
+        assert!(!mounts.iter().any(|(source, _)| source.contains(".icons")));
+        assert!(envs.contains(&"GTK_THEME=Foo:dark".to_string()));
+        assert!(envs.iter().any(|env| env.starts_with("XDG_DATA_DIRS=")));
+
+        let (_, envs) = host_theme(&home_str, Some("Missing"));
+
+        assert!(!envs.iter().any(|env| env.starts_with("GTK_THEME=")));
+
+        std::fs::remove_dir_all(&home).unwrap();
     }
 }
@@ -0,0 +1,592 @@
+use super::{
+    backend::Backend,
+    config::{Feature, Seccomp},
+    context::CONTEXT_PREFIX,
+    error::AppError,
+    util, Config,
+};
+use crate::System;
+#[cfg(test)]
+use mocktopus::macros::*;
+use serde::Serialize;
+use shiplift::Docker;
+use std::{
+    error::Error,
+    ffi::OsStr,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tokio::{prelude::Future, runtime::Runtime};
+
+/// Files left in the cache by an interrupted build of older versions
+const LEFTOVERS: &[&str] = &["tmp.deb", "Dockerfile", "base"];
+
+/// Result of a single diagnostic check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub details: String,
+    /// What to do about a failed check
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub(super) fn pass<T: Into<String>, S: Into<String>>(name: T, details: S) -> Self {
+        Check {
+            name: name.into(),
+            passed: true,
+            details: details.into(),
+            fix: None,
+        }
+    }
+
+    pub(super) fn fail<T: Into<String>, S: Into<String>, U: Into<String>>(
+        name: T,
+        details: S,
+        fix: U,
+    ) -> Self {
+        Check {
+            name: name.into(),
+            passed: false,
+            details: details.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "\t[{}] {}: {}",
+            match self.passed {
+                true => "pass",
+                false => "fail",
+            },
+            self.name,
+            self.details
+        )?;
+
+        if let Some(fix) = &self.fix {
+            write!(f, "\n\t       {}", fix)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks whether docker daemon responds. Usable without an `App`, as it can't be created
+/// when the daemon is unreachable
+pub fn check_docker(docker: &Docker) -> Check {
+    let mut rt = Runtime::new().unwrap();
+    let result = rt.block_on(docker.ping());
+
+    rt.shutdown_now().wait().unwrap_or(());
+
+    check_ping(
+        result
+            .map(|_| ())
+            .map_err(|err| AppError::DockerConnection(Some(err.into()))),
+    )
+}
+
+fn check_ping(result: Result<(), AppError>) -> Check {
+    match result {
+        Ok(_) => Check::pass("Docker daemon", "reachable"),
+        // The source explains more than the generic message of the connection error
+        Err(err) => Check::fail(
+            "Docker daemon",
+            format!(
+                "unreachable: {}",
+                err.source()
+                    .map(|source| source.to_string())
+                    .unwrap_or(err.to_string())
+            ),
+            "Start the daemon with `sudo systemctl start docker` or set DOCKER_HOST",
+        ),
+    }
+}
+
+pub(super) fn run(
+    backend: &dyn Backend,
+    config: &Config,
+    prefix: &str,
+    cache: &Path,
+) -> Vec<Check> {
+    vec![
+        check_ping(backend.ping()),
+        check_docker_mode(backend.system()),
+        match backend.system().rootless {
+            true => Check::pass("Docker group", "not required by a rootless daemon"),
+            false => check_docker_group(),
+        },
+        check_apparmor(backend.system(), config),
+    ]
+    .into_iter()
+    .chain(match backend.system().selinux {
+        true => Some(check_selinux(config)),
+        false => None,
+    })
+    .chain(vec![
+        check_display_socket(),
+        check_sound_socket(),
+        check_applications_dir(),
+        check_bin_dir(
+            util::user_bin_dir().ok(),
+            &std::env::var_os("PATH").unwrap_or_default(),
+        ),
+        check_writable("Cache directory", cache),
+        check_leftovers(cache),
+        check_images(backend, config, prefix),
+        check_packages(config),
+    ])
+    .collect()
+}
+
+fn check_docker_mode(system: &System) -> Check {
+    match system.rootless {
+        true => Check::pass(
+            "Docker mode",
+            "rootless, programs run as your user, devices are accessible only if you can access \
+             them",
+        ),
+        false => Check::pass("Docker mode", "rootful"),
+    }
+}
+
+fn check_apparmor(system: &System, config: &Config) -> Check {
+    let name = "AppArmor";
+    let ignored: Vec<String> = config
+        .programs
+        .iter()
+        .filter(|program| program.security.apparmor.is_some())
+        .map(|program| program.get_name_short())
+        .collect();
+
+    match (system.apparmor, ignored.is_empty()) {
+        (true, _) => Check::pass(name, "enabled, profiles of the programs are applied"),
+        (false, true) => Check::pass(name, "disabled, no program uses a profile"),
+        (false, false) => Check::fail(
+            name,
+            format!("disabled, profiles of {} are ignored", ignored.join(", ")),
+            "Enable AppArmor on the host and restart the docker daemon, or remove the profiles \
+             with `debian_bridge edit`",
+        ),
+    }
+}
+
+/// Programs reaching sockets of the host or the home folder, SELinux denies them to confined
+/// containers. Shared folders of the user are relabeled, see `share_mount`
+fn check_selinux(config: &Config) -> Check {
+    let name = "SELinux";
+    let sockets = [
+        Feature::Sound,
+        Feature::HomePersistent,
+        Feature::Notification,
+        Feature::InputMethod,
+        Feature::Keyring,
+        Feature::Bluetooth,
+        Feature::Mdns,
+        Feature::OpenUrls,
+        Feature::Portals,
+    ];
+    let denied: Vec<String> = config
+        .programs
+        .iter()
+        .filter(|program| program.security.seccomp != Seccomp::Unconfined)
+        .filter(|program| {
+            (program.uses(&Feature::Display) && program.remote_display.is_none())
+                || sockets
+                    .iter()
+                    .any(|feature| program.settings.contains(feature))
+        })
+        .map(|program| program.get_name_short())
+        .collect();
+
+    match denied.is_empty() {
+        true => Check::pass(
+            name,
+            "enabled, no program shares host sockets or the home folder",
+        ),
+        false => Check::fail(
+            name,
+            format!(
+                "enabled, {} may be denied the sockets of the host or the home folder",
+                denied.join(", ")
+            ),
+            "Run `debian_bridge edit <name> --security unconfined` if the program fails to \
+             connect, its container isn't labeled then",
+        ),
+    }
+}
+
+fn check_docker_group() -> Check {
+    let name = "Docker group";
+
+    match get_groups() {
+        Some(groups) if groups.iter().any(|g| g == "docker" || g == "root") => {
+            Check::pass(name, "current user is in the docker group")
+        }
+        Some(_) => Check::fail(
+            name,
+            "current user is not in the docker group",
+            "Run `sudo usermod -aG docker $USER` and log in again",
+        ),
+        None => Check::fail(
+            name,
+            "can't get groups of the current user",
+            "Make sure `id` command is available",
+        ),
+    }
+}
+
+fn check_display_socket() -> Check {
+    let name = "Display socket";
+
+    match get_display_socket() {
+        Some(path) if path.exists() => Check::pass(name, path.display().to_string()),
+        Some(path) => Check::fail(
+            name,
+            format!("{} doesn't exist", path.display()),
+            "Make sure you run debian_bridge from a graphical session",
+        ),
+        None => Check::fail(
+            name,
+            "neither DISPLAY nor WAYLAND_DISPLAY is set",
+            "Run debian_bridge from a graphical session or don't use the display feature",
+        ),
+    }
+}
+
+fn check_sound_socket() -> Check {
+    let name = "Sound socket";
+
+    match get_sound_sockets().into_iter().find(|path| path.exists()) {
+        Some(path) => Check::pass(name, path.display().to_string()),
+        None => Check::fail(
+            name,
+            "neither PulseAudio nor PipeWire socket found",
+            "Start PulseAudio/PipeWire for your session or don't use the sound feature",
+        ),
+    }
+}
+
+fn check_applications_dir() -> Check {
+    match dirs::data_dir() {
+        Some(mut path) => {
+            path.push("applications");
+            check_writable("Applications directory", &path)
+        }
+        None => Check::fail(
+            "Applications directory",
+            "can't determine a data directory",
+            "Set HOME or XDG_DATA_HOME environment variable",
+        ),
+    }
+}
+
+/// Wrappers of programs are written to the executables directory, see `App::install_wrapper`
+fn check_bin_dir(dir: Option<PathBuf>, path: &OsStr) -> Check {
+    let name = "Executables directory";
+
+    match dir {
+        Some(dir) if std::env::split_paths(path).any(|entry| entry == dir) => {
+            Check::pass(name, format!("{} is on PATH", dir.display()))
+        }
+        Some(dir) => Check::fail(
+            name,
+            format!(
+                "{} isn't on PATH, wrappers of programs aren't found",
+                dir.display()
+            ),
+            format!(
+                "Add it to PATH, e.g. with export PATH=\"{}:$PATH\" in ~/.profile",
+                dir.display()
+            ),
+        ),
+        None => Check::fail(
+            name,
+            "can't determine the executables directory",
+            "Set HOME or XDG_BIN_HOME environment variable",
+        ),
+    }
+}
+
+fn check_writable(name: &str, path: &Path) -> Check {
+    match is_writable(path) {
+        true => Check::pass(name, format!("{} is writable", path.display())),
+        false => Check::fail(
+            name,
+            format!("{} is not writable", path.display()),
+            format!("Check permissions of {}", path.display()),
+        ),
+    }
+}
+
+fn check_leftovers(cache: &Path) -> Check {
+    let name = "Leftover files";
+    let leftovers = get_leftovers(cache);
+
+    match leftovers.is_empty() {
+        true => Check::pass(name, "cache is clean"),
+        false => Check::fail(
+            name,
+            format!(
+                "{} left by an interrupted build",
+                leftovers
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            "Remove them manually, no build should be running",
+        ),
+    }
+}
+
+fn check_images(backend: &dyn Backend, config: &Config, prefix: &str) -> Check {
+    let name = "Program images";
+    let mut missing = vec![];
+
+    for program in &config.programs {
+        match backend.image_exists(&program.get_name(prefix)) {
+            Ok(true) => (),
+            Ok(false) => missing.push(program.get_name_short()),
+            Err(err) => {
+                return Check::fail(
+                    name,
+                    format!("can't list images: {}", err),
+                    "Make sure docker daemon is reachable",
+                )
+            }
+        }
+    }
+
+    match missing.is_empty() {
+        true => Check::pass(name, "all images exist"),
+        false => Check::fail(
+            name,
+            format!("missing images of {}", missing.join(", ")),
+            "Run `debian_bridge repair --all`",
+        ),
+    }
+}
+
+fn check_packages(config: &Config) -> Check {
+    let name = "Source packages";
+    let missing: Vec<String> = config
+        .programs
+        .iter()
+        .filter(|program| !program.path.exists())
+        .map(|program| format!("{} ({})", program.get_name_short(), program.path.display()))
+        .collect();
+
+    match missing.is_empty() {
+        true => Check::pass(name, "all packages exist"),
+        false => Check::fail(
+            name,
+            format!("missing packages of {}", missing.join(", ")),
+            "These programs can't be repaired, restore the packages or recreate the programs",
+        ),
+    }
+}
+
+#[cfg_attr(test, mockable)]
+fn get_groups() -> Option<Vec<String>> {
+    let output = Command::new("id").arg("-nG").output().ok()?;
+
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(|group| group.to_string())
+            .collect(),
+    )
+}
+
+fn get_display_socket() -> Option<PathBuf> {
+    if let Some(wayland) = std::env::var_os("WAYLAND_DISPLAY") {
+        let mut path = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?);
+        path.push(wayland);
+        return Some(path);
+    }
+
+    let display = std::env::var("DISPLAY").ok()?;
+    let number = display
+        .rsplit(':')
+        .next()?
+        .split('.')
+        .next()
+        .unwrap_or_default();
+
+    Some(PathBuf::from(format!("/tmp/.X11-unix/X{}", number)))
+}
+
+fn get_sound_sockets() -> Vec<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) => vec![
+            Path::new(&runtime).join("pulse/native"),
+            Path::new(&runtime).join("pipewire-0"),
+        ],
+        None => vec![],
+    }
+}
+
+/// Checks a directory can be written to, or created if it doesn't exist yet
+fn is_writable(path: &Path) -> bool {
+    if !path.exists() {
+        return path.parent().is_some_and(is_writable);
+    }
+
+    let probe = path.join(".debian_bridge_probe");
+
+    std::fs::write(&probe, b"")
+        .and_then(|_| std::fs::remove_file(&probe))
+        .is_ok()
+}
+
+/// Build contexts and files of interrupted builds left in the cache
+pub fn get_leftovers(cache: &Path) -> Vec<PathBuf> {
+    let contexts = std::fs::read_dir(cache)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(CONTEXT_PREFIX))
+                })
+                .collect::<Vec<PathBuf>>()
+        })
+        .unwrap_or_default();
+
+    LEFTOVERS
+        .iter()
+        .map(|name| cache.join(name))
+        .filter(|path| path.exists())
+        .chain(contexts)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use mocktopus::mocking::{MockResult, Mockable};
+
+    #[test]
+    fn test_check_docker_group() {
+        get_groups.mock_safe(|| MockResult::Return(Some(vec!["user".into(), "docker".into()])));
+        assert!(check_docker_group().passed);
+
+        get_groups.mock_safe(|| MockResult::Return(Some(vec!["user".into()])));
+        assert!(!check_docker_group().passed);
+        assert!(check_docker_group().fix.is_some());
+    }
+
+    #[test]
+    fn test_check_apparmor() {
+        let mut program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        );
+        let mut config = Config::default();
+        let system = System::offline();
+
+        config.push(&program).unwrap();
+        assert!(check_apparmor(&system, &config).passed);
+
+        program.security.apparmor = Some("desktop".to_string());
+        config.update(&program).unwrap();
+        assert!(!check_apparmor(&system, &config).passed);
+
+        let system = System {
+            apparmor: true,
+            ..system
+        };
+
+        assert!(check_apparmor(&system, &config).passed);
+    }
+
+    #[test]
+    fn test_check_selinux() {
+        let mut program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        );
+        let mut config = Config::default();
+
+        config.push(&program).unwrap();
+        assert!(check_selinux(&config).passed);
+
+        program.settings = vec![Feature::Display, Feature::Sound];
+        config.update(&program).unwrap();
+
+        let check = check_selinux(&config);
+
+        assert!(!check.passed);
+        assert!(check.details.contains("foo"));
+
+        program.security.seccomp = Seccomp::Unconfined;
+        config.update(&program).unwrap();
+        assert!(check_selinux(&config).passed);
+    }
+
+    #[test]
+    fn test_check_leftovers() {
+        let cache = std::env::temp_dir().join(format!(
+            "debian_bridge_test_leftovers_{}",
+            std::process::id()
+        ));
+
+        std::fs::create_dir_all(&cache).unwrap();
+        assert!(check_leftovers(&cache).passed);
+
+        std::fs::write(cache.join("tmp.deb"), b"").unwrap();
+        std::fs::create_dir(cache.join("build-1-2-3")).unwrap();
+
+        let check = check_leftovers(&cache);
+
+        std::fs::remove_dir_all(&cache).unwrap();
+        assert!(!check.passed);
+        assert!(check.details.contains("tmp.deb"));
+        assert!(check.details.contains("build-1-2-3"));
+    }
+
+    #[test]
+    fn test_check_bin_dir() {
+        let dir = PathBuf::from("/home/user/.local/bin");
+
+        assert!(
+            check_bin_dir(
+                Some(dir.to_owned()),
+                OsStr::new("/usr/bin:/home/user/.local/bin")
+            )
+            .passed
+        );
+
+        let check = check_bin_dir(Some(dir), OsStr::new("/usr/bin:/bin"));
+
+        assert!(!check.passed);
+        assert!(check.fix.unwrap().contains("/home/user/.local/bin:$PATH"));
+        assert!(!check_bin_dir(None, OsStr::new("/usr/bin")).passed);
+    }
+
+    #[test]
+    fn test_is_writable() {
+        let dir = std::env::temp_dir();
+
+        assert!(is_writable(&dir));
+        assert!(is_writable(&dir.join("debian_bridge_missing/nested")));
+    }
+}
@@ -1,34 +1,229 @@
-#[derive(Debug, Clone)]
+use super::network::NetworkStep;
+use crate::sys::error::SystemError;
+use std::error::Error;
+
+type Source = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum AppError {
-    Docker,
-    DockerStatus(i16),
-    File(String),
+    /// Docker daemon can't be reached
+    DockerConnection(Option<Source>),
+    /// Docker API responded with an error status while doing `operation`
+    DockerStatus {
+        code: u16,
+        message: String,
+        operation: String,
+    },
+    /// Image can't be built or tagged, the source is the error of the docker CLI if any
+    DockerBuild(String, Option<Source>),
+    /// Container can't be started or managed, the source is the error of the docker CLI if any
+    DockerRun(String, Option<Source>),
+    /// Config file can't be read, parsed or written, or contains invalid settings
+    Config(String, Option<Source>),
+    /// Package can't be read or parsed
+    Deb(String, Option<Source>),
+    DesktopEntry(String, Option<Source>),
+    /// Requested feature isn't supported by the system
+    Feature(String),
+    /// Program is missing or conflicts with an existing one
     Program(String),
+    /// Value given by the user is malformed, e.g. a size or a device pattern
+    Argument(String),
+    File(std::io::Error),
+    Locked,
+    /// Directory or variable required from the environment isn't set
+    Environment(String),
+    /// `operation` needs the network, which is off or unreachable, for `steps`
+    Offline {
+        operation: String,
+        steps: Vec<NetworkStep>,
+    },
 }
 
-impl std::error::Error for AppError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+impl AppError {
+    /// Wraps an error of a docker API request, `operation` describes what was requested
+    pub fn docker<T: Into<String>>(operation: T, err: shiplift::Error) -> Self {
+        match err {
+            shiplift::Error::Fault { code, message } => AppError::DockerStatus {
+                code: code.as_u16(),
+                message: message.trim().to_string(),
+                operation: operation.into(),
+            },
+            err => AppError::DockerConnection(Some(err.into())),
+        }
+    }
+
+    pub fn docker_build<T: Into<String>, E: Into<Source>>(message: T, err: E) -> Self {
+        AppError::DockerBuild(message.into(), Some(err.into()))
+    }
+
+    pub fn docker_run<T: Into<String>, E: Into<Source>>(message: T, err: E) -> Self {
+        AppError::DockerRun(message.into(), Some(err.into()))
+    }
+
+    pub fn config<T: Into<String>, E: Into<Source>>(message: T, err: E) -> Self {
+        AppError::Config(message.into(), Some(err.into()))
+    }
+
+    pub fn deb<T: Into<String>, E: Into<Source>>(message: T, err: E) -> Self {
+        AppError::Deb(message.into(), Some(err.into()))
+    }
+
+    pub fn desktop_entry<T: Into<String>, E: Into<Source>>(message: T, err: E) -> Self {
+        AppError::DesktopEntry(message.into(), Some(err.into()))
+    }
+
+    /// Stable name of the variant for scripts, unlike the message it never changes
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::DockerConnection(_) => "docker_connection",
+            AppError::DockerStatus { .. } => "docker_status",
+            AppError::DockerBuild(_, _) => "docker_build",
+            AppError::DockerRun(_, _) => "docker_run",
+            AppError::Config(_, _) => "config",
+            AppError::Deb(_, _) => "package",
+            AppError::DesktopEntry(_, _) => "desktop_entry",
+            AppError::Feature(_) => "feature",
+            AppError::Program(_) => "program",
+            AppError::Argument(_) => "argument",
+            AppError::File(_) => "file",
+            AppError::Locked => "locked",
+            AppError::Environment(_) => "environment",
+            AppError::Offline { .. } => "offline",
+        }
+    }
+
+    /// Whether docker or the host is at fault rather than the request, retrying or fixing the
+    /// environment may help
+    pub fn is_environment(&self) -> bool {
+        matches!(
+            self,
+            AppError::DockerConnection(_)
+                | AppError::DockerStatus { .. }
+                | AppError::DockerBuild(_, _)
+                | AppError::DockerRun(_, _)
+                | AppError::Locked
+                | AppError::Environment(_)
+                | AppError::Offline { .. }
+        )
+    }
+}
+
+impl Error for AppError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppError::DockerConnection(Some(err))
+            | AppError::DockerBuild(_, Some(err))
+            | AppError::DockerRun(_, Some(err))
+            | AppError::Config(_, Some(err))
+            | AppError::Deb(_, Some(err))
+            | AppError::DesktopEntry(_, Some(err)) => Some(err.as_ref()),
+            AppError::File(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
 impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                AppError::Docker => "Cannot connect to docker daemon",
-                AppError::DockerStatus(error) => {
-                    Box::leak(format!("Docker returned code: {}", error).into_boxed_str())
-                }
-                AppError::File(error) => {
-                    Box::leak(format!("IO errors occured: {}", error).into_boxed_str())
-                }
-                AppError::Program(error) => {
-                    Box::leak(format!("Program errors occured: {}", error).into_boxed_str())
+        match self {
+            AppError::DockerConnection(Some(err))
+                if err.to_string().to_lowercase().contains("permission denied") =>
+            {
+                write!(
+                    f,
+                    "Permission denied on docker socket, add yourself to the docker group with \
+                     `sudo usermod -aG docker $USER` and log in again"
+                )
+            }
+            AppError::DockerConnection(_) => write!(f, "Cannot connect to docker daemon"),
+            AppError::DockerStatus {
+                code,
+                message,
+                operation,
+            } => {
+                write!(
+                    f,
+                    "Docker failed to {} with code {}: {}",
+                    operation, code, message
+                )?;
+
+                match code {
+                    404 if message.starts_with("No such image") => {
+                        write!(f, ". The image is missing, run `debian_bridge repair`")
+                    }
+                    409 if message.contains("is already in use") => write!(
+                        f,
+                        ". A container with the same name exists, remove it with `docker rm`"
+                    ),
+                    _ => Ok(()),
                 }
             }
-        )
+            AppError::DockerBuild(error, _) => write!(f, "Failed to build an image: {}", error),
+            AppError::DockerRun(error, _) => write!(f, "Failed to run a container: {}", error),
+            AppError::Config(error, _) => write!(f, "Config errors occured: {}", error),
+            AppError::Deb(error, _) => write!(f, "Package errors occured: {}", error),
+            AppError::DesktopEntry(error, _) => {
+                write!(f, "Desktop entry errors occured: {}", error)
+            }
+            AppError::Feature(error) => write!(f, "Feature errors occured: {}", error),
+            AppError::Program(error) => write!(f, "Program errors occured: {}", error),
+            AppError::Argument(error) => write!(f, "Argument errors occured: {}", error),
+            AppError::File(error) => write!(f, "IO errors occured: {}", error),
+            AppError::Locked => write!(
+                f,
+                "Another debian_bridge instance is running, try again later"
+            ),
+            AppError::Environment(error) => write!(f, "Environment errors occured: {}", error),
+            AppError::Offline { operation, steps } => write!(
+                f,
+                "Can't {} offline, the network is needed to {}",
+                operation,
+                steps
+                    .iter()
+                    .map(NetworkStep::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }?;
+
+        match self.source() {
+            Some(source) if !matches!(self, AppError::File(_)) => write!(f, ": {}", source),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        AppError::File(err)
+    }
+}
+
+impl From<SystemError> for AppError {
+    fn from(err: SystemError) -> Self {
+        match err {
+            SystemError::DockerConnection => AppError::DockerConnection(None),
+            err => AppError::Environment(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::config("invalid JSON", err)
+    }
+}
+
+impl From<toml::de::Error> for AppError {
+    fn from(err: toml::de::Error) -> Self {
+        AppError::config("invalid TOML", err)
+    }
+}
+
+impl From<toml::ser::Error> for AppError {
+    fn from(err: toml::ser::Error) -> Self {
+        AppError::config("can't write TOML", err)
     }
 }
@@ -0,0 +1,105 @@
+use regex::Regex;
+use serde::Serialize;
+use std::{path::PathBuf, sync::mpsc::Sender};
+
+/// Progress of a long operation, sent to a frontend listening with `App::create_with_events`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event")]
+#[non_exhaustive]
+pub enum AppEvent {
+    DebParsed {
+        package: String,
+        version: Option<String>,
+    },
+    DockerfileGenerated {
+        dockerfile: String,
+    },
+    /// A build step of an image started. Steps of the base image come first if it's built
+    BuildStep {
+        step: u32,
+        total: u32,
+        detail: String,
+    },
+    ImageBuilt {
+        id: Option<String>,
+    },
+    EntryCreated {
+        path: PathBuf,
+    },
+    /// Bytes of an image written to or read from a tarball, `total` is the size of the image
+    ImageTransfer {
+        bytes: u64,
+        total: Option<u64>,
+    },
+}
+
+/// Sends events if a frontend listens, does nothing otherwise
+#[derive(Clone, Default)]
+pub struct Reporter(Option<Sender<AppEvent>>);
+
+impl Reporter {
+    pub fn new(tx: Sender<AppEvent>) -> Self {
+        Reporter(Some(tx))
+    }
+
+    pub fn is_listened(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// A frontend which stopped listening mustn't fail the operation, send errors are ignored
+    pub fn emit(&self, event: AppEvent) {
+        if let Some(tx) = &self.0 {
+            tx.send(event).unwrap_or(());
+        }
+    }
+}
+
+/// Parses a step header of `docker build` output: `Step 2/5 : RUN ...` of the legacy builder or
+/// `#6 [2/5] RUN ...` of BuildKit
+pub fn parse_build_step(line: &str) -> Option<AppEvent> {
+    let legacy = Regex::new(r"^Step (\d+)/(\d+) : (.*)$").unwrap();
+    let buildkit = Regex::new(r"^#\d+ \[(?:[^\]\s]+ )?(\d+)/(\d+)\] (.*)$").unwrap();
+    let line = line.trim_end();
+    let captures = legacy.captures(line).or_else(|| buildkit.captures(line))?;
+
+    Some(AppEvent::BuildStep {
+        step: captures[1].parse().ok()?,
+        total: captures[2].parse().ok()?,
+        detail: captures[3].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_build_step() {
+        assert_eq!(
+            parse_build_step("Step 2/5 : RUN apt-get update"),
+            Some(AppEvent::BuildStep {
+                step: 2,
+                total: 5,
+                detail: "RUN apt-get update".to_string()
+            })
+        );
+        assert_eq!(
+            parse_build_step("#6 [stage-1 3/4] COPY tmp.deb /tmp.deb\n"),
+            Some(AppEvent::BuildStep {
+                step: 3,
+                total: 4,
+                detail: "COPY tmp.deb /tmp.deb".to_string()
+            })
+        );
+        assert!(parse_build_step("#6 DONE 0.4s").is_none());
+        assert!(parse_build_step(" ---> Running in 0123456789ab").is_none());
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            serde_json::to_value(AppEvent::ImageBuilt { id: None }).unwrap(),
+            serde_json::json!({"event": "ImageBuilt", "id": null})
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use super::{error::AppError, Program};
+use std::process::{Command, Stdio};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Signals the run is shielded from, the terminal and `systemctl stop` send them to the whole
+/// process group so the container still gets them
+const SHIELDED: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+/// Runs a hook of the program in `sh` on the host. The output goes to the terminal, the error
+/// carries the standard error of a failed hook
+pub fn run(kind: &str, command: &str, program: &Program, exit_code: Option<i32>) -> AppResult<()> {
+    debug!("Running {} hook: {}", kind, command);
+
+    let mut cmd = Command::new("sh");
+
+    cmd.arg("-c")
+        .arg(command)
+        .env("DEBIAN_BRIDGE_PROGRAM", program.get_name_short())
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped());
+
+    if let Some(code) = exit_code {
+        cmd.env("DEBIAN_BRIDGE_EXIT_CODE", code.to_string());
+    }
+
+    let output = cmd
+        .output()
+        .map_err(|err| AppError::Program(format!("Can't run the {} hook: {}", kind, err)))?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(AppError::Program(format!(
+            "{} hook failed ({}): {}",
+            kind,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+/// Keeps the process alive while the container runs, so that the post-run hook runs after
+/// Ctrl-C as well. The signals are caught by a handler doing nothing rather than ignored:
+/// handlers are reset by exec, so docker still gets the default behaviour
+pub struct InterruptShield {
+    previous: Vec<(libc::c_int, libc::sighandler_t)>,
+}
+
+extern "C" fn ignore(_: libc::c_int) {}
+
+impl InterruptShield {
+    pub fn new() -> Self {
+        let previous = SHIELDED
+            .iter()
+            .map(|&signal| {
+                (signal, unsafe {
+                    libc::signal(
+                        signal,
+                        ignore as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                    )
+                })
+            })
+            .collect();
+
+        InterruptShield { previous }
+    }
+}
+
+impl Drop for InterruptShield {
+    fn drop(&mut self) {
+        for &(signal, handler) in &self.previous {
+            unsafe { libc::signal(signal, handler) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_run() {
+        let program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        );
+
+        assert!(run(
+            "pre-run",
+            "test \"$DEBIAN_BRIDGE_PROGRAM\" = foo",
+            &program,
+            None
+        )
+        .is_ok());
+        assert!(run(
+            "post-run",
+            "test \"$DEBIAN_BRIDGE_EXIT_CODE\" = 3",
+            &program,
+            Some(3)
+        )
+        .is_ok());
+
+        match run("pre-run", "echo 'no route' >&2; exit 1", &program, None) {
+            Err(AppError::Program(message)) => assert!(message.ends_with(": no route")),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use super::{error::AppError, util::icons_dir, AppResult, Icon, Program};
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, ImageFormat, RgbaImage,
+};
+use std::{
+    ffi::OsStr,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+/// Written out by `Icon::try_default` users, see `install_default`
+const DEFAULT_ICON: &[u8] = include_bytes!("../../resources/default.png");
+/// Sizes of the hicolor theme PNG icons are converted to, launchers don't show them bigger
+const SIZES: &[u32] = &[48, 64, 128, 256];
+
+/// Copies the icon into the managed directory, so the desktop entry doesn't depend on the
+/// original file. ICO and PNG files are converted to square PNG files of a standard size, see
+/// `to_png`. Sized icons go to `<size>/<program>.png` like in the hicolor theme, SVG
+/// ones to `scalable/<program>.svg`. Returns the path of the copy
+pub fn install(source: &Path, program: &str) -> AppResult<PathBuf> {
+    if is_managed(source) {
+        return Ok(source.to_owned());
+    }
+
+    let data = std::fs::read(source).map_err(|err| {
+        AppError::desktop_entry(format!("Can not read icon {}", source.display()), err)
+    })?;
+    let extension = source
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase);
+    let (dir, extension, data) = match image::guess_format(&data) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Ico)) => {
+            let (png, size) = to_png(data, format)?;
+
+            // Directory of the icon in a hicolor like theme, e.g. `48x48`
+            (Some(format!("{}x{}", size, size)), "png".to_string(), png)
+        }
+        _ if extension.as_deref() == Some("svg") || is_svg(&data) => {
+            (Some("scalable".to_string()), "svg".to_string(), data)
+        }
+        _ => {
+            // XPM, JPEG and so on are copied as is, a launcher may still render them
+            let extension = extension.ok_or(AppError::DesktopEntry(
+                format!("Format of icon {} isn't recognized", source.display()),
+                None,
+            ))?;
+
+            (None, extension, data)
+        }
+    };
+    let mut path = icons_dir()?;
+
+    path.extend(dir);
+    std::fs::create_dir_all(&path)
+        .map_err(|err| AppError::desktop_entry("Can not create a directory for icons", err))?;
+    path.push(format!("{}.{}", program, extension));
+
+    std::fs::write(&path, data)
+        .map_err(|err| AppError::desktop_entry("Can not write an icon", err))?;
+
+    Ok(path)
+}
+
+/// Icon of the program copied by `install`, the original one if it can't be copied. The default
+/// icon is shared by programs, it's written out once instead. `None` if it can't be written
+pub fn manage(icon: &Icon, program: &Program) -> Option<Icon> {
+    if icon.is_default() {
+        return match install_default(&icon.path) {
+            Ok(_) => Some(icon.to_owned()),
+            Err(err) => {
+                warn!("Program is left without an icon: {}", err);
+                None
+            }
+        };
+    }
+
+    match install(&icon.path, &program.get_image_name()) {
+        Ok(path) => Some(Icon::new(&path)),
+        Err(err) => {
+            warn!("Icon {} is used in place: {}", icon.path.display(), err);
+            Some(icon.to_owned())
+        }
+    }
+}
+
+/// Writes the icon shipped with debian_bridge to `path` unless it's there already
+pub fn install_default(path: &Path) -> AppResult<()> {
+    if path.exists() {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| {
+            AppError::desktop_entry("Can not create a directory for the default icon", err)
+        })?;
+    }
+
+    std::fs::write(path, DEFAULT_ICON)
+        .map_err(|err| AppError::desktop_entry("Can not write the default icon", err))
+}
+
+/// Whether the icon is a copy made by `install`
+pub fn is_managed(path: &Path) -> bool {
+    icons_dir().is_ok_and(|dir| path.starts_with(dir))
+}
+
+/// Deletes the icon if it's a copy made by `install`, returns whether it was deleted
+pub fn remove(path: &Path) -> AppResult<bool> {
+    if !is_managed(path) {
+        return Ok(false);
+    }
+
+    match std::fs::remove_file(path) {
+        Ok(_) => {
+            // The size directory is left if other programs have icons there
+            if let Some(dir) = path.parent() {
+                std::fs::remove_dir(dir).unwrap_or(());
+            }
+
+            Ok(true)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn is_svg(data: &[u8]) -> bool {
+    String::from_utf8_lossy(&data[..data.len().min(1024)]).contains("<svg")
+}
+
+/// Converts an ICO file, its largest image, or a PNG file to a square PNG file of a standard
+/// size: the smallest of `SIZES` the icon fits in, the largest one for larger icons. The icon is
+/// scaled to fit and centered on a transparent canvas. A PNG file of a standard size is kept as
+/// is. Returns the PNG file with its size
+fn to_png(data: Vec<u8>, format: ImageFormat) -> AppResult<(Vec<u8>, u32)> {
+    let icon = decode(&data, format)?;
+    let larger = icon.width().max(icon.height());
+    let size = SIZES
+        .iter()
+        .copied()
+        .find(|size| *size >= larger)
+        .unwrap_or(SIZES[SIZES.len() - 1]);
+
+    if format == ImageFormat::Png && icon.width() == size && icon.height() == size {
+        return Ok((data, size));
+    }
+
+    let icon = match larger == size {
+        true => icon,
+        false => icon.resize(size, size, FilterType::Triangle),
+    };
+    let mut canvas = RgbaImage::new(size, size);
+
+    imageops::replace(
+        &mut canvas,
+        &icon.to_rgba8(),
+        ((size - icon.width()) / 2).into(),
+        ((size - icon.height()) / 2).into(),
+    );
+
+    let mut png = vec![];
+
+    DynamicImage::from(canvas)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| AppError::desktop_entry("Icon can not be converted to PNG", err))?;
+
+    Ok((png, size))
+}
+
+fn decode(data: &[u8], format: ImageFormat) -> AppResult<DynamicImage> {
+    image::load_from_memory_with_format(data, format)
+        .map_err(|err| AppError::desktop_entry("Icon can not be decoded", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let pixels = RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x % 256) as u8, (y % 256) as u8, 7, 255])
+        });
+        let mut png = vec![];
+
+        DynamicImage::from(pixels)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn pixels(png: &[u8]) -> RgbaImage {
+        decode(png, ImageFormat::Png).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn test_to_png() {
+        let standard = gradient(48, 48);
+
+        assert_eq!(
+            to_png(standard.to_owned(), ImageFormat::Png).unwrap(),
+            (standard, 48)
+        );
+
+        // Larger icons are scaled down to the largest size, the short side is padded
+        let (png, size) = to_png(gradient(1024, 512), ImageFormat::Png).unwrap();
+        let large = pixels(&png);
+
+        assert_eq!(size, 256);
+        assert_eq!(large.dimensions(), (256, 256));
+        assert_eq!(large.get_pixel(128, 0)[3], 0);
+        assert_eq!(large.get_pixel(128, 128)[3], 255);
+        assert_eq!(large.get_pixel(128, 255)[3], 0);
+
+        // Odd sizes are scaled to the next standard one
+        let (png, size) = to_png(gradient(100, 100), ImageFormat::Png).unwrap();
+
+        assert_eq!(size, 128);
+        assert_eq!(pixels(&png).dimensions(), (128, 128));
+    }
+
+    #[test]
+    fn test_default_icon() {
+        let icon = decode(DEFAULT_ICON, ImageFormat::Png).unwrap().to_rgba8();
+
+        assert_eq!(icon.dimensions(), (256, 256));
+        assert!(icon.pixels().any(|pixel| pixel[3] > 0));
+    }
+
+    #[test]
+    fn test_ico_to_png() {
+        // An ICO with a single PNG image of 256 pixels
+        let mut ico = vec![0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0, 32, 0];
+
+        ico.extend_from_slice(&(DEFAULT_ICON.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&22u32.to_le_bytes());
+        ico.extend_from_slice(DEFAULT_ICON);
+
+        let (png, size) = to_png(ico, ImageFormat::Ico).unwrap();
+
+        assert_eq!(size, 256);
+        assert_eq!(pixels(&png), pixels(DEFAULT_ICON));
+
+        // An ICO with a single 2x2 bitmap, the top right pixel is masked out
+        let mut ico = vec![0, 0, 1, 0, 1, 0, 2, 2, 0, 0, 1, 0, 24, 0];
+        let mut bmp = vec![];
+
+        for value in [40u32, 2, 4] {
+            bmp.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bmp.extend_from_slice(&[1, 0, 24, 0]);
+        bmp.extend_from_slice(&[0; 24]);
+        // Bottom line then top line, BGR padded to 8 bytes
+        bmp.extend_from_slice(&[1, 2, 3, 4, 5, 6, 0, 0, 7, 8, 9, 10, 11, 12, 0, 0]);
+        bmp.extend_from_slice(&[0, 0, 0, 0, 0x40, 0, 0, 0]);
+        ico.extend_from_slice(&(bmp.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&22u32.to_le_bytes());
+        ico.extend_from_slice(&bmp);
+
+        let (png, size) = to_png(ico, ImageFormat::Ico).unwrap();
+        let icon = pixels(&png);
+
+        assert_eq!(size, 48);
+        assert_eq!(
+            [
+                icon.get_pixel(0, 0),
+                icon.get_pixel(47, 0),
+                icon.get_pixel(0, 47)
+            ],
+            [
+                &Rgba([9, 8, 7, 255]),
+                &Rgba([12, 11, 10, 0]),
+                &Rgba([3, 2, 1, 255])
+            ]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use super::{error::AppError, util::with_suffix, AppResult};
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::io::AsRawFd,
+    path::Path,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Advisory lock on a `.lock` file next to the config, released on drop.
+/// Take an exclusive lock before reading a config which is going to be saved, so concurrent
+/// instances can't overwrite each other's changes. `App::config_file` makes the app take it
+/// itself when it changes the config
+///
+/// # Example
+/// ```no_run
+/// # use debian_bridge_core::{Config, ConfigLock};
+/// # use std::path::Path;
+/// #
+/// let path = Path::new("./cfg");
+/// let lock = ConfigLock::exclusive(path).unwrap();
+/// let config = Config::deserialize(path).unwrap();
+/// //...
+/// config.serialize(path).unwrap();
+/// ```
+pub struct ConfigLock {
+    _file: File,
+}
+
+impl ConfigLock {
+    /// Lock for read-only access, any number of instances may hold it at once
+    pub fn shared(path: &Path) -> AppResult<Self> {
+        ConfigLock::acquire(path, libc::LOCK_SH, LOCK_TIMEOUT)
+    }
+
+    /// Lock for modifications, waits until all other instances release their locks
+    pub fn exclusive(path: &Path) -> AppResult<Self> {
+        ConfigLock::acquire(path, libc::LOCK_EX, LOCK_TIMEOUT)
+    }
+
+    fn acquire(path: &Path, operation: libc::c_int, timeout: Duration) -> AppResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(with_suffix(path, ".lock"))?;
+        let started = Instant::now();
+
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+                debug!("Config lock acquired");
+                return Ok(ConfigLock { _file: file });
+            }
+
+            let err = std::io::Error::last_os_error();
+
+            if err.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(AppError::File(err));
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(AppError::Locked);
+            }
+
+            sleep(LOCK_RETRY_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_lock_{}", std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("config.json");
+        let timeout = Duration::from_millis(200);
+
+        {
+            let _first = ConfigLock::acquire(&path, libc::LOCK_SH, timeout).unwrap();
+            let _second = ConfigLock::acquire(&path, libc::LOCK_SH, timeout).unwrap();
+
+            match ConfigLock::acquire(&path, libc::LOCK_EX, timeout) {
+                Err(AppError::Locked) => (),
+                _ => panic!("Exclusive lock acquired while shared locks are held"),
+            }
+        }
+
+        let _exclusive = ConfigLock::acquire(&path, libc::LOCK_EX, timeout).unwrap();
+
+        match ConfigLock::acquire(&path, libc::LOCK_SH, timeout) {
+            Err(AppError::Locked) => (),
+            _ => panic!("Shared lock acquired while exclusive lock is held"),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use crate::app::error::AppError;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Log files kept of every program, older ones are removed after a run
+pub const LOG_KEEP: usize = 10;
+/// Size the log files of a program take at most. A single run stops logging beyond it
+pub const LOG_MAX_BYTES: u64 = 16 << 20;
+
+/// Output a run of a program left, named after the time it started
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// File of a run starting at `started` in the log directory of a program
+pub fn path(dir: &Path, started: DateTime<Utc>) -> PathBuf {
+    dir.join(format!("{}.log", started.format("%Y%m%dT%H%M%S%.6f")))
+}
+
+/// Logs in `dir`, the oldest first
+pub fn files(dir: &Path) -> Vec<LogFile> {
+    let mut files: Vec<LogFile> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension() == Some("log".as_ref()))
+                .filter_map(|path| {
+                    let size = std::fs::metadata(&path).ok().filter(|m| m.is_file())?.len();
+
+                    Some(LogFile { path, size })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Removes the oldest logs in `dir` until at most `keep` files of `max_bytes` in total are
+/// left, the latest one is always kept. Returns the removed files
+pub fn rotate(dir: &Path, keep: usize, max_bytes: u64) -> Vec<PathBuf> {
+    let mut files = files(dir);
+    let mut total: u64 = files.iter().map(|file| file.size).sum();
+    let mut removed = vec![];
+
+    while files.len() > 1 && (files.len() > keep || total > max_bytes) {
+        let file = files.remove(0);
+
+        total -= file.size;
+
+        match std::fs::remove_file(&file.path) {
+            Ok(_) => removed.push(file.path),
+            Err(err) => warn!("Old log {} is left in place: {}", file.path.display(), err),
+        }
+    }
+
+    removed
+}
+
+/// Removes every log in `dir` but the latest one
+pub fn truncate(dir: &Path) -> AppResult<Vec<PathBuf>> {
+    let mut files = files(dir);
+
+    files.pop();
+    files
+        .into_iter()
+        .map(|file| {
+            std::fs::remove_file(&file.path)
+                .map(|_| file.path)
+                .map_err(AppError::File)
+        })
+        .collect()
+}
+
+/// Log of a run which stops growing at its limit, a line telling so ends it then
+pub struct LogWriter {
+    file: File,
+    written: u64,
+    limit: u64,
+}
+
+impl LogWriter {
+    pub fn create(path: &Path, limit: u64) -> AppResult<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(AppError::File)?;
+        }
+
+        Ok(LogWriter {
+            file: File::create(path).map_err(AppError::File)?,
+            written: 0,
+            limit,
+        })
+    }
+
+    /// Appends what fits below the limit. A failed write only warns, the program must run on
+    pub fn append(&mut self, data: &[u8]) {
+        if self.written >= self.limit {
+            return;
+        }
+
+        let fits = data.len().min((self.limit - self.written) as usize);
+        let mut result = self.file.write_all(&data[..fits]);
+
+        self.written += fits as u64;
+
+        if self.written >= self.limit {
+            result = result.and_then(|_| {
+                writeln!(
+                    self.file,
+                    "\n[log stopped at {} bytes, the output went on]",
+                    self.limit
+                )
+            });
+        }
+
+        if let Err(err) = result {
+            warn!("Can't write the log: {}", err);
+            self.written = self.limit;
+        }
+    }
+}
+
+/// Copies `input` to `output` and to the log until the input ends
+pub fn tee<R, W>(mut input: R, mut output: W, log: Arc<Mutex<LogWriter>>) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut buffer = [0; 8192];
+
+        loop {
+            match input.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => {
+                    output
+                        .write_all(&buffer[..read])
+                        .and_then(|_| output.flush())
+                        .unwrap_or(());
+
+                    if let Ok(mut log) = log.lock() {
+                        log.append(&buffer[..read]);
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::io::Cursor;
+
+    fn get_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "debian_bridge_test_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = get_dir("logs_rotate");
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for (second, size) in [(1, 10), (2, 10), (3, 10), (4, 30)] {
+            let started = Utc.ymd(2020, 1, 1).and_hms(0, 0, second);
+
+            std::fs::write(path(&dir, started), vec![b'x'; size]).unwrap();
+        }
+
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let files = files(&dir);
+
+        assert_eq!(files.len(), 4);
+        assert!(files[0].path.ends_with("20200101T000001.000000.log"));
+        assert_eq!(rotate(&dir, 3, 1000), vec![files[0].path.to_owned()]);
+        assert_eq!(rotate(&dir, 3, 45).len(), 1);
+        assert_eq!(super::files(&dir), files[2..].to_vec());
+        assert_eq!(rotate(&dir, 1, 0), vec![files[2].path.to_owned()]);
+        assert_eq!(super::files(&dir), files[3..].to_vec());
+        assert!(truncate(&dir).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tee() {
+        let dir = get_dir("logs_tee");
+        let file = dir.join("run.log");
+        let log = Arc::new(Mutex::new(LogWriter::create(&file, 8).unwrap()));
+
+        tee(
+            Cursor::new(b"hello ".to_vec()),
+            std::io::sink(),
+            log.clone(),
+        )
+        .join()
+        .unwrap();
+        tee(Cursor::new(b"world\n".to_vec()), std::io::sink(), log)
+            .join()
+            .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "hello wo\n[log stopped at 8 bytes, the output went on]\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,79 +1,603 @@
+mod backend;
+mod cancel;
+mod compose;
 mod config;
+mod context;
 mod deb;
 mod docker;
+mod doctor;
 pub mod error;
+mod events;
+mod hooks;
+mod icons;
+mod lock;
+mod logs;
+mod network;
+mod preset;
+mod repo;
+mod request;
+mod search;
+mod spec;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod trash;
+mod urls;
+mod usage;
+mod userdirs;
 mod util;
+mod verify;
+mod wrapper;
 
 use crate::System;
-use colorful::{core::StrMarker, Color, Colorful};
-pub use config::{Config, Feature, Icon, Program};
+pub use backend::Backend;
+pub use cancel::Cancel;
+use compose::ComposeService;
+pub use config::{
+    BuildSettings, Config, ConfigFormat, ContainerMode, Feature, GlobalSettings, Icon, Program,
+    ProgramKind, PulseServer, RemoteDisplay, Resources, RestartPolicy, Seccomp, Security, Share,
+    SETTING_KEYS, ULIMITS,
+};
+use context::BuildContext;
 use deb::Deb;
-use docker::DockerFacade;
+pub use deb::PackageSummary;
+use docker::{
+    BuildSpec, DockerFacade, BUILD_CONTEXT_API_VERSION, CREATED_FROM_LABEL, PROGRAM_LABEL,
+};
+pub use docker::{ManagedImage, RunOutcome};
+pub use doctor::{check_docker, Check};
 use error::AppError;
+pub use events::AppEvent;
+use events::Reporter;
+pub use lock::ConfigLock;
+pub use logs::{LogFile, LOG_KEEP, LOG_MAX_BYTES};
+pub use network::{NetworkMode, NetworkStep};
+pub use preset::{Preset, PRESETS_DIR};
+pub use request::{CreateRequest, ProgramChanges, RunOptions, RunOverrides};
+pub use search::{SearchField, SearchMatch};
+use serde::{Serialize, Serializer};
 use serde_json::to_string;
 use shiplift::Docker;
+use spec::build_run_spec;
+pub use spec::{Area, HostState, Permission};
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
+    collections::VecDeque,
     error::Error,
     fmt::{Display, Formatter},
-    net::IpAddr,
+    io::IsTerminal,
     path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex, OnceLock,
+    },
+};
+pub use trash::{TrashEntry, TRASH_KEEP};
+pub use urls::OPEN_URL_SCHEMES;
+pub use usage::{human_size, DiskUsage, ProgramUsage};
+pub use userdirs::UserDir;
+pub use util::{
+    gen_profile_prefix, parse_age, parse_capability, parse_device, parse_env, parse_label,
+    parse_nice, parse_oom_score_adj, parse_share, parse_size, parse_ulimit, user_bin_dir,
+    validate_prefix, validate_profile,
 };
 
 type AppResult<T> = Result<T, AppError>;
 
+/// `/dev/shm` of Chromium based programs, they crash or render garbage with a small one
+const CHROMIUM_SHM_SIZE: u64 = 1 << 30;
+/// Tag of the image an update replaced, see `App::rollback`
+const PREVIOUS_TAG: &str = "previous";
+
+/// Reference of the previous image of a program image, e.g. `debian_bridge_foo:previous`
+fn previous_tag(tag: &str) -> String {
+    format!("{}:{}", tag, PREVIOUS_TAG)
+}
+
 pub struct FeaturesList {
-    list: HashMap<Feature, bool>,
+    list: BTreeMap<Feature, bool>,
+    /// Missing prerequisites of unavailable features
+    reasons: BTreeMap<Feature, String>,
+}
+
+/// Feature which can't be used and the reason why
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureProblem {
+    pub feature: Feature,
+    pub reason: String,
+}
+
+impl Display for FeatureProblem {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} ({})", self.feature, self.reason)
+    }
 }
 
 impl FeaturesList {
     fn new(system: &System) -> Self {
-        let mut list = HashMap::new();
+        let mut list = BTreeMap::new();
+        let mut reasons = BTreeMap::new();
 
-        list.insert(Feature::Display, system.wm.is_some());
-        list.insert(Feature::Sound, system.sd.is_some());
+        list.insert(Feature::Display, system.display.is_some());
+        list.insert(Feature::Sound, system.sound.is_some());
         list.insert(Feature::Devices, true);
         list.insert(Feature::Notification, true);
         list.insert(Feature::Time, true);
         list.insert(Feature::HomePersistent, true);
+        list.insert(Feature::ReadOnlyRoot, true);
+        list.insert(Feature::InputMethod, system.input_method.is_some());
+        list.insert(Feature::HostTheme, true);
+        list.insert(Feature::Keyring, system.secret_service);
+        list.insert(Feature::Gamepad, !system.gamepads.is_empty());
+        list.insert(Feature::Bluetooth, system.bluetooth);
+        list.insert(Feature::Mdns, system.avahi);
+        list.insert(Feature::OpenUrls, true);
+        list.insert(Feature::Portals, system.portal);
+        list.insert(
+            Feature::Scanner,
+            !system.scanners.is_empty() || system.saned,
+        );
+
+        if system.display.is_none() {
+            reasons.insert(
+                Feature::Display,
+                "no X11 or Wayland session found, neither XDG_SESSION_TYPE nor DISPLAY or \
+                 WAYLAND_DISPLAY is set"
+                    .to_string(),
+            );
+        }
+
+        if system.sound.is_none() {
+            reasons.insert(
+                Feature::Sound,
+                "no sound server found, neither a PipeWire or PulseAudio socket nor working \
+                 `pactl` or `aplay`"
+                    .to_string(),
+            );
+        }
+
+        if system.input_method.is_none() {
+            reasons.insert(
+                Feature::InputMethod,
+                "no IBus or Fcitx found, neither XMODIFIERS nor GTK_IM_MODULE names one"
+                    .to_string(),
+            );
+        }
+
+        if !system.secret_service {
+            reasons.insert(
+                Feature::Keyring,
+                "no secret service owns org.freedesktop.secrets on the session bus, start \
+                 gnome-keyring or KeePassXC"
+                    .to_string(),
+            );
+        }
+
+        if !system.portal {
+            reasons.insert(
+                Feature::Portals,
+                "no xdg-desktop-portal owns org.freedesktop.portal.Desktop on the session bus, \
+                 install xdg-desktop-portal with a backend of your desktop"
+                    .to_string(),
+            );
+        }
+
+        if !system.bluetooth {
+            reasons.insert(
+                Feature::Bluetooth,
+                "no bluetoothd owns org.bluez on the system bus, start the bluetooth service"
+                    .to_string(),
+            );
+        }
+
+        if system.scanners.is_empty() && !system.saned {
+            reasons.insert(
+                Feature::Scanner,
+                "no scanner found, `scanimage -L` lists none and saned isn't installed, install \
+                 sane-utils and check the scanner works on the host"
+                    .to_string(),
+            );
+        }
+
+        if !system.avahi {
+            reasons.insert(
+                Feature::Mdns,
+                "no avahi-daemon owns org.freedesktop.Avahi on the system bus, start the avahi \
+                 service"
+                    .to_string(),
+            );
+        }
+
+        if system.gamepads.is_empty() {
+            reasons.insert(
+                Feature::Gamepad,
+                "no gamepad or joystick connected, plug one in".to_string(),
+            );
+        }
 
-        Self { list }
+        Self { list, reasons }
     }
 
-    fn validate(&self, settings: &Vec<Feature>) -> bool {
-        settings
+    pub fn is_available(&self, feature: &Feature) -> bool {
+        self.list.get(feature).cloned().unwrap_or(false)
+    }
+
+    /// Iterates over all known features and their availability
+    pub fn iter(&self) -> impl Iterator<Item = (Feature, bool)> + '_ {
+        self.list
             .iter()
-            .try_for_each(|f| {
-                if !*self.list.get(f).ok_or(())? {
-                    Err(())
-                } else {
-                    Ok(())
-                }
+            .map(|(feature, available)| (feature.to_owned(), *available))
+    }
+
+    /// Checks all `settings` are available, returns every unavailable one otherwise
+    pub fn validate(&self, settings: &[Feature]) -> Result<(), Vec<FeatureProblem>> {
+        let problems: Vec<FeatureProblem> = settings
+            .iter()
+            .filter(|feature| !self.is_available(feature))
+            .map(|feature| FeatureProblem {
+                feature: feature.to_owned(),
+                reason: self
+                    .reasons
+                    .get(feature)
+                    .cloned()
+                    .unwrap_or("unsupported feature".to_string()),
             })
-            .is_ok()
+            .collect();
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems),
+        }
+    }
+}
+
+/// Serialized as a map of features to their availability and the reason if unavailable
+impl Serialize for FeaturesList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Status<'a> {
+            available: bool,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            reason: Option<&'a String>,
+        }
+
+        serializer.collect_map(self.list.iter().map(|(feature, available)| {
+            (
+                feature,
+                Status {
+                    available: *available,
+                    reason: self.reasons.get(feature),
+                },
+            )
+        }))
     }
 }
 
 impl Display for FeaturesList {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        writeln!(f, "\n");
+        let names: Vec<String> = self
+            .list
+            .keys()
+            .map(|feature| feature.to_string())
+            .collect();
+        let width = names
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or_default();
 
-        for (feature, available) in &self.list {
+        for (name, available) in names.iter().zip(self.list.values()) {
             writeln!(
                 f,
-                "\t{:<15} ===> {}",
-                format!("{}", feature),
+                "\t{:<width$} ===> {}",
+                name,
                 match available {
-                    true => "available".color(Color::Green),
-                    false => "unavailable".color(Color::Red),
-                }
-            );
+                    true => "available",
+                    false => "unavailable",
+                },
+                width = width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Outcome of repairing a single program
+pub enum Repair {
+    /// Image exists, nothing to do
+    Intact,
+    Rebuilt,
+    /// Image was found by its label under another tag and tagged back
+    Retagged,
+    /// Image of a program created from an image was pulled again
+    Pulled,
+    /// Image can't be rebuilt, e.g. source package was removed
+    Skipped(String),
+    Failed(AppError),
+}
+
+/// Serialized as the status with the reason of a skipped or failed repair
+impl Serialize for Repair {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Status {
+            status: &'static str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            reason: Option<String>,
+        }
+
+        let (status, reason) = match self {
+            Repair::Intact => ("intact", None),
+            Repair::Rebuilt => ("repaired", None),
+            Repair::Retagged => ("retagged", None),
+            Repair::Pulled => ("pulled", None),
+            Repair::Skipped(reason) => ("skipped", Some(reason.to_owned())),
+            Repair::Failed(err) => ("failed", Some(err.to_string())),
+        };
+
+        Status { status, reason }.serialize(serializer)
+    }
+}
+
+impl Display for Repair {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Repair::Intact => write!(f, "intact"),
+            Repair::Rebuilt => write!(f, "repaired"),
+            Repair::Retagged => write!(f, "retagged"),
+            Repair::Pulled => write!(f, "pulled"),
+            Repair::Skipped(reason) => write!(f, "skipped: {}", reason),
+            Repair::Failed(err) => write!(f, "failed: {}", err),
+        }
+    }
+}
+
+/// What was cleaned up by removing a program
+#[derive(Debug, Clone, Serialize)]
+pub struct RemovalReport {
+    pub program: String,
+    /// Tag of the removed image, `None` if the image was already missing
+    pub image: Option<String>,
+    /// IDs of the removed containers
+    pub containers: Vec<String>,
+    /// Removed desktop entry
+    pub desktop_entry: Option<PathBuf>,
+    /// Removed copy of the icon, see `icons::install`
+    pub icon: Option<PathBuf>,
+    /// Removed entry starting the program at login
+    pub autostart: Option<PathBuf>,
+    /// Removed systemd user service
+    pub service: Option<PathBuf>,
+    /// Removed wrapper script, see `App::install_wrapper`
+    pub wrapper: Option<PathBuf>,
+    /// Removed image the program was created from, see `App::create_from_image`
+    pub source_image: Option<String>,
+    /// Backup of the program to bring it back with `App::restore`
+    pub backup: Option<PathBuf>,
+}
+
+impl Display for RemovalReport {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "\t{:<15} ===> {}", "Program", self.program)?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Image",
+            self.image.as_deref().unwrap_or("none")
+        )?;
+        writeln!(f, "\t{:<15} ===> {}", "Containers", self.containers.len())?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Desktop entry",
+            self.desktop_entry
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Icon",
+            self.icon
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Autostart",
+            self.autostart
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Service",
+            self.service
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Wrapper",
+            self.wrapper
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Source image",
+            self.source_image.as_deref().unwrap_or("none")
+        )?;
+        writeln!(
+            f,
+            "\t{:<15} ===> {}",
+            "Backup",
+            self.backup
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or("none".to_string())
+        )
+    }
+}
+
+/// What was removed by `clean`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanReport {
+    /// Tags of the removed base images
+    pub images: Vec<String>,
+    /// Tags of the removed previous images of programs, see `App::rollback`
+    pub previous: Vec<String>,
+    /// Names of the removed exited containers
+    pub containers: Vec<String>,
+    /// Removed backups of programs, see `App::empty_trash`
+    pub trash: Vec<PathBuf>,
+    /// Removed old logs, see `App::truncate_logs`
+    pub logs: Vec<PathBuf>,
+}
+
+/// Stored program along with its state in docker
+#[derive(Clone, Serialize)]
+pub struct ProgramDetails {
+    pub program: Program,
+    pub image_exists: bool,
+    /// Whether a persistent container is created
+    pub has_container: bool,
+}
+
+/// What was removed by `purge`. Everything which could be removed is, failures are collected
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PurgeReport {
+    /// Names of the programs which were stored in the config
+    pub programs: Vec<String>,
+    /// IDs of the removed containers
+    pub containers: Vec<String>,
+    /// Tags or IDs of the removed images
+    pub images: Vec<String>,
+    /// Removed files and directories: desktop entries, the config and the cache
+    pub files: Vec<PathBuf>,
+    /// What couldn't be removed and why
+    pub failures: Vec<String>,
+}
+
+impl Display for PurgeReport {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "\t{:<15} ===> {}", "Programs", self.programs.len())?;
+        writeln!(f, "\t{:<15} ===> {}", "Containers", self.containers.len())?;
+        writeln!(f, "\t{:<15} ===> {}", "Images", self.images.len())?;
+        writeln!(f, "\t{:<15} ===> {}", "Files", self.files.len())?;
+
+        for failure in &self.failures {
+            writeln!(f, "\t{:<15} ===> {}", "Failed", failure)?;
         }
 
         Ok(())
     }
 }
 
+/// Builds an `App`, see `App::builder`. Unless set, the docker client connects to the default
+/// socket, the system is probed through it and the cache is in the XDG cache directory
+pub struct AppBuilder {
+    package_name: String,
+    prefix: String,
+    profile: Option<String>,
+    cache_path: Option<PathBuf>,
+    config: Config,
+    docker: Option<Docker>,
+    system: Option<System>,
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        AppBuilder {
+            package_name: "debian_bridge".to_string(),
+            prefix: "debian_bridge".to_string(),
+            profile: None,
+            cache_path: None,
+            config: Config::default(),
+            docker: None,
+            system: None,
+        }
+    }
+}
+
+impl AppBuilder {
+    /// Name the desktop entries run the programs with
+    pub fn package_name<T: Into<String>>(mut self, name: T) -> Self {
+        self.package_name = name.into();
+        self
+    }
+
+    /// Prefix of the image and container names
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Profile of the programs, it namespaces the prefix and the desktop entries
+    pub fn profile<T: Into<String>>(mut self, profile: T) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn cache_path(mut self, path: &Path) -> Self {
+        self.cache_path = Some(path.to_owned());
+        self
+    }
+
+    pub fn config(mut self, config: &Config) -> Self {
+        self.config = config.to_owned();
+        self
+    }
+
+    pub fn docker(mut self, docker: Docker) -> Self {
+        self.docker = Some(docker);
+        self
+    }
+
+    /// Skips probing, e.g. to reuse a `System` probed earlier
+    pub fn system(mut self, system: System) -> Self {
+        self.system = Some(system);
+        self
+    }
+
+    pub fn build(self) -> AppResult<App> {
+        let docker = self.docker.unwrap_or_default();
+        let system = match self.system {
+            Some(system) => system,
+            None => System::try_new(&docker)?,
+        };
+        let cache_path = match self.cache_path {
+            Some(path) => path,
+            None => xdg::BaseDirectories::with_prefix(&self.package_name)
+                .map_err(|err| AppError::Environment(err.to_string()))?
+                .get_cache_home(),
+        };
+
+        let app = App::new(
+            self.package_name,
+            util::gen_profile_prefix(&self.prefix, self.profile.as_deref()),
+            &cache_path,
+            &self.config,
+            system,
+            docker,
+        )?;
+
+        Ok(match self.profile {
+            Some(profile) => app.profile(profile),
+            None => app,
+        })
+    }
+}
+
 /// Main structure to run application
 ///
 /// # Example
@@ -84,20 +608,32 @@ impl Display for FeaturesList {
 /// let docker = Docker::new();
 /// let config = Config::deserialize(Path::new("./cfg")).unwrap();
 /// let system = System::try_new(&docker).unwrap();
-/// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+/// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
 /// //...
 /// app.save(Path::new("./cfg")).unwrap();
 /// ```
-pub struct App<'a> {
+pub struct App {
     package_name: String,
     prefix: String,
+    /// Profile the programs belong to, `None` for the default one
+    profile: Option<String>,
     cache_path: PathBuf,
     config: Config,
-    docker: DockerFacade<'a>,
+    backend: Box<dyn Backend>,
+    events: Reporter,
+    network: NetworkMode,
+    /// Result of the probe of `NetworkMode::Detect`, the network is probed once
+    online: OnceLock<bool>,
+    /// File the config was read from, see `config_file`
+    config_path: Option<PathBuf>,
+    /// Exclusive lock on `config_path`, taken by the first change and released by `save`
+    lock: Option<ConfigLock>,
+    /// Raised to stop the builds of the app, see `cancel_handle`
+    cancel: Cancel,
     pub features: FeaturesList,
 }
 
-impl<'a> App<'a> {
+impl App {
     pub fn list(&self) -> Vec<String> {
         self.config
             .programs
@@ -117,102 +653,241 @@ impl<'a> App<'a> {
     /// # let docker = Docker::new();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
     /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.remove("foo-program").unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let report = app.remove("foo-program").unwrap();
+    ///
+    /// println!("{}", report);
     /// app.save(Path::new("./cfg")).unwrap();
     /// ```
-    pub fn remove<T: Into<String>>(&mut self, program: T) -> AppResult<&Self> {
-        let program = self
-            .config
-            .find(program.into())
-            .ok_or(AppError::Program("Input program doesn't exist".to_str()))?
-            .0;
+    pub fn remove<T: Into<String>>(&mut self, program: T) -> AppResult<RemovalReport> {
+        self.remove_with(program, false)
+    }
 
-        match self.docker.delete(&program) {
-            Ok(_) => (),
-            Err(AppError::DockerStatus(404)) => (),
+    /// Removes existed program like `remove`. A program created from an image keeps that image
+    /// if `keep_image` is set, only its own tag is removed then
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let report = app.remove_with("firefox", true).unwrap();
+    ///
+    /// println!("{}", report);
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn remove_with<T: Into<String>>(
+        &mut self,
+        program: T,
+        keep_image: bool,
+    ) -> AppResult<RemovalReport> {
+        self.lock_config()?;
+
+        let program = self.config.lookup(program)?;
+
+        // Nothing is deleted unless the program can be restored
+        let backup = trash::store(&self.trash_dir()?, &program, TRASH_KEEP)?;
+
+        let mut report = RemovalReport {
+            program: program.get_name_short(),
+            image: None,
+            containers: vec![],
+            desktop_entry: None,
+            icon: None,
+            autostart: None,
+            service: None,
+            wrapper: None,
+            source_image: None,
+            backup: Some(backup),
+        };
+
+        // The service would start the program again
+        match self.remove_service(&program) {
+            Ok(path) => report.service = path,
+            Err(err) => warn!("Service is left in place: {}", err),
+        }
+
+        match self.backend.delete(&program) {
+            Ok(containers) => {
+                report.image = Some(program.get_name(self.prefix.as_str()));
+                report.containers = containers;
+            }
+            Err(AppError::DockerStatus { code: 404, .. }) => (),
             Err(err) => return Err(err),
         };
+
+        if program.has_previous() {
+            let previous = previous_tag(&program.get_name(self.prefix.as_str()));
+
+            match self.backend.delete_image(&previous) {
+                Ok(_) | Err(AppError::DockerStatus { code: 404, .. }) => (),
+                Err(err) => warn!("Image {} is left in place: {}", previous, err),
+            }
+        }
+
+        if let Some(image) = program.source_image.as_ref().filter(|_| !keep_image) {
+            match self.backend.delete_image(image) {
+                Ok(_) => report.source_image = Some(image.to_owned()),
+                Err(AppError::DockerStatus { code: 404, .. }) => (),
+                Err(err) => warn!("Image {} is left in place: {}", image, err),
+            }
+        }
+
         self.config.remove(&program)?;
 
         if let Some(_) = program.icon {
-            let mut path = dirs::desktop_dir().unwrap();
-            let name = format!("{}.desktop", program.get_name_short());
+            match util::desktop_dir() {
+                Ok(mut path) => {
+                    path.push(self.entry_file(&program.get_name_short()));
+
+                    match std::fs::remove_file(&path) {
+                        Ok(_) => report.desktop_entry = Some(path),
+                        Err(err) => error!("Can't remove an entry file: '{}'", err.to_string()),
+                    }
+                }
+                Err(err) => warn!("Desktop entry is left in place: {}", err),
+            }
+        }
 
-            path.push(name);
+        match self.remove_autostart(&program) {
+            Ok(path) => report.autostart = path,
+            Err(err) => warn!("Autostart entry is left in place: {}", err),
+        }
 
-            std::fs::remove_file(path).unwrap_or_else(|err| {
-                error!("Can't remove an entry file: '{}'", err.to_string());
-                ()
-            });
+        if let Some(path) = &program.wrapper {
+            match wrapper::remove(path) {
+                Ok(true) => report.wrapper = Some(path.to_owned()),
+                Ok(false) => (),
+                Err(err) => warn!("Wrapper {} is left in place: {}", path.display(), err),
+            }
         }
 
-        Ok(self)
+        if let Some(icon) = &program.icon {
+            match icons::remove(&icon.path) {
+                Ok(true) => report.icon = Some(icon.path.to_owned()),
+                Ok(false) => (),
+                Err(err) => warn!("Icon {} is left in place: {}", icon.path.display(), err),
+            }
+        }
+
+        Ok(report)
     }
 
-    /// Creates new program
+    /// Shows what `remove` would delete without deleting anything
     ///
     /// # Example
     /// ```no_run
-    /// # use debian_bridge_core::{App, Config, Docker, System, Feature};
+    /// # use debian_bridge_core::{App, Config, Docker, System};
     /// # use std::path::Path;
     /// #
     /// # let docker = Docker::new();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
     /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.create(Path::new("./package.deb"), &vec![Feature::Display], &None, &None, &None).unwrap();
-    /// app.save(Path::new("./cfg")).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// println!("{}", app.plan_removal("foo-program").unwrap());
     /// ```
-    pub fn create(
-        &mut self,
-        app_path: &Path,
-        settings: &Vec<Feature>,
-        icon: &Option<Icon>,
-        cmd: &Option<String>,
-        deps: &Option<String>,
-    ) -> AppResult<&Self> {
-        if !self.features.validate(&settings) {
-            return Err(AppError::Program(
-                "You have set unavailable feature".to_string(),
-            ));
-        }
-
-        let deb = Deb::try_new(app_path)?;
-        let program = Program::new(&deb.package, &app_path, &settings, &icon, &cmd, &deps);
-        let mut app_tmp_path = self.cache_path.to_owned();
-
-        std::fs::create_dir_all(&app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
-        app_tmp_path.push(Path::new("tmp.deb"));
-        std::fs::copy(app_path, &app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
-
-        let mut dockerfile = util::gen_dockerfile(&deb, &program)?;
-
-        debug!("Generated dockerfile:\n{}", dockerfile);
+    pub fn plan_removal<T: Into<String>>(&self, program: T) -> AppResult<RemovalReport> {
+        let program = self.config.lookup(program)?;
+        let tag = program.get_name(self.prefix.as_str());
+        let exists = self.backend.image_exists(&tag)?;
 
-        let mut dockerfile_path = self.cache_path.to_owned();
-        dockerfile_path.push(Path::new("Dockerfile"));
+        Ok(RemovalReport {
+            program: program.get_name_short(),
+            containers: match exists {
+                true => self.backend.get_containers(&tag)?,
+                false => vec![],
+            },
+            image: Some(tag).filter(|_| exists),
+            desktop_entry: match program.icon {
+                Some(_) => util::desktop_dir()
+                    .ok()
+                    .map(|dir| dir.join(self.entry_file(&program.get_name_short())))
+                    .filter(|path| path.exists()),
+                None => None,
+            },
+            autostart: self
+                .autostart_path(&program)
+                .ok()
+                .filter(|path| path.exists()),
+            service: self
+                .service_path(&program)
+                .ok()
+                .filter(|path| path.exists()),
+            wrapper: program.wrapper.filter(|path| wrapper::is_wrapper(path)),
+            icon: program
+                .icon
+                .map(|icon| icon.path)
+                .filter(|path| icons::is_managed(path) && path.exists()),
+            source_image: match program.source_image {
+                Some(image) if self.backend.image_exists(&image)? => Some(image),
+                _ => None,
+            },
+            backup: None,
+        })
+    }
 
-        std::fs::write(&dockerfile_path, dockerfile)
-            .map_err(|err| AppError::File(err.to_string()))?;
+    /// Whether the program has a persistent container at the moment
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// if app.has_container("foo-program").unwrap() {
+    ///     println!("State of the previous run is kept");
+    /// }
+    /// ```
+    pub fn has_container<T: Into<String>>(&self, program: T) -> AppResult<bool> {
+        let program = self.config.lookup(program)?;
 
-        self.config.push(&program)?;
-        self.docker.create(&deb.package)?;
+        match program.container_mode {
+            ContainerMode::Persistent => Ok(self
+                .backend
+                .container_id(&program.get_name(self.prefix.as_str()))?
+                .is_some()),
+            ContainerMode::Ephemeral => Ok(false),
+        }
+    }
 
-        std::fs::remove_file(&dockerfile_path).map_err(|err| AppError::File(err.to_string()))?;
-        std::fs::remove_file(&app_tmp_path).map_err(|err| AppError::File(err.to_string()))?;
+    /// XDG user dirs of the host the program saves to, e.g. its downloads. Only a program with a
+    /// persistent home gets them, the list is empty for others
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for dir in app.user_dirs("foo-program").unwrap() {
+    ///     println!("{} ===> {}", dir.label(), dir.container.display());
+    /// }
+    /// ```
+    pub fn user_dirs<T: Into<String>>(&self, program: T) -> AppResult<Vec<UserDir>> {
+        let program = self.config.lookup(program)?;
 
-        if let Some(icon) = &icon {
-            self.create_entry(&icon, &deb).unwrap_or_else(|err| {
-                warn!("{}", err.to_string());
-                &self
-            });
+        match program.settings.contains(&Feature::HomePersistent) {
+            true => Ok(userdirs::host(&util::home_dir()?)),
+            false => Ok(vec![]),
         }
-
-        Ok(self)
     }
 
-    /// Runs existed program
+    /// What a run of the program can reach: the mounts, devices, network, buses and variables
+    /// `run` passes to its container, sorted by `Area`. They come from the same run spec the
+    /// container is started with, read from the host as it is now
     ///
     /// # Example
     /// ```no_run
@@ -222,21 +897,29 @@ impl<'a> App<'a> {
     /// # let docker = Docker::new();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
     /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.run("foo_program").unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for permission in app.permissions("foo-program").unwrap() {
+    ///     println!("{}: {} ===> {}", permission.area, permission.reason, permission.value);
+    /// }
     /// ```
-    pub fn run<T: Into<String>>(&self, program: T) -> AppResult<&Self> {
-        let program = self
-            .config
-            .find(program)
-            .ok_or(AppError::Program("Program not found".to_string()))?
-            .0;
+    pub fn permissions<T: Into<String>>(&self, program: T) -> AppResult<Vec<Permission>> {
+        let program = self.config.lookup(program)?;
+        let overrides = RunOverrides {
+            host: HostState::read(&program, self.backend.system(), &RunOverrides::default())?,
+            ..RunOverrides::default()
+        };
 
-        self.docker.run(&program)?;
-        Ok(self)
+        Ok(build_run_spec(
+            &program,
+            self.backend.system(),
+            self.backend.prefix(),
+            &overrides,
+        )?
+        .permissions())
     }
 
-    /// Saves current application configuration
+    /// Removes several programs, a failure doesn't stop removal of the rest
     ///
     /// # Example
     /// ```no_run
@@ -246,16 +929,41 @@ impl<'a> App<'a> {
     /// # let docker = Docker::new();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
     /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
-    /// app.save(Path::new("./cfg_new")).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for (program, result) in app.remove_many(&["foo", "bar"]) {
+    ///     println!("{}: {}", program, result.is_ok());
+    /// }
+    /// app.save(Path::new("./cfg")).unwrap();
     /// ```
-    pub fn save(&self, path: &Path) -> AppResult<&Self> {
-        self.config.serialize(path)?;
-        debug!("Config updated");
-        Ok(self)
+    pub fn remove_many<T: AsRef<str>>(
+        &mut self,
+        programs: &[T],
+    ) -> Vec<(String, AppResult<RemovalReport>)> {
+        self.remove_many_with(programs, false)
+    }
+
+    /// Removes several programs like `remove_many`, see `remove_with` for `keep_image`
+    pub fn remove_many_with<T: AsRef<str>>(
+        &mut self,
+        programs: &[T],
+        keep_image: bool,
+    ) -> Vec<(String, AppResult<RemovalReport>)> {
+        programs
+            .iter()
+            .map(|program| {
+                (
+                    program.as_ref().to_string(),
+                    self.remove_with(program.as_ref(), keep_image),
+                )
+            })
+            .collect()
     }
 
-    /// Creates new App instance
+    /// Creates a removed program again from its latest backup, see `remove`. The image is
+    /// rebuilt from the package the program was created from, or pulled if it was created from
+    /// an image. The backup is dropped once the program is restored and kept if it fails, e.g.
+    /// the package is gone
     ///
     /// # Example
     /// ```no_run
@@ -265,56 +973,3380 @@ impl<'a> App<'a> {
     /// # let docker = Docker::new();
     /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
     /// # let system = System::try_new(&docker).unwrap();
-    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, &system, &docker);
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let program = app.restore("foo-program").unwrap();
+    ///
+    /// println!("{}", program);
+    /// app.save(Path::new("./cfg")).unwrap();
     /// ```
-    pub fn new<T: Into<String>, S: Into<String>>(
-        package_name: T,
-        prefix: S,
-        cache_path: &Path,
-        config: &Config,
-        system: &'a System,
-        docker: &'a Docker,
-    ) -> Self {
-        let package_name = package_name.into();
-        let prefix = prefix.into();
+    pub fn restore<T: Into<String>>(&mut self, program: T) -> AppResult<Program> {
+        self.lock_config()?;
 
-        App {
-            package_name,
-            prefix: prefix.to_owned(),
-            config: config.to_owned(),
-            docker: DockerFacade::new(docker, system, prefix, cache_path),
-            cache_path: cache_path.to_owned(),
-            features: FeaturesList::new(&system),
+        let name = program.into();
+        let dir = self.trash_dir()?;
+        let entry = match trash::latest(&dir, &name) {
+            Some(entry) => entry,
+            None => {
+                let mut names: Vec<String> = trash::entries(&dir)
+                    .iter()
+                    .map(|entry| entry.program.get_name_short())
+                    .collect();
+
+                names.sort();
+                names.dedup();
+
+                return Err(AppError::Program(match names.is_empty() {
+                    true => format!("No backup of '{}', the trash is empty", name),
+                    false => format!(
+                        "No backup of '{}', the trash holds {}",
+                        name,
+                        names.join(", ")
+                    ),
+                }));
+            }
+        };
+        let mut program = entry.program.to_owned();
+
+        if program.source_image.is_none() && !program.path.exists() {
+            return Err(AppError::Program(format!(
+                "Can't restore '{}', its package '{}' doesn't exist anymore. Create it again, \
+                 the settings are kept in {}",
+                name,
+                program.path.display(),
+                entry.file.display()
+            )));
         }
-    }
 
-    fn create_entry(&self, icon: &Icon, deb: &Deb) -> AppResult<&Self> {
-        let entry = util::gen_desktop_entry(
-            &self.package_name,
-            &deb.package,
-            &deb.description
-                .to_owned()
-                .unwrap_or("Application".to_string()),
-            &icon.path,
-        );
+        // The managed copy of the icon was removed along with the program
+        if let Some(icon) = program.icon.as_ref().filter(|icon| !icon.path.exists()) {
+            warn!(
+                "Icon {} doesn't exist anymore, the default one is used",
+                icon.path.display()
+            );
+            program.icon = Icon::try_default().ok();
+        }
 
-        let entry = entry.map_err(|err| AppError::File(err.to_string()))?;
-        let mut path = dirs::desktop_dir().unwrap();
+        // The previous image was removed along with the program
+        program.drop_previous();
 
-        debug!(
-            "Generated new entry in '{}':\n{}",
-            path.to_str().unwrap(),
-            entry
-        );
+        // Pushed first to reject a duplicate before building, dropped if the build fails
+        self.config.push(&program)?;
 
-        if !path.exists() {
-            std::fs::create_dir(&path).map_err(|err| AppError::File(err.to_string()))?;
+        let built = match program.source_image {
+            Some(_) => self.pull_image(&mut program),
+            None => self.rebuild_image(&mut program),
+        };
+
+        if let Err(err) = built {
+            self.config.remove(&program)?;
+            return Err(err);
         }
 
-        path.push(format!("{}.desktop", deb.package));
+        let comment = program
+            .description
+            .as_ref()
+            .and_then(|description| description.lines().next())
+            .unwrap_or("Application")
+            .to_string();
 
-        std::fs::write(path, entry).map_err(|err| AppError::File(err.to_string()))?;
+        self.finish_create(&mut program, &comment)?;
 
-        Ok(self)
+        if let Err(err) = std::fs::remove_file(&entry.file) {
+            warn!("Backup {} is left in place: {}", entry.file.display(), err);
+        }
+
+        Ok(program)
+    }
+
+    /// Renames a program, its image and desktop entry. The home directory is shared with the
+    /// host as is, so no user data is bound to the name
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let program = app.rename("code_1.85.2-amd64", "vscode").unwrap();
+    ///
+    /// println!("{}", program);
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn rename<T: Into<String>, S: Into<String>>(
+        &mut self,
+        program: T,
+        name: S,
+    ) -> AppResult<Program> {
+        self.lock_config()?;
+
+        let original = self.config.lookup(program)?;
+        let mut program = original.to_owned();
+        let old = program.get_name_short();
+        let name = name.into();
+
+        validate_name(&name)?;
+
+        if self.config.find(name.as_str()).is_some() {
+            return Err(AppError::Program(format!(
+                "Program with such name already exists '{}'",
+                name
+            )));
+        }
+
+        let from = program.get_name(self.prefix.as_str());
+        let service = self.remove_service(&program).unwrap_or_else(|err| {
+            warn!("Service isn't renamed: {}", err);
+            None
+        });
+
+        program.set_name(name.as_str());
+
+        match self
+            .backend
+            .retag(&from, &program.get_name(self.prefix.as_str()))
+        {
+            Ok(_) => (),
+            Err(err) => warn!("Image isn't renamed, run `repair` to rebuild it: {}", err),
+        }
+
+        if program.has_previous() {
+            let to = previous_tag(&program.get_name(self.prefix.as_str()));
+
+            if let Err(err) = self.backend.retag(&previous_tag(&from), &to) {
+                warn!(
+                    "Previous image isn't renamed, it can't be rolled back to: {}",
+                    err
+                );
+                program.drop_previous();
+            }
+        }
+
+        if self.backend.container_id(&from)?.is_some() {
+            self.backend
+                .rename_container(&from, &program.get_name(self.prefix.as_str()))?;
+        }
+
+        self.config.remove(&original)?;
+        self.config.push(&program)?;
+
+        // The entry runs the program by its name
+        if program.autostart {
+            if let Err(err) = self
+                .remove_autostart(&original)
+                .and_then(|_| self.create_autostart(&program))
+            {
+                warn!("Autostart entry isn't renamed: {}", err);
+            }
+        }
+
+        // The unit runs the program by its name as well
+        if service.is_some() {
+            if let Err(err) = self
+                .write_service(&program)
+                .and_then(|_| util::systemctl(&["enable", "--now", &self.service_name(&program)]))
+            {
+                warn!("Service isn't renamed: {}", err);
+            }
+        }
+
+        if program.icon.is_some() {
+            if let Err(err) = self.rename_entry(&old, &name) {
+                warn!("Desktop entry isn't renamed: {}", err);
+            }
+        }
+
+        // So does the wrapper, its file is named after the command
+        if let Some(path) = &program.wrapper {
+            if let Err(err) = self.write_wrapper(&program, path) {
+                warn!("Wrapper isn't renamed: {}", err);
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// Adds a label to the program, e.g. `work` to operate on the group, see `group`. Returns
+    /// whether the program didn't have it yet
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.add_label("foo_program", "work").unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn add_label<T: Into<String>>(&mut self, program: T, label: &str) -> AppResult<bool> {
+        self.lock_config()?;
+
+        let mut program = self.config.lookup(program)?;
+        let label = parse_label(label)?;
+
+        if program.labels.contains(&label) {
+            return Ok(false);
+        }
+
+        program.labels.push(label);
+        self.config.update(&program)?;
+        Ok(true)
+    }
+
+    /// Takes a label from the program, returns whether it had the label
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.remove_label("foo_program", "work").unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn remove_label<T: Into<String>>(&mut self, program: T, label: &str) -> AppResult<bool> {
+        self.lock_config()?;
+
+        let mut program = self.config.lookup(program)?;
+        let count = program.labels.len();
+
+        program.labels.retain(|other| other != label);
+
+        if program.labels.len() == count {
+            return Ok(false);
+        }
+
+        self.config.update(&program)?;
+        Ok(true)
+    }
+
+    /// Changes features and settings of a program. The image is rebuilt only if the changes
+    /// affect it, which is reported by the returned flag
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, Feature, ProgramChanges, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let changes = ProgramChanges::new().add_feature(Feature::Sound);
+    /// let (program, rebuilt) = app.edit("foo_program", &changes).unwrap();
+    ///
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn edit<T: Into<String>>(
+        &mut self,
+        program: T,
+        changes: &ProgramChanges,
+    ) -> AppResult<(Program, bool)> {
+        self.lock_config()?;
+
+        if let Err(problems) = self.features.validate(&changes.add_features) {
+            return Err(AppError::Feature(format!(
+                "You have set unavailable features: {}",
+                problems
+                    .iter()
+                    .map(|problem| problem.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )));
+        }
+
+        for capability in &changes.add_capabilities {
+            parse_capability(capability)?;
+        }
+
+        for device in &changes.add_devices {
+            parse_device(device)?;
+        }
+
+        let mut program = self.config.lookup(program)?;
+        let previous_icon = program.icon.to_owned();
+        let persistent = program.container_mode == ContainerMode::Persistent;
+        let mut rebuild = changes.apply(&mut program);
+
+        if rebuild && program.source_image.is_some() {
+            warn!(
+                "The image of {} isn't built, dependencies and packages of the features aren't \
+                 installed",
+                program.get_name_short()
+            );
+            rebuild = false;
+        }
+
+        // CLI programs have no desktop entry, one made while the program was graphical is removed
+        if program.kind == ProgramKind::Cli {
+            if changes.icon.is_some() {
+                info!(
+                    "{} is a CLI program, the icon is ignored",
+                    program.get_name_short()
+                );
+            }
+
+            program.icon = None;
+
+            if let Some(previous) = &previous_icon {
+                let entry = util::desktop_dir()
+                    .map(|dir| dir.join(self.entry_file(&program.get_name_short())));
+
+                match entry.and_then(|path| {
+                    std::fs::remove_file(&path)
+                        .map(|_| path)
+                        .map_err(AppError::from)
+                }) {
+                    Ok(path) => info!("Desktop entry {} is removed", path.display()),
+                    Err(err) => warn!("Desktop entry is left in place: {}", err),
+                }
+
+                if let Err(err) = icons::remove(&previous.path) {
+                    warn!("Previous icon {} is left: {}", previous.path.display(), err);
+                }
+            }
+        }
+
+        if let Some(icon) = changes.icon.as_ref().filter(|_| program.icon.is_some()) {
+            if let Some(previous) = previous_icon.filter(|previous| previous.path != icon.path) {
+                if let Err(err) = icons::remove(&previous.path) {
+                    warn!("Previous icon {} is left: {}", previous.path.display(), err);
+                }
+            }
+
+            program.icon = icons::manage(icon, &program);
+        }
+
+        // The entry shows the icon as well
+        match changes.autostart {
+            Some(true) => {
+                self.create_autostart(&program)?;
+            }
+            Some(false) => {
+                self.remove_autostart(&program)?;
+            }
+            None if changes.icon.is_some() && program.autostart => {
+                self.create_autostart(&program)?;
+            }
+            None => (),
+        }
+
+        // Mounts and the image are fixed when a container is created
+        if persistent && self.backend.delete_persistent(&program)? {
+            info!("Persistent container is removed, it will be created on the next run");
+        }
+
+        if rebuild {
+            let deb = Deb::try_new(&program.path)?;
+            let mut overrides = BuildSettings::new(
+                &changes.build.apt_mirror,
+                &changes.build.proxy,
+                &program.snapshot,
+            );
+
+            overrides.local_repo = program.local_repo.to_owned();
+
+            let build = self.config.build.merge(&overrides);
+
+            build.validate()?;
+
+            let build = build.resolve();
+
+            program.snapshot = build.snapshot.to_owned();
+            self.update_image(&deb, &mut program, &build)?;
+        }
+
+        self.config.update(&program)?;
+
+        if changes.restart.is_some() && self.service_path(&program)?.exists() {
+            self.write_service(&program)?;
+        }
+
+        // The entry is written again to point at the new icon
+        if let (Some(_), Some(icon)) = (&changes.icon, &program.icon) {
+            let comment = match program.source_image {
+                Some(_) => Ok("Application".to_string()),
+                None => Deb::try_new(&program.path)
+                    .map(|deb| deb.synopsis().unwrap_or("Application").to_string()),
+            };
+
+            match comment {
+                Ok(comment) => {
+                    if let Err(err) = self.create_entry(icon, &program.get_name_short(), &comment) {
+                        warn!("Program is updated without a desktop entry: {}", err);
+                    }
+                }
+                Err(err) => warn!("Program is updated without a desktop entry: {}", err),
+            }
+        }
+
+        Ok((program, rebuild))
+    }
+
+    /// Rolls a program back to the image its last update replaced. The two images swap their
+    /// tags, so a second rollback returns to the update. A persistent container is created anew
+    /// from the image on the next run
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let program = app.rollback("foo_program").unwrap();
+    ///
+    /// println!("{}", program);
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn rollback<T: Into<String>>(&mut self, program: T) -> AppResult<Program> {
+        self.lock_config()?;
+
+        let mut program = self.config.lookup(program)?;
+        let tag = program.get_name(self.prefix.as_str());
+        let previous = previous_tag(&tag);
+
+        if !program.has_previous() {
+            return Err(AppError::Program(format!(
+                "{} has no previous image, one is kept when an update rebuilds the image",
+                program.get_name_short()
+            )));
+        }
+
+        if !self.backend.image_exists(&previous)? {
+            return Err(AppError::Program(format!(
+                "Previous image of {} doesn't exist anymore, e.g. it was removed by clean",
+                program.get_name_short()
+            )));
+        }
+
+        match self.backend.image_exists(&tag)? {
+            true => {
+                // The current image is held by a temporary tag while the tags move
+                let swap = format!("{}:rollback", tag);
+
+                self.backend.tag(&tag, &swap)?;
+                self.backend.tag(&previous, &tag)?;
+                self.backend.retag(&swap, &previous)?;
+                program.swap_images();
+            }
+            false => {
+                self.backend.retag(&previous, &tag)?;
+                program.swap_images().drop_previous();
+            }
+        }
+
+        // The image is fixed when a container is created
+        if program.container_mode == ContainerMode::Persistent
+            && self.backend.delete_persistent(&program)?
+        {
+            info!("Persistent container is removed, it will be created on the next run");
+        }
+
+        self.config.update(&program)?;
+
+        Ok(program)
+    }
+
+    /// Creates new program. The global settings of the config give the features of a request
+    /// without any and may disable the desktop entry
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, CreateRequest, Docker, System, Feature};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let request = CreateRequest::from_deb(Path::new("./package.deb")).feature(Feature::Display);
+    ///
+    /// let program = app.create(&request).unwrap();
+    ///
+    /// println!("{}", program);
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn create(&mut self, request: &CreateRequest) -> AppResult<Program> {
+        self.lock_config()?;
+
+        let request = &self.with_defaults(request);
+
+        request.validate()?;
+        self.validate_features(request)?;
+
+        let deb = Deb::try_new(&request.path)?;
+
+        self.events.emit(AppEvent::DebParsed {
+            package: deb.package.to_owned(),
+            version: deb.version.to_owned(),
+        });
+
+        let build = self.config.build.merge(&request.build);
+
+        build.validate()?;
+
+        let build = build.resolve();
+        let mut program = self.new_program(request, &deb, &build)?;
+
+        // Pushed first to reject a duplicate before building, dropped if the build fails
+        self.config.push(&program)?;
+
+        if let Err(err) = self.build_image(&deb, &mut program, &build, request.force) {
+            self.config.remove(&program)?;
+            return Err(err);
+        }
+
+        if !request.skip_check {
+            if let Err(err) = self.check_command(&program) {
+                self.drop_unchecked(&program)?;
+                return Err(err);
+            }
+        }
+
+        self.finish_create(&mut program, deb.synopsis().unwrap_or("Application"))?;
+
+        Ok(program)
+    }
+
+    /// Creates new program from an existing image instead of a package, e.g. one published by
+    /// the vendor. The image is pulled and tagged as the program, nothing is built, so
+    /// `deps` and features installing packages into the image have no effect. The command of
+    /// the image is run unless `command` is set in the request, the package path of the
+    /// request is ignored
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, CreateRequest, Docker, System, Feature};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let request = CreateRequest::default().feature(Feature::Display);
+    ///
+    /// let program = app
+    ///     .create_from_image("jess/firefox:latest", "firefox", &request)
+    ///     .unwrap();
+    ///
+    /// println!("{}", program);
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn create_from_image<T: Into<String>, S: Into<String>>(
+        &mut self,
+        reference: T,
+        name: S,
+        request: &CreateRequest,
+    ) -> AppResult<Program> {
+        self.lock_config()?;
+
+        let reference = reference.into();
+        let name = name.into();
+        let request = &self.with_defaults(request);
+
+        request.validate_settings()?;
+        self.validate_features(request)?;
+        validate_name(&name)?;
+
+        if request.resources.nice.is_some() && request.command.is_none() {
+            return Err(AppError::Program(
+                "The command of the image can't be run with a niceness, pass it with --command"
+                    .to_string(),
+            ));
+        }
+
+        if request.deps.is_some()
+            || request.dockerfile_extra.is_some()
+            || !util::feature_packages(&request.features, self.backend.system()).is_empty()
+        {
+            warn!(
+                "{} isn't built, dependencies, packages of the features and Dockerfile extras \
+                 aren't applied",
+                reference
+            );
+        }
+
+        let mut program = Program::new(
+            name.as_str(),
+            Path::new(""),
+            &request.features,
+            &request.icon,
+            &Some(request.command.to_owned().unwrap_or_default()),
+            &None,
+        );
+
+        program.source_image = Some(reference);
+        program.shm_size = request.shm_size;
+        self.configure(&mut program, request);
+
+        // Pushed first to reject a duplicate before pulling, dropped if the pull fails
+        self.config.push(&program)?;
+
+        if let Err(err) = self.pull_image(&mut program) {
+            self.config.remove(&program)?;
+            return Err(err);
+        }
+
+        if request.command.is_some() && !request.skip_check {
+            if let Err(err) = self.check_command(&program) {
+                self.drop_unchecked(&program)?;
+                return Err(err);
+            }
+        }
+
+        self.finish_create(&mut program, "Application")?;
+
+        Ok(program)
+    }
+
+    /// Returns the Dockerfile `create` would build the image from without building anything
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, CreateRequest, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let request = CreateRequest::from_deb(Path::new("./package.deb"))
+    ///     .dockerfile_extra("RUN echo 'enabled=false' > /etc/foo.conf");
+    ///
+    /// println!("{}", app.plan_create(&request).unwrap());
+    /// ```
+    pub fn plan_create(&self, request: &CreateRequest) -> AppResult<String> {
+        let request = &self.with_defaults(request);
+
+        request.validate()?;
+        self.validate_features(request)?;
+
+        let deb = Deb::try_new(&request.path)?;
+        let build = self.config.build.merge(&request.build);
+
+        build.validate()?;
+
+        let build = build.resolve();
+        let program = self.new_program(request, &deb, &build)?;
+
+        util::gen_dockerfile(
+            &deb,
+            &program,
+            &util::gen_base_tag(self.prefix.as_str(), &build)?,
+            self.backend.use_buildkit(),
+            &build,
+            self.backend.system(),
+            self.network == NetworkMode::Offline,
+        )
+    }
+
+    /// Package name, commands and likely features of the package at `path`, e.g. to choose
+    /// them before creating a program. Unreadable files of the package are logged and skipped
+    pub fn inspect_package(&self, path: &Path) -> AppResult<PackageSummary> {
+        let deb = Deb::try_new(path)?;
+        let files = Deb::list_files(path)
+            .map_err(|err| warn!("Can't read files of {}: {}", deb.package, err))
+            .unwrap_or_default();
+        let entries: Vec<String> = deb::desktop_entries(&files)
+            .iter()
+            .filter_map(|entry| Deb::read_file(path, entry).ok())
+            .collect();
+
+        Ok(PackageSummary::new(&deb, &files, &entries))
+    }
+
+    /// Creates new program like `create`, reporting the progress to `tx`. The sender is dropped
+    /// once the program is created, so a receiver can iterate until the channel closes
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, CreateRequest, Docker, System};
+    /// # use std::{path::Path, sync::mpsc::channel};
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let (tx, rx) = channel();
+    /// let progress = std::thread::spawn(move || {
+    ///     for event in rx {
+    ///         println!("{:?}", event);
+    ///     }
+    /// });
+    ///
+    /// app.create_with_events(&CreateRequest::from_deb(Path::new("./package.deb")), tx)
+    ///     .unwrap();
+    /// progress.join().unwrap();
+    /// ```
+    pub fn create_with_events(
+        &mut self,
+        request: &CreateRequest,
+        tx: Sender<AppEvent>,
+    ) -> AppResult<Program> {
+        self.events = Reporter::new(tx);
+
+        let program = self.create(request);
+
+        self.events = Reporter::default();
+        program
+    }
+
+    /// Creates new program, see `create`
+    #[deprecated(since = "0.2.3", note = "use `create` with a `CreateRequest`")]
+    pub fn create_from(
+        &mut self,
+        app_path: &Path,
+        settings: &Vec<Feature>,
+        icon: &Option<Icon>,
+        cmd: &Option<String>,
+        deps: &Option<String>,
+        overrides: &BuildSettings,
+    ) -> AppResult<Program> {
+        self.create(&CreateRequest {
+            path: app_path.to_owned(),
+            features: settings.to_owned(),
+            icon: icon.to_owned(),
+            command: cmd.to_owned(),
+            deps: deps.to_owned(),
+            build: overrides.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Checks whether every program still has its image
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for (program, exists) in app.verify_images().unwrap() {
+    ///     println!("{}: {}", program, exists);
+    /// }
+    /// ```
+    pub fn verify_images(&self) -> AppResult<Vec<(String, bool)>> {
+        self.config
+            .programs
+            .iter()
+            .map(|program| {
+                self.backend
+                    .image_exists(&program.get_name(self.prefix.as_str()))
+                    .map(|exists| (program.get_name_short(), exists))
+            })
+            .collect()
+    }
+
+    /// Checks a program is healthy, e.g. after a system upgrade: its config entry, image and the
+    /// package it was built from, desktop entry, icon and the host services its features need.
+    /// Failed checks carry a hint how to fix them
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for check in app.verify("foo-program") {
+    ///     println!("{}", check);
+    /// }
+    /// ```
+    pub fn verify<T: Into<String>>(&self, program: T) -> Vec<Check> {
+        let name = program.into();
+
+        match self.config.find(name.as_str()) {
+            Some((program, _)) => verify::run(
+                self.backend.as_ref(),
+                &self.features,
+                self.prefix.as_str(),
+                &self.entry_file(&program.get_name_short()),
+                &program,
+            ),
+            None => vec![Check::fail(
+                "Config entry",
+                format!("no program named '{}'", name),
+                "Create it with `debian_bridge create`",
+            )],
+        }
+    }
+
+    /// Rebuilds missing images of the given program or of all programs if `None` is passed.
+    /// Programs which source package doesn't exist anymore are skipped
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for (program, repair) in app.repair(None).unwrap() {
+    ///     println!("{}: {}", program, repair);
+    /// }
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn repair(&mut self, program: Option<&str>) -> AppResult<Vec<(String, Repair)>> {
+        self.lock_config()?;
+
+        let programs = self.repair_targets(program)?;
+
+        self.repair_parallel(programs, 1, None)
+    }
+
+    /// Repairs like `repair`, up to `jobs` programs at once. Events of every program are sent to
+    /// `tx` along with its name, the sender is dropped once all programs are done. A failed
+    /// program doesn't stop the others
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::{path::Path, sync::mpsc::channel};
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let (tx, rx) = channel();
+    /// let progress = std::thread::spawn(move || {
+    ///     for (program, event) in rx {
+    ///         println!("{}: {:?}", program, event);
+    ///     }
+    /// });
+    ///
+    /// let results = app.repair_with_events(None, 4, tx).unwrap();
+    ///
+    /// progress.join().unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn repair_with_events(
+        &mut self,
+        program: Option<&str>,
+        jobs: usize,
+        tx: Sender<(String, AppEvent)>,
+    ) -> AppResult<Vec<(String, Repair)>> {
+        self.lock_config()?;
+
+        let programs = self.repair_targets(program)?;
+
+        self.repair_parallel(programs, jobs, Some(tx))
+    }
+
+    /// Repairs several programs like `repair_with_events`, e.g. the ones of a group
+    pub fn repair_many_with_events<T: AsRef<str>>(
+        &mut self,
+        programs: &[T],
+        jobs: usize,
+        tx: Sender<(String, AppEvent)>,
+    ) -> AppResult<Vec<(String, Repair)>> {
+        self.lock_config()?;
+
+        let programs = programs
+            .iter()
+            .map(|program| self.info(program.as_ref()))
+            .collect::<AppResult<Vec<Program>>>()?;
+
+        self.repair_parallel(programs, jobs, Some(tx))
+    }
+
+    /// Stored programs along with their state in docker
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for details in app.list_detailed().unwrap() {
+    ///     println!("{}: {}", details.program.get_name_short(), details.image_exists);
+    /// }
+    /// ```
+    pub fn list_detailed(&self) -> AppResult<Vec<ProgramDetails>> {
+        self.config
+            .programs
+            .iter()
+            .map(|program| {
+                Ok(ProgramDetails {
+                    program: program.to_owned(),
+                    image_exists: self
+                        .backend
+                        .image_exists(&program.get_name(self.prefix.as_str()))?,
+                    has_container: self.has_container(program.get_name_short())?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a stored program
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// println!("{}", app.info("foo_program").unwrap());
+    /// ```
+    pub fn info<T: Into<String>>(&self, program: T) -> AppResult<Program> {
+        self.config.lookup(program)
+    }
+
+    /// Runs existed program
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let outcome = app.run("foo_program").unwrap();
+    ///
+    /// println!("Exited with {:?} after {:?}", outcome.exit_code, outcome.duration);
+    /// ```
+    pub fn run<T: Into<String>>(&mut self, program: T) -> AppResult<RunOutcome> {
+        self.run_with(program, &RunOptions::new())
+    }
+
+    /// Runs existed program with the given options. The launch is counted in the config, save it
+    /// to keep the statistics. The hooks of the program run around the container: a failed
+    /// pre-run hook aborts the run, the post-run hook runs however the container exits. The
+    /// output is written to a log file unless `RunOptions::no_log` or the `logs` setting turns
+    /// it off, see `logs`. A CLI program run from a terminal isn't logged, it's attached to the
+    /// terminal instead
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, RunOptions, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let outcome = app
+    ///     .run_with("foo_program", &RunOptions::new().keep_container(true))
+    ///     .unwrap();
+    ///
+    /// println!("Container {:?} is kept", outcome.container_id);
+    /// ```
+    pub fn run_with<T: Into<String>>(
+        &mut self,
+        program: T,
+        options: &RunOptions,
+    ) -> AppResult<RunOutcome> {
+        let program = self.config.lookup(program)?;
+
+        if let Some(hook) = &program.pre_run {
+            hooks::run("pre-run", hook, &program, None)?;
+        }
+
+        let shield = program
+            .post_run
+            .as_ref()
+            .map(|_| hooks::InterruptShield::new());
+        // A CLI program gets the terminal itself, piping its output into the log would take the
+        // raw mode and the window size from it
+        let attached = program.kind == ProgramKind::Cli && std::io::stdin().is_terminal();
+        let log = match options.no_log || !self.config.settings.logs || attached {
+            true => None,
+            false => match self.log_dir(&program.get_name_short()) {
+                Ok(dir) => Some(logs::path(&dir, chrono::Utc::now())),
+                Err(err) => {
+                    warn!("The output isn't logged: {}", err);
+                    None
+                }
+            },
+        };
+        let outcome = self.backend.run(&program, options, log.as_deref());
+
+        if let Some(dir) = log.as_ref().and_then(|log| log.parent()) {
+            logs::rotate(dir, LOG_KEEP, LOG_MAX_BYTES);
+        }
+
+        if let Some(hook) = &program.post_run {
+            let exit_code = outcome.as_ref().ok().and_then(|outcome| outcome.exit_code);
+
+            if let Err(err) = hooks::run("post-run", hook, &program, exit_code) {
+                warn!("{}", err);
+            }
+        }
+
+        drop(shield);
+
+        let mut outcome = outcome?;
+
+        outcome.log = log.filter(|log| log.exists());
+
+        // The lock is taken once the program exits, a running program must not block changes.
+        // The run succeeded, a locked config only loses the statistics
+        match self.lock_config() {
+            Ok(()) => {
+                self.config
+                    .record_run(&program.get_name_short(), outcome.started)?;
+            }
+            Err(err) => warn!("The launch isn't counted: {}", err),
+        }
+
+        Ok(outcome)
+    }
+
+    /// docker compose file with a service starting the program the way `run` does, to launch it
+    /// with `docker compose up` where debian_bridge isn't installed. Steps compose can't take are
+    /// described in comments
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// std::fs::write("compose.yaml", app.export_compose("foo_program").unwrap()).unwrap();
+    /// ```
+    pub fn export_compose<T: Into<String>>(&self, program: T) -> AppResult<String> {
+        let program = self.config.lookup(program)?;
+
+        ComposeService::try_new(&program, self.backend.system(), &self.prefix)
+            .map(|service| service.to_string())
+    }
+
+    /// Saves the image of the program to a tarball, e.g. to carry it to a machine without internet
+    /// access along with the config. The SHA-256 of the tarball is returned and written next to
+    /// it, see `import_image`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let checksum = app.export_image("foo_program", Path::new("foo.tar")).unwrap();
+    ///
+    /// println!("SHA-256: {}", checksum);
+    /// ```
+    pub fn export_image<T: Into<String>>(&self, program: T, path: &Path) -> AppResult<String> {
+        let program = self.config.lookup(program)?;
+        let checksum =
+            self.backend
+                .save_image(&program.get_name(&self.prefix), path, &self.events)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        std::fs::write(checksum_path(path), format!("{}  {}\n", checksum, name))?;
+
+        Ok(checksum)
+    }
+
+    /// Saves the image of the program to a tarball sending the progress, see `export_image`
+    pub fn export_image_with_events<T: Into<String>>(
+        &mut self,
+        program: T,
+        path: &Path,
+        tx: Sender<AppEvent>,
+    ) -> AppResult<String> {
+        self.events = Reporter::new(tx);
+
+        let checksum = self.export_image(program, path);
+
+        self.events = Reporter::default();
+        checksum
+    }
+
+    /// Loads images from a tarball written by `export_image`, returns the programs they belong
+    /// to. The tarball is verified against the checksum next to it first. Images of programs
+    /// missing in the config are an error, copy the config before importing
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for program in app.import_image(Path::new("foo.tar")).unwrap() {
+    ///     println!("{} is ready to run", program.get_name_short());
+    /// }
+    /// ```
+    pub fn import_image(&self, path: &Path) -> AppResult<Vec<Program>> {
+        let checksum_path = checksum_path(path);
+
+        match std::fs::read_to_string(&checksum_path) {
+            Ok(expected) => {
+                let expected = expected.split_whitespace().next().unwrap_or_default();
+
+                if util::sha256_file(path)? != expected {
+                    return Err(AppError::Program(format!(
+                        "Checksum of {} doesn't match {}, the tarball is damaged",
+                        path.display(),
+                        checksum_path.display()
+                    )));
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => warn!(
+                "{} is missing, {} isn't verified",
+                checksum_path.display(),
+                path.display()
+            ),
+            Err(err) => return Err(err.into()),
+        }
+
+        let tags = self.backend.load_image(path)?;
+        let (programs, unknown): (Vec<_>, Vec<_>) = tags
+            .iter()
+            .map(|tag| {
+                let name = tag.strip_suffix(":latest").unwrap_or(tag);
+
+                (
+                    tag,
+                    self.config
+                        .programs
+                        .iter()
+                        .find(|program| program.get_name(&self.prefix) == name),
+                )
+            })
+            .partition(|(_, program)| program.is_some());
+        let unknown: Vec<&str> = unknown.iter().map(|(tag, _)| tag.as_str()).collect();
+
+        if programs.is_empty() {
+            return Err(AppError::Program(format!(
+                "No program of the config expects {}, copy the config first",
+                match unknown.is_empty() {
+                    true => "an untagged image".to_string(),
+                    false => unknown.join(", "),
+                }
+            )));
+        }
+
+        if !unknown.is_empty() {
+            warn!(
+                "No program of the config expects {}, the images are loaded anyway",
+                unknown.join(", ")
+            );
+        }
+
+        Ok(programs
+            .into_iter()
+            .filter_map(|(_, program)| program.cloned())
+            .collect())
+    }
+
+    /// Stops the running containers of the program, returns their IDs
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let stopped = app.stop("foo_program").unwrap();
+    ///
+    /// println!("{} containers are stopped", stopped.len());
+    /// ```
+    pub fn stop<T: Into<String>>(&self, program: T) -> AppResult<Vec<String>> {
+        let program = self.config.lookup(program)?;
+
+        self.backend.stop(&program)
+    }
+
+    /// Writes a systemd user service running the program, e.g. for sync clients and other
+    /// daemons. The service restarts the program according to its `restart` policy and isn't
+    /// enabled, see `enable_service`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let path = app.install_service("foo_program").unwrap();
+    ///
+    /// println!("Service is written to {}", path.display());
+    /// ```
+    pub fn install_service<T: Into<String>>(&self, program: T) -> AppResult<PathBuf> {
+        let program = self.config.lookup(program)?;
+
+        self.write_service(&program)
+    }
+
+    /// Installs the service of the program, enables it at login and starts it now
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.enable_service("foo_program").unwrap();
+    /// ```
+    pub fn enable_service<T: Into<String>>(&self, program: T) -> AppResult<PathBuf> {
+        let program = self.config.lookup(program)?;
+        let path = self.write_service(&program)?;
+
+        util::systemctl(&["enable", "--now", &self.service_name(&program)])?;
+
+        Ok(path)
+    }
+
+    /// Stops and disables the service of the program and deletes its unit, returns its path if
+    /// there was one
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.disable_service("foo_program").unwrap();
+    /// ```
+    pub fn disable_service<T: Into<String>>(&self, program: T) -> AppResult<Option<PathBuf>> {
+        let program = self.config.lookup(program)?;
+
+        self.remove_service(&program)
+    }
+
+    /// Writes a shell script into `bin_dir`, e.g. `~/.local/bin`, which runs the program with
+    /// its arguments, so it's started like a host command. The script is named after the
+    /// command of the program and refused if `PATH` has another executable of the name. It's
+    /// removed along with the program
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let path = app
+    ///     .install_wrapper("foo_program", Path::new("/home/user/.local/bin"))
+    ///     .unwrap();
+    ///
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn install_wrapper<T: Into<String>>(
+        &mut self,
+        program: T,
+        bin_dir: &Path,
+    ) -> AppResult<PathBuf> {
+        self.lock_config()?;
+
+        let mut program = self.config.lookup(program)?;
+        let name = wrapper::name(&program.command).unwrap_or(program.get_name_short());
+        let path = bin_dir.join(&name);
+
+        if let Some(other) = self.config.programs.iter().find(|other| {
+            other.wrapper.as_ref() == Some(&path)
+                && other.get_name_short() != program.get_name_short()
+        }) {
+            return Err(AppError::Program(format!(
+                "{} is the wrapper of {} already",
+                path.display(),
+                other.get_name_short()
+            )));
+        }
+
+        let search = std::env::var_os("PATH").unwrap_or_default();
+
+        if let Some(existing) = wrapper::conflict(&name, bin_dir, &search) {
+            return Err(AppError::Program(format!(
+                "{} is a command of the host already, a wrapper named {} would clash with it",
+                existing.display(),
+                name
+            )));
+        }
+
+        if let Some(previous) = program
+            .wrapper
+            .as_ref()
+            .filter(|previous| **previous != path)
+        {
+            if let Err(err) = wrapper::remove(previous) {
+                warn!("Previous wrapper {} is left: {}", previous.display(), err);
+            }
+        }
+
+        self.write_wrapper(&program, &path)?;
+        program.wrapper = Some(path.to_owned());
+        self.config.update(&program)?;
+
+        Ok(path)
+    }
+
+    /// Output of `systemctl --user status` for the service of the program
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// println!("{}", app.service_status("foo_program").unwrap());
+    /// ```
+    pub fn service_status<T: Into<String>>(&self, program: T) -> AppResult<String> {
+        let program = self.config.lookup(program)?;
+
+        if !self.service_path(&program)?.exists() {
+            return Err(AppError::Program(format!(
+                "Program '{}' isn't installed as a service, enable it first",
+                program.get_name_short()
+            )));
+        }
+
+        // Status exits with an error for stopped services, the output tells why
+        util::systemctl_output(&["status", "--no-pager", &self.service_name(&program)])
+            .map(|(_, output)| output)
+    }
+
+    /// Programs not launched during `age`, e.g. to decide what to remove
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{parse_age, App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for program in app.unused_since(parse_age("90d").unwrap()) {
+    ///     println!("{}", program.get_name_short());
+    /// }
+    /// ```
+    pub fn unused_since(&self, age: chrono::Duration) -> Vec<Program> {
+        let now = chrono::Utc::now();
+
+        self.config
+            .programs
+            .iter()
+            .filter(|program| program.is_unused_since(age, now))
+            .cloned()
+            .collect()
+    }
+
+    /// Programs with the label, e.g. to remove or repair the group at once
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for program in app.group("work") {
+    ///     println!("{}", program.get_name_short());
+    /// }
+    /// ```
+    pub fn group(&self, label: &str) -> Vec<Program> {
+        self.config
+            .programs
+            .iter()
+            .filter(|program| program.labels.iter().any(|other| other == label))
+            .cloned()
+            .collect()
+    }
+
+    /// Programs whose name, command or description contains the query ignoring case, from the
+    /// most relevant one: matching names go first, then commands and descriptions
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for found in app.search("pdf") {
+    ///     println!("{}", found);
+    /// }
+    /// ```
+    pub fn search(&self, query: &str) -> Vec<SearchMatch> {
+        let mut matches = self
+            .config
+            .programs
+            .iter()
+            .filter_map(|program| SearchMatch::try_new(program, query.trim()))
+            .collect::<Vec<SearchMatch>>();
+
+        SearchMatch::rank(&mut matches);
+        matches
+    }
+
+    /// Stored programs
+    pub fn programs(&self) -> &[Program] {
+        &self.config.programs
+    }
+
+    /// Base images which are not referenced by any program, i.e. what `clean` would remove
+    pub fn unused_bases(&self) -> AppResult<Vec<String>> {
+        let referenced: Vec<String> = self
+            .config
+            .programs
+            .iter()
+            .filter_map(|program| program.base.to_owned())
+            .collect();
+
+        Ok(self
+            .backend
+            .managed_images()?
+            .into_iter()
+            .filter(|image| image.program.is_none())
+            .flat_map(|image| image.tags)
+            .filter(|tag| {
+                let used = referenced.contains(tag);
+
+                if used {
+                    debug!("Base image '{}' is in use", tag);
+                }
+
+                !used
+            })
+            .collect())
+    }
+
+    /// Program images which don't belong to any stored program, e.g. left by a removal which
+    /// failed halfway
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for image in app.orphaned_images().unwrap() {
+    ///     println!("{:?} was built for {:?}", image.tags, image.program);
+    /// }
+    /// ```
+    pub fn orphaned_images(&self) -> AppResult<Vec<ManagedImage>> {
+        let names = self.list();
+        let tags: Vec<String> = self
+            .config
+            .programs
+            .iter()
+            .map(|program| program.get_name(self.prefix.as_str()))
+            .collect();
+
+        Ok(self
+            .backend
+            .managed_images()?
+            .into_iter()
+            .filter(|image| match &image.program {
+                // A renamed program keeps the label of its old name, its tag is checked too
+                Some(program) => {
+                    !names.contains(program)
+                        && !image
+                            .tags
+                            .iter()
+                            .any(|tag| tags.iter().any(|t| t == tag.trim_end_matches(":latest")))
+                }
+                None => false,
+            })
+            .collect())
+    }
+
+    /// Disk space taken by every program, the shared base images and the cache. A missing image
+    /// is counted as zero with a note
+    pub fn disk_usage(&self) -> AppResult<DiskUsage> {
+        let images = self.backend.managed_images()?;
+        let base_size = |tag: &Option<String>| {
+            images
+                .iter()
+                .find(|image| tag.as_ref().is_some_and(|tag| image.tags.contains(tag)))
+                .map(|image| image.size)
+                .unwrap_or(0)
+        };
+        let mut usage = DiskUsage {
+            base: images
+                .iter()
+                .filter(|image| image.program.is_none())
+                .map(|image| image.size)
+                .sum(),
+            cache: match self.cache_path.exists() {
+                true => util::dir_size(&self.cache_path)?,
+                false => 0,
+            },
+            ..Default::default()
+        };
+
+        for program in &self.config.programs {
+            let name = program.get_name_short();
+            let image = self
+                .backend
+                .image_size(&program.get_name(self.prefix.as_str()))?;
+
+            usage.programs.push(ProgramUsage {
+                image: image
+                    .map(|size| size.saturating_sub(base_size(&program.base)))
+                    .unwrap_or(0),
+                containers: self.backend.containers_size(&name)?,
+                note: match image {
+                    Some(_) => None,
+                    None => Some("image is missing, run `repair`".to_string()),
+                },
+                name,
+            });
+        }
+
+        usage
+            .programs
+            .sort_by(|a, b| b.total().cmp(&a.total()).then(a.name.cmp(&b.name)));
+
+        Ok(usage)
+    }
+
+    /// Exited containers left by runs, i.e. what `clean` would remove. Persistent containers
+    /// of existing programs are kept
+    pub fn stale_containers(&self) -> AppResult<Vec<String>> {
+        let persistent: Vec<String> = self
+            .config
+            .programs
+            .iter()
+            .filter(|program| program.container_mode == ContainerMode::Persistent)
+            .map(|program| program.get_name(self.prefix.as_str()))
+            .collect();
+
+        Ok(self
+            .backend
+            .exited_containers()?
+            .into_iter()
+            .filter(|name| !persistent.contains(name))
+            .collect())
+    }
+
+    /// Programs whose previous image was built longer ago than the keep-previous setting, i.e.
+    /// the previous images `clean` would remove
+    pub fn stale_previous(&self) -> Vec<String> {
+        let kept_since = chrono::Utc::now() - self.config.settings.keep_previous();
+
+        self.config
+            .programs
+            .iter()
+            .filter(|program| {
+                program.has_previous()
+                    && program
+                        .previous_image_built
+                        .is_some_and(|built| built < kept_since)
+            })
+            .map(Program::get_name_short)
+            .collect()
+    }
+
+    /// Removes exited containers left by runs, base images which are not referenced by any
+    /// program and previous images older than the keep-previous setting
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let removed = app.clean().unwrap();
+    ///
+    /// println!("{} containers removed", removed.containers.len());
+    /// ```
+    pub fn clean(&mut self) -> AppResult<CleanReport> {
+        self.lock_config()?;
+
+        let mut removed = CleanReport::default();
+
+        for name in self.stale_containers()? {
+            match self.backend.delete_container(&name) {
+                Ok(_) => removed.containers.push(name),
+                Err(err) => warn!("Can't remove a container '{}': {}", name, err),
+            }
+        }
+
+        for tag in self.unused_bases()? {
+            match self.backend.delete_image(&tag) {
+                Ok(_) => removed.images.push(tag),
+                Err(err) => warn!("Can't remove an image '{}': {}", tag, err),
+            }
+        }
+
+        for name in self.stale_previous() {
+            let mut program = self.config.lookup(name.as_str())?;
+            let tag = previous_tag(&program.get_name(self.prefix.as_str()));
+
+            match self.backend.delete_image(&tag) {
+                Ok(_) => removed.previous.push(tag),
+                Err(AppError::DockerStatus { code: 404, .. }) => (),
+                Err(err) => {
+                    warn!("Can't remove an image '{}': {}", tag, err);
+                    continue;
+                }
+            }
+
+            program.drop_previous();
+            self.config.update(&program)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Log files of the runs of the program, the oldest first. The last one is written while
+    /// the program runs. Logs are kept after the program is removed
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// if let Some(log) = app.logs("foo_program").unwrap().last() {
+    ///     print!("{}", std::fs::read_to_string(&log.path).unwrap());
+    /// }
+    /// ```
+    pub fn logs(&self, name: &str) -> AppResult<Vec<LogFile>> {
+        Ok(logs::files(&self.log_dir(name)?))
+    }
+
+    /// Deletes all but the latest log of every program of the profile, returns the removed files
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// println!("{} logs removed", app.truncate_logs().unwrap().len());
+    /// ```
+    pub fn truncate_logs(&self) -> AppResult<Vec<PathBuf>> {
+        let mut removed = vec![];
+
+        for dir in self.log_dirs()? {
+            removed.extend(logs::truncate(&dir)?);
+        }
+
+        Ok(removed)
+    }
+
+    /// Backups of the removed programs, the oldest first, see `remove`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for entry in app.trash().unwrap() {
+    ///     println!("{} removed at {}", entry.program.get_name_short(), entry.removed);
+    /// }
+    /// ```
+    pub fn trash(&self) -> AppResult<Vec<TrashEntry>> {
+        Ok(trash::entries(&self.trash_dir()?))
+    }
+
+    /// Deletes the backups of the removed programs, they can't be restored afterwards. Returns
+    /// the removed files
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// println!("{} backups removed", app.empty_trash().unwrap().len());
+    /// ```
+    pub fn empty_trash(&self) -> AppResult<Vec<PathBuf>> {
+        trash::empty(&self.trash_dir()?)
+    }
+
+    /// Removes everything debian_bridge created: containers and images carrying its labels,
+    /// desktop entries, the cache and the config at `config_path`. Unlabeled images built by
+    /// older versions are removed only if they belong to a stored program or are base images.
+    /// Docker failures, e.g. an unreachable daemon, don't stop removal of the files
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let report = app.purge(Path::new("./cfg"));
+    ///
+    /// println!("{}", report);
+    /// ```
+    pub fn purge(&mut self, config_path: &Path) -> PurgeReport {
+        if let Err(err) = self.lock_config() {
+            return PurgeReport {
+                failures: vec![err.to_string()],
+                ..Default::default()
+            };
+        }
+
+        let mut report = PurgeReport {
+            programs: self.list(),
+            ..Default::default()
+        };
+
+        // Services are stopped first, they would restart the programs
+        for program in &self.config.programs {
+            match self.remove_service(program) {
+                Ok(path) => report.files.extend(path),
+                Err(err) => report.failures.push(err.to_string()),
+            }
+        }
+
+        self.purge_docker(&mut report);
+
+        for program in &self.config.programs {
+            if program.icon.is_none() {
+                continue;
+            }
+
+            match util::desktop_dir() {
+                Ok(dir) => {
+                    let path = dir.join(self.entry_file(&program.get_name_short()));
+
+                    match std::fs::remove_file(&path) {
+                        Ok(_) => report.files.push(path),
+                        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+                        Err(err) => report.failures.push(format!(
+                            "desktop entry {}: {}",
+                            path.display(),
+                            err
+                        )),
+                    }
+                }
+                Err(err) => report.failures.push(err.to_string()),
+            }
+        }
+
+        for program in &self.config.programs {
+            match self.remove_autostart(program) {
+                Ok(path) => report.files.extend(path),
+                Err(err) => report.failures.push(err.to_string()),
+            }
+        }
+
+        match self.log_dirs() {
+            Ok(dirs) => {
+                for dir in dirs {
+                    match std::fs::remove_dir_all(&dir) {
+                        Ok(_) => report.files.push(dir),
+                        Err(err) => report.failures.push(format!("{}: {}", dir.display(), err)),
+                    }
+                }
+            }
+            Err(err) => report.failures.push(err.to_string()),
+        }
+
+        match self.trash_dir() {
+            Ok(dir) => match trash::empty(&dir) {
+                Ok(files) => {
+                    report.files.extend(files);
+
+                    if std::fs::remove_dir(&dir).is_ok() {
+                        report.files.push(dir);
+                    }
+                }
+                Err(err) => report.failures.push(format!("{}: {}", dir.display(), err)),
+            },
+            Err(err) => report.failures.push(err.to_string()),
+        }
+
+        match util::icons_dir() {
+            Ok(dir) if dir.exists() => match std::fs::remove_dir_all(&dir) {
+                Ok(_) => report.files.push(dir),
+                Err(err) => report.failures.push(format!("{}: {}", dir.display(), err)),
+            },
+            Ok(_) => (),
+            Err(err) => report.failures.push(err.to_string()),
+        }
+
+        for path in doctor::get_leftovers(&self.cache_path) {
+            let removed = match path.is_dir() {
+                true => std::fs::remove_dir_all(&path),
+                false => std::fs::remove_file(&path),
+            };
+
+            match removed {
+                Ok(_) => report.files.push(path),
+                Err(err) => report.failures.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+
+        for suffix in &["", ".bak", ".tmp", ".lock"] {
+            let path = util::with_suffix(config_path, suffix);
+
+            match std::fs::remove_file(&path) {
+                Ok(_) => report.files.push(path),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+                Err(err) => report.failures.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+
+        // Directories are removed only if nothing else is left there
+        for dir in [Some(self.cache_path.as_path()), config_path.parent()]
+            .iter()
+            .flatten()
+        {
+            if std::fs::remove_dir(dir).is_ok() {
+                report.files.push(dir.to_path_buf());
+            }
+        }
+
+        self.config.programs.clear();
+
+        report
+    }
+
+    fn purge_docker(&mut self, report: &mut PurgeReport) {
+        let tags: Vec<String> = self
+            .config
+            .programs
+            .iter()
+            .map(|program| format!("{}:latest", program.get_name(self.prefix.as_str())))
+            .collect();
+        let images: Vec<ManagedImage> = match self.backend.managed_images() {
+            Ok(images) => images
+                .into_iter()
+                .filter(|image| {
+                    image.labeled
+                        || image.program.is_none()
+                        || image.tags.iter().any(|tag| tags.contains(tag))
+                })
+                .collect(),
+            Err(err) => {
+                report
+                    .failures
+                    .push(format!("images aren't removed: {}", err));
+                return;
+            }
+        };
+        let mut containers = match self.backend.labeled_containers() {
+            Ok(containers) => containers,
+            Err(err) => {
+                report
+                    .failures
+                    .push(format!("containers aren't removed: {}", err));
+                vec![]
+            }
+        };
+
+        for tag in images.iter().flat_map(|image| image.tags.iter()) {
+            match self.backend.get_containers(tag) {
+                Ok(ids) => containers.extend(ids),
+                Err(err) => report
+                    .failures
+                    .push(format!("containers of {}: {}", tag, err)),
+            }
+        }
+
+        containers.sort();
+        containers.dedup();
+
+        for id in containers {
+            match self.backend.delete_container(&id) {
+                Ok(_) => report.containers.push(id),
+                Err(err) => report.failures.push(format!("container {}: {}", id, err)),
+            }
+        }
+
+        // Program images are built on top of the base ones, so they go first
+        let (programs, bases): (Vec<ManagedImage>, Vec<ManagedImage>) = images
+            .into_iter()
+            .partition(|image| image.program.is_some());
+
+        for image in programs.into_iter().chain(bases) {
+            let references = match image.tags.is_empty() {
+                true => vec![image.id.to_owned()],
+                false => image.tags.to_owned(),
+            };
+
+            for reference in references {
+                match self.backend.delete_image(&reference) {
+                    Ok(_) => report.images.push(reference),
+                    Err(err) => report
+                        .failures
+                        .push(format!("image {}: {}", reference, err)),
+                }
+            }
+        }
+    }
+
+    /// Forces a rebuild of the shared base image, e.g. to pick up security updates.
+    /// Programs get the refreshed base once they are created again
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.rebuild_base().unwrap();
+    /// ```
+    pub fn rebuild_base(&mut self) -> AppResult<&Self> {
+        let settings = self.config.build.to_owned();
+
+        settings.validate()?;
+
+        let image = settings
+            .base_image
+            .to_owned()
+            .unwrap_or_else(|| util::BASE_IMAGE.to_string());
+        let tag = util::gen_base_tag(self.prefix.as_str(), &settings)?;
+
+        self.check_network(
+            "rebuild the base image",
+            vec![NetworkStep::Pull { image }, NetworkStep::BaseBuild { tag }],
+            &settings,
+        )?;
+        self.prepare_base(&settings, true)?;
+        Ok(self)
+    }
+
+    /// Checks whether the configured apt mirror responds, `None` if no mirror is configured
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// if let Some((mirror, false)) = app.check_mirror() {
+    ///     println!("{} is unreachable", mirror);
+    /// }
+    /// ```
+    pub fn check_mirror(&self) -> Option<(String, bool)> {
+        let settings = &self.config.build;
+
+        settings.apt_mirror.as_ref().map(|mirror| {
+            (
+                mirror.to_owned(),
+                util::is_url_reachable(mirror, &settings.proxy),
+            )
+        })
+    }
+
+    /// Runs environment diagnostics, each check carries a fix hint when it fails
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// for check in app.doctor().iter().filter(|check| !check.passed) {
+    ///     println!("{}", check);
+    /// }
+    /// ```
+    pub fn doctor(&self) -> Vec<Check> {
+        doctor::run(
+            self.backend.as_ref(),
+            &self.config,
+            self.prefix.as_str(),
+            self.cache_path.as_path(),
+        )
+    }
+
+    /// Rewrites the config file at `path` in another format, returns the new path
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, ConfigFormat, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg.json")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let path = app.convert_config(Path::new("./cfg.json"), ConfigFormat::Toml).unwrap();
+    /// ```
+    pub fn convert_config(&self, path: &Path, format: ConfigFormat) -> AppResult<PathBuf> {
+        let target = self.config.convert(path, format)?;
+        debug!("Config converted to {}", target.display());
+        Ok(target)
+    }
+
+    /// Value of a global setting of `SETTING_KEYS`, see `Config::setting`
+    pub fn setting(&self, key: &str) -> AppResult<Option<String>> {
+        self.config.setting(key)
+    }
+
+    /// Changes a global setting, save the config to keep it. An unknown key is an error
+    /// listing the valid ones
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    ///
+    /// app.set_setting("features", "display,sound").unwrap();
+    /// app.save(Path::new("./cfg")).unwrap();
+    /// ```
+    pub fn set_setting(&mut self, key: &str, value: &str) -> AppResult<&Self> {
+        self.lock_config()?;
+
+        self.config.set_setting(key, value)?;
+        debug!("Setting {} changed to '{}'", key, value);
+        Ok(self)
+    }
+
+    /// Saves current application configuration
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// app.save(Path::new("./cfg_new")).unwrap();
+    /// ```
+    pub fn save(&mut self, path: &Path) -> AppResult<&Self> {
+        // Nothing has changed a config read from `config_file`
+        if self.config_path.is_some() && self.lock.is_none() {
+            return Ok(self);
+        }
+
+        let saved = self.config.serialize(path);
+
+        self.lock = None;
+        saved?;
+        debug!("Config updated");
+        Ok(self)
+    }
+
+    /// Creates new App instance. Images and containers are named `<prefix>_<program>`, so the
+    /// prefix has to be valid in a docker image name, see `validate_prefix`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// ```
+    pub fn new<T: Into<String>, S: Into<String>>(
+        package_name: T,
+        prefix: S,
+        cache_path: &Path,
+        config: &Config,
+        system: System,
+        docker: Docker,
+    ) -> AppResult<Self> {
+        let prefix = prefix.into();
+
+        validate_prefix(&prefix)?;
+
+        Ok(App::with_backend(
+            package_name,
+            cache_path,
+            config,
+            Box::new(DockerFacade::new(docker, system, prefix)),
+        ))
+    }
+
+    /// Sets the profile of the programs. Their desktop entries are named after it and the
+    /// entries, autostart entries and services run the programs with `--profile`. The prefix
+    /// passed to `new` has to be namespaced too, see `gen_profile_prefix`
+    pub fn profile<T: Into<String>>(mut self, profile: T) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Sets whether builds and pulls may use the network. Offline, an operation which needs it
+    /// is refused before it starts with the steps it needs it for, see `NetworkStep`, and images
+    /// are built without downloading anything
+    pub fn network(mut self, mode: NetworkMode) -> Self {
+        self.network = mode;
+        self
+    }
+
+    /// Sets the file the config was read from. The first change then takes an exclusive lock on
+    /// it and reads the config again, so concurrent instances can't overwrite each other's
+    /// changes. `save` writes only a changed config and releases the lock. Without the file the
+    /// caller locks the config itself, see `ConfigLock`
+    pub fn config_file(mut self, path: &Path) -> Self {
+        self.config_path = Some(path.to_owned());
+        self
+    }
+
+    /// Flag stopping the builds of the app from another thread, e.g. once the client of a server
+    /// embedding the core goes away. A running build is stopped, the program isn't stored and
+    /// the image tag is moved back. The flag stays raised, later builds of the app fail as well
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config, CreateRequest, Docker, System};
+    /// # use std::path::Path;
+    /// #
+    /// # let docker = Docker::new();
+    /// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+    /// # let system = System::try_new(&docker).unwrap();
+    /// let mut app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+    /// let cancel = app.cancel_handle();
+    ///
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(60));
+    ///     cancel.cancel();
+    /// });
+    ///
+    /// let created = app.create(&CreateRequest::from_deb(Path::new("./package.deb")));
+    /// ```
+    pub fn cancel_handle(&self) -> Cancel {
+        self.cancel.to_owned()
+    }
+
+    /// Creates new App instance managing programs with another container engine, the prefix
+    /// and the system come from the backend. See `testing::MockBackend` for an example
+    pub fn with_backend<T: Into<String>>(
+        package_name: T,
+        cache_path: &Path,
+        config: &Config,
+        backend: Box<dyn Backend>,
+    ) -> Self {
+        App {
+            package_name: package_name.into(),
+            prefix: backend.prefix().to_owned(),
+            profile: None,
+            config: config.to_owned(),
+            features: FeaturesList::new(backend.system()),
+            backend,
+            cache_path: cache_path.to_owned(),
+            events: Reporter::default(),
+            network: NetworkMode::default(),
+            online: OnceLock::new(),
+            config_path: None,
+            lock: None,
+            cancel: Cancel::default(),
+        }
+    }
+
+    /// Builder which probes the system itself, only a config and paths are left to set up
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use debian_bridge_core::{App, Config};
+    /// # use std::path::Path;
+    /// #
+    /// let app = App::builder()
+    ///     .config(&Config::deserialize(Path::new("./cfg")).unwrap())
+    ///     .cache_path(Path::new("./cache"))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> AppBuilder {
+        AppBuilder::default()
+    }
+
+    pub fn system(&self) -> &System {
+        self.backend.system()
+    }
+
+    /// Applies the global settings of the config to a request: their features if it has none
+    /// and no desktop entry if entries are disabled
+    fn with_defaults(&self, request: &CreateRequest) -> CreateRequest {
+        let settings = &self.config.settings;
+        let mut request = request.to_owned();
+
+        if request.features.is_empty() {
+            request.features = settings.features.to_owned();
+        }
+
+        if !settings.desktop_entries && request.icon.take().is_some() {
+            info!("Desktop entries are disabled in the settings, the icon is ignored");
+        }
+
+        request
+    }
+
+    /// Checks the features of the request are available, a remote display or sound server
+    /// doesn't need a local one and a CLI program uses no graphical feature
+    fn validate_features(&self, request: &CreateRequest) -> AppResult<()> {
+        let local: Vec<Feature> = request
+            .features
+            .iter()
+            .filter(|f| request.kind != Some(ProgramKind::Cli) || !f.is_gui())
+            .filter(|f| request.remote_display.is_none() || **f != Feature::Display)
+            .filter(|f| request.pulse_server.is_none() || **f != Feature::Sound)
+            .cloned()
+            .collect();
+
+        self.features.validate(&local).map_err(|problems| {
+            AppError::Feature(format!(
+                "You have set unavailable features: {}",
+                problems
+                    .iter()
+                    .map(|problem| problem.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ))
+        })
+    }
+
+    /// Program `create` would add for the request
+    fn new_program(
+        &self,
+        request: &CreateRequest,
+        deb: &Deb,
+        build: &BuildSettings,
+    ) -> AppResult<Program> {
+        let (command, kind) = self.detect_program(request, deb)?;
+        let mut program = Program::new(
+            &deb.package,
+            &request.path,
+            &request.features,
+            &request.icon,
+            &Some(command),
+            &request.deps,
+        );
+
+        program.kind = kind;
+        program.description = deb.description.to_owned();
+        program.snapshot = build.snapshot.to_owned();
+        program.local_repo = build.local_repo.to_owned();
+        program.dockerfile_extra = request.dockerfile_extra.to_owned();
+        self.configure(&mut program, request);
+        program.shm_size = match request.shm_size {
+            Some(size) => Some(size),
+            None if deb.is_chromium_based() => {
+                info!(
+                    "{} looks like a Chromium based program, it gets {} of shared memory",
+                    deb.package,
+                    human_size(CHROMIUM_SHM_SIZE)
+                );
+                Some(CHROMIUM_SHM_SIZE)
+            }
+            None => None,
+        };
+
+        Ok(program)
+    }
+
+    /// Copies the run settings of the request to the program
+    fn configure(&self, program: &mut Program, request: &CreateRequest) {
+        program.container_mode = request.container_mode;
+        program.remote_display = request.remote_display;
+        program.pulse_server = request.pulse_server.to_owned();
+        program.security = request.security.to_owned();
+        program.capabilities = request.capabilities.to_owned();
+        program.devices = request.devices.to_owned();
+        program.shares = request.shares.to_owned();
+        program.resources = request.resources.to_owned();
+        program.autostart = request.autostart;
+        program.restart = request.restart;
+        program.pre_run = request.pre_run.to_owned();
+        program.post_run = request.post_run.to_owned();
+        program.labels = request.labels.to_owned();
+    }
+
+    /// Takes the exclusive lock on the config file before the first change. The config is read
+    /// again, another instance may have changed it since
+    fn lock_config(&mut self) -> AppResult<()> {
+        if self.lock.is_some() {
+            return Ok(());
+        }
+
+        if let Some(path) = &self.config_path {
+            let lock = ConfigLock::exclusive(path)?;
+
+            self.config = Config::deserialize(path)?;
+            self.lock = Some(lock);
+            debug!("Config locked for changes");
+        }
+
+        Ok(())
+    }
+
+    /// Installs the icon, the autostart and desktop entries of a just created program. A CLI
+    /// program gets no desktop entry
+    fn finish_create(&mut self, program: &mut Program, comment: &str) -> AppResult<()> {
+        if program.kind == ProgramKind::Cli && program.icon.take().is_some() {
+            info!(
+                "{} is a CLI program, it gets no desktop entry",
+                program.get_name_short()
+            );
+        }
+
+        program.icon = program
+            .icon
+            .as_ref()
+            .and_then(|icon| icons::manage(icon, program));
+
+        if program.autostart {
+            if let Err(err) = self.create_autostart(program) {
+                warn!("Program won't start at login: {}", err);
+                program.autostart = false;
+            }
+        }
+
+        // A restored program gets the wrapper it was removed with
+        if let Some(path) = program.wrapper.to_owned() {
+            if let Err(err) = self.write_wrapper(program, &path) {
+                warn!("Program is created without its wrapper: {}", err);
+                program.wrapper = None;
+            }
+        }
+
+        self.config.update(program)?;
+
+        if let Some(icon) = &program.icon {
+            match self.create_entry(icon, &program.get_name_short(), comment) {
+                Ok(path) => self.events.emit(AppEvent::EntryCreated { path }),
+                Err(err) => warn!("Program is created without a desktop entry: {}", err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pulls the image the program was created from and tags it as the program. An image
+    /// already present is used if the registry can't be reached
+    fn pull_image(&self, program: &mut Program) -> AppResult<()> {
+        let reference = program.source_image.to_owned().unwrap_or_default();
+        let tag = program.get_name(self.prefix.as_str());
+        let steps = match self.backend.image_exists(&reference)? {
+            true => vec![],
+            false => vec![NetworkStep::Pull {
+                image: reference.to_owned(),
+            }],
+        };
+        let offline = self.check_network(
+            &format!("pull the image of {}", program.get_name_short()),
+            steps,
+            &self.config.build,
+        )?;
+
+        match offline {
+            true => info!("Offline, the local image {} is used", reference),
+            false => match self.backend.pull(&reference) {
+                Ok(_) => (),
+                Err(err) if self.backend.image_exists(&reference)? => {
+                    warn!("Can't pull {}, the local image is used: {}", reference, err)
+                }
+                Err(err) => return Err(err),
+            },
+        }
+
+        self.backend.tag(&reference, &tag)?;
+        program.image_id = Some(self.backend.image_id(&tag)?);
+        program.image_built = Some(chrono::Utc::now());
+
+        Ok(())
+    }
+
+    /// The program to repair, all of them without one
+    fn repair_targets(&self, program: Option<&str>) -> AppResult<Vec<Program>> {
+        match program {
+            Some(name) => Ok(vec![self.info(name)?]),
+            None => Ok(self.config.programs.to_vec()),
+        }
+    }
+
+    /// Repairs the programs on `jobs` copies of the app in their own threads. The copies don't
+    /// touch the config, repaired programs are stored here one by one as they are done.
+    /// Programs sharing a missing base image may build it at once, the builds are the same
+    fn repair_parallel(
+        &mut self,
+        programs: Vec<Program>,
+        jobs: usize,
+        tx: Option<Sender<(String, AppEvent)>>,
+    ) -> AppResult<Vec<(String, Repair)>> {
+        let names: Vec<String> = programs.iter().map(Program::get_name_short).collect();
+        let queue = Arc::new(Mutex::new(
+            programs.into_iter().enumerate().collect::<VecDeque<_>>(),
+        ));
+        let (results_tx, results_rx) = channel();
+        let workers: Vec<_> = (0..jobs.clamp(1, names.len().max(1)))
+            .map(|_| {
+                let package_name = self.package_name.to_owned();
+                let profile = self.profile.to_owned();
+                let network = self.network;
+                let cache_path = self.cache_path.to_owned();
+                let config = self.config.to_owned();
+                let backend = self.backend.boxed_clone();
+                let queue = queue.clone();
+                let results_tx = results_tx.clone();
+                let tx = tx.clone();
+
+                std::thread::spawn(move || {
+                    let mut app = App::with_backend(package_name, &cache_path, &config, backend);
+
+                    app.profile = profile;
+                    app.network = network;
+
+                    while let Some((index, mut program)) =
+                        queue.lock().ok().and_then(|mut queue| queue.pop_front())
+                    {
+                        let name = program.get_name_short();
+                        let forward = tx.as_ref().map(|tx| {
+                            let (events_tx, events_rx) = channel();
+                            let (tx, name) = (tx.to_owned(), name.to_owned());
+
+                            app.events = Reporter::new(events_tx);
+                            std::thread::spawn(move || {
+                                for event in events_rx {
+                                    tx.send((name.to_owned(), event)).unwrap_or(());
+                                }
+                            })
+                        });
+                        let repair = match app.repair_program(&mut program) {
+                            Ok(repair) => repair,
+                            Err(err) => Repair::Failed(err),
+                        };
+
+                        app.events = Reporter::default();
+
+                        if let Some(forward) = forward {
+                            forward.join().unwrap_or(());
+                        }
+
+                        results_tx.send((index, program, repair)).unwrap_or(());
+                    }
+                })
+            })
+            .collect();
+
+        drop(results_tx);
+        drop(tx);
+
+        let mut results: Vec<Option<Repair>> = names.iter().map(|_| None).collect();
+
+        for (index, program, repair) in results_rx {
+            if let Repair::Rebuilt | Repair::Retagged | Repair::Pulled = repair {
+                self.config.update(&program)?;
+            }
+
+            results[index] = Some(repair);
+        }
+
+        for worker in workers {
+            worker.join().unwrap_or(());
+        }
+
+        Ok(names
+            .into_iter()
+            .zip(results)
+            .map(|(name, repair)| {
+                let repair = repair.unwrap_or_else(|| {
+                    Repair::Failed(AppError::Program(format!(
+                        "Repair of {} was interrupted",
+                        name
+                    )))
+                });
+
+                (name, repair)
+            })
+            .collect())
+    }
+
+    fn repair_program(&mut self, program: &mut Program) -> AppResult<Repair> {
+        let tag = program.get_name(self.prefix.as_str());
+
+        // A fresh image is pulled even if the tag exists, the source might be updated
+        if program.source_image.is_some() {
+            return match self.pull_image(program) {
+                Ok(_) => {
+                    self.config.update(program)?;
+                    Ok(Repair::Pulled)
+                }
+                Err(err) if self.backend.image_exists(&tag)? => {
+                    warn!(
+                        "Image of {} isn't updated: {}",
+                        program.get_name_short(),
+                        err
+                    );
+                    Ok(Repair::Intact)
+                }
+                Err(err) => Err(err),
+            };
+        }
+
+        if self.backend.image_exists(&tag)? {
+            return Ok(Repair::Intact);
+        }
+
+        let name = program.get_name_short();
+        let labeled = self
+            .backend
+            .managed_images()?
+            .into_iter()
+            .find(|image| image.program.as_ref() == Some(&name));
+
+        if let Some(image) = labeled {
+            self.backend.tag(&image.id, &tag)?;
+            program.image_id = Some(image.id);
+            self.config.update(program)?;
+
+            return Ok(Repair::Retagged);
+        }
+
+        if !program.path.exists() {
+            return Ok(Repair::Skipped(format!(
+                "Source package '{}' doesn't exist",
+                program.path.display()
+            )));
+        }
+
+        self.rebuild_image(program)?;
+        self.config.update(program)?;
+
+        Ok(Repair::Rebuilt)
+    }
+
+    /// Builds the image of a stored program again from its package with the settings of its
+    /// snapshot and its local repository
+    fn rebuild_image(&mut self, program: &mut Program) -> AppResult<()> {
+        let deb = Deb::try_new(&program.path)?;
+        let mut overrides = BuildSettings::new(&None, &None, &program.snapshot);
+
+        overrides.local_repo = program.local_repo.to_owned();
+
+        let build = self.config.build.merge(&overrides);
+
+        build.validate()?;
+        self.build_image(&deb, program, &build, false)?;
+
+        Ok(())
+    }
+
+    /// Directory the backups of removed programs are kept in, e.g.
+    /// `~/.local/state/debian_bridge/trash`, a profile has its own subdirectory
+    fn trash_dir(&self) -> AppResult<PathBuf> {
+        let dir = util::state_dir()?.join(&self.package_name).join("trash");
+
+        Ok(match &self.profile {
+            Some(profile) => dir.join(profile),
+            None => dir,
+        })
+    }
+
+    /// Directory the logs of the program are kept in, e.g.
+    /// `~/.local/state/debian_bridge/logs/foo`, `foo@work` in a profile
+    fn log_dir(&self, name: &str) -> AppResult<PathBuf> {
+        let dir = util::state_dir()?.join(&self.package_name).join("logs");
+
+        Ok(match &self.profile {
+            Some(profile) => dir.join(format!("{}@{}", name, profile)),
+            None => dir.join(name),
+        })
+    }
+
+    /// Log directories of the programs of the profile, the ones of removed programs included
+    fn log_dirs(&self) -> AppResult<Vec<PathBuf>> {
+        let dir = util::state_dir()?.join(&self.package_name).join("logs");
+
+        Ok(std::fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        let name = path.file_name().and_then(|name| name.to_str());
+                        let profile = name.and_then(|name| name.split_once('@')).map(|(_, p)| p);
+
+                        path.is_dir() && name.is_some() && profile == self.profile.as_deref()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Builds an image of the program on top of the base image. An image of another prefix under
+    /// the same name is overwritten only if `force` is set, see `Backend::create`
+    fn build_image(
+        &mut self,
+        deb: &Deb,
+        program: &mut Program,
+        build: &BuildSettings,
+        force: bool,
+    ) -> AppResult<&Self> {
+        let offline = self.check_network(
+            &format!("build the image of {}", program.get_name_short()),
+            self.build_network_steps(program, build)?,
+            build,
+        )?;
+        let base = self.prepare_base(build, false)?;
+
+        program.base = Some(base.to_owned());
+
+        let mut context = BuildContext::new(&self.cache_path)?;
+
+        context.add_file("tmp.deb", &program.path)?;
+
+        let mut labels = BuildSpec::managed_labels(&self.prefix);
+
+        labels.push((PROGRAM_LABEL.to_string(), program.get_name_short()));
+
+        match util::sha256_file(&program.path) {
+            Ok(sha) => labels.push((CREATED_FROM_LABEL.to_string(), sha)),
+            Err(err) => warn!(
+                "Can't hash the package, the image won't be labeled with it: {}",
+                err
+            ),
+        }
+
+        let spec = BuildSpec {
+            buildkit: self.buildkit(build)?,
+            args: build.build_args(),
+            labels,
+            contexts: build_contexts(build),
+            cancel: self.cancel.to_owned(),
+            ..Default::default()
+        };
+        let dockerfile = util::gen_dockerfile(
+            deb,
+            program,
+            &base,
+            spec.buildkit,
+            build,
+            self.backend.system(),
+            offline,
+        )?;
+
+        debug!("Generated dockerfile:\n{}", dockerfile);
+        self.events.emit(AppEvent::DockerfileGenerated {
+            dockerfile: dockerfile.to_owned(),
+        });
+
+        std::fs::write(context.path().join("Dockerfile"), dockerfile)?;
+
+        let tag = program.get_name(self.prefix.as_str());
+        let before = self.backend.image_id(&tag).ok();
+
+        if let Err(err) = self.backend.create(
+            &program.get_image_name(),
+            &context,
+            &spec,
+            &self.events,
+            force,
+        ) {
+            self.untag_cancelled(&tag, before);
+            return Err(err);
+        }
+
+        program.image_id = self
+            .backend
+            .image_id(&program.get_name(self.prefix.as_str()))
+            .map_err(|err| warn!("Can't get ID of the built image: {}", err))
+            .ok();
+        program.image_built = Some(chrono::Utc::now());
+        self.events.emit(AppEvent::ImageBuilt {
+            id: program.image_id.to_owned(),
+        });
+
+        Ok(self)
+    }
+
+    /// Builds a new image of the program like `build_image`, the current one is tagged
+    /// `previous` to roll back to. The image previous until then is removed, unless the build
+    /// fails or yields the same image
+    fn update_image(
+        &mut self,
+        deb: &Deb,
+        program: &mut Program,
+        build: &BuildSettings,
+    ) -> AppResult<()> {
+        let tag = program.get_name(self.prefix.as_str());
+        let previous = previous_tag(&tag);
+        // The current image is held by a temporary tag until the new one is built
+        let held = format!("{}:replaced", tag);
+        let built_at = program.image_built;
+        let current = match self.backend.image_exists(&tag)? {
+            true => {
+                self.backend.tag(&tag, &held)?;
+                Some(self.backend.image_id(&tag)?)
+            }
+            false => None,
+        };
+        let built = self.build_image(deb, program, build, false).map(|_| ());
+        let id = match current {
+            Some(id) if built.is_ok() && program.image_id.as_ref() != Some(&id) => id,
+            Some(_) => {
+                if let Err(err) = self.backend.delete_image(&held) {
+                    warn!("Image {} is left in place: {}", held, err);
+                }
+
+                return built;
+            }
+            None => return built,
+        };
+        let replaced = self.backend.image_id(&previous).ok();
+
+        self.backend.retag(&held, &previous)?;
+
+        if let Some(replaced) = replaced.filter(|replaced| replaced != &id) {
+            match self.backend.delete_image(&replaced) {
+                Ok(_) | Err(AppError::DockerStatus { code: 404, .. }) => (),
+                Err(err) => warn!(
+                    "Image {} replaced before is left in place: {}",
+                    replaced, err
+                ),
+            }
+        }
+
+        program.previous_image_id = Some(id);
+        program.previous_image_built = built_at;
+
+        Ok(())
+    }
+
+    /// Steps of a build of the program image which need the network: a missing base image is
+    /// pulled and built with apt, the dependencies of the program and its features are
+    /// installed with apt
+    fn build_network_steps(
+        &self,
+        program: &Program,
+        build: &BuildSettings,
+    ) -> AppResult<Vec<NetworkStep>> {
+        let mut steps = vec![];
+        let base = util::gen_base_tag(self.prefix.as_str(), build)?;
+
+        if !self.backend.image_exists(&base)? {
+            let image = build
+                .base_image
+                .to_owned()
+                .unwrap_or_else(|| util::BASE_IMAGE.to_string());
+
+            if !self.backend.image_exists(&image)? {
+                steps.push(NetworkStep::Pull { image });
+            }
+
+            steps.push(NetworkStep::BaseBuild { tag: base });
+        }
+
+        // apt reads a local repository from the disk
+        if build.local_repo.is_some() {
+            steps.retain(|step| matches!(step, NetworkStep::Pull { .. }));
+            return Ok(steps);
+        }
+
+        let packages: Vec<String> = program
+            .deps
+            .iter()
+            .flat_map(|deps| deps.split_whitespace())
+            .chain(util::feature_packages(
+                &program.features(),
+                self.backend.system(),
+            ))
+            .map(str::to_string)
+            .collect();
+
+        if !packages.is_empty() {
+            steps.push(NetworkStep::AptInstall { packages });
+        }
+
+        Ok(steps)
+    }
+
+    /// Whether `operation` runs offline, it's refused then if any of `steps` needs the network.
+    /// `NetworkMode::Detect` probes the apt source of `build`
+    fn check_network(
+        &self,
+        operation: &str,
+        steps: Vec<NetworkStep>,
+        build: &BuildSettings,
+    ) -> AppResult<bool> {
+        let offline = match self.network {
+            NetworkMode::Online => false,
+            NetworkMode::Offline => true,
+            NetworkMode::Detect => !*self.online.get_or_init(|| {
+                let url = util::apt_url(build);
+                let online = util::is_url_reachable(&url, &build.proxy);
+
+                if !online {
+                    warn!("{} is unreachable, working offline", url);
+                }
+
+                online
+            }),
+        };
+
+        match offline && !steps.is_empty() {
+            true => Err(AppError::Offline {
+                operation: operation.to_string(),
+                steps,
+            }),
+            false => Ok(offline),
+        }
+    }
+
+    /// Command and kind of a program the request doesn't set, detected from the files of the
+    /// package. The package name and a graphical program are used if they can't be read
+    fn detect_program(
+        &self,
+        request: &CreateRequest,
+        deb: &Deb,
+    ) -> AppResult<(String, ProgramKind)> {
+        if let (Some(command), Some(kind)) = (&request.command, request.kind) {
+            return Ok((command.to_owned(), kind));
+        }
+
+        let files = match Deb::list_files(&request.path) {
+            Ok(files) => files,
+            Err(err) => {
+                let fallback = match (&request.command, request.kind) {
+                    (Some(_), _) => "it's taken for a graphical program",
+                    (None, Some(_)) => "its name is used as the command",
+                    (None, None) => "its name is used as the command of a graphical program",
+                };
+
+                warn!("Can't read files of {}, {}: {}", deb.package, fallback, err);
+                return Ok((
+                    request.command.to_owned().unwrap_or(deb.package.to_owned()),
+                    request.kind.unwrap_or_default(),
+                ));
+            }
+        };
+        let kind = request.kind.unwrap_or_else(|| deb::detect_kind(&files));
+        let command = match &request.command {
+            Some(command) => command.to_owned(),
+            None => {
+                let entries: Vec<String> = deb::desktop_entries(&files)
+                    .iter()
+                    .filter_map(|entry| Deb::read_file(&request.path, entry).ok())
+                    .collect();
+
+                deb::detect_command(&deb.package, &files, &entries)?
+            }
+        };
+
+        Ok((command, kind))
+    }
+
+    /// Checks that the command of the program can start in its image
+    fn check_command(&self, program: &Program) -> AppResult<()> {
+        let (code, output) = self.backend.probe(
+            &program.get_name(self.prefix.as_str()),
+            &util::command_probe(&program.command),
+        )?;
+
+        match code {
+            0 => Ok(()),
+            util::PROBE_MISSING => {
+                let candidates = util::command_candidates(&output, &program.command);
+
+                Err(AppError::Program(format!(
+                    "Command '{}' isn't found in the image, pass the right one with --command{}",
+                    program.command,
+                    match candidates.is_empty() {
+                        true => String::new(),
+                        false => format!(". Executables found:\n\t{}", candidates.join("\n\t")),
+                    }
+                )))
+            }
+            util::PROBE_MISSING_LIBS => Err(AppError::Program(format!(
+                "Command '{}' misses shared libraries, add the packages providing them with \
+                 --deps:\n\t{}",
+                program.command,
+                output.trim().replace('\n', "\n\t")
+            ))),
+            code => Err(AppError::Program(format!(
+                "Can't check command '{}' in the image, the probe exited with {}, pass \
+                 --skip-check to create the program anyway",
+                program.command, code
+            ))),
+        }
+    }
+
+    /// Whether images are built with BuildKit. A local repository is passed as a named build
+    /// context, a build from it is refused without BuildKit of docker 23.0
+    fn buildkit(&self, settings: &BuildSettings) -> AppResult<bool> {
+        let buildkit = self.backend.use_buildkit();
+        let version = &self.backend.system().docker_version;
+
+        if settings.local_repo.is_some()
+            && !(buildkit
+                && version
+                    .api()
+                    .is_some_and(|v| v >= BUILD_CONTEXT_API_VERSION))
+        {
+            return Err(AppError::Feature(format!(
+                "Docker API {} can't build from a local repository (requires {}.{} with \
+                 BuildKit), build without --local-repo or upgrade Docker",
+                version, BUILD_CONTEXT_API_VERSION.0, BUILD_CONTEXT_API_VERSION.1
+            )));
+        }
+
+        Ok(buildkit)
+    }
+
+    fn prepare_base(&mut self, settings: &BuildSettings, force: bool) -> AppResult<String> {
+        let tag = util::gen_base_tag(self.prefix.as_str(), settings)?;
+
+        if !force && self.backend.image_exists(&tag)? {
+            debug!("Base image '{}' is up to date", tag);
+            return Ok(tag);
+        }
+
+        let context = BuildContext::new(&self.cache_path)?;
+        // Without a local repository the base builds the same with the legacy builder
+        let buildkit = settings.local_repo.is_some() && self.buildkit(settings)?;
+
+        std::fs::write(
+            context.path().join("Dockerfile"),
+            util::gen_base_dockerfile(settings, buildkit)?,
+        )?;
+
+        if force {
+            self.backend
+                .pull(settings.base_image.as_deref().unwrap_or(util::BASE_IMAGE))?;
+        }
+
+        let spec = BuildSpec {
+            nocache: force,
+            buildkit,
+            args: settings.build_args(),
+            labels: BuildSpec::managed_labels(&self.prefix),
+            contexts: build_contexts(settings),
+            cancel: self.cancel.to_owned(),
+        };
+        let before = self.backend.image_id(&tag).ok();
+
+        if let Err(err) = self.backend.build(&context, &tag, &spec, &self.events) {
+            self.untag_cancelled(&tag, before);
+            return Err(err);
+        }
+
+        Ok(tag)
+    }
+
+    /// Moves the tag back to the image it had before a cancelled build, `before`. The build may
+    /// have finished just as it was cancelled, its image is removed then
+    fn untag_cancelled(&self, tag: &str, before: Option<String>) {
+        if !self.cancel.is_cancelled() {
+            return;
+        }
+
+        let built = match self.backend.image_id(tag) {
+            Ok(id) if Some(&id) != before.as_ref() => id,
+            _ => return,
+        };
+        let restored = match before {
+            Some(id) => self.backend.tag(&id, tag),
+            None => Ok(()),
+        };
+
+        if let Err(err) = restored.and_then(|_| self.backend.delete_image(&built)) {
+            warn!(
+                "Image {} of the cancelled build is left in place: {}",
+                tag, err
+            );
+        }
+    }
+
+    /// Drops a program whose command check failed. Its image is removed if it can be, the
+    /// program is dropped from the config either way so the check error reaches the user
+    fn drop_unchecked(&mut self, program: &Program) -> AppResult<()> {
+        if let Err(err) = self.backend.delete(program) {
+            warn!(
+                "Image of {} is left in place: {}",
+                program.get_name_short(),
+                err
+            );
+        }
+
+        self.config.remove(program)?;
+        Ok(())
+    }
+
+    fn rename_entry(&self, from: &str, to: &str) -> AppResult<&Self> {
+        let dir = util::desktop_dir()?;
+        let source = dir.join(self.entry_file(from));
+        let entry = std::fs::read_to_string(&source)
+            .map_err(|err| AppError::desktop_entry("Can not read a desktop entry", err))?;
+
+        std::fs::write(
+            dir.join(self.entry_file(to)),
+            util::rename_desktop_entry(&entry, from, to),
+        )
+        .and_then(|_| std::fs::remove_file(&source))
+        .map_err(|err| AppError::desktop_entry("Can not write a desktop entry", err))?;
+
+        Ok(self)
+    }
+
+    /// Name of the systemd unit of the program, e.g. `debian-bridge-foo.service`
+    fn service_name(&self, program: &Program) -> String {
+        format!(
+            "{}-{}.service",
+            self.prefix.replace('_', "-"),
+            program.get_name_short()
+        )
+    }
+
+    fn service_path(&self, program: &Program) -> AppResult<PathBuf> {
+        Ok(util::systemd_user_dir()?.join(self.service_name(program)))
+    }
+
+    fn write_service(&self, program: &Program) -> AppResult<PathBuf> {
+        let unit = util::gen_service_unit(
+            &self.package_name,
+            program.get_name_short(),
+            program.restart,
+            self.profile.as_deref(),
+        )?;
+        let path = self.service_path(program)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| {
+                AppError::Environment(format!("Can not create {}: {}", dir.display(), err))
+            })?;
+        }
+
+        std::fs::write(&path, unit)?;
+        util::systemctl(&["daemon-reload"])?;
+
+        Ok(path)
+    }
+
+    /// Disables the service of the program and deletes its unit, returns its path if there
+    /// was one
+    fn remove_service(&self, program: &Program) -> AppResult<Option<PathBuf>> {
+        let path = self.service_path(program)?;
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        util::systemctl(&["disable", "--now", &self.service_name(program)])?;
+        std::fs::remove_file(&path)?;
+        util::systemctl(&["daemon-reload"])?;
+
+        Ok(Some(path))
+    }
+
+    fn autostart_path(&self, program: &Program) -> AppResult<PathBuf> {
+        Ok(util::autostart_dir()?.join(format!(
+            "{}.desktop",
+            program.get_name(self.prefix.as_str())
+        )))
+    }
+
+    fn create_autostart(&self, program: &Program) -> AppResult<PathBuf> {
+        let entry = util::gen_autostart_entry(
+            &self.package_name,
+            program.get_name_short(),
+            program.icon.as_ref().map(|icon| icon.path.as_path()),
+            self.profile.as_deref(),
+        )?;
+        let path = self.autostart_path(program)?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| {
+                AppError::desktop_entry("Can not create an autostart directory", err)
+            })?;
+        }
+
+        std::fs::write(&path, entry)
+            .map_err(|err| AppError::desktop_entry("Can not write an autostart entry", err))?;
+
+        Ok(path)
+    }
+
+    /// Deletes the autostart entry of the program, returns its path if there was one
+    fn remove_autostart(&self, program: &Program) -> AppResult<Option<PathBuf>> {
+        let path = self.autostart_path(program)?;
+
+        match std::fs::remove_file(&path) {
+            Ok(_) => Ok(Some(path)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(AppError::desktop_entry(
+                "Can not remove an autostart entry",
+                err,
+            )),
+        }
+    }
+
+    /// File name of the desktop entry of the program, e.g. `foo@work.desktop` in a profile
+    fn entry_file(&self, name: &str) -> String {
+        match &self.profile {
+            Some(profile) => format!("{}@{}.desktop", name, profile),
+            None => format!("{}.desktop", name),
+        }
+    }
+
+    /// Writes the wrapper of the program at `path`, see `install_wrapper`
+    fn write_wrapper(&self, program: &Program, path: &Path) -> AppResult<()> {
+        let executable = util::get_package_path(&self.package_name)?;
+        let global_args: Vec<&str> = self
+            .profile
+            .iter()
+            .flat_map(|profile| vec!["--profile", profile.as_str()])
+            .collect();
+
+        wrapper::write(
+            path,
+            &wrapper::script(&executable, &global_args, &program.get_name_short()),
+        )
+    }
+
+    fn create_entry(&self, icon: &Icon, name: &str, comment: &str) -> AppResult<PathBuf> {
+        let entry = util::gen_desktop_entry(
+            &self.package_name,
+            name,
+            comment,
+            &icon.path,
+            self.profile.as_deref(),
+        );
+
+        let entry = entry?;
+        let mut path = util::desktop_dir()?;
+
+        debug!("Generated new entry in '{}':\n{}", path.display(), entry);
+
+        if !path.exists() {
+            std::fs::create_dir(&path).map_err(|err| {
+                AppError::desktop_entry("Can not create a desktop directory", err)
+            })?;
+        }
+
+        path.push(self.entry_file(name));
+
+        std::fs::write(&path, entry)
+            .map_err(|err| AppError::desktop_entry("Can not write a desktop entry", err))?;
+
+        Ok(path)
+    }
+}
+
+/// Program names end up in image, container and file names
+fn validate_name(name: &str) -> AppResult<()> {
+    match !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+    {
+        true => Ok(()),
+        false => Err(AppError::Program(format!(
+            "Invalid name '{}', use lowercase letters, digits, '.', '_' and '-' only",
+            name
+        ))),
+    }
+}
+
+/// Checksum written next to an exported image, `<path>.sha256` like `sha256sum` output
+fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+
+    name.push(".sha256");
+    PathBuf::from(name)
+}
+
+/// Named build contexts of a build, the local repository if it has one
+fn build_contexts(settings: &BuildSettings) -> Vec<(String, PathBuf)> {
+    settings
+        .local_repo
+        .iter()
+        .map(|dir| (repo::CONTEXT_NAME.to_string(), dir.to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::driver::{DisplayServer, DockerVersion, WindowManager};
+
+    fn get_features() -> FeaturesList {
+        FeaturesList::new(&System {
+            display: Some(DisplayServer {
+                kind: WindowManager::X11,
+                socket: None,
+            }),
+            sound: None,
+            input_method: None,
+            secret_service: false,
+            portal: false,
+            bluetooth: false,
+            avahi: false,
+            scanners: vec![],
+            saned: false,
+            gamepads: vec![],
+            docker_version: DockerVersion("1.40".to_string()),
+            docker_server: None,
+            arch: "x86_64".to_string(),
+            runtime_dir: None,
+            rootless: false,
+            apparmor: false,
+            selinux: false,
+        })
+    }
+
+    #[test]
+    fn test_builder() {
+        fn assert_send<T: Send + 'static>() {}
+
+        assert_send::<App>();
+
+        let system = System {
+            docker_version: DockerVersion("1.40".to_string()),
+            ..System::offline()
+        };
+        let app = App::builder()
+            .prefix("foo_prefix")
+            .cache_path(Path::new("/tmp"))
+            .system(system)
+            .build()
+            .unwrap();
+
+        assert_eq!(app.prefix, "foo_prefix");
+        assert_eq!(app.cache_path, Path::new("/tmp"));
+        assert!(app.list().is_empty());
+    }
+
+    #[test]
+    fn test_features_serialize() {
+        let data = serde_json::to_string(&get_features()).unwrap();
+        let positions = [
+            "Display",
+            "Sound",
+            "Notification",
+            "Devices",
+            "HomePersistent",
+            "Time",
+        ]
+        .iter()
+        .map(|feature| data.find(&format!("\"{}\"", feature)).unwrap())
+        .collect::<Vec<usize>>();
+        let features: serde_json::Value = serde_json::from_str(&data).unwrap();
+
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            features["Display"],
+            serde_json::json!({ "available": true })
+        );
+        assert_eq!(features["Sound"]["available"], serde_json::json!(false));
+        assert!(features["Sound"]["reason"].is_string());
+    }
+
+    #[test]
+    fn test_features_display() {
+        let output = get_features().to_string();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), get_features().iter().count());
+        assert!(lines[0].starts_with("\tDisplay         ===> "));
+        assert!(lines.iter().all(|line| line.find("===>") == Some(17)));
+        assert!(lines[1].contains("unavailable"));
+        assert!(output.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_features_validate() {
+        let features = get_features();
+
+        assert!(features
+            .validate(&[Feature::Display, Feature::Time])
+            .is_ok());
+
+        let problems = features
+            .validate(&[Feature::Sound, Feature::Display])
+            .unwrap_err();
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].feature, Feature::Sound);
     }
 }
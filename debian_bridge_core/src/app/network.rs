@@ -0,0 +1,70 @@
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// Whether operations may use the network, see `App::network`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NetworkMode {
+    /// The network is assumed to be reachable
+    #[default]
+    Online,
+    /// The network is probed once an operation needs it, it's offline if the probe fails
+    Detect,
+    /// Operations needing the network are refused up front
+    Offline,
+}
+
+/// Step of an operation which needs the network, e.g. to tell why it's refused offline
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum NetworkStep {
+    /// Pull of an image missing locally
+    Pull { image: String },
+    /// Build of a missing base image, apt downloads the package lists and the base packages
+    BaseBuild { tag: String },
+    /// Packages apt downloads into the program image
+    AptInstall { packages: Vec<String> },
+}
+
+impl Display for NetworkStep {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            NetworkStep::Pull { image } => write!(f, "pull {}", image),
+            NetworkStep::BaseBuild { tag } => {
+                write!(f, "build the base image {} with apt-get update", tag)
+            }
+            NetworkStep::AptInstall { packages } => {
+                write!(f, "apt-get install {}", packages.join(" "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            NetworkStep::Pull {
+                image: "debian:9-slim".to_string()
+            }
+            .to_string(),
+            "pull debian:9-slim"
+        );
+        assert_eq!(
+            NetworkStep::AptInstall {
+                packages: vec!["libfoo".to_string(), "socat".to_string()]
+            }
+            .to_string(),
+            "apt-get install libfoo socat"
+        );
+        assert_eq!(
+            serde_json::to_value(NetworkStep::BaseBuild {
+                tag: "debian_bridge_base:1".to_string()
+            })
+            .unwrap(),
+            serde_json::json!({ "step": "base-build", "tag": "debian_bridge_base:1" })
+        );
+    }
+}
@@ -0,0 +1,427 @@
+use super::{
+    error::AppError,
+    usage::human_size,
+    util::{home_dir, parse_device, parse_share, parse_size},
+    AppResult, ContainerMode, CreateRequest, Feature, ProgramKind, Share,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+/// Directory of the config directory user presets are read from, one `<name>.toml` each
+pub const PRESETS_DIR: &str = "presets";
+
+/// Settings a new program starts from, e.g. the features every browser needs. Built-in
+/// presets come with debian_bridge, user ones are TOML files in `PRESETS_DIR`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Preset {
+    pub name: String,
+    /// File of a user preset, `None` for a built-in one
+    pub file: Option<PathBuf>,
+    pub features: Vec<Feature>,
+    /// Size of `/dev/shm` in bytes
+    pub shm_size: Option<u64>,
+    /// Device patterns passed through, see `parse_device`
+    pub devices: Vec<String>,
+    /// Host paths mounted into every container
+    pub shares: Vec<Share>,
+    pub container_mode: Option<ContainerMode>,
+    pub kind: Option<ProgramKind>,
+}
+
+/// Preset as its file spells it, features are named as their flags, e.g. `home`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct PresetFile {
+    #[serde(default)]
+    features: Vec<String>,
+    shm_size: Option<String>,
+    #[serde(default)]
+    devices: Vec<String>,
+    /// `PATH` or `PATH:ro`, see `parse_share`, `~/` stands for the home
+    #[serde(default)]
+    shares: Vec<String>,
+    container_mode: Option<String>,
+    kind: Option<String>,
+}
+
+impl Preset {
+    /// Presets shipped with debian_bridge
+    pub fn builtin() -> Vec<Preset> {
+        let preset = |name: &str, features: &[Feature]| Preset {
+            name: name.to_string(),
+            features: features.to_vec(),
+            ..Default::default()
+        };
+
+        vec![
+            Preset {
+                shm_size: Some(2 << 30),
+                ..preset(
+                    "browser",
+                    &[
+                        Feature::Display,
+                        Feature::Sound,
+                        Feature::Notification,
+                        Feature::Time,
+                        Feature::HostTheme,
+                        Feature::InputMethod,
+                        Feature::Portals,
+                    ],
+                )
+            },
+            Preset {
+                devices: vec!["/dev/video*".to_string()],
+                ..preset(
+                    "chat",
+                    &[
+                        Feature::Display,
+                        Feature::Sound,
+                        Feature::Notification,
+                        Feature::Time,
+                        Feature::HostTheme,
+                        Feature::InputMethod,
+                        Feature::OpenUrls,
+                    ],
+                )
+            },
+            preset(
+                "office",
+                &[
+                    Feature::Display,
+                    Feature::HomePersistent,
+                    Feature::Time,
+                    Feature::HostTheme,
+                    Feature::InputMethod,
+                    Feature::OpenUrls,
+                ],
+            ),
+            preset(
+                "media",
+                &[
+                    Feature::Display,
+                    Feature::Sound,
+                    Feature::HomePersistent,
+                    Feature::HostTheme,
+                ],
+            ),
+            preset(
+                "game",
+                &[
+                    Feature::Display,
+                    Feature::Sound,
+                    Feature::Gamepad,
+                    Feature::Time,
+                ],
+            ),
+            Preset {
+                kind: Some(ProgramKind::Cli),
+                ..preset("cli", &[Feature::HomePersistent, Feature::Time])
+            },
+        ]
+    }
+
+    /// Reads a user preset, its name is the file name without `.toml`
+    pub fn read(path: &Path) -> AppResult<Self> {
+        let invalid = |reason: String| {
+            AppError::Config(
+                format!("Preset {} is invalid: {}", path.display(), reason),
+                None,
+            )
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| {
+            AppError::config(format!("Can't read the preset {}", path.display()), err)
+        })?;
+        let file: PresetFile = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+
+        Ok(Preset {
+            name: path
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            file: Some(path.to_owned()),
+            features: file
+                .features
+                .iter()
+                .map(|feature| feature.parse())
+                .collect::<AppResult<Vec<Feature>>>()
+                .map_err(|err| invalid(err.to_string()))?,
+            shm_size: file
+                .shm_size
+                .map(|size| parse_size(&size))
+                .transpose()
+                .map_err(|err| invalid(err.to_string()))?,
+            devices: file
+                .devices
+                .iter()
+                .map(|device| parse_device(device))
+                .collect::<AppResult<Vec<String>>>()
+                .map_err(|err| invalid(err.to_string()))?,
+            shares: file
+                .shares
+                .iter()
+                .map(|share| read_share(share))
+                .collect::<AppResult<Vec<Share>>>()
+                .map_err(|err| invalid(err.to_string()))?,
+            container_mode: file
+                .container_mode
+                .map(|mode| mode.parse())
+                .transpose()
+                .map_err(|err: AppError| invalid(err.to_string()))?,
+            kind: file
+                .kind
+                .map(|kind| kind.parse())
+                .transpose()
+                .map_err(|err: AppError| invalid(err.to_string()))?,
+        })
+    }
+
+    /// Built-in presets followed by the user ones of `dir`, a user preset replaces the
+    /// built-in one of its name. Files which can't be read are skipped
+    pub fn all(dir: &Path) -> Vec<Preset> {
+        let mut user: Vec<Preset> = std::fs::read_dir(dir)
+            .map(|files| {
+                files
+                    .filter_map(Result::ok)
+                    .map(|file| file.path())
+                    .filter(|path| path.is_file() && path.extension() == Some("toml".as_ref()))
+                    .filter_map(|path| match Preset::read(&path) {
+                        Ok(preset) => Some(preset),
+                        Err(err) => {
+                            warn!("{}, it's skipped", err);
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        user.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut presets: Vec<Preset> = Preset::builtin()
+            .into_iter()
+            .filter(|builtin| !user.iter().any(|preset| preset.name == builtin.name))
+            .collect();
+
+        presets.append(&mut user);
+        presets
+    }
+
+    /// Preset of the name, the user one of `dir` if there is one
+    pub fn find(dir: &Path, name: &str) -> AppResult<Preset> {
+        let path = dir.join(format!("{}.toml", name));
+
+        if !name.contains('/') && path.is_file() {
+            return Preset::read(&path);
+        }
+
+        let presets = Preset::all(dir);
+
+        presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .cloned()
+            .ok_or_else(|| {
+                AppError::Argument(format!(
+                    "Unknown preset '{}', expected one of {}",
+                    name,
+                    presets
+                        .iter()
+                        .map(|preset| preset.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ))
+            })
+    }
+
+    /// Request with the settings of the preset, features are added to the ones it has
+    pub fn apply(&self, request: CreateRequest) -> CreateRequest {
+        let mut request = request.features(&self.features);
+
+        if let Some(size) = self.shm_size {
+            request = request.shm_size(size);
+        }
+
+        if !self.devices.is_empty() {
+            request = request.devices(&self.devices);
+        }
+
+        for share in &self.shares {
+            request = request.share(&share.path, share.read_only);
+        }
+
+        if let Some(mode) = self.container_mode {
+            request = request.container_mode(mode);
+        }
+
+        if let Some(kind) = self.kind {
+            request = request.kind(kind);
+        }
+
+        request
+    }
+
+    /// Part of the preset the request has, values given otherwise, e.g. with flags, are left
+    /// out
+    pub fn applied(&self, request: &CreateRequest) -> Preset {
+        Preset {
+            shm_size: self.shm_size.filter(|size| request.shm_size == Some(*size)),
+            devices: match request.devices == self.devices {
+                true => self.devices.to_owned(),
+                false => vec![],
+            },
+            shares: self
+                .shares
+                .iter()
+                .filter(|share| request.shares.contains(share))
+                .cloned()
+                .collect(),
+            container_mode: self
+                .container_mode
+                .filter(|mode| request.container_mode == *mode),
+            kind: self.kind.filter(|kind| request.kind == Some(*kind)),
+            ..self.to_owned()
+        }
+    }
+}
+
+/// Share of a preset file, a path in the home may start with `~/`
+fn read_share(share: &str) -> AppResult<Share> {
+    let (path, read_only) = parse_share(share)?;
+    let path = match path.strip_prefix("~") {
+        Ok(relative) => home_dir()?.join(relative),
+        Err(_) => path,
+    };
+
+    match path.is_absolute() {
+        true => Ok(Share { path, read_only }),
+        false => Err(AppError::Argument(format!(
+            "Shared path {} isn't absolute",
+            path.display()
+        ))),
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        if !self.features.is_empty() {
+            writeln!(
+                f,
+                "\t{:<15} ===> {}",
+                "Features",
+                self.features
+                    .iter()
+                    .map(Feature::name)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )?;
+        }
+
+        if let Some(size) = self.shm_size {
+            writeln!(f, "\t{:<15} ===> {}", "Shm size", human_size(size))?;
+        }
+
+        if !self.devices.is_empty() {
+            writeln!(f, "\t{:<15} ===> {}", "Devices", self.devices.join(", "))?;
+        }
+
+        if !self.shares.is_empty() {
+            writeln!(
+                f,
+                "\t{:<15} ===> {}",
+                "Shared paths",
+                self.shares
+                    .iter()
+                    .map(Share::to_string)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?;
+        }
+
+        if let Some(mode) = self.container_mode {
+            writeln!(f, "\t{:<15} ===> {}", "Container mode", mode)?;
+        }
+
+        if let Some(kind) = self.kind {
+            writeln!(f, "\t{:<15} ===> {}", "Kind", kind)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_presets_{}", std::process::id()));
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("browser.toml"),
+            "features = [\"display\", \"sound\"]\nshm-size = \"1g\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("tools.toml"),
+            "features = [\"home\"]\ndevices = [\"/dev/ttyUSB*\"]\nkind = \"cli\"\n\
+             shares = [\"/srv/data:ro\"]\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.toml"), "features = [\"wifi\"]\n").unwrap();
+        std::fs::write(dir.join("relative.toml"), "shares = [\"data\"]\n").unwrap();
+
+        let presets = Preset::all(&dir);
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+
+        assert_eq!(
+            names,
+            vec!["chat", "office", "media", "game", "cli", "browser", "tools"]
+        );
+        assert!(Preset::builtin().iter().all(|preset| preset.file.is_none()));
+
+        let browser = Preset::find(&dir, "browser").unwrap();
+
+        assert_eq!(browser.features, vec![Feature::Display, Feature::Sound]);
+        assert_eq!(browser.shm_size, Some(1 << 30));
+        assert_eq!(browser.file, Some(dir.join("browser.toml")));
+        let tools = Preset::find(&dir, "tools").unwrap();
+        let request = tools.apply(CreateRequest::from_deb(Path::new("foo.deb")));
+
+        assert_eq!(tools.kind, Some(ProgramKind::Cli));
+        assert_eq!(
+            request.shares,
+            vec![Share {
+                path: PathBuf::from("/srv/data"),
+                read_only: true
+            }]
+        );
+        assert_eq!(tools.applied(&request).shares, request.shares);
+        assert!(Preset::find(&dir, "relative").is_err());
+        assert!(Preset::find(&dir, "broken").is_err());
+        assert!(Preset::find(&dir, "missing").is_err());
+        assert!(Preset::find(&dir, "chat").unwrap().file.is_none());
+
+        let request = browser
+            .apply(CreateRequest::from_deb(Path::new("foo.deb")).feature(Feature::Time))
+            .shm_size(512 << 20);
+        let applied = browser.applied(&request);
+
+        assert_eq!(
+            request.features,
+            vec![Feature::Time, Feature::Display, Feature::Sound]
+        );
+        assert_eq!(applied.shm_size, None);
+        assert_eq!(
+            applied.to_string(),
+            "\tFeatures        ===> display, sound\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use super::{error::AppError, AppResult};
+use std::path::Path;
+
+/// Where a local repository is mounted during a build
+pub const REPO_DIR: &str = "/srv/debian-bridge-repo";
+/// Named build context the repository is passed in with `--build-context`
+pub const CONTEXT_NAME: &str = "repo";
+
+/// File based apt repository builds install from instead of the network, e.g. a mirror of
+/// the Debian archive made with debmirror
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalRepo {
+    /// Mirror with a `dists/` directory, the suites of the release it has with their
+    /// components which have packages, e.g. `stretch` and `stretch-updates` with `main`
+    Archive { suites: Vec<(String, Vec<String>)> },
+    /// Directory with a `Packages` index next to the packages, e.g. made with
+    /// dpkg-scanpackages
+    Flat,
+}
+
+impl LocalRepo {
+    /// Reads the layout of the repository at `dir`, an error tells why it doesn't look like one
+    pub fn open(dir: &Path, release: &str) -> AppResult<Self> {
+        let invalid = |reason: String| {
+            AppError::Config(
+                format!("{} isn't an apt repository, {}", dir.display(), reason),
+                None,
+            )
+        };
+
+        if !dir.is_dir() {
+            return Err(invalid("it's not a directory".to_string()));
+        }
+
+        let dists = dir.join("dists");
+
+        if !dists.is_dir() {
+            return match ["Packages", "Packages.gz", "Packages.xz"]
+                .iter()
+                .any(|index| dir.join(index).is_file())
+            {
+                true => Ok(LocalRepo::Flat),
+                false => Err(invalid(
+                    "it has neither a dists directory nor a Packages file".to_string(),
+                )),
+            };
+        }
+
+        let suites: Vec<(String, Vec<String>)> =
+            vec![release.to_string(), format!("{}-updates", release)]
+                .into_iter()
+                .map(|suite| {
+                    let components = components(&dists.join(&suite));
+
+                    (suite, components)
+                })
+                .filter(|(_, components)| !components.is_empty())
+                .collect();
+
+        match suites.first() {
+            Some((suite, _)) if suite == release => Ok(LocalRepo::Archive { suites }),
+            _ => Err(invalid(format!(
+                "dists/{} has no component with packages",
+                release
+            ))),
+        }
+    }
+
+    /// Lines of `sources.list` installing from the repository at `REPO_DIR`. It's trusted as
+    /// partial mirrors often lack the signed `Release` files
+    pub fn sources(&self) -> Vec<String> {
+        match self {
+            LocalRepo::Archive { suites } => suites
+                .iter()
+                .map(|(suite, components)| {
+                    format!(
+                        "deb [trusted=yes] file://{} {} {}",
+                        REPO_DIR,
+                        suite,
+                        components.join(" ")
+                    )
+                })
+                .collect(),
+            LocalRepo::Flat => vec![format!("deb [trusted=yes] file://{} ./", REPO_DIR)],
+        }
+    }
+}
+
+/// Components of a suite directory which have a binary index, e.g. `main`
+fn components(suite: &Path) -> Vec<String> {
+    let mut components: Vec<String> = std::fs::read_dir(suite)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| {
+                    std::fs::read_dir(entry.path())
+                        .map(|mut inner| {
+                            inner.any(|inner| {
+                                inner.is_ok_and(|inner| {
+                                    inner.file_name().to_string_lossy().starts_with("binary-")
+                                })
+                            })
+                        })
+                        .unwrap_or(false)
+                })
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    components.sort();
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_repo() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_repo_{}", std::process::id()));
+        let write = |name: &str| {
+            let path = dir.join(name);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        };
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(LocalRepo::open(&dir, "stretch").is_err());
+        assert!(LocalRepo::open(&dir.join("missing"), "stretch").is_err());
+
+        write("Packages.gz");
+        assert_eq!(LocalRepo::open(&dir, "stretch").unwrap(), LocalRepo::Flat);
+
+        write("dists/buster/main/binary-amd64/Packages");
+        assert!(LocalRepo::open(&dir, "stretch").is_err());
+
+        write("dists/stretch/Release");
+        write("dists/stretch/main/binary-amd64/Packages.xz");
+        write("dists/stretch/main/source/Sources.xz");
+        write("dists/stretch/contrib/binary-all/Packages");
+
+        let repo = LocalRepo::open(&dir, "stretch").unwrap();
+
+        assert_eq!(
+            repo.sources(),
+            vec!["deb [trusted=yes] file:///srv/debian-bridge-repo stretch contrib main"]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,860 @@
+use super::{
+    error::AppError,
+    spec::HostState,
+    util::{parse_capability, parse_device, parse_dockerfile_extra, parse_label},
+    AppResult, BuildSettings, ContainerMode, Feature, Icon, Program, ProgramKind, PulseServer,
+    RemoteDisplay, Resources, RestartPolicy, Seccomp, Security, Share,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Everything needed to create a program from a .deb package
+///
+/// # Example
+/// ```
+/// # use debian_bridge_core::{CreateRequest, Feature};
+/// # use std::path::Path;
+/// #
+/// let request = CreateRequest::from_deb(Path::new("./package.deb"))
+///     .feature(Feature::Display)
+///     .feature(Feature::Sound)
+///     .command("foo-desktop")
+///     .deps("libasound2");
+/// ```
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CreateRequest {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    #[serde(default)]
+    pub icon: Option<Icon>,
+    /// Command to run in the container, package name is used by default
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Additional packages to install
+    #[serde(default)]
+    pub deps: Option<String>,
+    /// Dockerfile instructions run after the package is installed, see `parse_dockerfile_extra`
+    #[serde(default)]
+    pub dockerfile_extra: Option<String>,
+    /// Overrides of the build settings from the config
+    #[serde(default)]
+    pub build: BuildSettings,
+    #[serde(default)]
+    pub container_mode: ContainerMode,
+    /// Kind of the program, detected from the desktop entries of the package if it isn't set
+    #[serde(default)]
+    pub kind: Option<ProgramKind>,
+    /// X server to show the program on instead of the local one, the display feature is
+    /// available then even without a local display server
+    #[serde(default)]
+    pub remote_display: Option<RemoteDisplay>,
+    /// PulseAudio server to play sound on instead of the local devices, the sound feature is
+    /// available then even without a local sound server
+    #[serde(default)]
+    pub pulse_server: Option<PulseServer>,
+    #[serde(default)]
+    pub security: Security,
+    /// Capabilities added back after all are dropped, see `parse_capability`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Size of `/dev/shm` in bytes, 1 GiB is used for Chromium based programs if it isn't set
+    #[serde(default)]
+    pub shm_size: Option<u64>,
+    /// Device nodes or patterns passed through, see `parse_device`
+    #[serde(default)]
+    pub devices: Vec<String>,
+    /// Host paths mounted into every container, they must be absolute
+    #[serde(default)]
+    pub shares: Vec<Share>,
+    #[serde(default)]
+    pub resources: Resources,
+    /// Don't probe the built image for the command, e.g. for one which starts only with a
+    /// display attached
+    #[serde(default)]
+    pub skip_check: bool,
+    /// Overwrite an image of the same name built by an app of another prefix, see
+    /// `Backend::create`
+    #[serde(default)]
+    pub force: bool,
+    /// Start the program at login, see `Program::autostart`
+    #[serde(default)]
+    pub autostart: bool,
+    /// Restart policy of the systemd user service, if the program is installed as one
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    /// Host command run before every run, see `Program::pre_run`
+    #[serde(default)]
+    pub pre_run: Option<String>,
+    /// Host command run after every run, see `Program::post_run`
+    #[serde(default)]
+    pub post_run: Option<String>,
+    /// Groups of the program, see `parse_label`
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl CreateRequest {
+    pub fn from_deb(path: &Path) -> Self {
+        CreateRequest {
+            path: path.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn feature(mut self, feature: Feature) -> Self {
+        if !self.features.contains(&feature) {
+            self.features.push(feature);
+        }
+
+        self
+    }
+
+    pub fn features(self, features: &[Feature]) -> Self {
+        features
+            .iter()
+            .fold(self, |request, feature| request.feature(feature.to_owned()))
+    }
+
+    pub fn icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn command<T: Into<String>>(mut self, command: T) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    pub fn deps<T: Into<String>>(mut self, deps: T) -> Self {
+        self.deps = Some(deps.into());
+        self
+    }
+
+    pub fn dockerfile_extra<T: Into<String>>(mut self, snippet: T) -> Self {
+        self.dockerfile_extra = Some(snippet.into());
+        self
+    }
+
+    pub fn build_settings(mut self, build: &BuildSettings) -> Self {
+        self.build = build.to_owned();
+        self
+    }
+
+    pub fn container_mode(mut self, mode: ContainerMode) -> Self {
+        self.container_mode = mode;
+        self
+    }
+
+    pub fn kind(mut self, kind: ProgramKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn remote_display(mut self, display: RemoteDisplay) -> Self {
+        self.remote_display = Some(display);
+        self
+    }
+
+    pub fn pulse_server(mut self, server: &PulseServer) -> Self {
+        self.pulse_server = Some(server.to_owned());
+        self
+    }
+
+    pub fn security(mut self, security: &Security) -> Self {
+        self.security = security.to_owned();
+        self
+    }
+
+    pub fn capabilities(mut self, capabilities: &[String]) -> Self {
+        self.capabilities = capabilities.to_vec();
+        self
+    }
+
+    pub fn label<T: Into<String>>(mut self, label: T) -> Self {
+        let label = label.into();
+
+        if !self.labels.contains(&label) {
+            self.labels.push(label);
+        }
+
+        self
+    }
+
+    pub fn devices(mut self, devices: &[String]) -> Self {
+        self.devices = devices.to_vec();
+        self
+    }
+
+    pub fn share(mut self, path: &Path, read_only: bool) -> Self {
+        self.shares.retain(|share| share.path != path);
+        self.shares.push(Share {
+            path: path.to_owned(),
+            read_only,
+        });
+        self
+    }
+
+    pub fn resources(mut self, resources: &Resources) -> Self {
+        self.resources = resources.to_owned();
+        self
+    }
+
+    pub fn skip_check(mut self, skip: bool) -> Self {
+        self.skip_check = skip;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn autostart(mut self, autostart: bool) -> Self {
+        self.autostart = autostart;
+        self
+    }
+
+    pub fn restart(mut self, restart: RestartPolicy) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    pub fn pre_run<T: Into<String>>(mut self, command: T) -> Self {
+        self.pre_run = Some(command.into());
+        self
+    }
+
+    pub fn post_run<T: Into<String>>(mut self, command: T) -> Self {
+        self.post_run = Some(command.into());
+        self
+    }
+
+    pub fn shm_size(mut self, size: u64) -> Self {
+        self.shm_size = Some(size);
+        self
+    }
+
+    /// Checks the request can be fulfilled, warns about suspicious but valid requests
+    pub fn validate(&self) -> AppResult<&Self> {
+        if !self.path.is_file() {
+            return Err(AppError::Deb(
+                format!("Package {} doesn't exist", self.path.display()),
+                None,
+            ));
+        }
+
+        self.validate_settings()
+    }
+
+    /// Checks the settings of the request without the package, see `App::create_from_image`
+    pub fn validate_settings(&self) -> AppResult<&Self> {
+        if let Some(icon) = &self.icon {
+            if !icon.path.exists() {
+                warn!(
+                    "Icon {} doesn't exist, the desktop entry will be shown without it",
+                    icon.path.display()
+                );
+            }
+        }
+
+        if let Some(cookie) = self.pulse_server.as_ref().and_then(|s| s.cookie.as_ref()) {
+            if !cookie.is_file() {
+                return Err(AppError::Program(format!(
+                    "Pulse cookie {} doesn't exist",
+                    cookie.display()
+                )));
+            }
+        }
+
+        if let Seccomp::Profile(profile) = &self.security.seccomp {
+            if !profile.is_file() {
+                return Err(AppError::Program(format!(
+                    "Seccomp profile {} doesn't exist",
+                    profile.display()
+                )));
+            }
+        }
+
+        for capability in &self.capabilities {
+            parse_capability(capability)?;
+        }
+
+        for label in &self.labels {
+            parse_label(label)?;
+        }
+
+        for device in &self.devices {
+            parse_device(device)?;
+        }
+
+        if let Some(share) = self.shares.iter().find(|share| !share.path.is_absolute()) {
+            return Err(AppError::Argument(format!(
+                "Shared path {} isn't absolute",
+                share.path.display()
+            )));
+        }
+
+        if let Some(extra) = &self.dockerfile_extra {
+            parse_dockerfile_extra(extra)?;
+        }
+
+        if self.features.is_empty() {
+            warn!("No features requested, the program won't have access to a display or sound");
+        }
+
+        Ok(self)
+    }
+}
+
+/// Changes to apply to an existing program
+///
+/// # Example
+/// ```
+/// # use debian_bridge_core::{Feature, ProgramChanges};
+/// #
+/// let changes = ProgramChanges::new()
+///     .add_feature(Feature::Sound)
+///     .remove_feature(Feature::Devices)
+///     .deps("libasound2");
+/// ```
+#[derive(Clone, Default)]
+pub struct ProgramChanges {
+    pub add_features: Vec<Feature>,
+    pub remove_features: Vec<Feature>,
+    pub icon: Option<Icon>,
+    pub command: Option<String>,
+    pub deps: Option<String>,
+    /// Overrides of the build settings used if the image is rebuilt, the snapshot is stored
+    pub build: BuildSettings,
+    pub container_mode: Option<ContainerMode>,
+    pub kind: Option<ProgramKind>,
+    pub seccomp: Option<Seccomp>,
+    /// `Some(None)` removes the AppArmor profile
+    pub apparmor: Option<Option<String>>,
+    pub add_capabilities: Vec<String>,
+    pub remove_capabilities: Vec<String>,
+    pub shm_size: Option<u64>,
+    pub add_devices: Vec<String>,
+    pub remove_devices: Vec<String>,
+    pub set_ulimits: Vec<(String, u64)>,
+    pub remove_ulimits: Vec<String>,
+    /// A zero resets the niceness
+    pub nice: Option<i32>,
+    /// A zero resets the adjustment
+    pub oom_score_adj: Option<i32>,
+    pub autostart: Option<bool>,
+    pub restart: Option<RestartPolicy>,
+    /// `Some(None)` removes the hook
+    pub pre_run: Option<Option<String>>,
+    /// `Some(None)` removes the hook
+    pub post_run: Option<Option<String>>,
+}
+
+impl ProgramChanges {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_feature(mut self, feature: Feature) -> Self {
+        self.remove_features.retain(|f| f != &feature);
+        self.add_features.push(feature);
+        self
+    }
+
+    pub fn remove_feature(mut self, feature: Feature) -> Self {
+        self.add_features.retain(|f| f != &feature);
+        self.remove_features.push(feature);
+        self
+    }
+
+    pub fn icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn command<T: Into<String>>(mut self, command: T) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    pub fn deps<T: Into<String>>(mut self, deps: T) -> Self {
+        self.deps = Some(deps.into());
+        self
+    }
+
+    pub fn build_settings(mut self, build: &BuildSettings) -> Self {
+        self.build = build.to_owned();
+        self
+    }
+
+    pub fn container_mode(mut self, mode: ContainerMode) -> Self {
+        self.container_mode = Some(mode);
+        self
+    }
+
+    pub fn kind(mut self, kind: ProgramKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn seccomp(mut self, seccomp: Seccomp) -> Self {
+        self.seccomp = Some(seccomp);
+        self
+    }
+
+    pub fn apparmor(mut self, profile: Option<String>) -> Self {
+        self.apparmor = Some(profile);
+        self
+    }
+
+    pub fn add_capability<T: Into<String>>(mut self, capability: T) -> Self {
+        let capability = capability.into();
+
+        self.remove_capabilities.retain(|c| c != &capability);
+        self.add_capabilities.push(capability);
+        self
+    }
+
+    pub fn remove_capability<T: Into<String>>(mut self, capability: T) -> Self {
+        let capability = capability.into();
+
+        self.add_capabilities.retain(|c| c != &capability);
+        self.remove_capabilities.push(capability);
+        self
+    }
+
+    pub fn add_device<T: Into<String>>(mut self, device: T) -> Self {
+        let device = device.into();
+
+        self.remove_devices.retain(|d| d != &device);
+        self.add_devices.push(device);
+        self
+    }
+
+    pub fn remove_device<T: Into<String>>(mut self, device: T) -> Self {
+        let device = device.into();
+
+        self.add_devices.retain(|d| d != &device);
+        self.remove_devices.push(device);
+        self
+    }
+
+    pub fn ulimit<T: Into<String>>(mut self, name: T, value: u64) -> Self {
+        let name = name.into();
+
+        self.remove_ulimits.retain(|n| n != &name);
+        self.set_ulimits.push((name, value));
+        self
+    }
+
+    pub fn remove_ulimit<T: Into<String>>(mut self, name: T) -> Self {
+        let name = name.into();
+
+        self.set_ulimits.retain(|(n, _)| n != &name);
+        self.remove_ulimits.push(name);
+        self
+    }
+
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    pub fn oom_score_adj(mut self, adj: i32) -> Self {
+        self.oom_score_adj = Some(adj);
+        self
+    }
+
+    pub fn autostart(mut self, autostart: bool) -> Self {
+        self.autostart = Some(autostart);
+        self
+    }
+
+    pub fn restart(mut self, restart: RestartPolicy) -> Self {
+        self.restart = Some(restart);
+        self
+    }
+
+    pub fn pre_run(mut self, command: Option<String>) -> Self {
+        self.pre_run = Some(command);
+        self
+    }
+
+    pub fn post_run(mut self, command: Option<String>) -> Self {
+        self.post_run = Some(command);
+        self
+    }
+
+    pub fn shm_size(mut self, size: u64) -> Self {
+        self.shm_size = Some(size);
+        self
+    }
+
+    /// Applies the changes to `program`, returns whether its image has to be rebuilt:
+    /// the command, dependencies and snapshot are baked into the image, the rest is used at
+    /// run time only
+    pub fn apply(&self, program: &mut Program) -> bool {
+        let mut rebuild = false;
+
+        program
+            .settings
+            .retain(|feature| !self.remove_features.contains(feature));
+
+        for feature in &self.add_features {
+            if !program.settings.contains(feature) {
+                program.settings.push(feature.to_owned());
+                rebuild |= feature.installs_packages();
+            }
+        }
+
+        if let Some(icon) = &self.icon {
+            program.icon = Some(icon.to_owned());
+        }
+
+        if let Some(mode) = self.container_mode {
+            program.container_mode = mode;
+        }
+
+        // The graphical features of a CLI program are ignored, their packages too
+        if let Some(kind) = self.kind.filter(|kind| *kind != program.kind) {
+            program.kind = kind;
+            rebuild |= program
+                .settings
+                .iter()
+                .any(|feature| feature.is_gui() && feature.installs_packages());
+        }
+
+        if let Some(seccomp) = &self.seccomp {
+            program.security.seccomp = seccomp.to_owned();
+        }
+
+        if let Some(profile) = &self.apparmor {
+            program.security.apparmor = profile.to_owned();
+        }
+
+        if self.shm_size.is_some() {
+            program.shm_size = self.shm_size;
+        }
+
+        if let Some(autostart) = self.autostart {
+            program.autostart = autostart;
+        }
+
+        if let Some(restart) = self.restart {
+            program.restart = restart;
+        }
+
+        if let Some(command) = &self.pre_run {
+            program.pre_run = command.to_owned();
+        }
+
+        if let Some(command) = &self.post_run {
+            program.post_run = command.to_owned();
+        }
+
+        program
+            .capabilities
+            .retain(|capability| !self.remove_capabilities.contains(capability));
+
+        for capability in &self.add_capabilities {
+            if !program.capabilities.contains(capability) {
+                program.capabilities.push(capability.to_owned());
+            }
+        }
+
+        program
+            .devices
+            .retain(|device| !self.remove_devices.contains(device));
+
+        for device in &self.add_devices {
+            if !program.devices.contains(device) {
+                program.devices.push(device.to_owned());
+            }
+        }
+
+        let resources = &mut program.resources;
+
+        for name in &self.remove_ulimits {
+            resources.ulimits.remove(name);
+        }
+
+        for (name, value) in &self.set_ulimits {
+            resources.ulimits.insert(name.to_owned(), *value);
+        }
+
+        if let Some(nice) = self.nice {
+            resources.nice = Some(nice).filter(|nice| *nice != 0);
+        }
+
+        if let Some(adj) = self.oom_score_adj {
+            resources.oom_score_adj = Some(adj).filter(|adj| *adj != 0);
+        }
+
+        if let Some(command) = self.command.as_ref().filter(|c| *c != &program.command) {
+            program.command = command.to_owned();
+            rebuild = true;
+        }
+
+        if self.deps.is_some() && self.deps != program.deps {
+            program.deps = self.deps.to_owned();
+            rebuild = true;
+        }
+
+        if self.build.snapshot.is_some() && self.build.snapshot != program.snapshot {
+            program.snapshot = self.build.snapshot.to_owned();
+            rebuild = true;
+        }
+
+        rebuild
+    }
+}
+
+/// How a single run of a program differs from the usual one
+///
+/// # Example
+/// ```
+/// # use debian_bridge_core::RunOptions;
+/// #
+/// let options = RunOptions::new()
+///     .keep_container(true)
+///     .env("LANG=C")
+///     .no_sound(true);
+/// ```
+#[derive(Clone, Default)]
+pub struct RunOptions {
+    /// Changes to the container, see `RunOverrides`
+    pub overrides: RunOverrides,
+    /// The output isn't written to a log file this time, see `App::logs`
+    pub no_log: bool,
+}
+
+impl RunOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn keep_container(mut self, keep: bool) -> Self {
+        self.overrides.keep_container = keep;
+        self
+    }
+
+    pub fn display(mut self, display: RemoteDisplay) -> Self {
+        self.overrides.display = Some(display);
+        self
+    }
+
+    pub fn pulse_server(mut self, server: &PulseServer) -> Self {
+        self.overrides.pulse_server = Some(server.to_owned());
+        self
+    }
+
+    pub fn no_log(mut self, no_log: bool) -> Self {
+        self.no_log = no_log;
+        self
+    }
+
+    /// Sets a variable, `NAME=value`, or forwards the one of the host, `NAME`
+    pub fn env<T: Into<String>>(mut self, env: T) -> Self {
+        self.overrides.env.push(env.into());
+        self
+    }
+
+    /// Shares a host path at the same path in the container
+    pub fn share(mut self, path: &Path, read_only: bool) -> Self {
+        self.overrides.shares.push((path.to_owned(), read_only));
+        self
+    }
+
+    pub fn network<T: Into<String>>(mut self, network: T) -> Self {
+        self.overrides.network = Some(network.into());
+        self
+    }
+
+    pub fn no_sound(mut self, no_sound: bool) -> Self {
+        self.overrides.no_sound = no_sound;
+        self
+    }
+
+    pub fn command<T: Into<String>>(mut self, command: T) -> Self {
+        self.overrides.command = Some(command.into());
+        self
+    }
+
+    pub fn arg<T: Into<String>>(mut self, arg: T) -> Self {
+        self.overrides.args.push(arg.into());
+        self
+    }
+}
+
+/// Changes a single run makes to the container of the stored program, applied on top of it by
+/// `build_run_spec`. A persistent container keeps the ones it was created with
+#[derive(Debug, Clone, Default)]
+pub struct RunOverrides {
+    /// Don't remove an ephemeral container after exit, e.g. to copy crash logs out of it
+    pub keep_container: bool,
+    /// X server to show the program on this time, overrides the one stored with the program
+    pub display: Option<RemoteDisplay>,
+    /// PulseAudio server to play sound on this time, overrides the one stored with the program
+    pub pulse_server: Option<PulseServer>,
+    /// Variables set in the container, a bare name forwards the one of the host
+    pub env: Vec<String>,
+    /// Host paths shared at the same path, read-only if the flag is set
+    pub shares: Vec<(PathBuf, bool)>,
+    /// Network mode instead of the host network, e.g. `none`
+    pub network: Option<String>,
+    /// Runs without the sound feature
+    pub no_sound: bool,
+    /// Shell command run instead of the stored one
+    pub command: Option<String>,
+    /// Arguments appended to the command, e.g. the ones of a wrapper script
+    pub args: Vec<String>,
+    /// What the run finds on the host, the backend reads it right before the run
+    pub host: HostState,
+}
+
+impl RunOverrides {
+    /// Whether the container differs from the one of the stored program, an ephemeral container
+    /// which is kept doesn't
+    pub fn changes_container(&self) -> bool {
+        self.display.is_some()
+            || self.pulse_server.is_some()
+            || !self.env.is_empty()
+            || !self.shares.is_empty()
+            || self.network.is_some()
+            || self.no_sound
+            || self.command.is_some()
+            || !self.args.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let request = CreateRequest::from_deb(Path::new("/tmp/foo.deb"))
+            .feature(Feature::Display)
+            .features(&[Feature::Display, Feature::Sound])
+            .command("foo-desktop");
+
+        assert_eq!(request.features, vec![Feature::Display, Feature::Sound]);
+        assert_eq!(request.command, Some("foo-desktop".to_string()));
+        assert!(request.deps.is_none());
+    }
+
+    #[test]
+    fn test_run_options() {
+        let options = RunOptions::new()
+            .keep_container(true)
+            .env("LANG=C")
+            .share(Path::new("/srv/data"), true);
+
+        assert!(options.overrides.keep_container);
+        assert_eq!(options.overrides.env, vec!["LANG=C"]);
+        assert_eq!(
+            options.overrides.shares,
+            vec![(PathBuf::from("/srv/data"), true)]
+        );
+        assert!(options.overrides.changes_container());
+        assert!(!RunOptions::new()
+            .keep_container(true)
+            .overrides
+            .changes_container());
+    }
+
+    #[test]
+    fn test_validate() {
+        let package = std::env::temp_dir().join(format!(
+            "debian_bridge_test_request_{}.deb",
+            std::process::id()
+        ));
+
+        assert!(CreateRequest::from_deb(&package).validate().is_err());
+
+        std::fs::write(&package, b"").unwrap();
+
+        let result = CreateRequest::from_deb(&package)
+            .icon(Icon::new(Path::new("/nonexistent/icon.png")))
+            .validate()
+            .is_ok();
+
+        std::fs::remove_file(&package).unwrap();
+        assert!(result);
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let mut program = Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![Feature::Display, Feature::Devices],
+            &None,
+            &None,
+            &None,
+        );
+
+        let changes = ProgramChanges::new()
+            .add_feature(Feature::Sound)
+            .remove_feature(Feature::Devices);
+
+        assert!(!changes.apply(&mut program));
+        assert_eq!(program.settings, vec![Feature::Display, Feature::Sound]);
+        assert!(!ProgramChanges::new().command("foo").apply(&mut program));
+        assert!(ProgramChanges::new().deps("libasound2").apply(&mut program));
+        assert_eq!(program.deps, Some("libasound2".to_string()));
+
+        let changes = ProgramChanges::new()
+            .add_capability("NET_ADMIN")
+            .add_capability("SYS_PTRACE")
+            .remove_capability("SYS_PTRACE");
+
+        assert!(!changes.apply(&mut program));
+        assert_eq!(program.capabilities, vec!["NET_ADMIN".to_string()]);
+
+        let changes = ProgramChanges::new()
+            .add_device("/dev/ttyUSB0")
+            .add_device("/dev/ttyACM*")
+            .remove_device("/dev/ttyUSB0");
+
+        assert!(!changes.apply(&mut program));
+        assert_eq!(program.devices, vec!["/dev/ttyACM*".to_string()]);
+
+        let changes = ProgramChanges::new()
+            .ulimit("nofile", 4096)
+            .ulimit("rtprio", 95)
+            .remove_ulimit("rtprio")
+            .nice(5)
+            .oom_score_adj(500);
+
+        assert!(!changes.apply(&mut program));
+        assert_eq!(
+            program.resources.to_string(),
+            "nofile=4096, nice 5, OOM score +500"
+        );
+        assert!(!ProgramChanges::new().nice(0).apply(&mut program));
+        assert_eq!(program.resources.nice, None);
+        assert!(!ProgramChanges::new().shm_size(2 << 30).apply(&mut program));
+        assert!(ProgramChanges::new()
+            .add_feature(Feature::HostTheme)
+            .apply(&mut program));
+        assert_eq!(program.shm_size, Some(2 << 30));
+        assert!(ProgramChanges::new()
+            .kind(ProgramKind::Cli)
+            .apply(&mut program));
+        assert_eq!(program.kind, ProgramKind::Cli);
+        assert!(!ProgramChanges::new()
+            .kind(ProgramKind::Cli)
+            .apply(&mut program));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let request: CreateRequest =
+            serde_json::from_str(r#"{"path":"/tmp/foo.deb","features":["Sound"]}"#).unwrap();
+
+        assert_eq!(request.features, vec![Feature::Sound]);
+        assert!(request.icon.is_none());
+    }
+}
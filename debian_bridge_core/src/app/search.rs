@@ -0,0 +1,210 @@
+use super::Program;
+use serde::Serialize;
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+};
+
+/// Characters of the matched line kept on each side of the match in a snippet
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Part of a program a query matched, in the order matches are ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum SearchField {
+    Name,
+    Command,
+    Description,
+}
+
+impl Display for SearchField {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let name = match self {
+            SearchField::Name => "name",
+            SearchField::Command => "command",
+            SearchField::Description => "description",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Program found by `App::search`
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    pub name: String,
+    /// The best ranked field the query matched
+    pub field: SearchField,
+    /// Line of the field around the match
+    pub snippet: String,
+    /// Byte range of the match in `snippet`
+    pub range: Range<usize>,
+    #[serde(skip)]
+    rank: u8,
+}
+
+impl SearchMatch {
+    /// Matches the query against the name, the command and the description of the program,
+    /// ignoring case. `None` if none of them contains it
+    pub fn try_new(program: &Program, query: &str) -> Option<Self> {
+        let name = program.get_name_short();
+        let fields = [
+            (SearchField::Name, name.as_str()),
+            (SearchField::Command, program.command.as_str()),
+            (
+                SearchField::Description,
+                program.description.as_deref().unwrap_or_default(),
+            ),
+        ];
+
+        fields.iter().find_map(|(field, text)| {
+            let range = find_ignore_case(text, query)?;
+            // An exact name is better than a prefix, which is better than a part of the name
+            let rank = match field {
+                SearchField::Name if range.len() == text.len() => 0,
+                SearchField::Name if range.start == 0 => 1,
+                SearchField::Name => 2,
+                SearchField::Command => 3,
+                SearchField::Description => 4,
+            };
+            let (snippet, range) = snippet(text, range);
+
+            Some(SearchMatch {
+                name: name.to_owned(),
+                field: *field,
+                snippet,
+                range,
+                rank,
+            })
+        })
+    }
+
+    /// Sorts matches from the most relevant one
+    pub fn rank(matches: &mut [SearchMatch]) {
+        matches.sort_by(|a, b| a.rank.cmp(&b.rank).then_with(|| a.name.cmp(&b.name)));
+    }
+}
+
+impl Display for SearchMatch {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "\t{:<15} ===> {}: {}{}{}",
+            self.name,
+            self.field,
+            &self.snippet[..self.range.start],
+            &self.snippet[self.range.clone()],
+            &self.snippet[self.range.end..]
+        )
+    }
+}
+
+/// Byte range of the first occurrence of `query` in `text` ignoring case
+fn find_ignore_case(text: &str, query: &str) -> Option<Range<usize>> {
+    let query = query.to_lowercase().chars().collect::<Vec<char>>();
+
+    if query.is_empty() {
+        return None;
+    }
+
+    text.char_indices().find_map(|(start, _)| {
+        let mut chars = text[start..].char_indices().flat_map(|(offset, c)| {
+            c.to_lowercase()
+                .map(move |lower| (offset + c.len_utf8(), lower))
+        });
+        let mut end = start;
+
+        for expected in &query {
+            match chars.next() {
+                Some((offset, lower)) if lower == *expected => end = start + offset,
+                _ => return None,
+            }
+        }
+
+        Some(start..end)
+    })
+}
+
+/// Line of `text` containing the match, shortened to some context around it. Returns the
+/// snippet with the range of the match in it
+fn snippet(text: &str, range: Range<usize>) -> (String, Range<usize>) {
+    let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| range.end + i);
+    let start = text[line_start..range.start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT - 1)
+        .map_or(line_start, |(i, _)| line_start + i);
+    let end = text[range.end..line_end]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(line_end, |(i, _)| range.end + i);
+    let prefix = match start > line_start {
+        true => "...",
+        false => "",
+    };
+    let suffix = match end < line_end {
+        true => "...",
+        false => "",
+    };
+    let offset = prefix.len() + range.start - start;
+
+    (
+        format!("{}{}{}", prefix, &text[start..end], suffix),
+        offset..offset + range.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_find_ignore_case() {
+        assert_eq!(find_ignore_case("Okular PDF viewer", "pdf"), Some(7..10));
+        assert_eq!(find_ignore_case("Grüße aus Köln", "KÖLN"), Some(12..17));
+        assert_eq!(find_ignore_case("foo", "bar"), None);
+        assert_eq!(find_ignore_case("foo", ""), None);
+    }
+
+    #[test]
+    fn test_snippet() {
+        let text = "Document viewer\n Okular is a universal document viewer supporting PDF, \
+                    PostScript, DjVu, CHM, XPS, ePub and others.";
+        let range = find_ignore_case(text, "djvu").unwrap();
+        let (shortened, range) = snippet(text, range);
+
+        assert_eq!(
+            shortened,
+            "...r supporting PDF, PostScript, DjVu, CHM, XPS, ePub and others."
+        );
+        assert_eq!(&shortened[range], "DjVu");
+        assert_eq!(snippet("viewer", 0..6), ("viewer".to_string(), 0..6));
+    }
+
+    #[test]
+    fn test_try_new() {
+        let mut program = Program::new(
+            "okular",
+            Path::new("/tmp/okular.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        );
+
+        program.description = Some("Universal document viewer\n Shows PDF files".to_string());
+
+        let found = SearchMatch::try_new(&program, "pdf").unwrap();
+
+        assert_eq!(found.field, SearchField::Description);
+        assert_eq!(&found.snippet[found.range], "PDF");
+        assert_eq!(
+            SearchMatch::try_new(&program, "OKU").unwrap().field,
+            SearchField::Name
+        );
+        assert!(SearchMatch::try_new(&program, "spreadsheet").is_none());
+    }
+}
@@ -0,0 +1,1533 @@
+use super::{
+    docker::{
+        host_theme, AVAHI_SOCKET, DEVICE_GROUPS, DEVICE_RULES, DEVICE_RULES_API_VERSION,
+        MANAGED_LABEL, PREFIX_LABEL, PROGRAM_LABEL, PROXY_BUS, PULSE_COOKIE, SANE_NET_CONF,
+        SYSTEM_BUS,
+    },
+    error::AppError,
+    urls::{OPEN_URL_SCHEMES, OPEN_URL_SOCKET},
+    userdirs::{self, UserDir},
+    util::{
+        bind_mount, expand_devices, find_command, get_group_ids, home_dir, host_devices, path_str,
+        share_mount,
+    },
+    ContainerMode, Feature, Program, RunOverrides, Seccomp, System,
+};
+use serde::Serialize;
+use std::{
+    fmt::{Display, Formatter},
+    io::IsTerminal,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Seccomp profile of the programs unless another one is configured
+const SECCOMP_PROFILE: &str = include_str!("../../resources/seccomp.json");
+
+/// What a permission exposes to a program, `permissions` groups them in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Area {
+    Filesystem,
+    Devices,
+    Network,
+    Ipc,
+}
+
+impl Display for Area {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Area::Filesystem => "Filesystem",
+                Area::Devices => "Devices",
+                Area::Network => "Network",
+                Area::Ipc => "IPC",
+            }
+        )
+    }
+}
+
+/// How a mount is passed to docker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MountKind {
+    /// `-v`, docker creates a missing source as a directory
+    Volume,
+    /// `--mount`, a missing source fails the start
+    Bind,
+    /// Folder of the user, relabeled on SELinux hosts, see `share_mount`
+    Shared,
+}
+
+/// Host path mounted into a container
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecMount {
+    pub source: String,
+    pub target: String,
+    pub read_only: bool,
+    pub kind: MountKind,
+    pub area: Area,
+    /// What the mount is for, e.g. `Sound`
+    pub reason: String,
+}
+
+impl SpecMount {
+    /// Flag and value passing the mount to docker
+    pub fn arg(&self, relabel: bool) -> (&'static str, String) {
+        match self.kind {
+            MountKind::Volume => (
+                "-v",
+                match self.read_only {
+                    true => format!("{}:{}:ro", self.source, self.target),
+                    false => format!("{}:{}", self.source, self.target),
+                },
+            ),
+            MountKind::Bind => (
+                "--mount",
+                bind_mount(&self.source, &self.target, self.read_only),
+            ),
+            MountKind::Shared => share_mount(&self.source, &self.target, self.read_only, relabel),
+        }
+    }
+}
+
+/// Variable of a container, a bare name forwards the one of the host
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecEnv {
+    pub value: String,
+    pub area: Area,
+    pub reason: String,
+}
+
+/// Device node passed to a container
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecDevice {
+    pub path: String,
+    pub reason: String,
+}
+
+/// xdg-dbus-proxy a container reaches a bus through
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecProxy {
+    pub bus: PathBuf,
+    /// Socket of the proxy on the host
+    pub socket: PathBuf,
+    /// Names the container may talk to
+    pub names: Vec<String>,
+}
+
+/// File written on the host before the container starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecFile {
+    pub path: PathBuf,
+    pub content: String,
+    /// Whether it's removed after the run, the others are bound again by persistent containers
+    pub temporary: bool,
+}
+
+/// Everything a run passes to the container of a program, built by `build_run_spec` without
+/// changing the host. `DockerFacade::run` applies it, `App::permissions` shows it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSpec {
+    /// Image of the program, the name of its persistent container as well
+    pub image: String,
+    pub labels: Vec<String>,
+    /// Whether a terminal is allocated, there is none when started from a desktop entry
+    pub tty: bool,
+    pub network: String,
+    /// Capabilities kept, the others are dropped
+    pub capabilities: Vec<String>,
+    pub security_options: Vec<String>,
+    pub user: Option<String>,
+    /// Size of `/dev/shm`, the one of the host is shared without it
+    pub shm_size: Option<u64>,
+    pub read_only: bool,
+    pub tmpfs: Vec<String>,
+    pub mounts: Vec<SpecMount>,
+    /// Whether shared mounts are relabeled for SELinux
+    pub relabel: bool,
+    pub devices: Vec<SpecDevice>,
+    /// `--device-cgroup-rule` values
+    pub device_rules: Vec<String>,
+    /// Host group IDs the program user joins to open the devices
+    pub groups: Vec<String>,
+    pub env: Vec<SpecEnv>,
+    /// Whether the whole session bus is shared, the mount is missing without a session bus
+    pub whole_session_bus: bool,
+    pub session_proxy: Option<SpecProxy>,
+    pub system_proxy: Option<SpecProxy>,
+    /// Socket the program opens URLs through, see `UrlOpener`
+    pub url_socket: Option<PathBuf>,
+    pub files: Vec<SpecFile>,
+    /// Directories created on the host before the start
+    pub dirs: Vec<PathBuf>,
+    /// Whether the container is removed on exit
+    pub remove: bool,
+    /// Name of a persistent container
+    pub name: Option<String>,
+    /// Where docker writes the container ID, it must not exist beforehand
+    pub cid_file: PathBuf,
+    /// `--ulimit` values
+    pub ulimits: Vec<String>,
+    pub oom_score_adj: Option<i32>,
+    pub nice: Option<i32>,
+    /// Shell command run instead of the command of the image
+    pub command: Option<String>,
+    /// Positional parameters of the shell running `command`
+    pub command_args: Vec<String>,
+}
+
+impl RunSpec {
+    fn push_mount(
+        &mut self,
+        reason: &str,
+        area: Area,
+        kind: MountKind,
+        (source, target): (&str, &str),
+        read_only: bool,
+    ) {
+        self.mounts.push(SpecMount {
+            source: source.to_string(),
+            target: target.to_string(),
+            read_only,
+            kind,
+            area,
+            reason: reason.to_string(),
+        });
+    }
+
+    fn push_env(&mut self, reason: &str, area: Area, value: &str) {
+        self.env.push(SpecEnv {
+            value: value.to_string(),
+            area,
+            reason: reason.to_string(),
+        });
+    }
+
+    fn push_device(&mut self, reason: &str, path: &str) {
+        self.devices.push(SpecDevice {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Arguments of `docker run`
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![];
+        let mut push = |values: &[&str]| args.extend(values.iter().map(|value| value.to_string()));
+
+        push(&[
+            "run",
+            match self.tty {
+                true => "-ti",
+                false => "-i",
+            },
+        ]);
+
+        for label in &self.labels {
+            push(&["--label", label]);
+        }
+
+        push(&["--net", &self.network]);
+
+        // None of the features needs a capability, the programs run as a regular user
+        push(&["--cap-drop", "ALL"]);
+
+        for capability in &self.capabilities {
+            push(&["--cap-add", capability]);
+        }
+
+        for option in &self.security_options {
+            push(&["--security-opt", option]);
+        }
+
+        if let Some(user) = &self.user {
+            push(&["--user", user]);
+        }
+
+        if let Some(size) = self.shm_size {
+            push(&["--shm-size", &size.to_string()]);
+        }
+
+        if self.read_only {
+            push(&["--read-only"]);
+        }
+
+        for tmpfs in &self.tmpfs {
+            push(&["--tmpfs", tmpfs]);
+        }
+
+        for mount in &self.mounts {
+            let (flag, value) = mount.arg(self.relabel);
+
+            push(&[flag, &value]);
+        }
+
+        for device in &self.devices {
+            push(&["--device", &device.path]);
+        }
+
+        for rule in &self.device_rules {
+            push(&["--device-cgroup-rule", rule]);
+        }
+
+        for id in &self.groups {
+            push(&["--group-add", id]);
+        }
+
+        for env in &self.env {
+            push(&["--env", &env.value]);
+        }
+
+        if self.remove {
+            push(&["--rm"]);
+        }
+
+        if let Some(name) = &self.name {
+            push(&["--name", name]);
+        }
+
+        push(&["--cidfile", &self.cid_file.display().to_string()]);
+
+        for ulimit in &self.ulimits {
+            push(&["--ulimit", ulimit]);
+        }
+
+        if let Some(adj) = self.oom_score_adj {
+            push(&["--oom-score-adj", &adj.to_string()]);
+        }
+
+        // docker can't set the niceness, the command of the image is started through nice
+        match self.nice {
+            Some(nice) => push(&["--entrypoint", "nice", &self.image, "-n", &nice.to_string()]),
+            None => push(&[&self.image]),
+        }
+
+        if let Some(command) = &self.command {
+            push(&["/bin/sh", "-c", command]);
+
+            if !self.command_args.is_empty() {
+                push(&["sh"]);
+                args.extend(self.command_args.iter().cloned());
+            }
+        }
+
+        args
+    }
+
+    /// What a container started with the spec can reach, by area
+    pub fn permissions(&self) -> Vec<Permission> {
+        let permission = |area: Area, reason: &str, value: String| Permission {
+            area,
+            reason: reason.to_string(),
+            value,
+        };
+        let mut permissions = vec![permission(
+            Area::Filesystem,
+            "Root",
+            match self.read_only {
+                true => format!("read-only, in memory: {}", self.tmpfs.join(", ")),
+                false => "writable".to_string(),
+            },
+        )];
+
+        permissions.extend(self.mounts.iter().map(|mount| {
+            let mut value = match mount.source == mount.target {
+                true => mount.source.to_owned(),
+                false => format!("{} as {}", mount.source, mount.target),
+            };
+
+            if mount.read_only {
+                value.push_str(" (read-only)");
+            }
+
+            permission(mount.area, &mount.reason, value)
+        }));
+        permissions.extend(self.env.iter().map(|env| match env.value.contains('=') {
+            true => permission(env.area, &env.reason, env.value.to_owned()),
+            false => permission(env.area, &env.reason, format!("${} of the host", env.value)),
+        }));
+
+        permissions.extend(
+            self.devices
+                .iter()
+                .map(|device| permission(Area::Devices, &device.reason, device.path.to_owned())),
+        );
+
+        if !self.device_rules.is_empty() {
+            permissions.push(permission(
+                Area::Devices,
+                "Device classes",
+                self.device_rules.join(", "),
+            ));
+        }
+
+        if !self.groups.is_empty() {
+            permissions.push(permission(Area::Devices, "Groups", self.groups.join(", ")));
+        }
+
+        permissions.push(permission(
+            Area::Network,
+            "Mode",
+            match self.network.as_str() {
+                "host" => "host, abstract sockets included".to_string(),
+                network => network.to_string(),
+            },
+        ));
+
+        if self.whole_session_bus {
+            permissions.push(permission(
+                Area::Ipc,
+                "Session bus",
+                "whole bus".to_string(),
+            ));
+        }
+
+        for (reason, proxy) in [
+            ("Session bus", &self.session_proxy),
+            ("System bus", &self.system_proxy),
+        ] {
+            if let Some(proxy) = proxy {
+                permissions.push(permission(Area::Ipc, reason, proxy.names.join(", ")));
+            }
+        }
+
+        if self.url_socket.is_some() {
+            permissions.push(permission(
+                Area::Ipc,
+                "Open URLs",
+                format!("{} links", OPEN_URL_SCHEMES.join(", ")),
+            ));
+        }
+
+        permissions.sort_by_key(|permission| permission.area);
+
+        permissions
+    }
+}
+
+/// Something a container of a program can reach, see `App::permissions`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Permission {
+    pub area: Area,
+    /// What it's for, e.g. `Sound`
+    pub reason: String,
+    /// e.g. `/dev/snd` or `/usr/share/fonts (read-only)`
+    pub value: String,
+}
+
+/// What a run of a program finds on the host, read by `HostState::read` right before the run.
+/// Only what the features of the program need is read, the rest is left empty
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostState {
+    pub home: String,
+    /// Directory of the files docker CLI reads before the start, e.g. the seccomp profile
+    pub temp_dir: PathBuf,
+    /// Keeps the temporary files of concurrent runs apart
+    pub process_id: u32,
+    /// Whether stdin is a terminal
+    pub tty: bool,
+    /// Contents of `/etc/group`
+    pub groups: String,
+    /// Binds and variables of the host theme, see `host_theme`
+    pub theme: (Vec<(String, String)>, Vec<String>),
+    pub user_dirs: Vec<UserDir>,
+    /// `user-dirs.dirs` of the user, if it exists inside the home
+    pub user_dirs_file: Option<PathBuf>,
+    /// Whether xdg-dbus-proxy is installed
+    pub dbus_proxy: bool,
+    /// Socket of the session bus, if it exists
+    pub session_bus: Option<PathBuf>,
+    /// Whether the document portal is mounted in the runtime directory
+    pub document_portal: bool,
+    /// Whether the socket of avahi-daemon exists
+    pub avahi: bool,
+    /// IBus directories of the home which exist, read-only if the flag is set
+    pub ibus_dirs: Vec<(String, bool)>,
+    /// Nodes of the gamepads connected now, `/dev/uinput` included
+    pub gamepads: Vec<String>,
+    /// Nodes of the USB scanners connected now and the groups owning them
+    pub scanners: Vec<(PathBuf, u32)>,
+    /// Nodes matching the devices of the program and the groups owning them
+    pub devices: Vec<(PathBuf, u32)>,
+    /// Device patterns of the program no node matches
+    pub unmatched_devices: Vec<String>,
+    /// Directories of the device classes the devices feature binds, see `host_devices`
+    pub device_dirs: Vec<PathBuf>,
+    /// Nodes of the device classes the devices feature passes, see `host_devices`
+    pub device_nodes: Vec<PathBuf>,
+}
+
+impl HostState {
+    /// Reads what a run of the program with the overrides needs from the host. A file the
+    /// program points to, e.g. a pulse cookie or a seccomp profile, must exist
+    pub fn read(program: &Program, system: &System, overrides: &RunOverrides) -> AppResult<Self> {
+        let has = |feature: Feature| {
+            program.uses(&feature) && !(overrides.no_sound && feature == Feature::Sound)
+        };
+        let node_group = |node: &PathBuf| {
+            std::fs::metadata(node)
+                .ok()
+                .map(|metadata| (node.to_owned(), metadata.gid()))
+        };
+
+        // docker would create a directory in place of a missing file
+        if let Some(cookie) = overrides
+            .pulse_server
+            .as_ref()
+            .or(program.pulse_server.as_ref())
+            .and_then(|server| server.cookie.as_ref())
+            .filter(|cookie| !cookie.is_file())
+        {
+            return Err(AppError::Program(format!(
+                "Pulse cookie {} doesn't exist",
+                cookie.display()
+            )));
+        }
+
+        if let Seccomp::Profile(path) = &program.security.seccomp {
+            if !path.is_file() {
+                return Err(AppError::Program(format!(
+                    "Seccomp profile {} doesn't exist",
+                    path.display()
+                )));
+            }
+        }
+
+        let home = path_str(&home_dir()?)?.to_string();
+        let mut host = HostState {
+            temp_dir: std::env::temp_dir(),
+            process_id: std::process::id(),
+            tty: std::io::stdin().is_terminal(),
+            groups: std::fs::read_to_string("/etc/group").unwrap_or_default(),
+            dbus_proxy: find_command("xdg-dbus-proxy").is_some(),
+            session_bus: system
+                .runtime_dir
+                .as_ref()
+                .map(|dir| dir.join("bus"))
+                .filter(|bus| bus.exists()),
+            document_portal: system
+                .runtime_dir
+                .as_ref()
+                .is_some_and(|dir| dir.join("doc").is_dir()),
+            avahi: has(Feature::Mdns) && Path::new(AVAHI_SOCKET).exists(),
+            ..HostState::default()
+        };
+
+        if has(Feature::HomePersistent) {
+            host.user_dirs = userdirs::host(Path::new(&home));
+            host.user_dirs_file =
+                userdirs::file().filter(|file| file.starts_with(&home) && file.exists());
+        }
+
+        if has(Feature::InputMethod) {
+            host.ibus_dirs = [(".config/ibus", true), (".cache/ibus", false)]
+                .iter()
+                .map(|(dir, read_only)| (format!("{}/{}", home, dir), *read_only))
+                .filter(|(dir, _)| Path::new(dir).exists())
+                .collect();
+        }
+
+        if has(Feature::HostTheme) {
+            host.theme = host_theme(&home, std::env::var("GTK_THEME").ok().as_deref());
+        }
+
+        // Resolved on every run as gamepads and scanners come and go
+        if has(Feature::Gamepad) {
+            host.gamepads = System::connected_gamepads()
+                .iter()
+                .chain(Some(PathBuf::from("/dev/uinput")).iter())
+                .filter(|device| device.exists())
+                .map(|device| device.display().to_string())
+                .collect();
+        }
+
+        if has(Feature::Scanner) {
+            host.scanners = System::connected_scanners()
+                .iter()
+                .filter_map(|device| System::scanner_node(device))
+                .filter_map(|node| node_group(&node))
+                .collect();
+        }
+
+        if has(Feature::Devices) {
+            let (dirs, nodes) = host_devices();
+
+            host.device_dirs = dirs;
+            host.device_nodes = nodes;
+        }
+
+        let (devices, unmatched) = expand_devices(&program.devices);
+
+        host.devices = devices.iter().filter_map(node_group).collect();
+        host.unmatched_devices = unmatched;
+        host.home = home;
+        Ok(host)
+    }
+}
+
+/// Run spec of the program of the app with name prefix `prefix`, the overrides of this run
+/// applied on top of it. The host is taken from `overrides.host` and isn't read or changed:
+/// the files and the proxies are described only
+pub fn build_run_spec(
+    program: &Program,
+    system: &System,
+    prefix: &str,
+    overrides: &RunOverrides,
+) -> AppResult<RunSpec> {
+    let host = &overrides.host;
+    let home = host.home.as_str();
+    let image = program.get_name(prefix);
+    let has = |feature: Feature| {
+        program.uses(&feature) && !(overrides.no_sound && feature == Feature::Sound)
+    };
+    // The socket paths are stable, a persistent container binds them on every start
+    let socket_dir = system
+        .runtime_dir
+        .to_owned()
+        .unwrap_or_else(|| host.temp_dir.to_owned());
+    // docker CLI reads the files before it starts the container
+    let temp_file = |extension: &str| {
+        host.temp_dir
+            .join(format!("{}-{}.{}", image, host.process_id, extension))
+    };
+    let mut spec = RunSpec {
+        image: image.to_owned(),
+        labels: vec![
+            format!("{}=true", MANAGED_LABEL),
+            format!("{}={}", PROGRAM_LABEL, program.get_name_short()),
+            format!("{}={}", PREFIX_LABEL, prefix),
+        ],
+        tty: host.tty,
+        network: overrides
+            .network
+            .to_owned()
+            .unwrap_or_else(|| "host".to_string()),
+        relabel: system.selinux,
+        cid_file: temp_file("cid"),
+        ..RunSpec::default()
+    };
+
+    spec.push_mount(
+        "Machine ID",
+        Area::Filesystem,
+        MountKind::Volume,
+        ("/etc/machine-id", "/etc/machine-id"),
+        false,
+    );
+    spec.push_mount(
+        "Machine ID",
+        Area::Filesystem,
+        MountKind::Volume,
+        ("/var/lib/dbus", "/var/lib/dbus"),
+        false,
+    );
+
+    match program.shm_size {
+        Some(size) => spec.shm_size = Some(size),
+        None => spec.push_mount(
+            "Shared memory",
+            Area::Ipc,
+            MountKind::Volume,
+            ("/dev/shm", "/dev/shm"),
+            false,
+        ),
+    }
+
+    let devices = has(Feature::Devices);
+    let device_rules = system
+        .docker_version
+        .api()
+        .is_some_and(|v| v >= DEVICE_RULES_API_VERSION);
+
+    // Opening /dev without device cgroup rules takes a privileged container, which turns off
+    // every confinement. Refuse it, the user can upgrade Docker or remove the feature
+    if devices && !device_rules {
+        return Err(AppError::Feature(format!(
+            "Docker API {} can't open devices to a container (requires {}.{}), remove the \
+             devices feature or upgrade Docker",
+            system.docker_version, DEVICE_RULES_API_VERSION.0, DEVICE_RULES_API_VERSION.1
+        )));
+    }
+
+    spec.capabilities = program.capabilities.to_owned();
+
+    for (capability, _) in program.resources.capabilities() {
+        if !spec.capabilities.iter().any(|c| c == capability) {
+            spec.capabilities.push(capability.to_string());
+        }
+    }
+
+    let seccomp = temp_file("seccomp");
+
+    spec.security_options = security_options(program, system, &seccomp);
+
+    if program.security.seccomp == Seccomp::Builtin {
+        spec.files.push(SpecFile {
+            path: seccomp,
+            content: SECCOMP_PROFILE.to_string(),
+            temporary: true,
+        });
+    }
+
+    // The image user maps to a subordinate ID of a rootless daemon, files it writes to the
+    // shared folders wouldn't belong to the current user. Root is the current user there
+    if system.rootless {
+        spec.user = Some("0:0".to_string());
+    }
+
+    if has(Feature::Display) {
+        match overrides.display.or(program.remote_display) {
+            Some(display) => {
+                warn!(
+                    "X11 traffic to {} is unencrypted, use a remote display on trusted networks \
+                     only",
+                    display
+                );
+                spec.push_env("Display", Area::Network, &format!("DISPLAY={}", display));
+            }
+            None => {
+                spec.push_mount(
+                    "Display",
+                    Area::Ipc,
+                    MountKind::Volume,
+                    ("/tmp/.X11-unix", "/tmp/.X11-unix"),
+                    false,
+                );
+                spec.push_env("Display", Area::Ipc, "DISPLAY");
+            }
+        }
+    }
+
+    let pulse_server = overrides
+        .pulse_server
+        .as_ref()
+        .or(program.pulse_server.as_ref());
+    let cookie = pulse_server.and_then(|server| server.cookie.as_ref());
+
+    if has(Feature::Sound) {
+        match pulse_server {
+            Some(server) => {
+                spec.push_env(
+                    "Sound",
+                    Area::Network,
+                    &format!("PULSE_SERVER={}", server.address()),
+                );
+
+                // Host sockets keep their labels, folders of the user are relabeled to be shared
+                if let Some(cookie) = cookie {
+                    spec.push_mount(
+                        "Sound",
+                        Area::Filesystem,
+                        MountKind::Shared,
+                        (path_str(cookie)?, PULSE_COOKIE),
+                        true,
+                    );
+                    spec.push_env(
+                        "Sound",
+                        Area::Filesystem,
+                        &format!("PULSE_COOKIE={}", PULSE_COOKIE),
+                    );
+                }
+            }
+            None => spec.push_device("Sound", "/dev/snd"),
+        }
+    }
+
+    // The user dirs of the host are shared along with the home, the ones outside it point into
+    // it through a user-dirs.dirs of their own
+    if has(Feature::HomePersistent) {
+        let reason = Feature::HomePersistent.to_string();
+        let user_dirs = &host.user_dirs;
+        let user_dirs_file = host
+            .user_dirs_file
+            .as_ref()
+            .filter(|_| user_dirs.iter().any(|dir| !dir.shared));
+
+        spec.push_mount(
+            &reason,
+            Area::Filesystem,
+            MountKind::Bind,
+            (home, home),
+            false,
+        );
+
+        for dir in user_dirs {
+            spec.push_env(
+                &reason,
+                Area::Filesystem,
+                &format!("{}={}", dir.variable(), dir.container.display()),
+            );
+        }
+
+        if let Some(file) = user_dirs_file {
+            let path = socket_dir.join(format!("{}.user-dirs", image));
+
+            spec.dirs.extend(
+                user_dirs
+                    .iter()
+                    .filter(|dir| !dir.shared)
+                    .map(|dir| dir.container.to_owned()),
+            );
+            spec.push_mount(
+                &reason,
+                Area::Filesystem,
+                MountKind::Bind,
+                (path_str(&path)?, path_str(file)?),
+                true,
+            );
+            spec.files.push(SpecFile {
+                path,
+                content: userdirs::render(user_dirs, Path::new(home)),
+                temporary: false,
+            });
+        }
+    }
+
+    // A share of the run replaces the stored one of its path
+    for share in program
+        .shares
+        .iter()
+        .filter(|share| !overrides.shares.iter().any(|(path, _)| *path == share.path))
+    {
+        spec.push_mount(
+            "Share",
+            Area::Filesystem,
+            MountKind::Shared,
+            (path_str(&share.path)?, path_str(&share.path)?),
+            share.read_only,
+        );
+    }
+
+    for (path, read_only) in &overrides.shares {
+        spec.push_mount(
+            "--share",
+            Area::Filesystem,
+            MountKind::Shared,
+            (path_str(path)?, path_str(path)?),
+            *read_only,
+        );
+    }
+
+    // The input method feature shares the whole session bus anyway. Names the filtered session
+    // bus lets through, the socket keeps the name it got for the keyring alone
+    let session_bus = &host.session_bus;
+    let session_names: Vec<String> = [
+        (Feature::Keyring, "org.freedesktop.secrets"),
+        (Feature::Portals, "org.freedesktop.portal.*"),
+    ]
+    .iter()
+    .filter(|(feature, _)| has(feature.to_owned()))
+    .map(|(_, name)| name.to_string())
+    .collect();
+    let dbus_proxy = host.dbus_proxy;
+
+    if !session_names.is_empty() && !dbus_proxy && !has(Feature::InputMethod) {
+        warn!(
+            "xdg-dbus-proxy isn't installed, {} gets the whole session bus to reach {}",
+            program.get_name_short(),
+            match (has(Feature::Keyring), has(Feature::Portals)) {
+                (true, true) => "the keyring and the portals",
+                (true, false) => "the keyring",
+                _ => "the portals",
+            }
+        );
+    }
+
+    spec.whole_session_bus =
+        has(Feature::InputMethod) || (!session_names.is_empty() && !dbus_proxy);
+
+    match session_bus {
+        Some(bus) if spec.whole_session_bus => {
+            let bus = path_str(bus)?;
+
+            spec.push_mount("Session bus", Area::Ipc, MountKind::Bind, (bus, bus), false);
+            spec.push_env(
+                "Session bus",
+                Area::Ipc,
+                &format!("DBUS_SESSION_BUS_ADDRESS=unix:path={}", bus),
+            );
+        }
+        Some(bus) if !session_names.is_empty() => {
+            let socket = socket_dir.join(format!("{}.secrets", image));
+
+            spec.push_mount(
+                "Session bus",
+                Area::Ipc,
+                MountKind::Bind,
+                (path_str(&socket)?, PROXY_BUS),
+                false,
+            );
+            spec.push_env(
+                "Session bus",
+                Area::Ipc,
+                &format!("DBUS_SESSION_BUS_ADDRESS=unix:path={}", PROXY_BUS),
+            );
+            spec.session_proxy = Some(SpecProxy {
+                bus: bus.to_owned(),
+                socket,
+                names: session_names,
+            });
+        }
+        _ => (),
+    }
+
+    // Files picked through the portal are exported to the FUSE mount of the document portal.
+    // A rootful daemon can't enter a FUSE mount of the user, the picked paths of the host are
+    // given to the program then
+    if has(Feature::Portals) {
+        spec.push_env("Portals", Area::Ipc, "GTK_USE_PORTAL=1");
+
+        match &system.runtime_dir {
+            Some(dir) if host.document_portal && system.rootless => {
+                let doc = path_str(&dir.join("doc"))?.to_string();
+
+                spec.push_mount(
+                    "Portals",
+                    Area::Filesystem,
+                    MountKind::Bind,
+                    (&doc, &doc),
+                    false,
+                );
+            }
+            Some(_) if host.document_portal => {
+                debug!("The document portal isn't shared with a rootful daemon")
+            }
+            _ => debug!("The document portal isn't running"),
+        }
+    }
+
+    // The system bus is never shared as a whole, it controls the host. BlueZ and Avahi are
+    // reached over the sockets, not the network
+    let system_names: Vec<String> = [
+        (Feature::Bluetooth, "org.bluez"),
+        (Feature::Mdns, "org.freedesktop.Avahi"),
+    ]
+    .iter()
+    .filter(|(feature, _)| has(feature.to_owned()))
+    .map(|(_, name)| name.to_string())
+    .collect();
+
+    if !system_names.is_empty() {
+        if !dbus_proxy {
+            return Err(AppError::Environment(format!(
+                "xdg-dbus-proxy isn't installed, it's required to reach {} on the system bus",
+                system_names.join(" and ")
+            )));
+        }
+
+        let socket = socket_dir.join(format!("{}.system", image));
+
+        spec.push_mount(
+            "System bus",
+            Area::Ipc,
+            MountKind::Bind,
+            (path_str(&socket)?, SYSTEM_BUS),
+            false,
+        );
+        spec.system_proxy = Some(SpecProxy {
+            bus: PathBuf::from(SYSTEM_BUS),
+            socket,
+            names: system_names,
+        });
+    }
+
+    // Listens as long as the program runs, the socket path is stable like the proxy ones
+    if has(Feature::OpenUrls) {
+        let socket = socket_dir.join(format!("{}.open-url", image));
+
+        spec.push_mount(
+            "Open URLs",
+            Area::Ipc,
+            MountKind::Bind,
+            (path_str(&socket)?, OPEN_URL_SOCKET),
+            false,
+        );
+        spec.url_socket = Some(socket);
+    }
+
+    // nss-mdns resolves `.local` names through the daemon of the host, docker would create a
+    // directory in place of a missing socket
+    if has(Feature::Mdns) {
+        match host.avahi {
+            true => spec.push_mount(
+                "mDNS",
+                Area::Ipc,
+                MountKind::Volume,
+                (AVAHI_SOCKET, AVAHI_SOCKET),
+                false,
+            ),
+            false => warn!(
+                "{} doesn't exist, {} can browse services but can't resolve .local names",
+                AVAHI_SOCKET,
+                program.get_name_short()
+            ),
+        }
+    }
+
+    // IBus publishes its address in the home, Fcitx is reached over the session bus. Abstract
+    // sockets are shared along with the host network
+    if has(Feature::InputMethod) {
+        for env in ["XMODIFIERS", "GTK_IM_MODULE", "QT_IM_MODULE"] {
+            spec.push_env("Input method", Area::Ipc, env);
+        }
+
+        for (dir, read_only) in &host.ibus_dirs {
+            spec.push_mount(
+                "Input method",
+                Area::Filesystem,
+                MountKind::Shared,
+                (dir, dir),
+                *read_only,
+            );
+        }
+    }
+
+    if has(Feature::HostTheme) {
+        let (binds, envs) = &host.theme;
+
+        for (source, target) in binds {
+            spec.push_mount(
+                "Host theme",
+                Area::Filesystem,
+                MountKind::Shared,
+                (source, target),
+                true,
+            );
+        }
+
+        for env in envs {
+            spec.push_env("Host theme", Area::Filesystem, env);
+        }
+    }
+
+    let host_groups = host.groups.as_str();
+
+    if has(Feature::Gamepad) {
+        let gamepads = &host.gamepads;
+
+        if !gamepads
+            .iter()
+            .any(|device| device.starts_with("/dev/input/"))
+        {
+            warn!(
+                "No gamepad is connected, plug one in and start {} again",
+                program.get_name_short()
+            );
+        }
+
+        for device in gamepads {
+            spec.push_device("Gamepad", device);
+        }
+
+        spec.groups.extend(get_group_ids(host_groups, &["input"]));
+    }
+
+    // USB scanners are passed through, the others are reached over saned of the host, which
+    // listens on localhost of the shared network
+    if has(Feature::Scanner) {
+        let scanners = &host.scanners;
+
+        if !scanners.is_empty() {
+            for (node, _) in scanners {
+                spec.push_device("Scanner", &node.display().to_string());
+            }
+
+            spec.groups
+                .extend(scanners.iter().map(|(_, group)| group.to_string()));
+        } else if system.saned {
+            let path = temp_file("sane");
+
+            spec.push_mount(
+                "Scanner",
+                Area::Filesystem,
+                MountKind::Bind,
+                (path_str(&path)?, SANE_NET_CONF),
+                true,
+            );
+            spec.files.push(SpecFile {
+                path,
+                content: "localhost\n".to_string(),
+                temporary: true,
+            });
+        } else {
+            return Err(AppError::Environment(format!(
+                "No scanner found for {}: `scanimage -L` lists no USB scanner and saned isn't \
+                 installed. Check the scanner works on the host with `scanimage -L`, or install \
+                 sane-utils and enable saned.socket to share a network scanner",
+                program.get_name_short()
+            )));
+        }
+    }
+
+    // docker adds the cgroup rule of each node, the groups owning them let the user open them
+    let mut device_groups: Vec<String> = host
+        .devices
+        .iter()
+        .map(|(_, group)| group.to_string())
+        .collect();
+
+    device_groups.sort();
+    device_groups.dedup();
+
+    if !host.unmatched_devices.is_empty() {
+        warn!(
+            "No device matches {}, {} starts without them",
+            host.unmatched_devices.join(", "),
+            program.get_name_short()
+        );
+    }
+
+    for (device, _) in &host.devices {
+        spec.push_device("Shared device", &device.display().to_string());
+    }
+
+    spec.groups.extend(device_groups);
+
+    // Programs crash without writable temporary directories, the home is kept in memory unless
+    // it's shared
+    if has(Feature::ReadOnlyRoot) {
+        spec.read_only = true;
+        spec.tmpfs = vec!["/tmp".to_string(), "/run".to_string()];
+
+        if !has(Feature::HomePersistent) {
+            spec.tmpfs.push(format!("{}:mode=1777", home));
+        }
+    }
+
+    if has(Feature::Time) {
+        spec.push_mount(
+            "Timezone",
+            Area::Filesystem,
+            MountKind::Volume,
+            ("/etc/localtime", "/etc/localtime"),
+            false,
+        );
+    }
+
+    if has(Feature::Notification) {
+        spec.push_mount(
+            "Notification",
+            Area::Filesystem,
+            MountKind::Volume,
+            ("/var/lib/dbus", "/var/lib/dbus"),
+            false,
+        );
+    }
+
+    // Only the device classes are shared: the whole /dev would open the terminals of the user
+    // too. Their directories are bound to see the devices plugged in later, the cgroup rules
+    // let the program open them
+    if devices {
+        for dir in &host.device_dirs {
+            let dir = dir.display().to_string();
+
+            // A directory shared by another feature is kept as it is
+            if !spec.devices.iter().any(|device| device.path == dir) {
+                spec.push_mount(
+                    "Devices",
+                    Area::Devices,
+                    MountKind::Volume,
+                    (&dir, &dir),
+                    false,
+                );
+            }
+        }
+
+        for node in &host.device_nodes {
+            spec.push_device("Devices", &node.display().to_string());
+        }
+
+        spec.device_rules = DEVICE_RULES.iter().map(|rule| rule.to_string()).collect();
+        spec.groups
+            .extend(get_group_ids(host_groups, DEVICE_GROUPS));
+    }
+
+    match program.container_mode {
+        ContainerMode::Ephemeral if overrides.keep_container => {
+            info!("The container is kept after exit, remove it with `docker rm` or `clean`")
+        }
+        ContainerMode::Ephemeral => spec.remove = true,
+        ContainerMode::Persistent => spec.name = Some(image.to_owned()),
+    }
+
+    for env in &overrides.env {
+        spec.push_env("--env", Area::Ipc, env);
+    }
+
+    spec.ulimits = program
+        .resources
+        .ulimits
+        .iter()
+        .map(|(name, value)| format!("{}={}:{}", name, value, value))
+        .collect();
+    spec.oom_score_adj = program.resources.oom_score_adj;
+    spec.nice = program.resources.nice;
+    // A program created from an image runs the command of the image unless it's set
+    spec.command = match (spec.nice, &overrides.command) {
+        (_, Some(command)) => Some(command.to_owned()),
+        (Some(_), None) => Some(program.command.to_owned()),
+        (None, None) if program.source_image.is_some() && !program.command.is_empty() => {
+            Some(program.command.to_owned())
+        }
+        (None, None) => None,
+    };
+
+    // The arguments reach the command as the parameters of its shell
+    if !overrides.args.is_empty() {
+        let command = spec
+            .command
+            .to_owned()
+            .unwrap_or(program.command.to_owned());
+
+        if command.is_empty() {
+            return Err(AppError::Argument(format!(
+                "{} runs the command of its image, set one with `edit --command` to pass \
+                 arguments to it",
+                program.get_name_short()
+            )));
+        }
+
+        spec.command = Some(format!("{} \"$@\"", command));
+        spec.command_args = overrides.args.to_owned();
+    }
+
+    Ok(spec)
+}
+
+/// `--security-opt` values confining a container of the program, the built-in seccomp profile
+/// is read from `builtin`
+fn security_options(program: &Program, system: &System, builtin: &Path) -> Vec<String> {
+    let mut options = vec![];
+
+    match &program.security.seccomp {
+        Seccomp::Builtin => options.push(format!("seccomp={}", builtin.display())),
+        // SELinux denies the sockets of the host to confined containers
+        Seccomp::Unconfined if system.selinux => {
+            options.push("seccomp=unconfined".to_string());
+            options.push("label=disable".to_string());
+        }
+        Seccomp::Unconfined => options.push("seccomp=unconfined".to_string()),
+        Seccomp::Profile(path) => options.push(format!("seccomp={}", path.display())),
+    }
+
+    if let Some(profile) = &program.security.apparmor {
+        match system.apparmor {
+            true => options.push(format!("apparmor={}", profile)),
+            false => warn!(
+                "AppArmor isn't enabled on the host, profile {} is ignored",
+                profile
+            ),
+        }
+    }
+
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::driver::DockerVersion;
+
+    fn get_program() -> Program {
+        Program::new(
+            "foo",
+            Path::new("/tmp/foo.deb"),
+            &vec![],
+            &None,
+            &None,
+            &None,
+        )
+    }
+
+    #[test]
+    fn test_build_run_spec() {
+        let mut program = get_program();
+
+        program.settings = vec![Feature::Display, Feature::Sound, Feature::ReadOnlyRoot];
+        program.resources.nice = Some(5);
+
+        let spec = build_run_spec(
+            &program,
+            &System::offline(),
+            "debian_bridge",
+            &RunOverrides::default(),
+        )
+        .unwrap();
+        let args = spec.args();
+        let has_args = |pair: &[&str]| args.windows(pair.len()).any(|window| window == pair);
+
+        assert_eq!(spec.image, "debian_bridge_foo");
+        assert!(spec.remove);
+        assert!(spec.read_only);
+        assert!(has_args(&["-v", "/tmp/.X11-unix:/tmp/.X11-unix"]));
+        assert!(has_args(&["--env", "DISPLAY"]));
+        assert!(has_args(&["--device", "/dev/snd"]));
+        assert!(has_args(&["--cap-drop", "ALL"]));
+        assert!(has_args(&["--tmpfs", "/tmp"]));
+        assert!(has_args(&["--entrypoint", "nice"]));
+        assert!(has_args(&["debian_bridge_foo", "-n", "5", "/bin/sh", "-c"]));
+        assert!(args.contains(&"--rm".to_string()));
+        assert_eq!(spec.files.len(), 1);
+        assert!(spec.files[0].temporary);
+        assert!(spec.files[0].content.contains("SCMP_ACT_ERRNO"));
+        assert!(spec.session_proxy.is_none() && !spec.whole_session_bus);
+
+        program.settings = vec![Feature::InputMethod, Feature::Keyring];
+        program.container_mode = ContainerMode::Persistent;
+
+        let spec = build_run_spec(
+            &program,
+            &System {
+                rootless: true,
+                ..System::offline()
+            },
+            "debian_bridge",
+            &RunOverrides::default(),
+        )
+        .unwrap();
+
+        assert!(spec.whole_session_bus);
+        assert!(spec.session_proxy.is_none());
+        assert_eq!(spec.name, Some("debian_bridge_foo".to_string()));
+        assert_eq!(spec.user, Some("0:0".to_string()));
+        assert!(!spec.args().contains(&"--rm".to_string()));
+    }
+
+    #[test]
+    fn test_build_run_spec_host() {
+        let mut program = get_program();
+
+        program.settings = vec![
+            Feature::Gamepad,
+            Feature::InputMethod,
+            Feature::ReadOnlyRoot,
+        ];
+
+        let overrides = RunOverrides {
+            host: HostState {
+                home: "/home/user".to_string(),
+                temp_dir: PathBuf::from("/var/tmp"),
+                process_id: 42,
+                groups: "input:x:104:\nvideo:x:44:\n".to_string(),
+                ibus_dirs: vec![("/home/user/.config/ibus".to_string(), true)],
+                gamepads: vec!["/dev/input/event3".to_string()],
+                devices: vec![(PathBuf::from("/dev/ttyUSB0"), 20)],
+                ..HostState::default()
+            },
+            ..RunOverrides::default()
+        };
+        let spec =
+            build_run_spec(&program, &System::offline(), "debian_bridge", &overrides).unwrap();
+
+        assert_eq!(
+            spec.cid_file,
+            PathBuf::from("/var/tmp/debian_bridge_foo-42.cid")
+        );
+        assert!(spec.tmpfs.contains(&"/home/user:mode=1777".to_string()));
+        assert!(spec
+            .mounts
+            .iter()
+            .any(|mount| mount.reason == "Input method"
+                && mount.source == "/home/user/.config/ibus"
+                && mount.read_only));
+        assert_eq!(
+            spec.devices
+                .iter()
+                .map(|device| device.path.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["/dev/input/event3", "/dev/ttyUSB0"]
+        );
+        assert_eq!(spec.groups, vec!["104", "20"]);
+        assert!(!spec.tty);
+    }
+
+    #[test]
+    fn test_build_run_spec_overrides() {
+        let mut program = get_program();
+
+        program.settings = vec![Feature::Display, Feature::Sound];
+
+        let overrides = RunOverrides {
+            env: vec!["LANG=C".to_string()],
+            shares: vec![(PathBuf::from("/srv/data"), true)],
+            network: Some("none".to_string()),
+            no_sound: true,
+            command: Some("foo --safe-mode".to_string()),
+            ..RunOverrides::default()
+        };
+        let spec =
+            build_run_spec(&program, &System::offline(), "debian_bridge", &overrides).unwrap();
+        let args = spec.args();
+        let has_args = |pair: &[&str]| args.windows(pair.len()).any(|window| window == pair);
+
+        assert!(has_args(&["--net", "none"]));
+        assert!(has_args(&["--env", "LANG=C"]));
+        assert!(has_args(&[
+            "--mount",
+            "type=bind,source=/srv/data,target=/srv/data,readonly"
+        ]));
+        assert!(!has_args(&["--device", "/dev/snd"]));
+        assert!(has_args(&[
+            "debian_bridge_foo",
+            "/bin/sh",
+            "-c",
+            "foo --safe-mode"
+        ]));
+
+        let overrides = RunOverrides {
+            args: vec!["--depth".to_string(), "two words".to_string()],
+            ..RunOverrides::default()
+        };
+        let args = build_run_spec(&program, &System::offline(), "debian_bridge", &overrides)
+            .unwrap()
+            .args();
+
+        assert_eq!(
+            args[args.len() - 6..],
+            ["/bin/sh", "-c", "foo \"$@\"", "sh", "--depth", "two words"]
+        );
+        assert!(spec.permissions().contains(&Permission {
+            area: Area::Network,
+            reason: "Mode".to_string(),
+            value: "none".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let mut program = get_program();
+
+        program.settings = vec![Feature::Display, Feature::Sound, Feature::Devices];
+
+        // Docker API of an offline system is unknown, it can't open devices safely
+        assert!(build_run_spec(
+            &program,
+            &System::offline(),
+            "debian_bridge",
+            &RunOverrides::default(),
+        )
+        .is_err());
+
+        let system = System {
+            docker_version: DockerVersion("1.40".to_string()),
+            ..System::offline()
+        };
+        let spec =
+            build_run_spec(&program, &system, "debian_bridge", &RunOverrides::default()).unwrap();
+        let permissions = spec.permissions();
+        let areas: Vec<Area> = permissions
+            .iter()
+            .map(|permission| permission.area)
+            .collect();
+
+        assert!(areas.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(
+            permissions[0],
+            Permission {
+                area: Area::Filesystem,
+                reason: "Root".to_string(),
+                value: "writable".to_string(),
+            }
+        );
+        assert!(permissions.contains(&Permission {
+            area: Area::Devices,
+            reason: "Sound".to_string(),
+            value: "/dev/snd".to_string(),
+        }));
+        assert!(permissions.contains(&Permission {
+            area: Area::Ipc,
+            reason: "Display".to_string(),
+            value: "$DISPLAY of the host".to_string(),
+        }));
+        assert!(permissions
+            .iter()
+            .any(|permission| permission.area == Area::Network && permission.reason == "Mode"));
+    }
+
+    #[test]
+    fn test_build_run_spec_devices() {
+        let mut program = get_program();
+
+        program.settings = vec![Feature::Devices];
+
+        let system = System {
+            docker_version: DockerVersion("1.40".to_string()),
+            ..System::offline()
+        };
+        let overrides = RunOverrides {
+            host: HostState {
+                device_dirs: vec![PathBuf::from("/dev/input"), PathBuf::from("/dev/snd")],
+                device_nodes: vec![PathBuf::from("/dev/video0")],
+                ..HostState::default()
+            },
+            ..RunOverrides::default()
+        };
+        let spec = build_run_spec(&program, &system, "debian_bridge", &overrides).unwrap();
+        let mounts: Vec<&str> = spec
+            .mounts
+            .iter()
+            .filter(|mount| mount.reason == "Devices")
+            .map(|mount| mount.source.as_str())
+            .collect();
+
+        assert_eq!(mounts, vec!["/dev/input", "/dev/snd"]);
+        assert!(!spec.mounts.iter().any(|mount| mount.source == "/dev"));
+        assert!(spec
+            .devices
+            .iter()
+            .any(|device| device.path == "/dev/video0"));
+        assert!(!spec.device_rules.is_empty());
+    }
+
+    #[test]
+    fn test_security_options() {
+        let builtin =
+            std::env::temp_dir().join(format!("debian_bridge_test_seccomp_{}", std::process::id()));
+        let mut program = get_program();
+
+        program.security.apparmor = Some("desktop".to_string());
+
+        let options = security_options(&program, &System::offline(), &builtin);
+
+        assert_eq!(options, vec![format!("seccomp={}", builtin.display())]);
+
+        program.security.seccomp = Seccomp::Unconfined;
+
+        let system = System {
+            apparmor: true,
+            ..System::offline()
+        };
+
+        assert_eq!(
+            security_options(&program, &system, &builtin),
+            vec!["seccomp=unconfined", "apparmor=desktop"]
+        );
+        assert_eq!(
+            security_options(
+                &program,
+                &System {
+                    selinux: true,
+                    ..System::offline()
+                },
+                &builtin
+            ),
+            vec!["seccomp=unconfined", "label=disable"]
+        );
+
+        program.security.seccomp = Seccomp::Profile(builtin.to_owned());
+
+        assert_eq!(
+            security_options(&program, &system, &builtin),
+            vec![
+                format!("seccomp={}", builtin.display()),
+                "apparmor=desktop".to_string()
+            ]
+        );
+        // A missing profile is found when the host is read
+        assert!(HostState::read(&program, &system, &RunOverrides::default()).is_err());
+    }
+}
@@ -0,0 +1,1770 @@
+//! Doubles to test code embedding the core without a docker daemon, enabled by the `testing`
+//! feature
+
+use super::{
+    backend::Backend,
+    context::BuildContext,
+    docker::{
+        BuildSpec, ManagedImage, RunOutcome, CREATED_FROM_LABEL, MANAGED_LABEL, PREFIX_LABEL,
+        PROGRAM_LABEL,
+    },
+    error::AppError,
+    events::{AppEvent, Reporter},
+    util::sha256_file,
+    ContainerMode, Program, RunOptions,
+};
+use crate::{
+    sys::driver::{DisplayServer, DockerVersion, WindowManager},
+    System,
+};
+use chrono::Utc;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Operation changing the state of a `MockBackend`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    Build {
+        tag: String,
+    },
+    Pull(String),
+    Tag {
+        from: String,
+        to: String,
+    },
+    DeleteImage(String),
+    RenameContainer {
+        from: String,
+        to: String,
+    },
+    DeleteContainer(String),
+    StopContainer(String),
+    SaveImage(String),
+    LoadImage(PathBuf),
+    /// Run of the image with the given name
+    Run(String),
+}
+
+/// Container started by a run of a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockContainer {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub program: Option<String>,
+}
+
+type Failure = Box<dyn Fn(&Call) -> bool + Send>;
+
+#[derive(Default)]
+struct State {
+    images: Vec<ManagedImage>,
+    containers: Vec<MockContainer>,
+    calls: Vec<Call>,
+    failures: Vec<Failure>,
+    /// Exit code and output of every probe
+    probe: (i32, String),
+    next_id: u64,
+}
+
+/// Backend keeping images and containers in memory. Calls changing them are recorded and can be
+/// scripted to fail. Clones share the state, so a clone kept by a test sees what the app did
+///
+/// # Example
+/// ```
+/// # use debian_bridge_core::{testing::{Call, MockBackend}, App, Config};
+/// # use std::path::Path;
+/// #
+/// let backend = MockBackend::new()
+///     .image("debian_bridge_foo", Some("foo"))
+///     .fail_on(|call| matches!(call, Call::Build { .. }));
+/// let app = App::with_backend("debian_bridge", Path::new("./cache"), &Config::default(), Box::new(backend.clone()));
+///
+/// assert!(backend.calls().is_empty());
+/// ```
+#[derive(Clone)]
+pub struct MockBackend {
+    system: System,
+    prefix: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        MockBackend {
+            system: System {
+                display: Some(DisplayServer {
+                    kind: WindowManager::X11,
+                    socket: None,
+                }),
+                sound: None,
+                input_method: None,
+                secret_service: false,
+                portal: false,
+                bluetooth: false,
+                avahi: false,
+                scanners: vec![],
+                saned: false,
+                gamepads: vec![],
+                docker_version: DockerVersion("1.42".to_string()),
+                docker_server: None,
+                arch: "x86_64".to_string(),
+                runtime_dir: None,
+                rootless: false,
+                apparmor: false,
+                selinux: false,
+            },
+            prefix: "debian_bridge".to_string(),
+            state: Default::default(),
+        }
+    }
+}
+
+impl MockBackend {
+    /// Backend of an X11 system without sound and with a BuildKit capable engine
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn system(mut self, system: System) -> Self {
+        self.system = system;
+        self
+    }
+
+    /// Adds a labeled image, built for `program` or a base one, by an app of the prefix set so
+    /// far
+    pub fn image(self, tag: &str, program: Option<&str>) -> Self {
+        let id = self.next_id("image");
+
+        self.state().images.push(ManagedImage {
+            id,
+            tags: vec![normalize(tag)],
+            program: program.map(|program| program.to_string()),
+            size: 0,
+            labeled: true,
+            created_from: None,
+            prefix: Some(self.prefix.to_owned()),
+        });
+        self
+    }
+
+    /// Makes calls matching `predicate` fail, the state is left untouched
+    pub fn fail_on<F: Fn(&Call) -> bool + Send + 'static>(self, predicate: F) -> Self {
+        self.state().failures.push(Box::new(predicate));
+        self
+    }
+
+    /// Makes probes of the images exit with `code` and print `output`, they succeed silently by
+    /// default
+    pub fn probe_result<T: Into<String>>(self, code: i32, output: T) -> Self {
+        self.state().probe = (code, output.into());
+        self
+    }
+
+    /// Calls made so far, in order
+    pub fn calls(&self) -> Vec<Call> {
+        self.state().calls.to_owned()
+    }
+
+    pub fn images(&self) -> Vec<ManagedImage> {
+        self.state().images.to_owned()
+    }
+
+    pub fn containers(&self) -> Vec<MockContainer> {
+        self.state().containers.to_owned()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn next_id(&self, kind: &str) -> String {
+        let mut state = self.state();
+
+        state.next_id += 1;
+        format!("{}{:012}", kind, state.next_id)
+    }
+
+    /// Records the call, fails if it's scripted to
+    fn record(&self, call: Call) -> AppResult<()> {
+        let mut state = self.state();
+        let failed = state.failures.iter().any(|failure| failure(&call));
+
+        state.calls.push(call.to_owned());
+
+        match (failed, call) {
+            (false, _) => Ok(()),
+            (true, Call::Build { tag }) => Err(AppError::DockerBuild(
+                format!("scripted failure of '{}'", tag),
+                None,
+            )),
+            (true, Call::Run(image)) => Err(AppError::DockerRun(
+                format!("scripted failure of '{}'", image),
+                None,
+            )),
+            (true, call) => Err(AppError::DockerStatus {
+                code: 500,
+                message: "scripted failure".to_string(),
+                operation: format!("{:?}", call),
+            }),
+        }
+    }
+
+    fn find_image(&self, reference: &str) -> Option<ManagedImage> {
+        let tag = normalize(reference);
+
+        self.state()
+            .images
+            .iter()
+            .find(|image| image.id == reference || image.tags.contains(&tag))
+            .cloned()
+    }
+}
+
+impl Backend for MockBackend {
+    fn system(&self) -> &System {
+        &self.system
+    }
+
+    fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Backend> {
+        Box::new(self.clone())
+    }
+
+    fn ping(&self) -> AppResult<()> {
+        Ok(())
+    }
+
+    fn get_containers(&self, image: &str) -> AppResult<Vec<String>> {
+        let image = normalize(image);
+
+        Ok(self
+            .state()
+            .containers
+            .iter()
+            .filter(|container| normalize(&container.image) == image)
+            .map(|container| container.id.to_owned())
+            .collect())
+    }
+
+    fn exited_containers(&self) -> AppResult<Vec<String>> {
+        Ok(self
+            .state()
+            .containers
+            .iter()
+            .filter(|container| container.program.is_some())
+            .map(|container| container.name.to_owned())
+            .collect())
+    }
+
+    fn labeled_containers(&self) -> AppResult<Vec<String>> {
+        Ok(self
+            .state()
+            .containers
+            .iter()
+            .filter(|container| container.program.is_some())
+            .map(|container| container.id.to_owned())
+            .collect())
+    }
+
+    /// Containers of the mock never run past `run`
+    fn running_containers(&self, _program: &str) -> AppResult<Vec<String>> {
+        Ok(vec![])
+    }
+
+    fn containers_size(&self, _program: &str) -> AppResult<u64> {
+        Ok(0)
+    }
+
+    fn container_id(&self, name: &str) -> AppResult<Option<String>> {
+        Ok(self
+            .state()
+            .containers
+            .iter()
+            .find(|container| container.name == name || container.id == name)
+            .map(|container| container.id.to_owned()))
+    }
+
+    fn rename_container(&self, from: &str, to: &str) -> AppResult<()> {
+        self.record(Call::RenameContainer {
+            from: from.to_string(),
+            to: to.to_string(),
+        })?;
+
+        match self
+            .state()
+            .containers
+            .iter_mut()
+            .find(|container| container.name == from)
+        {
+            Some(container) => {
+                container.name = to.to_string();
+                Ok(())
+            }
+            None => Err(not_found("container", from)),
+        }
+    }
+
+    fn stop_container(&self, id: &str) -> AppResult<()> {
+        self.record(Call::StopContainer(id.to_string()))
+    }
+
+    fn delete_container(&self, id: &str) -> AppResult<()> {
+        self.record(Call::DeleteContainer(id.to_string()))?;
+
+        let mut state = self.state();
+        let count = state.containers.len();
+
+        state
+            .containers
+            .retain(|container| container.id != id && container.name != id);
+
+        match state.containers.len() < count {
+            true => Ok(()),
+            false => Err(not_found("container", id)),
+        }
+    }
+
+    fn build(
+        &self,
+        _context: &BuildContext,
+        tag: &str,
+        spec: &BuildSpec,
+        _reporter: &Reporter,
+    ) -> AppResult<()> {
+        self.record(Call::Build {
+            tag: tag.to_string(),
+        })?;
+
+        let id = self.next_id("image");
+        let tag = normalize(tag);
+        let label = |name: &str| {
+            spec.labels
+                .iter()
+                .find(|(label, _)| label == name)
+                .map(|(_, value)| value.to_owned())
+        };
+        let mut state = self.state();
+
+        // The tag moves to the new image, the previous one is left dangling and pruned
+        state
+            .images
+            .iter_mut()
+            .for_each(|image| image.tags.retain(|t| t != &tag));
+        state.images.retain(|image| !image.tags.is_empty());
+        state.images.push(ManagedImage {
+            id,
+            tags: vec![tag],
+            program: label(PROGRAM_LABEL),
+            size: 0,
+            labeled: label(MANAGED_LABEL).is_some(),
+            created_from: label(CREATED_FROM_LABEL),
+            prefix: label(PREFIX_LABEL),
+        });
+
+        // A cancelled build fails once its image is tagged, like one which finished meanwhile
+        match spec.cancel.is_cancelled() {
+            true => Err(AppError::DockerBuild(
+                "The build was cancelled".to_string(),
+                None,
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn pull(&self, image: &str) -> AppResult<()> {
+        self.record(Call::Pull(image.to_string()))
+    }
+
+    fn image_exists(&self, tag: &str) -> AppResult<bool> {
+        Ok(self.find_image(tag).is_some())
+    }
+
+    fn image_size(&self, tag: &str) -> AppResult<Option<u64>> {
+        Ok(self.find_image(tag).map(|image| image.size))
+    }
+
+    fn image_id(&self, tag: &str) -> AppResult<String> {
+        self.find_image(tag)
+            .map(|image| image.id)
+            .ok_or_else(|| not_found("image", tag))
+    }
+
+    /// Images of other prefixes are left out like docker does
+    fn managed_images(&self) -> AppResult<Vec<ManagedImage>> {
+        Ok(self
+            .images()
+            .into_iter()
+            .filter(|image| image.prefix.as_ref().is_none_or(|p| p == &self.prefix))
+            .collect())
+    }
+
+    fn image_prefix(&self, tag: &str) -> AppResult<Option<String>> {
+        Ok(self.find_image(tag).and_then(|image| image.prefix))
+    }
+
+    fn tag(&self, from: &str, to: &str) -> AppResult<()> {
+        self.record(Call::Tag {
+            from: from.to_string(),
+            to: to.to_string(),
+        })?;
+
+        let image = self
+            .find_image(from)
+            .ok_or_else(|| not_found("image", from))?;
+        let to = normalize(to);
+        let mut state = self.state();
+
+        state
+            .images
+            .iter_mut()
+            .for_each(|image| image.tags.retain(|t| t != &to));
+
+        if let Some(image) = state.images.iter_mut().find(|i| i.id == image.id) {
+            image.tags.push(to);
+        }
+
+        state.images.retain(|image| !image.tags.is_empty());
+        Ok(())
+    }
+
+    fn delete_image(&self, tag: &str) -> AppResult<()> {
+        self.record(Call::DeleteImage(tag.to_string()))?;
+
+        let image = self
+            .find_image(tag)
+            .ok_or_else(|| not_found("image", tag))?;
+        let reference = normalize(tag);
+        let mut state = self.state();
+
+        match image.id == tag || image.tags.len() == 1 {
+            true => state.images.retain(|i| i.id != image.id),
+            false => state
+                .images
+                .iter_mut()
+                .for_each(|image| image.tags.retain(|t| t != &reference)),
+        }
+
+        Ok(())
+    }
+
+    /// The tarball lists the tags of the image, one per line
+    fn save_image(&self, tag: &str, path: &Path, reporter: &Reporter) -> AppResult<String> {
+        self.record(Call::SaveImage(tag.to_string()))?;
+
+        let image = self
+            .find_image(tag)
+            .ok_or_else(|| not_found("image", tag))?;
+        let content = image.tags.join("\n");
+
+        std::fs::write(path, &content)?;
+        reporter.emit(AppEvent::ImageTransfer {
+            bytes: content.len() as u64,
+            total: Some(image.size),
+        });
+
+        sha256_file(path)
+    }
+
+    fn load_image(&self, path: &Path) -> AppResult<Vec<String>> {
+        self.record(Call::LoadImage(path.to_owned()))?;
+
+        let tags: Vec<String> = std::fs::read_to_string(path)?
+            .lines()
+            .map(normalize)
+            .collect();
+        let id = self.next_id("image");
+        let mut state = self.state();
+
+        state
+            .images
+            .iter_mut()
+            .for_each(|image| image.tags.retain(|t| !tags.contains(t)));
+        state.images.retain(|image| !image.tags.is_empty());
+        state.images.push(ManagedImage {
+            id,
+            tags: tags.to_owned(),
+            program: None,
+            size: 0,
+            labeled: true,
+            created_from: None,
+            prefix: None,
+        });
+
+        Ok(tags)
+    }
+
+    fn probe(&self, image: &str, _script: &str) -> AppResult<(i32, String)> {
+        if self.find_image(image).is_none() {
+            return Err(not_found("image", image));
+        }
+
+        Ok(self.state().probe.to_owned())
+    }
+
+    fn run(
+        &self,
+        program: &Program,
+        options: &RunOptions,
+        log: Option<&Path>,
+    ) -> AppResult<RunOutcome> {
+        let image = program.get_name(&self.prefix);
+
+        self.record(Call::Run(image.to_owned()))?;
+
+        if self.find_image(&image).is_none() {
+            return Err(not_found("image", &image));
+        }
+
+        if let Some(log) = log {
+            std::fs::create_dir_all(log.parent().unwrap_or(log))?;
+            std::fs::write(log, format!("{} started\n", image))?;
+        }
+
+        let id = match program.container_mode {
+            ContainerMode::Persistent => self.container_id(&image)?,
+            ContainerMode::Ephemeral => None,
+        };
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let id = self.next_id("container");
+                let name = match program.container_mode {
+                    ContainerMode::Persistent => image.to_owned(),
+                    ContainerMode::Ephemeral => format!("mock_{}", id),
+                };
+
+                if program.container_mode == ContainerMode::Persistent
+                    || options.overrides.keep_container
+                {
+                    self.state().containers.push(MockContainer {
+                        id: id.to_owned(),
+                        name,
+                        image,
+                        program: Some(program.get_name_short()),
+                    });
+                }
+
+                id
+            }
+        };
+
+        Ok(RunOutcome {
+            exit_code: Some(0),
+            container_id: Some(id),
+            started: Utc::now(),
+            duration: Duration::from_secs(0),
+            log: None,
+        })
+    }
+}
+
+/// Writes a minimal package named `package` into `dir`, returns its path. `ar` and `tar` are
+/// used, like when a package is read
+pub fn write_deb(dir: &Path, package: &str, version: &str) -> AppResult<PathBuf> {
+    let work = dir.join(format!("{}.control", package));
+    let path = dir.join(format!("{}.deb", package));
+
+    std::fs::create_dir_all(&work)?;
+    std::fs::write(work.join("debian-binary"), "2.0\n")?;
+    std::fs::write(
+        work.join("control"),
+        format!(
+            "Package: {}\nVersion: {}\nArchitecture: amd64\nDescription: Test package\n",
+            package, version
+        ),
+    )?;
+
+    std::fs::remove_file(&path).unwrap_or(());
+
+    for args in [
+        vec!["tar", "czf", "control.tar.gz", "./control"],
+        vec![
+            "ar",
+            "rc",
+            path.to_str().unwrap_or_default(),
+            "debian-binary",
+            "control.tar.gz",
+        ],
+    ] {
+        let status = Command::new(args[0])
+            .args(&args[1..])
+            .current_dir(&work)
+            .status()?;
+
+        if !status.success() {
+            return Err(AppError::Deb(
+                format!("'{}' failed: {}", args[0], status),
+                None,
+            ));
+        }
+    }
+
+    std::fs::remove_dir_all(&work)?;
+    Ok(path)
+}
+
+/// Adds the default tag like docker does
+fn normalize(reference: &str) -> String {
+    match reference
+        .rsplit('/')
+        .next()
+        .unwrap_or(reference)
+        .contains(':')
+    {
+        true => reference.to_string(),
+        false => format!("{}:latest", reference),
+    }
+}
+
+fn not_found(kind: &str, reference: &str) -> AppError {
+    AppError::DockerStatus {
+        code: 404,
+        message: format!("No such {}: {}", kind, reference),
+        operation: format!("inspect {} '{}'", kind, reference),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::util::{self, get_user},
+        App, BuildSettings, Config, CreateRequest, Feature, Icon, NetworkMode, NetworkStep,
+        ProgramChanges, ProgramKind, PulseServer, Repair, RestartPolicy, RunOptions, SearchField,
+        LOG_KEEP,
+    };
+    use mocktopus::mocking::{MockResult, Mockable};
+
+    /// Temporary directory removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "debian_bridge_test_{}_{}",
+                name,
+                std::process::id()
+            ));
+
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).unwrap_or(());
+        }
+    }
+
+    fn get_app(dir: &TempDir, config: &Config, backend: &MockBackend) -> App {
+        let state = dir.0.join("state");
+
+        get_user.mock_safe(|| MockResult::Return(Some("user".to_string())));
+        util::state_dir.mock_safe(move || MockResult::Return(Ok(state.to_owned())));
+
+        App::with_backend(
+            "debian_bridge",
+            &dir.0,
+            config,
+            Box::new(backend.to_owned()),
+        )
+    }
+
+    fn builds(backend: &MockBackend) -> Vec<String> {
+        backend
+            .calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Build { tag } => Some(tag),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_create() {
+        let dir = TempDir::new("create");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        let program = app.create(&CreateRequest::from_deb(&deb)).unwrap();
+        let builds = builds(&backend);
+
+        assert_eq!(builds.len(), 2);
+        assert!(builds[0].starts_with("debian_bridge_base:"));
+        assert_eq!(builds[1], "debian_bridge_foo");
+        assert_eq!(app.list(), vec!["foo".to_string()]);
+        assert_eq!(program.base.as_ref(), Some(&builds[0]));
+        assert!(program.image_id.is_some());
+        assert_eq!(
+            backend
+                .images()
+                .iter()
+                .find(|image| image.tags.contains(&"debian_bridge_foo:latest".to_string()))
+                .and_then(|image| image.program.to_owned()),
+            Some("foo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_create_rollback() {
+        let dir = TempDir::new("rollback");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new().fail_on(|call| {
+            call == &Call::Build {
+                tag: "debian_bridge_foo".into(),
+            }
+        });
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        match app.create(&CreateRequest::from_deb(&deb)) {
+            Err(AppError::DockerBuild(_, _)) => (),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert!(app.list().is_empty());
+        assert!(!backend.image_exists("debian_bridge_foo").unwrap());
+    }
+
+    #[test]
+    fn test_create_cancelled() {
+        let dir = TempDir::new("create_cancelled");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        app.create(&CreateRequest::from_deb(&foo)).unwrap();
+        app.cancel_handle().cancel();
+
+        assert!(app.create(&CreateRequest::from_deb(&bar)).is_err());
+        assert_eq!(app.list(), vec!["foo".to_string()]);
+        assert!(!backend.image_exists("debian_bridge_bar").unwrap());
+        assert!(backend.image_exists("debian_bridge_foo").unwrap());
+    }
+
+    #[test]
+    fn test_create_duplicate() {
+        let dir = TempDir::new("duplicate");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        app.create(&CreateRequest::from_deb(&deb)).unwrap();
+
+        match app.create(&CreateRequest::from_deb(&deb)) {
+            Err(AppError::Program(_)) => (),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert_eq!(builds(&backend).len(), 2);
+        assert_eq!(app.list().len(), 1);
+    }
+
+    #[test]
+    fn test_create_remote_sound() {
+        let dir = TempDir::new("remote_sound");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new().system(System {
+            sound: None,
+            ..System::offline()
+        });
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let request = CreateRequest::from_deb(&deb).features(&[Feature::Sound]);
+
+        match app.create(&request) {
+            Err(AppError::Feature(_)) => (),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let server = PulseServer::new("192.168.1.5".parse().unwrap());
+        let program = app.create(&request.pulse_server(&server)).unwrap();
+
+        assert_eq!(program.pulse_server, Some(server));
+    }
+
+    #[test]
+    fn test_create_settings() {
+        let dir = TempDir::new("create_settings");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut config = Config::default();
+
+        config.set_setting("features", "home,timezone").unwrap();
+        config.set_setting("desktop-entries", "false").unwrap();
+        config.set_setting("base-image", "debian:stretch").unwrap();
+
+        let mut app = get_app(&dir, &config, &backend);
+        let program = app
+            .create(&CreateRequest::from_deb(&foo).icon(Icon::new(Path::new("/tmp/foo.png"))))
+            .unwrap();
+
+        assert_eq!(
+            program.settings,
+            vec![Feature::HomePersistent, Feature::Time]
+        );
+        assert!(program.icon.is_none());
+        assert_eq!(
+            builds(&backend)[0],
+            util::gen_base_tag("debian_bridge", &config.build).unwrap()
+        );
+
+        let program = app
+            .create(&CreateRequest::from_deb(&bar).features(&[Feature::Display]))
+            .unwrap();
+
+        assert_eq!(program.settings, vec![Feature::Display]);
+    }
+
+    #[test]
+    fn test_create_checks_command() {
+        let dir = TempDir::new("check_command");
+        let deb = write_deb(&dir.0, "code", "1.0").unwrap();
+        let backend = MockBackend::new()
+            .probe_result(util::PROBE_MISSING, "/usr/bin/ls\n/usr/share/code/code\n");
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let request = CreateRequest::from_deb(&deb);
+
+        match app.create(&request) {
+            Err(AppError::Program(message)) => assert!(message.contains("/usr/share/code/code")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert!(app.config.programs.is_empty());
+        assert!(!backend.image_exists("debian_bridge_code").unwrap());
+        assert!(app.create(&request.skip_check(true)).is_ok());
+    }
+
+    #[test]
+    fn test_create_checks_command_delete_failure() {
+        let dir = TempDir::new("check_command_delete");
+        let deb = write_deb(&dir.0, "code", "1.0").unwrap();
+        let backend = MockBackend::new()
+            .probe_result(util::PROBE_MISSING, "/usr/share/code/code\n")
+            .fail_on(|call| matches!(call, Call::DeleteImage(_)));
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        // The check error is returned, not the one of the image removal
+        match app.create(&CreateRequest::from_deb(&deb)) {
+            Err(AppError::Program(message)) => assert!(message.contains("/usr/share/code/code")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert!(app.config.programs.is_empty());
+    }
+
+    #[test]
+    fn test_create_default_icon() {
+        let dir = TempDir::new("default_icon");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let (icons, desktop) = (dir.0.join("icons"), dir.0.join("Desktop"));
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        util::user_icons_dir.mock_safe(move || MockResult::Return(Ok(icons.to_owned())));
+        util::desktop_dir.mock_safe(move || MockResult::Return(Ok(desktop.to_owned())));
+        util::is_gnome_terminal.mock_safe(|| MockResult::Return(true));
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        let icon = Icon::try_default().unwrap();
+        let program = app
+            .create(&CreateRequest::from_deb(&deb).icon(icon.to_owned()))
+            .unwrap();
+        let entry = std::fs::read_to_string(dir.0.join("Desktop").join("foo.desktop")).unwrap();
+
+        assert_eq!(program.icon.unwrap().path, icon.path);
+        assert!(icon.path.starts_with(dir.0.join("icons")));
+        assert!(std::fs::read(&icon.path)
+            .unwrap()
+            .starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(entry.contains(&format!("Icon={}\n", icon.path.display())));
+    }
+
+    #[test]
+    fn test_cli_program() {
+        let dir = TempDir::new("cli_program");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let (icons, desktop) = (dir.0.join("icons"), dir.0.join("Desktop"));
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        util::user_icons_dir.mock_safe(move || MockResult::Return(Ok(icons.to_owned())));
+        util::desktop_dir.mock_safe(move || MockResult::Return(Ok(desktop.to_owned())));
+        util::is_gnome_terminal.mock_safe(|| MockResult::Return(true));
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        let program = app
+            .create(
+                &CreateRequest::from_deb(&foo)
+                    .icon(Icon::try_default().unwrap())
+                    .kind(ProgramKind::Cli),
+            )
+            .unwrap();
+
+        assert_eq!(program.kind, ProgramKind::Cli);
+        assert!(program.icon.is_none());
+        assert!(!dir.0.join("Desktop").join("foo.desktop").exists());
+
+        app.create(&CreateRequest::from_deb(&bar).icon(Icon::try_default().unwrap()))
+            .unwrap();
+        assert!(dir.0.join("Desktop").join("bar.desktop").exists());
+
+        let (program, rebuilt) = app
+            .edit("bar", &ProgramChanges::new().kind(ProgramKind::Cli))
+            .unwrap();
+
+        assert!(!rebuilt);
+        assert!(program.icon.is_none());
+        assert!(!dir.0.join("Desktop").join("bar.desktop").exists());
+    }
+
+    #[test]
+    fn test_labels() {
+        let dir = TempDir::new("labels");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        assert!(app
+            .create(&CreateRequest::from_deb(&foo).label("my work"))
+            .is_err());
+
+        let program = app
+            .create(&CreateRequest::from_deb(&foo).label("work").label("work"))
+            .unwrap();
+
+        assert_eq!(program.labels, vec!["work"]);
+
+        app.create(&CreateRequest::from_deb(&bar)).unwrap();
+
+        assert!(app.add_label("bar", "work").unwrap());
+        assert!(!app.add_label("bar", "work").unwrap());
+        assert!(app.add_label("bar", "games").unwrap());
+        assert!(app.add_label("bar", "a,b").is_err());
+        assert_eq!(
+            app.group("work")
+                .iter()
+                .map(Program::get_name_short)
+                .collect::<Vec<String>>(),
+            vec!["foo", "bar"]
+        );
+
+        assert!(app.remove_label("foo", "work").unwrap());
+        assert!(!app.remove_label("foo", "work").unwrap());
+        assert_eq!(app.info("bar").unwrap().labels, vec!["work", "games"]);
+        assert_eq!(app.group("work").len(), 1);
+        assert!(app.group("missing").is_empty());
+    }
+
+    #[test]
+    fn test_profile_entry() {
+        let dir = TempDir::new("profile_entry");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let (icons, desktop) = (dir.0.join("icons"), dir.0.join("Desktop"));
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend).profile("work");
+
+        util::user_icons_dir.mock_safe(move || MockResult::Return(Ok(icons.to_owned())));
+        util::desktop_dir.mock_safe(move || MockResult::Return(Ok(desktop.to_owned())));
+        util::is_gnome_terminal.mock_safe(|| MockResult::Return(true));
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        app.create(&CreateRequest::from_deb(&deb).icon(Icon::try_default().unwrap()))
+            .unwrap();
+
+        let path = dir.0.join("Desktop").join("foo@work.desktop");
+        let entry = std::fs::read_to_string(&path).unwrap();
+
+        assert!(entry.contains("Name=foo (work)\n"));
+        assert!(entry
+            .contains("Exec=gnome-terminal -- /usr/bin/debian_bridge --profile work run foo\n"));
+        assert!(!dir.0.join("Desktop").join("foo.desktop").exists());
+        assert_eq!(app.remove("foo").unwrap().desktop_entry, Some(path));
+    }
+
+    #[test]
+    fn test_autostart() {
+        let dir = TempDir::new("autostart");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let autostart = dir.0.join("autostart");
+        let entry = autostart.join("debian_bridge_foo.desktop");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        util::autostart_dir.mock_safe(move || MockResult::Return(Ok(autostart.to_owned())));
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        let program = app
+            .create(&CreateRequest::from_deb(&deb).autostart(true))
+            .unwrap();
+
+        assert!(program.autostart);
+        assert!(std::fs::read_to_string(&entry)
+            .unwrap()
+            .contains("Exec=/usr/bin/debian_bridge run --detach foo\n"));
+
+        let (program, _) = app
+            .edit("foo", &ProgramChanges::new().autostart(false))
+            .unwrap();
+
+        assert!(!program.autostart);
+        assert!(!entry.exists());
+
+        app.edit("foo", &ProgramChanges::new().autostart(true))
+            .unwrap();
+
+        assert_eq!(app.remove("foo").unwrap().autostart, Some(entry.to_owned()));
+        assert!(!entry.exists());
+    }
+
+    #[test]
+    fn test_service() {
+        let dir = TempDir::new("service");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let units = dir.0.join("systemd");
+        let unit = units.join("debian-bridge-foo.service");
+        let commands = Arc::new(Mutex::new(vec![]));
+        let recorded = commands.clone();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        util::systemd_user_dir.mock_safe(move || MockResult::Return(Ok(units.to_owned())));
+        util::systemctl_output.mock_safe(move |args| {
+            recorded.lock().unwrap().push(args.join(" "));
+            MockResult::Return(Ok((true, String::new())))
+        });
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        app.create(&CreateRequest::from_deb(&deb)).unwrap();
+        assert!(app.service_status("foo").is_err());
+        assert_eq!(app.enable_service("foo").unwrap(), unit);
+        assert!(std::fs::read_to_string(&unit)
+            .unwrap()
+            .contains("ExecStart=/usr/bin/debian_bridge run foo\nExecStop=/usr/bin/debian_bridge stop foo\nRestart=on-failure\n"));
+
+        app.edit("foo", &ProgramChanges::new().restart(RestartPolicy::No))
+            .unwrap();
+
+        assert!(std::fs::read_to_string(&unit)
+            .unwrap()
+            .contains("Restart=no\n"));
+        assert_eq!(app.remove("foo").unwrap().service, Some(unit.to_owned()));
+        assert!(!unit.exists());
+        assert_eq!(
+            commands.lock().unwrap().as_slice(),
+            [
+                "daemon-reload",
+                "enable --now debian-bridge-foo.service",
+                "daemon-reload",
+                "disable --now debian-bridge-foo.service",
+                "daemon-reload",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_hooks() {
+        let dir = TempDir::new("hooks");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let log = dir.0.join("hooks.log");
+        let backend = MockBackend::new()
+            .fail_on(|call| matches!(call, Call::Run(image) if image.ends_with("foo")));
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let request = CreateRequest::from_deb(&deb)
+            .pre_run(format!("echo pre >> {}", log.display()))
+            .post_run(format!("echo post >> {}", log.display()));
+
+        app.create(&request).unwrap();
+
+        // The post-run hook runs even though the container failed
+        assert!(app.run("foo").is_err());
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "pre\npost\n");
+
+        app.edit(
+            "foo",
+            &ProgramChanges::new().pre_run(Some("echo 'firewall is down' >&2; false".to_string())),
+        )
+        .unwrap();
+
+        match app.run("foo") {
+            Err(AppError::Program(message)) => assert!(message.ends_with("firewall is down")),
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        assert_eq!(
+            backend
+                .calls()
+                .iter()
+                .filter(|call| matches!(call, Call::Run(_)))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_run_logs() {
+        let dir = TempDir::new("run_logs");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        app.create(&CreateRequest::from_deb(&foo)).unwrap();
+
+        for _ in 0..LOG_KEEP + 2 {
+            assert!(app.run("foo").unwrap().log.is_some());
+        }
+
+        let outcome = app
+            .run_with("foo", &RunOptions::new().no_log(true))
+            .unwrap();
+        let logs = app.logs("foo").unwrap();
+
+        assert!(outcome.log.is_none());
+        assert_eq!(logs.len(), LOG_KEEP);
+        assert!(std::fs::read_to_string(&logs[0].path)
+            .unwrap()
+            .ends_with("foo started\n"));
+        assert_eq!(app.truncate_logs().unwrap().len(), LOG_KEEP - 1);
+        assert_eq!(app.logs("foo").unwrap(), logs[LOG_KEEP - 1..]);
+
+        let mut config = Config::default();
+
+        config.set_setting("logs", "false").unwrap();
+
+        let mut app = get_app(&dir, &config, &backend);
+
+        app.create(&CreateRequest::from_deb(&bar)).unwrap();
+
+        assert!(app.run("bar").unwrap().log.is_none());
+        assert!(app.logs("bar").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_export_import_image() {
+        let dir = TempDir::new("export_image");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let tarball = dir.0.join("foo.tar");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        app.create(&CreateRequest::from_deb(&deb)).unwrap();
+
+        let checksum = app.export_image("foo", &tarball).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.0.join("foo.tar.sha256")).unwrap(),
+            format!("{}  foo.tar\n", checksum)
+        );
+
+        backend.delete_image("debian_bridge_foo").unwrap();
+
+        let imported = app.import_image(&tarball).unwrap();
+
+        assert_eq!(imported[0].get_name_short(), "foo");
+        assert!(backend.image_exists("debian_bridge_foo").unwrap());
+
+        std::fs::write(&tarball, "debian_bridge_foo:latest\nextra").unwrap();
+        assert!(matches!(
+            app.import_image(&tarball),
+            Err(AppError::Program(message)) if message.contains("damaged")
+        ));
+
+        std::fs::write(&tarball, "debian_bridge_bar:latest").unwrap();
+        std::fs::remove_file(dir.0.join("foo.tar.sha256")).unwrap();
+        assert!(matches!(
+            app.import_image(&tarball),
+            Err(AppError::Program(message)) if message.contains("debian_bridge_bar:latest")
+        ));
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = TempDir::new("search");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        for package in ["test-viewer", "foo"] {
+            let deb = write_deb(&dir.0, package, "1.0").unwrap();
+
+            app.create(&CreateRequest::from_deb(&deb)).unwrap();
+        }
+
+        let found = app.search("TEST");
+
+        assert_eq!(
+            app.info("foo").unwrap().description.as_deref(),
+            Some("Test package")
+        );
+        assert_eq!(
+            found
+                .iter()
+                .map(|found| (found.name.as_str(), found.field))
+                .collect::<Vec<_>>(),
+            vec![
+                ("test-viewer", SearchField::Name),
+                ("foo", SearchField::Description)
+            ]
+        );
+        assert!(app.search("spreadsheet").is_empty());
+    }
+
+    #[test]
+    fn test_remove_missing_image() {
+        let dir = TempDir::new("remove");
+        let mut config = Config::default();
+
+        config
+            .push(&Program::new(
+                "foo",
+                &dir.0.join("foo.deb"),
+                &vec![],
+                &None,
+                &None,
+                &None,
+            ))
+            .unwrap();
+
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &config, &backend);
+        let report = app.remove("foo").unwrap();
+
+        assert!(report.image.is_none());
+        assert!(app.list().is_empty());
+        assert_eq!(
+            backend.calls(),
+            vec![Call::DeleteImage("debian_bridge_foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_repair_parallel() {
+        let dir = TempDir::new("repair");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        // Mocks are per thread, the builds run in their own ones
+        if std::env::var_os("USER").is_none() {
+            std::env::set_var("USER", "user");
+        }
+
+        for name in ["foo", "bar", "baz"] {
+            let deb = write_deb(&dir.0, name, "1.0").unwrap();
+
+            app.create(&CreateRequest::from_deb(&deb)).unwrap();
+            backend
+                .delete_image(&format!("debian_bridge_{}", name))
+                .unwrap();
+        }
+
+        std::fs::remove_file(dir.0.join("bar.deb")).unwrap();
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let results = app.repair_with_events(None, 2, tx).unwrap();
+        let events: Vec<(String, AppEvent)> = rx.iter().collect();
+
+        assert_eq!(
+            results
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["foo", "bar", "baz"]
+        );
+        assert!(matches!(results[0].1, Repair::Rebuilt));
+        assert!(matches!(results[1].1, Repair::Skipped(_)));
+        assert!(matches!(results[2].1, Repair::Rebuilt));
+        assert!(events
+            .iter()
+            .any(|(name, event)| name == "baz" && matches!(event, AppEvent::ImageBuilt { .. })));
+        assert!(!events.iter().any(|(name, _)| name == "bar"));
+
+        for name in ["foo", "baz"] {
+            assert_eq!(
+                app.info(name).unwrap().image_id,
+                Some(
+                    backend
+                        .image_id(&format!("debian_bridge_{}", name))
+                        .unwrap()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefix_collision() {
+        let dir = TempDir::new("prefixes");
+        let foo_bar = write_deb(&dir.0, "foo_bar", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let other = backend.clone().prefix("debian_bridge_foo");
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let mut other_app = get_app(&dir, &Config::default(), &other);
+
+        app.create(&CreateRequest::from_deb(&foo_bar)).unwrap();
+
+        match other_app.create(&CreateRequest::from_deb(&bar)) {
+            Err(AppError::Program(message)) => {
+                assert!(message.contains("belongs to programs of 'debian_bridge'"))
+            }
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert!(other_app.list().is_empty());
+        assert_eq!(
+            app.verify_images().unwrap(),
+            vec![("foo_bar".to_string(), true)]
+        );
+        assert!(app.unused_bases().unwrap().is_empty());
+        assert!(other_app.orphaned_images().unwrap().is_empty());
+        assert!(app.clean().unwrap().images.is_empty());
+
+        other_app
+            .create(&CreateRequest::from_deb(&bar).force(true))
+            .unwrap();
+
+        assert_eq!(
+            other
+                .image_prefix("debian_bridge_foo_bar")
+                .unwrap()
+                .as_deref(),
+            Some("debian_bridge_foo")
+        );
+        assert!(app.orphaned_images().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remove_restore() {
+        let dir = TempDir::new("restore");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        app.create(&CreateRequest::from_deb(&deb).command("foo --safe"))
+            .unwrap();
+
+        let backup = app.remove("foo").unwrap().backup.unwrap();
+
+        assert!(backup.starts_with(dir.0.join("state").join("debian_bridge").join("trash")));
+        assert_eq!(app.trash().unwrap().len(), 1);
+
+        let program = app.restore("foo").unwrap();
+
+        assert_eq!(program.command, "foo --safe");
+        assert_eq!(app.list(), vec!["foo".to_string()]);
+        assert!(backend.image_exists("debian_bridge_foo").unwrap());
+        assert!(!backup.exists());
+
+        match app.restore("foo") {
+            Err(AppError::Program(message)) => assert!(message.contains("the trash is empty")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let backup = app.remove("foo").unwrap().backup.unwrap();
+
+        std::fs::remove_file(&deb).unwrap();
+
+        match app.restore("foo") {
+            Err(AppError::Program(message)) => assert!(message.contains("doesn't exist anymore")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        assert!(app.list().is_empty());
+        assert!(backup.exists());
+        assert_eq!(app.empty_trash().unwrap(), vec![backup]);
+    }
+
+    #[test]
+    fn test_wrapper() {
+        let dir = TempDir::new("wrapper");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let bin = dir.0.join("bin");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+
+        util::get_package_path
+            .mock_safe(|_| MockResult::Return(Ok("/usr/bin/debian_bridge".to_string())));
+
+        app.create(&CreateRequest::from_deb(&foo).command("/opt/foo/foo-cli --quiet"))
+            .unwrap();
+        app.create(&CreateRequest::from_deb(&bar).command("foo-cli"))
+            .unwrap();
+
+        let path = app.install_wrapper("foo", &bin).unwrap();
+
+        assert_eq!(path, bin.join("foo-cli"));
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("exec /usr/bin/debian_bridge run foo -- \"$@\"\n"));
+        assert!(app.install_wrapper("bar", &bin).is_err());
+
+        app.rename("foo", "baz").unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains(" run baz -- "));
+        assert_eq!(
+            app.plan_removal("baz").unwrap().wrapper,
+            Some(path.to_owned())
+        );
+        assert_eq!(app.remove("baz").unwrap().wrapper, Some(path.to_owned()));
+        assert!(!path.exists());
+
+        app.restore("baz").unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_update_rollback() {
+        let dir = TempDir::new("update_rollback");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let fail = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let failing = fail.clone();
+        let backend = MockBackend::new().fail_on(move |call| {
+            failing.load(std::sync::atomic::Ordering::SeqCst)
+                && call
+                    == &Call::Build {
+                        tag: "debian_bridge_foo".into(),
+                    }
+        });
+        let mut config = Config::default();
+
+        config.set_setting("keep-previous", "0h").unwrap();
+
+        let mut app = get_app(&dir, &config, &backend);
+        let created = app.create(&CreateRequest::from_deb(&deb)).unwrap();
+
+        assert!(created.image_built.is_some());
+
+        match app.rollback("foo") {
+            Err(AppError::Program(message)) => assert!(message.contains("no previous image")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let (updated, _) = app.edit("foo", &ProgramChanges::new().deps("bar")).unwrap();
+
+        assert_eq!(updated.previous_image_id, created.image_id);
+        assert_eq!(updated.previous_image_built, created.image_built);
+        assert_eq!(
+            backend.image_id("debian_bridge_foo:previous").ok(),
+            created.image_id
+        );
+
+        let (twice, _) = app.edit("foo", &ProgramChanges::new().deps("baz")).unwrap();
+
+        assert_eq!(twice.previous_image_id, updated.image_id);
+        assert!(!backend
+            .image_exists(created.image_id.as_deref().unwrap())
+            .unwrap());
+
+        fail.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(app.edit("foo", &ProgramChanges::new().deps("qux")).is_err());
+        assert_eq!(app.info("foo").unwrap().previous_image_id, updated.image_id);
+        assert_eq!(backend.image_id("debian_bridge_foo").ok(), twice.image_id);
+        fail.store(false, std::sync::atomic::Ordering::SeqCst);
+
+        let rolled = app.rollback("foo").unwrap();
+
+        assert_eq!(rolled.image_id, updated.image_id);
+        assert_eq!(rolled.previous_image_id, twice.image_id);
+        assert_eq!(backend.image_id("debian_bridge_foo").ok(), updated.image_id);
+        assert_eq!(
+            backend.image_id("debian_bridge_foo:previous").ok(),
+            twice.image_id
+        );
+        assert!(!backend.image_exists("debian_bridge_foo:rollback").unwrap());
+
+        assert_eq!(app.stale_previous(), vec!["foo".to_string()]);
+        assert_eq!(
+            app.clean().unwrap().previous,
+            vec!["debian_bridge_foo:previous".to_string()]
+        );
+        assert!(!app.info("foo").unwrap().has_previous());
+        assert!(backend.image_exists("debian_bridge_foo").unwrap());
+        assert!(app.rollback("foo").is_err());
+    }
+
+    #[test]
+    fn test_offline() {
+        let dir = TempDir::new("offline");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let config = Config::default();
+        let base = util::gen_base_tag("debian_bridge", &config.build).unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &config, &backend).network(NetworkMode::Offline);
+
+        match app.create(&CreateRequest::from_deb(&deb).deps("libfoo")) {
+            Err(AppError::Offline { steps, .. }) => assert_eq!(
+                steps,
+                vec![
+                    NetworkStep::Pull {
+                        image: util::BASE_IMAGE.to_string()
+                    },
+                    NetworkStep::BaseBuild {
+                        tag: base.to_owned()
+                    },
+                    NetworkStep::AptInstall {
+                        packages: vec!["libfoo".to_string()]
+                    },
+                ]
+            ),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+        assert!(backend.calls().is_empty());
+
+        let backend = MockBackend::new().image(&base, None);
+        let mut app = get_app(&dir, &config, &backend).network(NetworkMode::Offline);
+
+        app.create(&CreateRequest::from_deb(&deb)).unwrap();
+        assert!(!backend
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::Pull(_))));
+        assert_eq!(app.list(), vec!["foo".to_string()]);
+
+        let repo = dir.0.join("repo");
+
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join("Packages"), b"").unwrap();
+
+        let mut build = BuildSettings::default();
+
+        build.local_repo = Some(repo.to_owned());
+
+        let request = CreateRequest::from_deb(&write_deb(&dir.0, "bar", "1.0").unwrap())
+            .deps("libbar")
+            .build_settings(&build);
+
+        match app.create(&request) {
+            Err(AppError::Offline { steps, .. }) => assert_eq!(
+                steps,
+                vec![NetworkStep::Pull {
+                    image: util::BASE_IMAGE.to_string()
+                }]
+            ),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        let backend = backend.image(util::BASE_IMAGE, None);
+        let mut app = get_app(&dir, &config, &backend).network(NetworkMode::Offline);
+        let program = app.create(&request).unwrap();
+
+        assert_eq!(program.local_repo, Some(repo.canonicalize().unwrap()));
+        // Offline the update succeeds only with the repository of the program
+        app.edit("bar", &ProgramChanges::new().deps("libbaz"))
+            .unwrap();
+
+        // The repository is a named build context, the docker CLI before 23.0 can't pass it
+        let backend = backend.to_owned().system(System {
+            docker_version: DockerVersion("1.41".to_string()),
+            ..System::offline()
+        });
+        let mut app = get_app(&dir, &config, &backend).network(NetworkMode::Offline);
+
+        let request = CreateRequest::from_deb(&write_deb(&dir.0, "baz", "1.0").unwrap())
+            .build_settings(&build);
+
+        match app.create(&request) {
+            Err(AppError::Feature(_)) => (),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = TempDir::new("verify");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let failed = |app: &App| {
+            app.verify("foo")
+                .into_iter()
+                .filter(|check| !check.passed)
+                .map(|check| (check.name, check.fix.unwrap_or_default()))
+                .collect::<Vec<_>>()
+        };
+
+        app.create(&CreateRequest::from_deb(&deb)).unwrap();
+
+        assert_eq!(
+            app.verify("foo")
+                .iter()
+                .map(|check| check.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Config entry", "Image", "Package", "Features"]
+        );
+        assert!(failed(&app).is_empty());
+
+        write_deb(&dir.0, "foo", "1.1").unwrap();
+        assert_eq!(failed(&app)[0].0, "Package");
+
+        backend.delete_image("debian_bridge_foo").unwrap();
+        assert_eq!(
+            failed(&app),
+            vec![(
+                "Image".to_string(),
+                "Run `debian_bridge repair foo`".to_string()
+            )]
+        );
+        assert!(!app.verify("bar")[0].passed);
+    }
+
+    #[test]
+    fn test_plan_create() {
+        let dir = TempDir::new("plan_create");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let request = CreateRequest::from_deb(&deb).dockerfile_extra("RUN touch /etc/foo.conf");
+        let dockerfile = app.plan_create(&request).unwrap();
+
+        assert!(dockerfile.contains("\nRUN touch /etc/foo.conf\nUSER $informuser\n"));
+        assert!(backend.calls().is_empty());
+        assert!(app.list().is_empty());
+
+        let program = app.create(&request).unwrap();
+
+        assert_eq!(
+            program.dockerfile_extra.as_deref(),
+            Some("RUN touch /etc/foo.conf")
+        );
+        assert!(matches!(
+            app.plan_create(&CreateRequest::from_deb(&deb).dockerfile_extra("FROM debian")),
+            Err(AppError::Argument(message)) if message.contains("FROM isn't allowed")
+        ));
+    }
+
+    #[test]
+    fn test_create_from_image() {
+        let dir = TempDir::new("from_image");
+        let backend = MockBackend::new().image("jess/firefox:latest", None);
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let program = app
+            .create_from_image("jess/firefox:latest", "firefox", &CreateRequest::default())
+            .unwrap();
+
+        assert_eq!(program.source_image.as_deref(), Some("jess/firefox:latest"));
+        assert_eq!(
+            program.image_id,
+            Some(backend.image_id("jess/firefox").unwrap())
+        );
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::Pull("jess/firefox:latest".to_string()),
+                Call::Tag {
+                    from: "jess/firefox:latest".to_string(),
+                    to: "debian_bridge_firefox".to_string()
+                }
+            ]
+        );
+        assert!(matches!(
+            app.create_from_image("jess/firefox:latest", "Firefox", &CreateRequest::default()),
+            Err(AppError::Program(message)) if message.starts_with("Invalid name")
+        ));
+
+        backend.delete_image("debian_bridge_firefox").unwrap();
+        assert!(matches!(
+            app.repair(Some("firefox")).unwrap()[0].1,
+            Repair::Pulled
+        ));
+
+        let report = app.remove_with("firefox", true).unwrap();
+
+        assert!(report.source_image.is_none());
+        assert!(backend.image_exists("jess/firefox:latest").unwrap());
+        assert!(!backend.image_exists("debian_bridge_firefox").unwrap());
+
+        app.create_from_image("jess/firefox:latest", "firefox", &CreateRequest::default())
+            .unwrap();
+
+        let report = app.remove("firefox").unwrap();
+
+        assert_eq!(report.source_image.as_deref(), Some("jess/firefox:latest"));
+        assert!(backend.images().is_empty());
+        assert!(matches!(
+            app.create_from_image("jess/firefox:latest", "firefox", &CreateRequest::default()),
+            Err(AppError::DockerStatus { code: 404, .. })
+        ));
+        assert!(app.list().is_empty());
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = TempDir::new("round_trip");
+        let deb = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let path = dir.0.join("config.json");
+        let backend = MockBackend::new();
+        let mut app = get_app(&dir, &Config::default(), &backend);
+        let created = app.create(&CreateRequest::from_deb(&deb)).unwrap();
+
+        app.save(&path).unwrap();
+
+        let app = get_app(&dir, &Config::deserialize(&path).unwrap(), &backend);
+        let program = app.info("foo").unwrap();
+
+        assert_eq!(program.image_id, created.image_id);
+        assert_eq!(program.base, created.base);
+        assert_eq!(
+            app.verify_images().unwrap(),
+            vec![("foo".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn test_config_file() {
+        let dir = TempDir::new("config_file");
+        let foo = write_deb(&dir.0, "foo", "1.0").unwrap();
+        let bar = write_deb(&dir.0, "bar", "1.0").unwrap();
+        let path = dir.0.join("config.json");
+        let backend = MockBackend::new();
+
+        Config::default().serialize(&path).unwrap();
+
+        let mut app = get_app(&dir, &Config::default(), &backend).config_file(&path);
+        // Another instance adds a program after the config was read
+        let mut other = get_app(&dir, &Config::default(), &backend);
+
+        other.create(&CreateRequest::from_deb(&foo)).unwrap();
+        other.save(&path).unwrap();
+
+        app.create(&CreateRequest::from_deb(&bar)).unwrap();
+        app.save(&path).unwrap();
+        assert_eq!(
+            Config::deserialize(&path).unwrap().programs.len(),
+            2,
+            "The change of the other instance is lost"
+        );
+
+        // An unchanged config isn't written
+        std::fs::remove_file(&path).unwrap();
+        get_app(&dir, &Config::default(), &backend)
+            .config_file(&path)
+            .save(&path)
+            .unwrap();
+        assert!(!path.exists());
+    }
+}
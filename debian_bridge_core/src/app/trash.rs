@@ -0,0 +1,174 @@
+use crate::app::{config::Program, error::AppError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Backups kept of every program name, older ones are dropped when another one is stored
+pub const TRASH_KEEP: usize = 5;
+
+/// Definition of a removed program, the path of the package it was created from included, see
+/// `App::restore`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub program: Program,
+    pub removed: DateTime<Utc>,
+    /// File the entry is stored in
+    #[serde(skip)]
+    pub file: PathBuf,
+}
+
+/// Writes a backup of the program to `dir` as `<name>-<timestamp>.json`, keeping only the
+/// latest `keep` ones of its name
+pub fn store(dir: &Path, program: &Program, keep: usize) -> AppResult<PathBuf> {
+    let removed = Utc::now();
+    let file = dir.join(format!(
+        "{}-{}.json",
+        program.get_name_short(),
+        removed.format("%Y%m%dT%H%M%S%.6f")
+    ));
+    let entry = TrashEntry {
+        program: program.to_owned(),
+        removed,
+        file: file.to_owned(),
+    };
+
+    std::fs::create_dir_all(dir).map_err(|err| {
+        AppError::config(
+            format!("Can't create the trash directory {}", dir.display()),
+            err,
+        )
+    })?;
+    std::fs::write(
+        &file,
+        serde_json::to_string_pretty(&entry)
+            .map_err(|err| AppError::config("Can't serialize the program", err))?,
+    )
+    .map_err(|err| {
+        AppError::config(
+            format!("Can't back up the program to {}", file.display()),
+            err,
+        )
+    })?;
+
+    let name = program.get_name_short();
+
+    for old in entries(dir)
+        .into_iter()
+        .rev()
+        .filter(|entry| entry.program.get_name_short() == name)
+        .skip(keep)
+    {
+        if let Err(err) = std::fs::remove_file(&old.file) {
+            warn!(
+                "Old backup {} is left in place: {}",
+                old.file.display(),
+                err
+            );
+        }
+    }
+
+    Ok(file)
+}
+
+/// Backups stored in `dir`, the oldest first. Files which can't be read are skipped
+pub fn entries(dir: &Path) -> Vec<TrashEntry> {
+    let mut entries: Vec<TrashEntry> = std::fs::read_dir(dir)
+        .map(|files| {
+            files
+                .filter_map(Result::ok)
+                .map(|file| file.path())
+                .filter(|path| path.is_file() && path.extension() == Some("json".as_ref()))
+                .filter_map(|path| {
+                    let entry = std::fs::read_to_string(&path)
+                        .map_err(|err| err.to_string())
+                        .and_then(|text| {
+                            serde_json::from_str::<TrashEntry>(&text).map_err(|err| err.to_string())
+                        });
+
+                    match entry {
+                        Ok(entry) => Some(TrashEntry {
+                            file: path,
+                            ..entry
+                        }),
+                        Err(err) => {
+                            warn!("Backup {} is skipped: {}", path.display(), err);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    entries.sort_by_key(|entry| entry.removed);
+    entries
+}
+
+/// Latest backup of the program
+pub fn latest(dir: &Path, name: &str) -> Option<TrashEntry> {
+    entries(dir)
+        .into_iter()
+        .rev()
+        .find(|entry| entry.program.get_name_short() == name)
+}
+
+/// Removes every backup in `dir`, returns the removed files
+pub fn empty(dir: &Path) -> AppResult<Vec<PathBuf>> {
+    entries(dir)
+        .into_iter()
+        .map(|entry| {
+            std::fs::remove_file(&entry.file)
+                .map(|_| entry.file)
+                .map_err(AppError::File)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_trash_{}", std::process::id()));
+        let program = |name: &str| {
+            Program::new(
+                name,
+                Path::new("/tmp/foo.deb"),
+                &vec![],
+                &None,
+                &Some("foo --safe".to_string()),
+                &None,
+            )
+        };
+
+        for _ in 0..3 {
+            store(&dir, &program("foo"), 2).unwrap();
+        }
+
+        store(&dir, &program("bar"), 2).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let entries = entries(&dir);
+        let latest = latest(&dir, "foo").unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.program.get_name_short())
+                .collect::<Vec<String>>(),
+            vec!["foo", "foo", "bar"]
+        );
+        assert_eq!(latest.file, entries[1].file);
+        assert_eq!(latest.program.command, "foo --safe");
+        assert_eq!(latest.program.path, Path::new("/tmp/foo.deb"));
+        assert!(super::latest(&dir, "baz").is_none());
+        assert_eq!(empty(&dir).unwrap().len(), 3);
+        assert!(super::entries(&dir).is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
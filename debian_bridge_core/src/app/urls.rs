@@ -0,0 +1,235 @@
+use crate::app::error::AppError;
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+type AppResult<T> = Result<T, AppError>;
+
+/// Where the socket of `UrlOpener` is mounted in a container, the `xdg-open` of the image
+/// writes the URL to it
+pub const OPEN_URL_SOCKET: &str = "/run/debian-bridge/open-url.sock";
+/// Schemes a program may open on the host, other URLs are refused
+pub const OPEN_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+/// Longest URL which is opened, a longer one is refused
+const URL_MAX_LEN: usize = 8192;
+
+/// `xdg-open` of the image, it writes the URL to `OPEN_URL_SOCKET` and fails unless the host
+/// answers `ok`. Lines are single quoted in the Dockerfile, they contain no single quote
+pub const OPEN_URL_SHIM: &[&str] = &[
+    "#!/bin/sh",
+    "# Written by debian_bridge, the URL is opened on the host",
+    "reply=$(printf \"%s\\n\" \"$1\" | socat -t 5 - UNIX-CONNECT:/run/debian-bridge/open-url.sock)",
+    "[ \"$reply\" = ok ] && exit 0",
+    "echo \"xdg-open: ${reply:-the host did not answer}\" >&2",
+    "exit 4",
+];
+
+/// Refuses URLs of other schemes than `schemes`, with whitespace or control characters, and
+/// web URLs without a host
+pub fn check_url(url: &str, schemes: &[&str]) -> Result<(), String> {
+    if url.len() > URL_MAX_LEN {
+        return Err(format!("the URL is longer than {} bytes", URL_MAX_LEN));
+    }
+
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("the URL contains whitespace or control characters".to_string());
+    }
+
+    let (scheme, rest) = url
+        .split_once(':')
+        .filter(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+        .ok_or_else(|| "the URL has no scheme".to_string())?;
+    let scheme = scheme.to_ascii_lowercase();
+
+    if !schemes.contains(&scheme.as_str()) {
+        return Err(format!(
+            "{} URLs aren't opened, only {} are",
+            scheme,
+            schemes.join(", ")
+        ));
+    }
+
+    match scheme.as_str() {
+        "http" | "https" => match rest.strip_prefix("//") {
+            Some(rest) if !rest.is_empty() && !rest.starts_with('/') => Ok(()),
+            _ => Err("the URL has no host".to_string()),
+        },
+        _ if rest.is_empty() => Err("the URL is empty".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Listener of the URLs a program opens, every allowed one is handed to `opener` on the host,
+/// e.g. its `xdg-open`. It stops when dropped
+pub struct UrlOpener {
+    socket: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UrlOpener {
+    /// How often the listener checks whether to stop
+    const POLL: Duration = Duration::from_millis(100);
+    /// How long a client may take to send the URL
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn spawn(socket: &Path, opener: &str) -> AppResult<Self> {
+        std::fs::remove_file(socket).unwrap_or(());
+
+        let listener = UnixListener::bind(socket)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|err| {
+                AppError::Environment(format!(
+                    "Can't listen for URLs on {}: {}",
+                    socket.display(),
+                    err
+                ))
+            })?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let opener = opener.to_string();
+        let thread = std::thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => answer(stream, &opener),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(Self::POLL)
+                    }
+                    Err(err) => {
+                        warn!("Links of the program aren't opened anymore: {}", err);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(UrlOpener {
+            socket: socket.to_owned(),
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for UrlOpener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap_or(());
+        }
+
+        std::fs::remove_file(&self.socket).unwrap_or(());
+    }
+}
+
+/// Reads the URL of a client and opens it if it's allowed, the client gets `ok` or the reason
+/// of the refusal
+fn answer(stream: UnixStream, opener: &str) {
+    let mut url = String::new();
+    let read = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(UrlOpener::TIMEOUT)))
+        .and_then(|_| BufReader::new((&stream).take(URL_MAX_LEN as u64 + 1)).read_line(&mut url));
+    let url = url.trim_end_matches('\n');
+    let reply = match read {
+        Ok(_) => check_url(url, OPEN_URL_SCHEMES).and_then(|_| {
+            Command::new(opener)
+                .arg(url)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|err| format!("{} can't be started: {}", opener, err))
+        }),
+        Err(err) => Err(format!("the URL can't be read: {}", err)),
+    };
+
+    let reply = match reply {
+        Ok(mut child) => {
+            info!("Opening {}", url);
+            // A browser started by the opener may run long, it's waited for aside
+            std::thread::spawn(move || child.wait().map(|_| ()).unwrap_or(()));
+            "ok".to_string()
+        }
+        Err(reason) => {
+            warn!("A link isn't opened, {}", reason);
+            reason
+        }
+    };
+
+    writeln!(&stream, "{}", reply).unwrap_or(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_url() {
+        for url in [
+            "https://example.com/a?b=c#d",
+            "HTTP://example.com",
+            "mailto:foo@example.com",
+        ] {
+            assert_eq!(check_url(url, OPEN_URL_SCHEMES), Ok(()), "{}", url);
+        }
+
+        for url in [
+            "file:///etc/passwd",
+            "javascript:alert(1)",
+            "https://",
+            "https:///etc",
+            "http:example.com",
+            "mailto:",
+            "--help",
+            "example.com",
+            "https://example.com/a b",
+            "https://example.com/\u{1b}",
+        ] {
+            assert!(check_url(url, OPEN_URL_SCHEMES).is_err(), "{}", url);
+        }
+
+        assert!(check_url(
+            &format!("https://{}", "a".repeat(URL_MAX_LEN)),
+            OPEN_URL_SCHEMES
+        )
+        .is_err());
+        assert_eq!(check_url("file:///tmp", &["file"]), Ok(()));
+    }
+
+    #[test]
+    fn test_url_opener() {
+        let socket =
+            std::env::temp_dir().join(format!("debian_bridge_test_urls_{}", std::process::id()));
+        let opener = UrlOpener::spawn(&socket, "true").unwrap();
+        let ask = |url: &str| {
+            let mut stream = UnixStream::connect(&socket).unwrap();
+            let mut reply = String::new();
+
+            writeln!(stream, "{}", url).unwrap();
+            stream.read_to_string(&mut reply).unwrap();
+            reply
+        };
+
+        assert_eq!(ask("https://example.com"), "ok\n");
+        assert!(ask("file:///etc/passwd").starts_with("file URLs aren't opened"));
+
+        drop(opener);
+        assert!(!socket.exists());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// Disk space taken by a single program
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramUsage {
+    pub name: String,
+    /// Size of the image layers on top of the base image
+    pub image: u64,
+    /// Writable layers of the containers kept after runs, e.g. a persistent one
+    pub containers: u64,
+    /// Why the numbers may be incomplete, e.g. the image is missing
+    pub note: Option<String>,
+}
+
+impl ProgramUsage {
+    pub fn total(&self) -> u64 {
+        self.image + self.containers
+    }
+}
+
+/// Disk space taken by debian_bridge
+///
+/// # Example
+/// ```no_run
+/// # use debian_bridge_core::{App, Config, Docker, System};
+/// # use std::path::Path;
+/// #
+/// # let docker = Docker::new();
+/// # let config = Config::deserialize(Path::new("./cfg")).unwrap();
+/// # let system = System::try_new(&docker).unwrap();
+/// let app = App::new("debian_bridge", "foo_package", Path::new("./cache"), &config, system, docker).unwrap();
+/// let usage = app.disk_usage().unwrap();
+///
+/// println!("{} bytes in total", usage.total());
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiskUsage {
+    /// Sorted from the largest program
+    pub programs: Vec<ProgramUsage>,
+    /// Base images shared by the programs, counted once
+    pub base: u64,
+    /// Build leftovers in the cache directory
+    pub cache: u64,
+}
+
+impl DiskUsage {
+    pub fn total(&self) -> u64 {
+        self.programs.iter().map(ProgramUsage::total).sum::<u64>() + self.base + self.cache
+    }
+}
+
+impl Display for DiskUsage {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for program in &self.programs {
+            write!(
+                f,
+                "\t{:<15} ===> {:>10}",
+                program.name,
+                human_size(program.total())
+            )?;
+
+            match &program.note {
+                Some(note) => writeln!(f, " ({})", note)?,
+                None if program.containers > 0 => {
+                    writeln!(f, " (containers: {})", human_size(program.containers))?
+                }
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(
+            f,
+            "\t{:<15} ===> {:>10}",
+            "Base images",
+            human_size(self.base)
+        )?;
+        writeln!(f, "\t{:<15} ===> {:>10}", "Cache", human_size(self.cache))?;
+        writeln!(f, "\t{:<15} ===> {:>10}", "Total", human_size(self.total()))
+    }
+}
+
+/// Formats a size with binary units, e.g. `1.5 GiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_total() {
+        let usage = DiskUsage {
+            programs: vec![ProgramUsage {
+                name: "foo".to_string(),
+                image: 100,
+                containers: 20,
+                note: None,
+            }],
+            base: 1000,
+            cache: 3,
+        };
+
+        assert_eq!(usage.total(), 1123);
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Keys of the XDG user dirs with the directories xdg-user-dirs creates for them
+const DEFAULT_DIRS: &[(&str, &str)] = &[
+    ("DESKTOP", "Desktop"),
+    ("DOWNLOAD", "Downloads"),
+    ("TEMPLATES", "Templates"),
+    ("PUBLICSHARE", "Public"),
+    ("DOCUMENTS", "Documents"),
+    ("MUSIC", "Music"),
+    ("PICTURES", "Pictures"),
+    ("VIDEOS", "Videos"),
+];
+
+/// XDG user dir of the host as a program with a persistent home sees it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserDir {
+    /// Key of `user-dirs.dirs`, e.g. `DOWNLOAD`
+    pub key: String,
+    pub host: PathBuf,
+    pub container: PathBuf,
+    /// Whether `container` is the host directory. Directories outside the home aren't shared,
+    /// theirs point to the home instead
+    pub shared: bool,
+}
+
+impl UserDir {
+    /// Variable the directory is passed in, e.g. `XDG_DOWNLOAD_DIR`
+    pub fn variable(&self) -> String {
+        format!("XDG_{}_DIR", self.key)
+    }
+
+    /// Name to show, e.g. `Downloads`
+    pub fn label(&self) -> &str {
+        DEFAULT_DIRS
+            .iter()
+            .find(|(key, _)| *key == self.key)
+            .map_or(self.key.as_str(), |(_, name)| name)
+    }
+}
+
+/// `user-dirs.dirs` of the host user, e.g. `~/.config/user-dirs.dirs`
+pub fn file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("user-dirs.dirs"))
+}
+
+/// Directories of the host user mapped onto the shared home, the defaults without a
+/// `user-dirs.dirs`
+pub fn host(home: &Path) -> Vec<UserDir> {
+    let text = file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .unwrap_or_default();
+
+    map(&parse(&text, home), home)
+}
+
+/// Directories of `user-dirs.dirs`, `$HOME` expanded to `home`. Keys the file lacks get the
+/// default directories, the ones of the file come in its order after them
+pub fn parse(text: &str, home: &Path) -> Vec<(String, PathBuf)> {
+    let mut dirs: Vec<(String, PathBuf)> = DEFAULT_DIRS
+        .iter()
+        .map(|(key, name)| (key.to_string(), home.join(name)))
+        .collect();
+
+    for line in text.lines().map(str::trim) {
+        let (variable, value) = match line.split_once('=') {
+            Some(pair) if !line.starts_with('#') => pair,
+            _ => continue,
+        };
+        let key = match variable
+            .strip_prefix("XDG_")
+            .and_then(|key| key.strip_suffix("_DIR"))
+        {
+            Some(key) if !key.is_empty() => key,
+            _ => continue,
+        };
+        let value = value.trim_matches('"');
+        let path = match value.strip_prefix("$HOME") {
+            Some(rest) => home.join(rest.trim_start_matches('/')),
+            None if value.starts_with('/') => PathBuf::from(value),
+            None => continue,
+        };
+
+        match dirs.iter_mut().find(|(known, _)| known == key) {
+            Some(dir) => dir.1 = path,
+            None => dirs.push((key.to_string(), path)),
+        }
+    }
+
+    dirs
+}
+
+/// Maps the host directories onto the home shared at the same path. A directory outside the
+/// home points to the one of its name in the home instead, saves survive restarts there
+pub fn map(dirs: &[(String, PathBuf)], home: &Path) -> Vec<UserDir> {
+    dirs.iter()
+        .map(|(key, host)| {
+            let shared = host.starts_with(home);
+            let container = match shared {
+                true => host.to_owned(),
+                false => home.join(
+                    DEFAULT_DIRS
+                        .iter()
+                        .find(|(known, _)| known == key)
+                        .map(|(_, name)| Path::new(name))
+                        .or_else(|| host.file_name().map(Path::new))
+                        .unwrap_or_else(|| Path::new(key)),
+                ),
+            };
+
+            UserDir {
+                key: key.to_owned(),
+                host: host.to_owned(),
+                container,
+                shared,
+            }
+        })
+        .collect()
+}
+
+/// `user-dirs.dirs` of the container directories, relative to `$HOME` where they are in it
+pub fn render(dirs: &[UserDir], home: &Path) -> String {
+    let mut text = "# Written by debian_bridge, the host directories outside the home point \
+                    into it\n"
+        .to_string();
+
+    for dir in dirs {
+        let path = match dir.container.strip_prefix(home) {
+            Ok(rest) if rest.as_os_str().is_empty() => "$HOME".to_string(),
+            Ok(rest) => format!("$HOME/{}", rest.display()),
+            Err(_) => dir.container.display().to_string(),
+        };
+
+        text.push_str(&format!("{}=\"{}\"\n", dir.variable(), path));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map() {
+        let home = Path::new("/home/foo");
+        let dirs = parse(
+            "# This file is written by xdg-user-dirs-update\n\
+             XDG_DESKTOP_DIR=\"$HOME\"\n\
+             XDG_DOWNLOAD_DIR=\"$HOME/Téléchargements\"\n\
+             XDG_MUSIC_DIR=\"/mnt/media/music\"\n\
+             XDG_PROJECTS_DIR=\"/srv/projects\"\n\
+             XDG_VIDEOS_DIR=\"relative\"\n",
+            home,
+        );
+        let mapped = map(&dirs, home);
+
+        assert_eq!(dirs.len(), 9);
+        assert_eq!(dirs[0], ("DESKTOP".to_string(), PathBuf::from("/home/foo")));
+        assert_eq!(dirs[7].1, Path::new("/home/foo/Videos"));
+        assert_eq!(
+            mapped[1],
+            UserDir {
+                key: "DOWNLOAD".to_string(),
+                host: PathBuf::from("/home/foo/Téléchargements"),
+                container: PathBuf::from("/home/foo/Téléchargements"),
+                shared: true,
+            }
+        );
+        assert_eq!(mapped[5].container, Path::new("/home/foo/Music"));
+        assert!(!mapped[5].shared);
+        assert_eq!(mapped[8].container, Path::new("/home/foo/projects"));
+        assert_eq!(mapped[8].label(), "PROJECTS");
+        assert_eq!(mapped[1].variable(), "XDG_DOWNLOAD_DIR");
+
+        let text = render(&mapped, home);
+
+        assert!(text.contains("\nXDG_DESKTOP_DIR=\"$HOME\"\n"));
+        assert!(text.contains("\nXDG_MUSIC_DIR=\"$HOME/Music\"\n"));
+        assert_eq!(
+            parse(&text, home),
+            map(&dirs, home)
+                .into_iter()
+                .map(|dir| (dir.key, dir.container))
+                .collect::<Vec<_>>()
+        );
+    }
+}
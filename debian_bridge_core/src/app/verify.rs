@@ -0,0 +1,201 @@
+use super::{backend::Backend, doctor::Check, util, Feature, FeaturesList, Program};
+use std::path::Path;
+
+/// Checks a single program end-to-end: its image, the package the image was built from, the
+/// desktop entry, the icon and the host services its features need
+pub(super) fn run(
+    backend: &dyn Backend,
+    features: &FeaturesList,
+    prefix: &str,
+    entry: &str,
+    program: &Program,
+) -> Vec<Check> {
+    let name = program.get_name_short();
+    let tag = program.get_name(prefix);
+    let mut checks = vec![Check::pass("Config entry", "present")];
+
+    let image = check_image(backend, &tag, &name);
+    let image_exists = image.passed;
+
+    checks.push(image);
+
+    if image_exists && program.source_image.is_none() {
+        checks.push(check_package(backend, &tag, program));
+    }
+
+    if let Some(icon) = &program.icon {
+        checks.push(check_entry(&name, entry));
+        checks.push(match icon.path.exists() {
+            true => Check::pass("Icon", icon.path.display().to_string()),
+            false => Check::fail(
+                "Icon",
+                format!("{} is missing", icon.path.display()),
+                format!(
+                    "Set it again with `debian_bridge edit {} --desktop-icon <path>`",
+                    name
+                ),
+            ),
+        });
+    }
+
+    checks.push(check_features(features, program));
+    checks
+}
+
+fn check_image(backend: &dyn Backend, tag: &str, name: &str) -> Check {
+    match backend.image_exists(tag) {
+        Ok(true) => Check::pass("Image", tag),
+        Ok(false) => Check::fail(
+            "Image",
+            format!("{} is missing", tag),
+            format!("Run `debian_bridge repair {}`", name),
+        ),
+        Err(err) => Check::fail(
+            "Image",
+            format!("can't be inspected: {}", err),
+            "Check docker with `debian_bridge doctor`",
+        ),
+    }
+}
+
+/// Compares the package checksum the image is labeled with to the package on disk
+fn check_package(backend: &dyn Backend, tag: &str, program: &Program) -> Check {
+    if !program.path.is_file() {
+        return Check::pass(
+            "Package",
+            format!(
+                "not compared, {} is missing, the image can't be rebuilt",
+                program.path.display()
+            ),
+        );
+    }
+
+    let label = backend.image_id(tag).and_then(|id| {
+        Ok(backend
+            .managed_images()?
+            .into_iter()
+            .find(|image| image.id == id)
+            .and_then(|image| image.created_from))
+    });
+
+    match (label, util::sha256_file(&program.path)) {
+        (Ok(Some(label)), Ok(sha)) if label == sha => Check::pass(
+            "Package",
+            format!("{} matches the image", program.path.display()),
+        ),
+        (Ok(Some(_)), Ok(_)) => Check::fail(
+            "Package",
+            format!(
+                "{} changed since the image was built",
+                program.path.display()
+            ),
+            format!(
+                "Delete the image with `docker rmi {}` and run `debian_bridge repair {}`",
+                tag,
+                program.get_name_short()
+            ),
+        ),
+        (Ok(None), _) => Check::pass(
+            "Package",
+            "not compared, the image isn't labeled with a checksum",
+        ),
+        (Err(err), _) => Check::fail(
+            "Package",
+            format!("image can't be inspected: {}", err),
+            "Check docker with `debian_bridge doctor`",
+        ),
+        (_, Err(err)) => Check::fail(
+            "Package",
+            format!("{} can't be read: {}", program.path.display(), err),
+            "Check permissions of the package",
+        ),
+    }
+}
+
+/// The entry has to exist and start this program through an existing debian_bridge binary
+fn check_entry(name: &str, entry: &str) -> Check {
+    let fix = format!(
+        "Write it again with `debian_bridge edit {} --desktop-icon <path>`",
+        name
+    );
+    let path = match util::desktop_dir() {
+        Ok(dir) => dir.join(entry),
+        Err(err) => return Check::fail("Desktop entry", err.to_string(), fix),
+    };
+    let entry = match std::fs::read_to_string(&path) {
+        Ok(entry) => entry,
+        Err(_) => {
+            return Check::fail(
+                "Desktop entry",
+                format!("{} is missing", path.display()),
+                fix,
+            )
+        }
+    };
+    let exec = entry
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Exec="))
+        .unwrap_or_default();
+    let binary = exec
+        .split_whitespace()
+        .skip_while(|word| *word != "--")
+        .nth(1)
+        .map(|binary| binary.trim_matches('"'));
+
+    match binary {
+        Some(binary) if exec.ends_with(&format!(" run {}", util::exec_quote(name))) => {
+            match Path::new(binary).exists() {
+                true => Check::pass("Desktop entry", path.display().to_string()),
+                false => Check::fail(
+                    "Desktop entry",
+                    format!("{} starts {} which doesn't exist", path.display(), binary),
+                    fix,
+                ),
+            }
+        }
+        _ => Check::fail(
+            "Desktop entry",
+            format!("{} starts '{}' instead of {}", path.display(), exec, name),
+            fix,
+        ),
+    }
+}
+
+/// A remote display or sound server doesn't need a local one
+fn check_features(features: &FeaturesList, program: &Program) -> Check {
+    let local: Vec<Feature> = program
+        .settings
+        .iter()
+        .filter(|f| program.remote_display.is_none() || **f != Feature::Display)
+        .filter(|f| program.pulse_server.is_none() || **f != Feature::Sound)
+        .cloned()
+        .collect();
+
+    match features.validate(&local) {
+        Ok(_) if local.is_empty() => Check::pass("Features", "none requested"),
+        Ok(_) => Check::pass(
+            "Features",
+            format!(
+                "{} available",
+                local
+                    .iter()
+                    .map(|feature| feature.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        ),
+        Err(problems) => Check::fail(
+            "Features",
+            problems
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            format!(
+                "Fix the host or drop the features with `debian_bridge edit {} --remove-feature \
+                 <feature>`",
+                program.get_name_short()
+            ),
+        ),
+    }
+}
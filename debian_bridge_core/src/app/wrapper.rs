@@ -0,0 +1,148 @@
+use super::{error::AppError, AppResult};
+use std::{
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Second line of a wrapper, files without it aren't overwritten or removed
+const MARKER: &str = "# Written by debian_bridge";
+
+/// Name a wrapper of the command is installed under, the file name of its executable, e.g.
+/// `foo` for `/opt/foo/foo --safe-mode`
+pub fn name(command: &str) -> Option<String> {
+    command
+        .split_whitespace()
+        .next()
+        .and_then(|executable| Path::new(executable).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+}
+
+/// Shell script running the program `program` through `executable`, its arguments are passed
+/// on after `--`. The global arguments go before the subcommand, e.g. `--profile`
+pub fn script(executable: &str, global_args: &[&str], program: &str) -> String {
+    let args: Vec<String> = global_args.iter().map(|arg| quote(arg)).collect();
+
+    format!(
+        "#!/bin/sh\n{} for {}, it's removed with the program\nexec {}{} run {} -- \"$@\"\n",
+        MARKER,
+        program,
+        quote(executable),
+        args.iter()
+            .map(|arg| format!(" {}", arg))
+            .collect::<String>(),
+        quote(program)
+    )
+}
+
+/// Whether the file at `path` is a wrapper written by `script`
+pub fn is_wrapper(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|script| {
+            script
+                .lines()
+                .nth(1)
+                .is_some_and(|line| line.starts_with(MARKER))
+        })
+        .unwrap_or(false)
+}
+
+/// Executable of the name in another directory of `path`, a `PATH` value. The wrapper would
+/// shadow it, or it would shadow the wrapper
+pub fn conflict(name: &str, bin_dir: &Path, path: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .filter(|dir| dir != bin_dir)
+        .map(|dir| dir.join(name))
+        .find(|file| {
+            std::fs::metadata(file)
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+}
+
+/// Writes the script executable at `path`, a file there which isn't a wrapper is kept
+pub fn write(path: &Path, script: &str) -> AppResult<()> {
+    if path.exists() && !is_wrapper(path) {
+        return Err(AppError::Program(format!(
+            "{} exists and isn't a wrapper of debian_bridge, it's left in place",
+            path.display()
+        )));
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(path, script)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+/// Removes the wrapper at `path`, returns whether there was one. Other files are left
+pub fn remove(path: &Path) -> AppResult<bool> {
+    match is_wrapper(path) {
+        true => {
+            std::fs::remove_file(path)?;
+            Ok(true)
+        }
+        false => Ok(false),
+    }
+}
+
+/// Single quoted shell word
+fn quote(value: &str) -> String {
+    match value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-=".contains(c))
+    {
+        true => value.to_string(),
+        false => format!("'{}'", value.replace('\'', "'\\''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapper() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_wrapper_{}", std::process::id()));
+        let (bin, other) = (dir.join("bin"), dir.join("other"));
+        let path = bin.join("foo");
+        let script = script("/usr/bin/debian_bridge", &["--profile", "work"], "foo");
+
+        assert_eq!(name("/opt/foo/foo --safe-mode").as_deref(), Some("foo"));
+        assert_eq!(name(""), None);
+        assert_eq!(
+            script,
+            "#!/bin/sh\n# Written by debian_bridge for foo, it's removed with the program\n\
+             exec /usr/bin/debian_bridge --profile work run foo -- \"$@\"\n"
+        );
+        assert_eq!(quote("it's"), "'it'\\''s'");
+
+        std::fs::create_dir_all(&other).unwrap();
+        write(&path, &script).unwrap();
+
+        let search = std::env::join_paths([&bin, &other]).unwrap();
+
+        assert!(is_wrapper(&path));
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(conflict("foo", &bin, &search), None);
+
+        std::fs::write(other.join("foo"), "").unwrap();
+        assert_eq!(conflict("foo", &bin, &search), None);
+
+        std::fs::set_permissions(other.join("foo"), std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        assert_eq!(conflict("foo", &bin, &search), Some(other.join("foo")));
+        assert!(write(&other.join("foo"), &script).is_err());
+        assert!(!remove(&other.join("foo")).unwrap());
+        assert!(remove(&path).unwrap());
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -5,20 +5,27 @@ extern crate mocktopus;
 #[macro_use]
 pub extern crate log;
 
-extern crate colorful;
+extern crate chrono;
 extern crate dirs;
 extern crate dockerfile;
 extern crate freedesktop_desktop_entry;
-extern crate pipers;
+extern crate image;
+extern crate libc;
 extern crate pretty_env_logger;
 extern crate regex;
 extern crate serde_json;
 extern crate shiplift;
+extern crate tar;
 extern crate tokio;
+extern crate toml;
 
 mod app;
 mod sys;
 
 pub use app::*;
 pub use shiplift::Docker;
-pub use sys::System;
+pub use sys::{
+    driver::{DisplayServer, DockerVersion, InputMethod, SoundDriver, SoundServer, WindowManager},
+    error::SystemError,
+    System, MIN_DOCKER_API, PROBE_TTL,
+};
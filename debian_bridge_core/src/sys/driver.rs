@@ -1,6 +1,10 @@
-use std::fmt::{Display, Formatter, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter, Result},
+    path::PathBuf,
+};
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WindowManager {
     X11,
     Wayland,
@@ -17,12 +21,11 @@ impl Display for WindowManager {
     }
 }
 
-impl Driver for WindowManager {}
-
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SoundDriver {
     Alsa,
     PulseAudio,
+    PipeWire,
 }
 
 impl Display for SoundDriver {
@@ -30,23 +33,102 @@ impl Display for SoundDriver {
         let val = match self {
             SoundDriver::Alsa => "Alsa",
             SoundDriver::PulseAudio => "PulseAudio",
+            SoundDriver::PipeWire => "PipeWire",
         };
 
         write!(f, "{}", val)
     }
 }
 
-impl Driver for SoundDriver {}
+/// Detected display server, the socket is where it's expected to listen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisplayServer {
+    pub kind: WindowManager,
+    pub socket: Option<PathBuf>,
+}
+
+impl Display for DisplayServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.socket {
+            Some(socket) => write!(f, "{} ({})", self.kind, socket.display()),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Detected sound server, the socket is `None` if it was found by its tools only
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundServer {
+    pub kind: SoundDriver,
+    pub socket: Option<PathBuf>,
+}
+
+impl Display for SoundServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.socket {
+            Some(socket) => write!(f, "{} ({})", self.kind, socket.display()),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Input method framework, e.g. for CJK input
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum InputMethod {
+    IBus,
+    /// Fcitx 5 serves clients of Fcitx 4 as well, the same client modules fit both
+    Fcitx,
+}
+
+impl InputMethod {
+    pub const ALL: &'static [InputMethod] = &[InputMethod::IBus, InputMethod::Fcitx];
+
+    /// Value of `GTK_IM_MODULE` and `QT_IM_MODULE`, `XMODIFIERS` is `@im=` followed by it
+    pub fn module(&self) -> &'static str {
+        match self {
+            InputMethod::IBus => "ibus",
+            InputMethod::Fcitx => "fcitx",
+        }
+    }
+
+    /// Client modules programs load to talk to the framework
+    pub fn packages(&self) -> &'static [&'static str] {
+        match self {
+            InputMethod::IBus => &["ibus-gtk", "ibus-gtk3"],
+            InputMethod::Fcitx => &[
+                "fcitx-frontend-gtk2",
+                "fcitx-frontend-gtk3",
+                "fcitx-frontend-qt5",
+            ],
+        }
+    }
+}
+
+impl Display for InputMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let val = match self {
+            InputMethod::IBus => "IBus",
+            InputMethod::Fcitx => "Fcitx",
+        };
 
-#[derive(Clone)]
+        write!(f, "{}", val)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DockerVersion(pub String);
 
+impl DockerVersion {
+    /// Returns an API version as a `(major, minor)` pair
+    pub fn api(&self) -> Option<(u32, u32)> {
+        let mut parts = self.0.split('.').map(|part| part.parse::<u32>().ok());
+
+        Some((parts.next()??, parts.next()??))
+    }
+}
+
 impl Display for DockerVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         write!(f, "{}", self.0)
     }
 }
-
-impl Driver for DockerVersion {}
-
-pub trait Driver: Display + Clone {}
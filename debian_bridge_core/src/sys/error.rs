@@ -1,6 +1,11 @@
 #[derive(Debug, Clone)]
 pub enum SystemError {
     DockerConnection,
+    /// The daemon is older than `MIN_DOCKER_API`
+    UnsupportedDocker {
+        found: String,
+        required: String,
+    },
 }
 
 impl std::error::Error for SystemError {
@@ -11,12 +16,13 @@ impl std::error::Error for SystemError {
 
 impl std::fmt::Display for SystemError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                SystemError::DockerConnection => "Cannot connect to docker daemon",
-            }
-        )
+        match self {
+            SystemError::DockerConnection => write!(f, "Cannot connect to docker daemon"),
+            SystemError::UnsupportedDocker { found, required } => write!(
+                f,
+                "Docker {} is too old, API {} or newer is required, update docker",
+                found, required
+            ),
+        }
     }
 }
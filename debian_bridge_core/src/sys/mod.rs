@@ -1,101 +1,663 @@
 pub mod driver;
 pub mod error;
+mod probe;
+
+pub use probe::PROBE_TTL;
 
-use colorful::{Color, Colorful};
 use driver::*;
 use error::SystemError;
+use serde::{Deserialize, Serialize};
 use shiplift::{rep::Version, Docker};
 use std::{
-    error::Error,
-    ffi::OsString,
     fmt::{Display, Formatter},
-    fs::File,
-    process::{Command, ExitStatus, Stdio},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
 };
 use tokio::{prelude::Future, runtime::Runtime};
 
 type SystemResult<T> = Result<T, SystemError>;
 
-#[derive(Clone)]
+/// Input devices known to the kernel, named as their nodes in `/dev/input`
+const INPUT_CLASS: &str = "/sys/class/input";
+/// The first joystick button code, gamepad buttons follow it up to `0x13f`
+const BTN_JOYSTICK: usize = 0x120;
+const BTN_GAMEPAD_LAST: usize = 0x13f;
+
+/// Where sane-utils installs the network scanner daemon
+const SANED_PATHS: &[&str] = &["/usr/sbin/saned", "/usr/bin/saned"];
+
+/// The oldest docker API debian_bridge works with (docker 1.13): labels, container filters and
+/// `--cidfile` of the runs are used. Newer APIs only enable optional functionality like BuildKit
+pub const MIN_DOCKER_API: (u32, u32) = (1, 25);
+
+/// Facts about the host the features depend on
+#[derive(Clone, Serialize, Deserialize)]
 pub struct System {
-    pub wm: Option<WindowManager>,
-    pub sd: Option<SoundDriver>,
+    pub display: Option<DisplayServer>,
+    pub sound: Option<SoundServer>,
+    pub input_method: Option<InputMethod>,
+    /// Whether a keyring serves the secret service API on the session bus
+    pub secret_service: bool,
+    /// Whether xdg-desktop-portal owns `org.freedesktop.portal.Desktop` on the session bus
+    pub portal: bool,
+    /// Whether bluetoothd owns `org.bluez` on the system bus
+    pub bluetooth: bool,
+    /// Whether avahi-daemon owns `org.freedesktop.Avahi` on the system bus
+    pub avahi: bool,
+    /// Device nodes of the gamepads and joysticks connected during the detection
+    pub gamepads: Vec<PathBuf>,
+    /// SANE devices found during the detection, e.g. `epson2:libusb:001:004`
+    pub scanners: Vec<String>,
+    /// Whether saned is installed to share the scanners over the network
+    pub saned: bool,
+    /// API version of the docker daemon
     pub docker_version: DockerVersion,
+    /// Release of the docker daemon, e.g. `19.03.5`
+    pub docker_server: Option<String>,
+    /// Architecture debian_bridge is built for, e.g. `x86_64`
+    pub arch: String,
+    pub runtime_dir: Option<PathBuf>,
+    /// Whether the daemon runs as the current user. Root in a container is the user then, other
+    /// users are mapped to subordinate IDs
+    pub rootless: bool,
+    /// Whether the daemon confines containers with AppArmor profiles
+    pub apparmor: bool,
+    /// Whether the daemon labels containers with SELinux, e.g. on an enforcing Fedora host.
+    /// Bind mounts without the container label are denied then
+    pub selinux: bool,
 }
 
 impl System {
     pub fn try_new(docker: &Docker) -> SystemResult<Self> {
+        let (docker_version, docker_server) = Self::get_docker(docker)?;
+
+        check_api(&docker_version, &docker_server)?;
+
+        let security = Self::get_security_options();
+
         Ok(Self {
-            wm: Self::get_window_manager(),
-            sd: Self::get_sound_driver(),
-            docker_version: Self::get_docker(docker)?,
+            docker_version,
+            docker_server: Some(docker_server),
+            rootless: has_security_option(&security, "rootless"),
+            apparmor: has_security_option(&security, "apparmor"),
+            selinux: has_security_option(&security, "selinux"),
+            ..Self::offline()
         })
     }
 
-    fn get_docker(docker: &Docker) -> SystemResult<DockerVersion> {
+    /// Socket of a rootless daemon of the current user, if it's running
+    pub fn rootless_socket() -> Option<PathBuf> {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() })));
+
+        Some(runtime_dir.join("docker.sock")).filter(|socket| socket.exists())
+    }
+
+    /// System probed without the docker daemon, for the operations which make sense without it
+    pub fn offline() -> Self {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+
+        Self {
+            display: Self::get_display(runtime_dir.as_deref()),
+            sound: Self::get_sound(runtime_dir.as_deref()),
+            input_method: detect_input_method(
+                std::env::var("XMODIFIERS").ok().as_deref(),
+                std::env::var("GTK_IM_MODULE").ok().as_deref(),
+            ),
+            secret_service: Self::has_bus_name("--session", "org.freedesktop.secrets"),
+            portal: Self::has_bus_name("--session", "org.freedesktop.portal.Desktop"),
+            bluetooth: Self::has_bus_name("--system", "org.bluez"),
+            avahi: Self::has_bus_name("--system", "org.freedesktop.Avahi"),
+            gamepads: Self::connected_gamepads(),
+            scanners: Self::connected_scanners(),
+            saned: SANED_PATHS.iter().any(|path| Path::new(path).exists()),
+            docker_version: DockerVersion("unknown".to_string()),
+            docker_server: None,
+            arch: std::env::consts::ARCH.to_string(),
+            runtime_dir,
+            rootless: false,
+            apparmor: false,
+            selinux: false,
+        }
+    }
+
+    /// Structured facts for frontends, the same as `test --json` prints
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys, serialization can't fail
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    fn get_docker(docker: &Docker) -> SystemResult<(DockerVersion, String)> {
         let version = docker.version();
         let mut rt = Runtime::new().unwrap();
 
         let result = match rt.block_on(version) {
-            Ok(Version { api_version: v, .. }) => Ok(DockerVersion(v.to_owned())),
-            Err(err) => Err(SystemError::DockerConnection),
+            Ok(Version {
+                api_version,
+                version,
+                ..
+            }) => Ok((DockerVersion(api_version), version)),
+            Err(_) => Err(SystemError::DockerConnection),
         };
 
         rt.shutdown_now()
             .wait()
-            .map_err(|err| SystemError::DockerConnection)?;
+            .map_err(|_| SystemError::DockerConnection)?;
 
         result
     }
 
-    fn get_window_manager() -> Option<WindowManager> {
-        std::env::var_os("XDG_SESSION_TYPE")
-            .map(|os_string| match os_string.as_os_str().to_str() {
-                Some("x11") => Some(WindowManager::X11),
-                Some("wayland") => Some(WindowManager::Wayland),
-                _ => return None,
-            })
-            .unwrap_or(None)
+    /// shiplift doesn't expose the security options of the daemon, docker CLI is asked instead.
+    /// It connects to the same daemon, as `DOCKER_HOST` is shared
+    fn get_security_options() -> Vec<String> {
+        Command::new("docker")
+            .args(["info", "--format", "{{json .SecurityOptions}}"])
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .and_then(|output| serde_json::from_slice(&output.stdout).ok())
+            .unwrap_or_default()
+    }
+
+    /// Device nodes of the gamepads connected now, they may be plugged in after the detection
+    pub fn connected_gamepads() -> Vec<PathBuf> {
+        find_gamepads(Path::new(INPUT_CLASS))
+    }
+
+    /// SANE devices available now, a scanner may be plugged in after the detection
+    pub fn connected_scanners() -> Vec<String> {
+        Command::new("scanimage")
+            .arg("-L")
+            .stderr(Stdio::null())
+            .output()
+            .map(|output| parse_scanners(&String::from_utf8_lossy(&output.stdout)))
+            .unwrap_or_default()
+    }
+
+    /// USB device node of a SANE device reached with libusb, e.g. `/dev/bus/usb/001/004` for
+    /// `epson2:libusb:001:004`
+    pub fn scanner_node(device: &str) -> Option<PathBuf> {
+        let (_, address) = device.split_once("libusb:")?;
+        let mut parts = address.split(':');
+        let (bus, node) = (parts.next()?, parts.next()?);
+
+        Some(Path::new("/dev/bus/usb").join(bus).join(node))
     }
 
-    fn get_sound_driver() -> Option<SoundDriver> {
+    /// Whether a service owns the name on the bus, `--session` or `--system`
+    fn has_bus_name(bus: &str, name: &str) -> bool {
+        Command::new("dbus-send")
+            .args([
+                bus,
+                "--print-reply",
+                "--dest=org.freedesktop.DBus",
+                "/org/freedesktop/DBus",
+                "org.freedesktop.DBus.NameHasOwner",
+                &format!("string:{}", name),
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("boolean true"))
+            .unwrap_or(false)
+    }
+
+    fn get_display(runtime_dir: Option<&Path>) -> Option<DisplayServer> {
+        detect_display(
+            std::env::var("XDG_SESSION_TYPE").ok().as_deref(),
+            std::env::var("WAYLAND_DISPLAY").ok().as_deref(),
+            std::env::var("DISPLAY").ok().as_deref(),
+            runtime_dir,
+        )
+    }
+
+    /// Sound servers are found by their sockets first, then by their tools
+    fn get_sound(runtime_dir: Option<&Path>) -> Option<SoundServer> {
+        if let Some(server) =
+            detect_sound(runtime_dir, std::env::var("PULSE_SERVER").ok().as_deref())
+        {
+            return Some(server);
+        }
+
         let pulse = Command::new("pactl")
-            .arg("list")
-            .stdout(Stdio::null())
-            .status();
+            .arg("info")
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success());
+
+        if let Some(output) = pulse {
+            return Some(SoundServer {
+                // pipewire-pulse introduces itself as "PulseAudio (on PipeWire x.y.z)"
+                kind: match String::from_utf8_lossy(&output.stdout).contains("PipeWire") {
+                    true => SoundDriver::PipeWire,
+                    false => SoundDriver::PulseAudio,
+                },
+                socket: None,
+            });
+        }
 
-        let alsa = Command::new("aplay")
+        Command::new("aplay")
             .arg("-l")
             .stdout(Stdio::null())
-            .status();
-
-        pulse
+            .stderr(Stdio::null())
+            .status()
             .ok()
-            .map(|o| SoundDriver::PulseAudio)
-            .or(alsa.ok().map(|o| SoundDriver::Alsa))
+            .filter(|status| status.success())
+            .map(|_| SoundServer {
+                kind: SoundDriver::Alsa,
+                socket: None,
+            })
+    }
+}
+
+/// Fails if the daemon is older than `MIN_DOCKER_API`. An unparsable version is let through, as
+/// rejecting a working daemon is worse than a late error
+fn check_api(version: &DockerVersion, server: &str) -> SystemResult<()> {
+    match version.api() {
+        Some(api) if api < MIN_DOCKER_API => Err(SystemError::UnsupportedDocker {
+            found: format!("{} (API {})", server, version),
+            required: format!("{}.{}", MIN_DOCKER_API.0, MIN_DOCKER_API.1),
+        }),
+        Some(_) => Ok(()),
+        None => {
+            warn!(
+                "Can't parse docker API version '{}', assuming it's supported",
+                version
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Device nodes of the connected gamepads and joysticks: every `js*` node and the `event*` nodes
+/// reporting joystick or gamepad buttons
+fn find_gamepads(class_dir: &Path) -> Vec<PathBuf> {
+    let mut gamepads: Vec<PathBuf> = std::fs::read_dir(class_dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| {
+                    name.starts_with("js")
+                        || (name.starts_with("event")
+                            && std::fs::read_to_string(
+                                class_dir.join(name).join("device/capabilities/key"),
+                            )
+                            .is_ok_and(|keys| has_gamepad_buttons(&keys)))
+                })
+                .map(|name| Path::new("/dev/input").join(name))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    gamepads.sort();
+    gamepads
+}
+
+/// Device names of `scanimage -L` lines like
+/// "device `epson2:libusb:001:004' is a Epson PID 0x0142 flatbed scanner"
+fn parse_scanners(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("device `"))
+        .filter_map(|line| line.split_once('\''))
+        .map(|(device, _)| device.to_string())
+        .collect()
+}
+
+/// Checks a key capabilities bitmap of an input device, hex words of `unsigned long` size with
+/// the most significant first
+fn has_gamepad_buttons(keys: &str) -> bool {
+    let bits = std::mem::size_of::<libc::c_ulong>() * 8;
+    let words: Vec<u64> = keys
+        .split_whitespace()
+        .rev()
+        .map(|word| u64::from_str_radix(word, 16).unwrap_or_default())
+        .collect();
+
+    (BTN_JOYSTICK..=BTN_GAMEPAD_LAST).any(|code| {
+        words
+            .get(code / bits)
+            .is_some_and(|word| word & (1 << (code % bits)) != 0)
+    })
+}
+
+/// Detects an input method framework by the variables which make programs use it
+fn detect_input_method(xmodifiers: Option<&str>, gtk_module: Option<&str>) -> Option<InputMethod> {
+    let module = xmodifiers
+        .and_then(|modifiers| modifiers.strip_prefix("@im="))
+        .or(gtk_module)?;
+
+    match module {
+        "ibus" => Some(InputMethod::IBus),
+        "fcitx" | "fcitx5" => Some(InputMethod::Fcitx),
+        _ => None,
+    }
+}
+
+/// Detects a display server by the session type, falls back to the display variables when the
+/// session type isn't set, e.g. in a terminal multiplexer
+fn detect_display(
+    session: Option<&str>,
+    wayland: Option<&str>,
+    x11: Option<&str>,
+    runtime_dir: Option<&Path>,
+) -> Option<DisplayServer> {
+    let kind = match (session, wayland, x11) {
+        (Some("x11"), _, _) => WindowManager::X11,
+        (Some("wayland"), _, _) => WindowManager::Wayland,
+        (_, Some(_), _) => WindowManager::Wayland,
+        (_, None, Some(_)) => WindowManager::X11,
+        _ => return None,
+    };
+    let socket = match kind {
+        WindowManager::Wayland => runtime_dir.map(|dir| dir.join(wayland.unwrap_or("wayland-0"))),
+        WindowManager::X11 => x11.and_then(x11_socket),
+    };
+
+    Some(DisplayServer { kind, socket })
+}
+
+/// Socket of an X11 display like `:0` or `localhost:10.0`
+fn x11_socket(display: &str) -> Option<PathBuf> {
+    let number = display.rsplit(':').next()?.split('.').next()?;
+
+    match number.parse::<u32>() {
+        Ok(number) => Some(PathBuf::from(format!("/tmp/.X11-unix/X{}", number))),
+        Err(_) => None,
+    }
+}
+
+/// Detects a sound server by its socket. PipeWire is checked first, as it serves PulseAudio
+/// clients too
+fn detect_sound(runtime_dir: Option<&Path>, pulse_server: Option<&str>) -> Option<SoundServer> {
+    let found = |kind: SoundDriver, socket: PathBuf| match socket.exists() {
+        true => Some(SoundServer {
+            kind,
+            socket: Some(socket),
+        }),
+        false => None,
+    };
+
+    runtime_dir
+        .and_then(|dir| found(SoundDriver::PipeWire, dir.join("pipewire-0")))
+        .or_else(|| {
+            runtime_dir.and_then(|dir| found(SoundDriver::PulseAudio, dir.join("pulse/native")))
+        })
+        .or_else(|| {
+            pulse_server
+                .and_then(|server| server.strip_prefix("unix:"))
+                .and_then(|path| found(SoundDriver::PulseAudio, PathBuf::from(path)))
+        })
+}
+
+/// Whether the daemon reports the option, e.g. `name=seccomp,profile=default` for `seccomp`
+fn has_security_option(options: &[String], name: &str) -> bool {
+    let name = format!("name={}", name);
+
+    options
+        .iter()
+        .any(|option| option.split(',').any(|field| field == name))
+}
+
+impl System {
+    /// Names and values of the detected settings, `None` for what wasn't found
+    pub fn rows(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("Docker API", Some(self.docker_version.to_string())),
+            ("Docker version", self.docker_server.to_owned()),
+            (
+                "Docker mode",
+                Some(
+                    match self.rootless {
+                        true => "rootless",
+                        false => "rootful",
+                    }
+                    .to_string(),
+                ),
+            ),
+            (
+                "AppArmor",
+                Some(
+                    match self.apparmor {
+                        true => "enabled",
+                        false => "disabled",
+                    }
+                    .to_string(),
+                ),
+            ),
+            (
+                "SELinux",
+                Some(
+                    match self.selinux {
+                        true => "enabled",
+                        false => "disabled",
+                    }
+                    .to_string(),
+                ),
+            ),
+            (
+                "Display server",
+                self.display.as_ref().map(|display| display.to_string()),
+            ),
+            (
+                "Sound server",
+                self.sound.as_ref().map(|sound| sound.to_string()),
+            ),
+            (
+                "Input method",
+                self.input_method
+                    .map(|input_method| input_method.to_string()),
+            ),
+            (
+                "Gamepads",
+                Some(match self.gamepads.is_empty() {
+                    true => "none".to_string(),
+                    false => self
+                        .gamepads
+                        .iter()
+                        .map(|gamepad| gamepad.display().to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                }),
+            ),
+            (
+                "Scanners",
+                Some(match (self.scanners.is_empty(), self.saned) {
+                    (true, false) => "none".to_string(),
+                    (true, true) => "shared by saned".to_string(),
+                    (false, _) => self.scanners.join(", "),
+                }),
+            ),
+            ("Architecture", Some(self.arch.to_owned())),
+            (
+                "Runtime dir",
+                self.runtime_dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string()),
+            ),
+        ]
     }
 }
 
 impl Display for System {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        writeln!(
-            f,
-            "\n\n\tDocker version  ===> {docker_version}\n\tWindow manager  ===> \
-             {window_manager}\n\tSound driver    ===> {sound_driver}",
-            docker_version = DisplayOption(Some(self.docker_version.to_owned())),
-            window_manager = DisplayOption(self.wm.to_owned()),
-            sound_driver = DisplayOption(self.sd.to_owned()),
-        )
+        let rows = self.rows();
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        for (name, value) in rows.iter() {
+            writeln!(
+                f,
+                "\t{:<width$} ===> {}",
+                name,
+                value.as_deref().unwrap_or("None"),
+                width = width
+            )?;
+        }
+
+        Ok(())
     }
 }
 
-struct DisplayOption<T>(pub Option<T>);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl<T: Driver> Display for DisplayOption<T> {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        match self.0 {
-            Some(ref v) => write!(f, "{}", format!("{}", v).color(Color::Green)),
-            None => write!(f, "{}", "None".color(Color::Red)),
+    #[test]
+    fn test_check_api() {
+        assert!(check_api(&DockerVersion("1.40".to_string()), "19.03.5").is_ok());
+        assert!(check_api(&DockerVersion("unknown".to_string()), "").is_ok());
+
+        match check_api(&DockerVersion("1.24".to_string()), "1.12.6") {
+            Err(SystemError::UnsupportedDocker { found, required }) => {
+                assert_eq!(found, "1.12.6 (API 1.24)");
+                assert_eq!(required, "1.25");
+            }
+            result => panic!("Unexpected result: {:?}", result),
         }
     }
+
+    #[test]
+    fn test_has_security_option() {
+        let options = vec![
+            "name=apparmor".to_string(),
+            "name=seccomp,profile=default".to_string(),
+            "name=selinux".to_string(),
+        ];
+
+        assert!(has_security_option(&options, "apparmor"));
+        assert!(has_security_option(&options, "selinux"));
+        assert!(has_security_option(&options, "seccomp"));
+        assert!(!has_security_option(&options, "rootless"));
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_has_gamepad_buttons() {
+        // BTN_SOUTH (0x130) is bit 48 of the fifth 64-bit word
+        assert!(has_gamepad_buttons("7fdb000000000000 0 0 0 0\n"));
+        // Keyboard keys only
+        assert!(!has_gamepad_buttons("0 0 fffffffffffffffe\n"));
+        assert!(!has_gamepad_buttons(""));
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_find_gamepads() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_input_{}", std::process::id()));
+
+        for (name, keys) in [("event3", "7fdb000000000000 0 0 0 0"), ("event4", "0 fffe")] {
+            std::fs::create_dir_all(dir.join(name).join("device/capabilities")).unwrap();
+            std::fs::write(dir.join(name).join("device/capabilities/key"), keys).unwrap();
+        }
+
+        std::fs::create_dir_all(dir.join("js0")).unwrap();
+        std::fs::create_dir_all(dir.join("mouse0")).unwrap();
+
+        assert_eq!(
+            find_gamepads(&dir),
+            vec![
+                PathBuf::from("/dev/input/event3"),
+                PathBuf::from("/dev/input/js0")
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_scanners() {
+        let output = "device `epson2:libusb:001:004' is a Epson PID 0x0142 flatbed scanner\n\
+                      device `net:localhost:hpaio:/usb/Deskjet' is a Hewlett-Packard all-in-one\n";
+
+        assert_eq!(
+            parse_scanners(output),
+            vec![
+                "epson2:libusb:001:004".to_string(),
+                "net:localhost:hpaio:/usb/Deskjet".to_string()
+            ]
+        );
+        assert!(parse_scanners("\nNo scanners were identified.\n").is_empty());
+    }
+
+    #[test]
+    fn test_scanner_node() {
+        assert_eq!(
+            System::scanner_node("epson2:libusb:001:004"),
+            Some(PathBuf::from("/dev/bus/usb/001/004"))
+        );
+        assert_eq!(
+            System::scanner_node("net:localhost:hpaio:/usb/Deskjet"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_input_method() {
+        assert_eq!(
+            detect_input_method(Some("@im=ibus"), None),
+            Some(InputMethod::IBus)
+        );
+        assert_eq!(
+            detect_input_method(None, Some("fcitx")),
+            Some(InputMethod::Fcitx)
+        );
+        assert_eq!(detect_input_method(Some("@im=none"), Some("xim")), None);
+        assert_eq!(detect_input_method(None, None), None);
+    }
+
+    #[test]
+    fn test_detect_display() {
+        assert_eq!(
+            detect_display(Some("x11"), None, Some(":1"), None),
+            Some(DisplayServer {
+                kind: WindowManager::X11,
+                socket: Some(PathBuf::from("/tmp/.X11-unix/X1")),
+            })
+        );
+        assert_eq!(
+            detect_display(
+                None,
+                Some("wayland-1"),
+                Some(":0"),
+                Some(Path::new("/run/user/1000"))
+            ),
+            Some(DisplayServer {
+                kind: WindowManager::Wayland,
+                socket: Some(PathBuf::from("/run/user/1000/wayland-1")),
+            })
+        );
+        assert_eq!(
+            detect_display(Some("tty"), None, Some("localhost:10.0"), None)
+                .and_then(|display| display.socket),
+            Some(PathBuf::from("/tmp/.X11-unix/X10"))
+        );
+        assert!(detect_display(Some("tty"), None, None, None).is_none());
+    }
+
+    #[test]
+    fn test_detect_sound() {
+        let dir =
+            std::env::temp_dir().join(format!("debian_bridge_test_sound_{}", std::process::id()));
+
+        std::fs::create_dir_all(dir.join("pulse")).unwrap();
+        std::fs::write(dir.join("pulse/native"), b"").unwrap();
+
+        let pulse = detect_sound(Some(&dir), None).map(|server| server.kind);
+
+        std::fs::write(dir.join("pipewire-0"), b"").unwrap();
+
+        let pipewire = detect_sound(Some(&dir), None).map(|server| server.kind);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(pulse, Some(SoundDriver::PulseAudio));
+        assert_eq!(pipewire, Some(SoundDriver::PipeWire));
+        assert!(detect_sound(Some(&dir), Some("unix:/nonexistent/native")).is_none());
+    }
 }
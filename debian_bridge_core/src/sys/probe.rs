@@ -0,0 +1,170 @@
+use super::{System, SystemResult};
+use serde::{Deserialize, Serialize};
+use shiplift::Docker;
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Seconds a probe stored by `System::cached` is reused for
+pub const PROBE_TTL: u64 = 60;
+
+/// Variables the detection reads, a probe of other values is stale
+const PROBE_ENV: &[&str] = &[
+    "DOCKER_HOST",
+    "XDG_RUNTIME_DIR",
+    "XDG_SESSION_TYPE",
+    "WAYLAND_DISPLAY",
+    "DISPLAY",
+    "PULSE_SERVER",
+    "XMODIFIERS",
+    "GTK_IM_MODULE",
+    "DBUS_SESSION_BUS_ADDRESS",
+];
+
+/// Sockets and directories which are recreated when a daemon restarts or a device is plugged
+/// in, the ones of the runtime directory are relative to it
+const PROBE_PATHS: &[&str] = &[
+    "/var/run/docker.sock",
+    "/run/dbus/system_bus_socket",
+    "/tmp/.X11-unix",
+    "/dev/input",
+];
+const PROBE_RUNTIME_PATHS: &[&str] = &["docker.sock", "bus", "pipewire-0", "pulse/native"];
+
+/// What a probe is valid for: the variables and the modification times of the paths, cheap to
+/// compare with the current ones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ProbeKey {
+    env: Vec<(String, Option<String>)>,
+    mtimes: Vec<(PathBuf, Option<u64>)>,
+}
+
+impl ProbeKey {
+    fn current() -> Self {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        let paths = PROBE_PATHS.iter().map(PathBuf::from).chain(
+            runtime_dir
+                .iter()
+                .flat_map(|dir| PROBE_RUNTIME_PATHS.iter().map(move |path| dir.join(path))),
+        );
+
+        ProbeKey {
+            env: PROBE_ENV
+                .iter()
+                .map(|name| (name.to_string(), std::env::var(name).ok()))
+                .collect(),
+            mtimes: paths
+                .map(|path| {
+                    let mtime = std::fs::metadata(&path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                        .map(|time| time.as_secs());
+
+                    (path, mtime)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Probe {
+    key: ProbeKey,
+    /// Seconds since the epoch
+    probed: u64,
+    system: System,
+}
+
+impl System {
+    /// `try_new` result stored in `file` and reused for `PROBE_TTL` seconds while the variables
+    /// and the sockets it depends on stay the same. `refresh` probes anew and stores the result,
+    /// a failed probe isn't stored
+    pub fn cached(docker: &Docker, file: &Path, refresh: bool) -> SystemResult<Self> {
+        let key = ProbeKey::current();
+        let now = now();
+
+        if !refresh {
+            if let Some(system) = load(file, &key, now) {
+                debug!("Using the system probe of {}", file.display());
+                return Ok(system);
+            }
+        }
+
+        let system = Self::try_new(docker)?;
+        let probe = Probe {
+            key,
+            probed: now,
+            system,
+        };
+        let stored = serde_json::to_string(&probe)
+            .map_err(|err| err.to_string())
+            .and_then(|text| std::fs::write(file, text).map_err(|err| err.to_string()));
+
+        if let Err(err) = stored {
+            debug!(
+                "Can't store the system probe to {}: {}",
+                file.display(),
+                err
+            );
+        }
+
+        Ok(probe.system)
+    }
+}
+
+/// System of the probe in `file` if it's of `key` and younger than `PROBE_TTL` at `now`
+fn load(file: &Path, key: &ProbeKey, now: u64) -> Option<System> {
+    let probe: Probe = serde_json::from_str(&std::fs::read_to_string(file).ok()?).ok()?;
+
+    match probe.key == *key && now >= probe.probed && now - probe.probed < PROBE_TTL {
+        true => Some(probe.system),
+        false => None,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let file =
+            std::env::temp_dir().join(format!("debian_bridge_test_probe_{}", std::process::id()));
+        let key = ProbeKey::current();
+        let probe = Probe {
+            key: key.to_owned(),
+            probed: 1000,
+            system: System {
+                rootless: true,
+                ..System::offline()
+            },
+        };
+
+        std::fs::write(&file, serde_json::to_string(&probe).unwrap()).unwrap();
+
+        let restarted = ProbeKey {
+            mtimes: vec![(PathBuf::from("/var/run/docker.sock"), Some(2000))],
+            ..key.to_owned()
+        };
+
+        assert!(load(&file, &key, 1000 + PROBE_TTL - 1).unwrap().rootless);
+        assert!(load(&file, &key, 1000 + PROBE_TTL).is_none());
+        assert!(load(&file, &key, 999).is_none());
+        assert!(load(&file, &restarted, 1001).is_none());
+
+        std::fs::write(&file, "{").unwrap();
+        assert!(load(&file, &key, 1001).is_none());
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(load(&file, &key, 1001).is_none());
+    }
+}